@@ -1,18 +1,22 @@
 use std::{
+    fs,
     sync::{
         mpsc::{self, channel, Sender},
-        Arc, Mutex,
+        Arc, Mutex, MutexGuard,
     },
     thread::{self},
 };
 
 use crate::{
     blockchain::block::{pow_poi_validation, SerializedBlock},
+    blockchain::transaction::{Fee, Transaction},
     blockchain::{
         file::header_count,
         file_manager::{FileManager, FileMessages},
     },
-    common::{uint256::Uint256, utils_data::total_reintentos},
+    common::{
+        address::Address, red::red_configurada, uint256::Uint256, utils_data::total_reintentos,
+    },
     config,
     errores::{InterfaceError, InterfaceMessage, NodoBitcoinError},
     interface::{
@@ -26,6 +30,11 @@ use crate::{
         initial_block_download::get_full_blockchain,
     },
     wallet::{
+        billetera::{programar_bloqueo, segundos_de_bloqueo, Billetera},
+        bip39::generar_mnemonico,
+        cifrado::{a_hexa, desde_hexa},
+        multisig::{Multisig, TipoMultisig},
+        psbt::Psbt,
         transaction_manager::{create_transaction_manager, TransactionMessages},
         user::Account,
         uxto_set::TxReport,
@@ -35,7 +44,9 @@ use crate::{
 #[derive(Clone)]
 pub struct ApplicationManager {
     pub current_account: Option<Account>,
-    pub accounts: Vec<Account>,
+    // las cuentas se comparten con la copia que atiende los mensajes, para que las dos vean
+    // las mismas claves y el mismo estado de bloqueo
+    billetera: Arc<Mutex<Billetera>>,
     pub tx_manager: mpsc::Sender<TransactionMessages>,
     sender_frontend: glib::Sender<ViewObject>,
     logger: mpsc::Sender<LogMessages>,
@@ -59,20 +70,36 @@ pub enum ApplicationManagerMessages {
     UpdateProgressBar(usize, usize),
     POIInvalido,
     GetMerklePath(Vec<(Uint256, ProofOrder)>),
+    FeeEstimate(u64),
+    NewReceiveAddress(String),
+    UpdateAccount(Box<Account>),
+    LockWallet(u64),
+    UnsignedTx(Option<Transaction>),
+    MultisigTx(Option<Transaction>, bool),
+    Psbt(Option<Psbt>),
+    TxBroadcast(bool),
+    FeeBumped(bool),
 }
 
 impl ApplicationManager {
     pub fn new(sender_frontend: glib::Sender<ViewObject>) -> Self {
-        let accounts = match Account::get_all_accounts() {
-            Ok(accounts) => accounts,
-            Err(_) => Vec::new(),
-        };
         let (sender_app_manager, receiver_app_manager) = channel();
         let logger = create_logger_actor(config::get_valor("LOG_FILE".to_string()));
+        let billetera = match Billetera::cargar() {
+            Ok(billetera) => billetera,
+            Err(error) => {
+                log_error_message(
+                    logger.clone(),
+                    format!("No se pudieron leer las cuentas guardadas: {}", error),
+                );
+                Billetera::new(Vec::new(), None)
+            }
+        };
+        let accounts = billetera.cuentas_sin_secretos();
 
         let file_manager = FileManager::create(logger.clone());
         let tx_manager = create_transaction_manager(
-            accounts.clone(),
+            accounts,
             logger.clone(),
             sender_app_manager.clone(),
             file_manager.clone(),
@@ -81,13 +108,14 @@ impl ApplicationManager {
         let mut app_manager = ApplicationManager {
             current_account: None,
             sender_app_manager,
-            accounts,
+            billetera: Arc::new(Mutex::new(billetera)),
             sender_frontend,
             logger,
             tx_manager,
             _file_manager: file_manager,
             shutdown_sent: false,
         };
+        app_manager.advertir_si_sin_cifrar();
         app_manager.thread_download_blockchain(0);
         let ret_value = app_manager.clone();
 
@@ -250,6 +278,88 @@ impl ApplicationManager {
                     end_loading(self.sender_frontend.clone());
                 }
             }
+            ApplicationManagerMessages::FeeEstimate(sat_por_vbyte) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::UploadFeeEstimate(sat_por_vbyte));
+            }
+            ApplicationManagerMessages::NewReceiveAddress(address) => {
+                _ = self.sender_frontend.send(ViewObject::Message(
+                    InterfaceMessage::NewReceiveAddress(address),
+                ));
+            }
+            ApplicationManagerMessages::UpdateAccount(account) => {
+                // el transaction manager avisa cuando una cuenta HD usó direcciones nuevas
+                if let Ok(mut billetera) = self.billetera() {
+                    billetera.actualizar(*account);
+                }
+                self.save_accounts();
+            }
+            ApplicationManagerMessages::LockWallet(desbloqueo) => {
+                let bloqueada = match self.billetera() {
+                    Ok(mut billetera) => billetera.bloquear_si_vencio(desbloqueo),
+                    Err(_) => false,
+                };
+                if bloqueada {
+                    log_info_message(
+                        self.logger.clone(),
+                        "Wallet bloqueada por tiempo.".to_string(),
+                    );
+                    _ = self
+                        .sender_frontend
+                        .send(ViewObject::Message(InterfaceMessage::WalletLocked));
+                }
+            }
+            ApplicationManagerMessages::UnsignedTx(tx) => {
+                let exportada = match tx {
+                    Some(tx) => self.export_tx(&tx, "tx_sin_firmar"),
+                    None => Err(NodoBitcoinError::NoSePuedeEnviarTransaccion),
+                };
+                match exportada {
+                    Ok(path) => {
+                        _ = self.sender_frontend.send(ViewObject::Message(
+                            InterfaceMessage::UnsignedTxExported(path),
+                        ));
+                    }
+                    Err(_) => {
+                        _ = self
+                            .sender_frontend
+                            .send(ViewObject::Error(InterfaceError::UnsignedTxNotExported));
+                    }
+                }
+            }
+            ApplicationManagerMessages::MultisigTx(tx, enviada) => {
+                let respuesta = match (tx, enviada) {
+                    (Some(_), true) => ViewObject::Message(InterfaceMessage::TransactionSent),
+                    (Some(tx), false) => match self.export_tx(&tx, "tx_parcial") {
+                        Ok(path) => ViewObject::Message(InterfaceMessage::PartialTxExported(path)),
+                        Err(_) => ViewObject::Error(InterfaceError::PartialTxNotSigned),
+                    },
+                    (None, _) => ViewObject::Error(InterfaceError::PartialTxNotSigned),
+                };
+                _ = self.sender_frontend.send(respuesta);
+            }
+            ApplicationManagerMessages::Psbt(psbt) => {
+                let respuesta = match psbt.map(|psbt| self.export_psbt(&psbt)) {
+                    Some(Ok(path)) => ViewObject::Message(InterfaceMessage::PsbtExported(path)),
+                    _ => ViewObject::Error(InterfaceError::PsbtNotValid),
+                };
+                _ = self.sender_frontend.send(respuesta);
+            }
+            ApplicationManagerMessages::TxBroadcast(enviada) => {
+                let respuesta = match enviada {
+                    true => ViewObject::Message(InterfaceMessage::TransactionSent),
+                    false => ViewObject::Error(InterfaceError::TransactionNotSent),
+                };
+                _ = self.sender_frontend.send(respuesta);
+            }
+            ApplicationManagerMessages::FeeBumped(aumentado) => {
+                let respuesta = match aumentado {
+                    true => ViewObject::Message(InterfaceMessage::FeeBumped),
+                    false => ViewObject::Error(InterfaceError::BumpFeeNotValid),
+                };
+                _ = self.sender_frontend.send(respuesta);
+            }
             ApplicationManagerMessages::GetMerklePath(path) => {
                 if path.is_empty() {
                     _ = self
@@ -262,11 +372,14 @@ impl ApplicationManager {
         }
     }
 
+    /// Envía target_amount BTC a target_address. Con fee_por_vbyte el fee es un fee rate en
+    /// sat/vB, como el de los presets, y si no el total en BTC
     pub fn send_transaction(
         &self,
         target_address: String,
         target_amount_string: String,
         fee_string: String,
+        fee_por_vbyte: bool,
     ) -> Result<(), NodoBitcoinError> {
         let target_amount = match target_amount_string.parse::<f64>() {
             Ok(target_amount) => (target_amount * 100_000_000.0) as u64,
@@ -277,21 +390,40 @@ impl ApplicationManager {
                 return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
             }
         };
-        let fee: u64 = match fee_string.parse::<f64>() {
-            Ok(fee) => (fee * 100_000_000.0) as u64,
-            Err(_) => {
+        let fee = if fee_por_vbyte {
+            fee_string.trim().parse::<u64>().ok().map(Fee::PorVbyte)
+        } else {
+            fee_string
+                .parse::<f64>()
+                .ok()
+                .map(|fee| Fee::Total((fee * 100_000_000.0) as u64))
+        };
+        let fee = match fee {
+            Some(fee) => fee,
+            None => {
                 _ = self
                     .sender_frontend
                     .send(ViewObject::Error(InterfaceError::FeeNotValid));
                 return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
             }
         };
+        let target_address = self.validate_address(target_address.trim())?;
 
-        let account = self.get_current_account()?;
+        let current_account = self.get_current_account()?;
+        if self
+            .billetera()?
+            .es_solo_lectura(&current_account.public_key)
+        {
+            return self.request_unsigned_tx(current_account, target_address, target_amount, fee);
+        }
+        let account = self.get_signing_account()?;
+        if account.multisig.is_some() {
+            return self.request_multisig_tx(account, target_address, target_amount, fee);
+        }
         let logger = self.logger.clone();
 
         let message = format!(
-            "Enviando tx desde {:?} a {:?}. Monto: {:?}. Fee: {:?} ...",
+            "Enviando tx desde {:?} a {}. Monto: {:?}. Fee: {:?} ...",
             account.public_key, target_address, target_amount, fee
         );
         log_info_message(self.logger.clone(), message);
@@ -319,6 +451,426 @@ impl ApplicationManager {
         Ok(())
     }
 
+    // el nodo no tiene la clave de las cuentas de solo lectura: en lugar de enviar la
+    // transacción le pide al transaction manager que la arme sin firmar para exportarla
+    fn request_unsigned_tx(
+        &self,
+        account: Account,
+        target_address: Address,
+        target_amount: u64,
+        fee: Fee,
+    ) -> Result<(), NodoBitcoinError> {
+        let message = format!(
+            "Armando tx sin firmar desde la cuenta de solo lectura {:?} a {}. Monto: {:?}. Fee: {:?} ...",
+            account.public_key, target_address, target_amount, fee
+        );
+        log_info_message(self.logger.clone(), message);
+        if self
+            .tx_manager
+            .send(TransactionMessages::ExportUnsignedTx(
+                account,
+                target_address,
+                target_amount,
+                fee,
+                self.logger.clone(),
+            ))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::UnsignedTxNotExported));
+            return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+        }
+        Ok(())
+    }
+
+    // el nodo agrega la firma de su clave y el transaction manager envía la transacción si
+    // con eso alcanza, o la devuelve para exportarla a los otros participantes
+    fn request_multisig_tx(
+        &self,
+        account: Account,
+        target_address: Address,
+        target_amount: u64,
+        fee: Fee,
+    ) -> Result<(), NodoBitcoinError> {
+        let message = format!(
+            "Firmando tx multisig desde {:?} a {}. Monto: {:?}. Fee: {:?} ...",
+            account.public_key, target_address, target_amount, fee
+        );
+        log_info_message(self.logger.clone(), message);
+        if self
+            .tx_manager
+            .send(TransactionMessages::SendMultisigTx(
+                account,
+                target_address,
+                target_amount,
+                fee,
+                self.logger.clone(),
+            ))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::TransactionNotSent));
+            return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+        }
+        Ok(())
+    }
+
+    /// Lee una transacción multisig firmada en parte por otro participante, en hexadecimal, y
+    /// le agrega la firma de la cuenta seleccionada. Si queda completa se envía y si no se
+    /// vuelve a exportar
+    pub fn sign_partial_tx(&self, path: String) -> Result<(), NodoBitcoinError> {
+        let tx = fs::read_to_string(path.trim())
+            .map_err(|_| NodoBitcoinError::NoExisteArchivo)
+            .and_then(|texto| desde_hexa(texto.trim()))
+            .and_then(|bytes| Transaction::deserialize(&bytes));
+        let tx = match tx {
+            Ok(tx) => tx,
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::PartialTxNotSigned));
+                return Err(error);
+            }
+        };
+        let account = self.get_signing_account()?;
+        if account.multisig.is_none() {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::PartialTxNotSigned));
+            return Err(NodoBitcoinError::InvalidAccount);
+        }
+        if self
+            .tx_manager
+            .send(TransactionMessages::SignPartialTx(
+                account,
+                tx,
+                self.logger.clone(),
+            ))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::PartialTxNotSigned));
+            return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+        }
+        Ok(())
+    }
+
+    /// Guarda la transacción serializada en hexadecimal en un archivo con el prefijo y su txid
+    /// Devuelve el nombre del archivo
+    fn export_tx(&self, tx: &Transaction, prefijo: &str) -> Result<String, NodoBitcoinError> {
+        let path = format!("{}_{}.hex", prefijo, tx.txid()?.to_hexa_le_string());
+        fs::write(&path, a_hexa(&tx.serialize()?))
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        log_info_message(
+            self.logger.clone(),
+            format!("Transacción guardada en {}.", path),
+        );
+        Ok(path)
+    }
+
+    /// Arma una PSBT sin firmar desde la cuenta seleccionada, que puede ser de solo lectura
+    /// Los destinatarios van como `direccion=monto` separados por comas o saltos de línea,
+    /// con los montos y el fee en BTC. La respuesta llega como una PSBT para guardar
+    pub fn create_psbt(
+        &self,
+        recipients: String,
+        fee_string: String,
+    ) -> Result<(), NodoBitcoinError> {
+        let destinos = match leer_destinatarios(&recipients) {
+            Some(destinos) => destinos,
+            None => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::PsbtRecipientsNotValid));
+                return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+            }
+        };
+        let fee: u64 = match fee_string.trim().parse::<f64>() {
+            Ok(fee) => (fee * 100_000_000.0) as u64,
+            Err(_) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::FeeNotValid));
+                return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+            }
+        };
+        let direcciones = destinos
+            .iter()
+            .map(|(direccion, monto)| Ok((self.validate_address(direccion)?, *monto)))
+            .collect::<Result<Vec<(Address, u64)>, NodoBitcoinError>>()?;
+        let account = self.get_current_account()?;
+        log_info_message(
+            self.logger.clone(),
+            format!(
+                "Armando PSBT desde {:?} a {:?}. Fee: {:?} ...",
+                account.public_key, destinos, fee
+            ),
+        );
+        if self
+            .tx_manager
+            .send(TransactionMessages::CreatePsbt(
+                account,
+                direcciones,
+                fee,
+                self.logger.clone(),
+            ))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::PsbtNotValid));
+            return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+        }
+        Ok(())
+    }
+
+    /// Lee una PSBT en base64 y le agrega las firmas de la cuenta seleccionada. Si con eso
+    /// quedan todos los inputs firmados se finaliza y se envía, si no se vuelve a guardar
+    pub fn sign_psbt(&self, path: String) -> Result<(), NodoBitcoinError> {
+        let mut psbt = self.read_psbt(path.trim())?;
+        let account = self.get_signing_account()?;
+        let firmados = match psbt.firmar(&account) {
+            Ok(firmados) if firmados > 0 => firmados,
+            _ => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::PsbtNotSigned));
+                return Err(NodoBitcoinError::InvalidAccount);
+            }
+        };
+        log_info_message(
+            self.logger.clone(),
+            format!("PSBT firmada en {} inputs.", firmados),
+        );
+        self.finalize_or_export_psbt(psbt)
+    }
+
+    /// Junta las firmas de las PSBTs de los archivos, separados por comas, que tienen que ser
+    /// de la misma transacción. Si quedan todos los inputs firmados se finaliza y se envía,
+    /// si no se guarda la PSBT combinada
+    pub fn combine_psbts(&self, paths: String) -> Result<(), NodoBitcoinError> {
+        let mut psbts = vec![];
+        for path in paths
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        {
+            psbts.push(self.read_psbt(path)?);
+        }
+        let combinada = match psbts.split_first() {
+            Some((primera, otras)) => otras.iter().try_fold(primera.clone(), |mut psbt, otra| {
+                psbt.combinar(otra).map(|_| psbt)
+            }),
+            None => Err(NodoBitcoinError::PsbtInvalida),
+        };
+        match combinada {
+            Ok(psbt) => self.finalize_or_export_psbt(psbt),
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::PsbtNotValid));
+                Err(error)
+            }
+        }
+    }
+
+    // finaliza la PSBT y le pide al transaction manager que envíe la transacción extraída, o
+    // la guarda si todavía le faltan firmas
+    fn finalize_or_export_psbt(&self, mut psbt: Psbt) -> Result<(), NodoBitcoinError> {
+        let resultado = match psbt.finalizar() {
+            Ok(true) => psbt.extraer().and_then(|tx| {
+                self.tx_manager
+                    .send(TransactionMessages::BroadcastTx(tx, self.logger.clone()))
+                    .map_err(|_| NodoBitcoinError::NoSePuedeEnviarTransaccion)
+            }),
+            Ok(false) => self.export_psbt(&psbt).map(|path| {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Message(InterfaceMessage::PsbtExported(path)));
+            }),
+            Err(error) => Err(error),
+        };
+        if resultado.is_err() {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::PsbtNotValid));
+        }
+        resultado
+    }
+
+    /// Valida el checksum y la red de la dirección de destino antes de armar la transacción.
+    /// Si no es válida avisa en la interfaz
+    fn validate_address(&self, direccion: &str) -> Result<Address, NodoBitcoinError> {
+        match Address::en_red(direccion, red_configurada()) {
+            Ok(address) => Ok(address),
+            Err(error) => {
+                let error_interfaz = match error {
+                    NodoBitcoinError::DireccionDeOtraRed => InterfaceError::AddressWrongNetwork,
+                    _ => InterfaceError::AddressNotValid,
+                };
+                _ = self.sender_frontend.send(ViewObject::Error(error_interfaz));
+                Err(error)
+            }
+        }
+    }
+
+    fn read_psbt(&self, path: &str) -> Result<Psbt, NodoBitcoinError> {
+        let psbt = fs::read_to_string(path)
+            .map_err(|_| NodoBitcoinError::NoExisteArchivo)
+            .and_then(|texto| Psbt::desde_base64(&texto));
+        if psbt.is_err() {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::PsbtNotValid));
+        }
+        psbt
+    }
+
+    /// Guarda la PSBT en base64 en un archivo con el txid de su transacción
+    /// Devuelve el nombre del archivo
+    fn export_psbt(&self, psbt: &Psbt) -> Result<String, NodoBitcoinError> {
+        let path = format!("psbt_{}.psbt", psbt.tx.txid()?.to_hexa_le_string());
+        fs::write(&path, psbt.a_base64()?)
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        log_info_message(self.logger.clone(), format!("PSBT guardada en {}.", path));
+        Ok(path)
+    }
+
+    /// Pide el fee estimado para que una transacción se confirme dentro de la cantidad de
+    /// bloques del preset elegido. La respuesta llega a la vista como UploadFeeEstimate
+    pub fn estimate_fee(&self, target_blocks: String) -> Result<(), NodoBitcoinError> {
+        let target_blocks = match target_blocks.parse::<usize>() {
+            Ok(target_blocks) => target_blocks,
+            Err(_) => return Err(NodoBitcoinError::ValorFueraDeRango),
+        };
+        if self
+            .tx_manager
+            .send(TransactionMessages::EstimateFee(target_blocks))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::FeeEstimateError));
+            return Err(NodoBitcoinError::ValorFueraDeRango);
+        }
+        Ok(())
+    }
+
+    /// Pide una dirección de recepción sin usar de la cuenta seleccionada
+    /// La respuesta llega a la vista como un mensaje
+    pub fn new_receive_address(&self) -> Result<(), NodoBitcoinError> {
+        let account = self.get_current_account()?;
+        if self
+            .tx_manager
+            .send(TransactionMessages::NewReceiveAddress(account.public_key))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::NewAddressError));
+            return Err(NodoBitcoinError::InvalidAccount);
+        }
+        Ok(())
+    }
+
+    /// Reemplaza la transacción pendiente seleccionada por otra con los mismos inputs y más fee
+    /// El resultado se muestra cuando responde el transaction manager
+    pub fn bump_fee(&self, tx_id: String, fee_string: String) -> Result<(), NodoBitcoinError> {
+        let (tx_id, fee) = self.parse_tx_id_and_fee(tx_id, fee_string)?;
+        let account = self.get_signing_account()?;
+        let message = format!(
+            "Aumentando el fee de la tx {} a {:?} ...",
+            tx_id.to_hexa_le_string(),
+            fee
+        );
+        log_info_message(self.logger.clone(), message);
+
+        if self
+            .tx_manager
+            .send(TransactionMessages::BumpFee(
+                account,
+                tx_id,
+                fee,
+                self.logger.clone(),
+            ))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::BumpFeeNotValid));
+            return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+        }
+        Ok(())
+    }
+
+    /// Acelera la transacción seleccionada gastando sus salidas sin confirmar con una hija que paga el fee
+    pub fn cpfp(&self, tx_id: String, fee_string: String) -> Result<(), NodoBitcoinError> {
+        let (tx_id, fee) = self.parse_tx_id_and_fee(tx_id, fee_string)?;
+        let account = self.get_signing_account()?;
+        let message = format!(
+            "Acelerando la tx {} con una hija de fee {:?} ...",
+            tx_id.to_hexa_le_string(),
+            fee
+        );
+        log_info_message(self.logger.clone(), message);
+
+        if self
+            .tx_manager
+            .send(TransactionMessages::Cpfp(
+                account,
+                tx_id,
+                fee,
+                self.logger.clone(),
+            ))
+            .is_err()
+        {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::TransactionNotSent));
+            return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+        }
+
+        _ = self
+            .sender_frontend
+            .send(ViewObject::Message(InterfaceMessage::ChildTxSent));
+        Ok(())
+    }
+
+    fn parse_tx_id_and_fee(
+        &self,
+        tx_id: String,
+        fee_string: String,
+    ) -> Result<(Uint256, u64), NodoBitcoinError> {
+        let fee: u64 = match fee_string.parse::<f64>() {
+            Ok(fee) => (fee * 100_000_000.0) as u64,
+            Err(_) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::FeeNotValid));
+                return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion);
+            }
+        };
+        let tx_id_bytes: Vec<u8> = (0..tx_id.len())
+            .step_by(2)
+            .filter_map(|i| tx_id.get(i..i + 2))
+            .filter_map(|byte| u8::from_str_radix(byte, 16).ok())
+            .collect();
+        let tx_id_bytes: [u8; 32] = match tx_id_bytes.try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::TxIdNotValid));
+                return Err(NodoBitcoinError::TxPendienteNoEncontrada);
+            }
+        };
+        // la tabla muestra el txid en little endian
+        Ok((Uint256::from_le_bytes(tx_id_bytes), fee))
+    }
+
     pub fn close(&self) -> Result<(), NodoBitcoinError> {
         start_loading(
             self.sender_frontend.clone(),
@@ -326,7 +878,7 @@ impl ApplicationManager {
         );
 
         log_info_message(self.logger.clone(), "Cerrando aplicación...".to_string());
-        _ = Account::save_all_accounts(self.accounts.clone());
+        self.save_accounts();
         _ = self
             .sender_app_manager
             .send(ApplicationManagerMessages::ShutDown);
@@ -343,6 +895,156 @@ impl ApplicationManager {
         Ok(current_account)
     }
 
+    /// Devuelve la cuenta seleccionada con sus claves privadas, o avisa a la vista si la
+    /// wallet está bloqueada o la cuenta es de solo lectura
+    fn get_signing_account(&self) -> Result<Account, NodoBitcoinError> {
+        let current_account = self.get_current_account()?;
+        match self
+            .billetera()?
+            .cuenta_para_firmar(&current_account.public_key)
+        {
+            Ok(account) => Ok(account),
+            Err(NodoBitcoinError::WalletBloqueada) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::WalletLocked));
+                Err(NodoBitcoinError::WalletBloqueada)
+            }
+            Err(NodoBitcoinError::CuentaSoloLectura) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::WatchOnlyAccount));
+                Err(NodoBitcoinError::CuentaSoloLectura)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn billetera(&self) -> Result<MutexGuard<'_, Billetera>, NodoBitcoinError> {
+        self.billetera
+            .lock()
+            .map_err(|_| NodoBitcoinError::ErrorAlAccederALaWallet)
+    }
+
+    fn save_accounts(&self) {
+        let guardadas = self.billetera().and_then(|billetera| billetera.guardar());
+        if let Err(error) = guardadas {
+            log_error_message(
+                self.logger.clone(),
+                format!("No se pudieron guardar las cuentas: {}", error),
+            );
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::SaveAccountsError));
+        }
+    }
+
+    // sin passphrase las claves privadas quedan en texto plano en el archivo de cuentas, se
+    // avisa al abrir la wallet y al agregar cuentas hasta que se configure una
+    fn advertir_si_sin_cifrar(&self) {
+        let sin_cifrar = match self.billetera() {
+            Ok(billetera) => billetera.guarda_secretos_sin_cifrar(),
+            Err(_) => false,
+        };
+        if sin_cifrar {
+            log_info_message(
+                self.logger.clone(),
+                "La wallet no tiene passphrase, las claves privadas se guardan sin cifrar."
+                    .to_string(),
+            );
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Message(InterfaceMessage::UnencryptedWallet));
+        }
+    }
+
+    /// Devuelve los nombres de las cuentas para la lista de la vista
+    pub fn account_names(&self) -> Vec<String> {
+        match self.billetera() {
+            Ok(billetera) => billetera
+                .accounts
+                .iter()
+                .map(|account| account.wallet_name.clone())
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Desbloquea la wallet con la passphrase y programa el bloqueo automático
+    pub fn unlock_wallet(&self, passphrase: String) -> Result<(), NodoBitcoinError> {
+        let desbloqueo = match self.billetera()?.desbloquear(&passphrase) {
+            Ok(desbloqueo) => desbloqueo,
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::WrongPassphrase));
+                return Err(error);
+            }
+        };
+        let sender_app_manager = self.sender_app_manager.clone();
+        programar_bloqueo(desbloqueo, move |desbloqueo| {
+            _ = sender_app_manager.send(ApplicationManagerMessages::LockWallet(desbloqueo));
+        });
+        log_info_message(self.logger.clone(), "Wallet desbloqueada.".to_string());
+        _ = self
+            .sender_frontend
+            .send(ViewObject::Message(InterfaceMessage::WalletUnlocked(
+                segundos_de_bloqueo(),
+            )));
+        Ok(())
+    }
+
+    /// Bloquea la wallet sin esperar el bloqueo automático
+    pub fn lock_wallet(&self) -> Result<(), NodoBitcoinError> {
+        self.billetera()?.bloquear();
+        log_info_message(self.logger.clone(), "Wallet bloqueada.".to_string());
+        _ = self
+            .sender_frontend
+            .send(ViewObject::Message(InterfaceMessage::WalletLocked));
+        Ok(())
+    }
+
+    /// Cifra las claves de la wallet con una passphrase nueva. Si la wallet ya tenía
+    /// passphrase hay que ingresar la actual
+    pub fn change_passphrase(
+        &self,
+        current: String,
+        new: String,
+        confirmation: String,
+    ) -> Result<(), NodoBitcoinError> {
+        if new.is_empty() || new != confirmation {
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(InterfaceError::PassphraseNotValid));
+            return Err(NodoBitcoinError::PassphraseIncorrecta);
+        }
+        let cambio = self.billetera()?.cambiar_passphrase(&current, &new);
+        match cambio {
+            Ok(_) => {
+                log_info_message(
+                    self.logger.clone(),
+                    "Claves de la wallet cifradas con la passphrase nueva.".to_string(),
+                );
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Message(InterfaceMessage::PassphraseChanged));
+                Ok(())
+            }
+            Err(NodoBitcoinError::PassphraseIncorrecta) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::WrongPassphrase));
+                Err(NodoBitcoinError::PassphraseIncorrecta)
+            }
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::SaveAccountsError));
+                Err(error)
+            }
+        }
+    }
+
     fn thread_download_blockchain(&mut self, intento: usize) {
         let logger = self.logger.clone();
         let sender_frontend = self.sender_frontend.clone();
@@ -399,29 +1101,217 @@ impl ApplicationManager {
         Ok(admin_connections)
     }
 
-    pub fn create_account(&mut self, secret_key: String, address: String, name: String) -> Account {
-        let new_account = Account::new(secret_key, address, name);
+    /// Crea la cuenta con la clave privada y la dirección, o una cuenta HD si la clave es
+    /// una clave privada extendida (xprv o tprv)
+    pub fn create_account(
+        &mut self,
+        secret_key: String,
+        address: String,
+        name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let new_account = if secret_key.starts_with("xprv") || secret_key.starts_with("tprv") {
+            match Account::desde_clave_extendida(&secret_key, name) {
+                Ok(account) => account,
+                Err(error) => {
+                    _ = self
+                        .sender_frontend
+                        .send(ViewObject::Error(InterfaceError::CreateAccount));
+                    return Err(error);
+                }
+            }
+        } else {
+            Account::new(secret_key, address, name)
+        };
+        let new_account = self.add_account(new_account)?;
+
+        _ = self
+            .sender_frontend
+            .send(ViewObject::Message(InterfaceMessage::CreateAccount));
 
+        Ok(new_account)
+    }
+
+    /// Crea una cuenta de solo lectura con una dirección o una clave pública extendida (xpub o
+    /// tpub). Al agregarla el transaction manager recorre los bloques guardados para sus UTXOs
+    pub fn create_watch_only_account(
+        &mut self,
+        address: String,
+        name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let new_account = match Account::solo_lectura(address.trim(), name) {
+            Ok(account) => account,
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::CreateAccount));
+                return Err(error);
+            }
+        };
+        let new_account = self.add_account(new_account)?;
+
+        _ = self.sender_frontend.send(ViewObject::Message(
+            InterfaceMessage::WatchOnlyAccountCreated,
+        ));
+
+        Ok(new_account)
+    }
+
+    /// Crea una cuenta multisig M de N con las claves públicas de los participantes en
+    /// hexadecimal, separadas por comas o espacios y en el orden del redeem script. La clave
+    /// privada es la de este participante, sin ella la cuenta es de solo lectura
+    pub fn create_multisig_account(
+        &mut self,
+        required: String,
+        public_keys: String,
+        secret_key: String,
+        segwit: bool,
+        name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let tipo = match segwit {
+            true => TipoMultisig::P2WSH,
+            false => TipoMultisig::P2SH,
+        };
+        let new_account = required
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| NodoBitcoinError::MultisigInvalido)
+            .and_then(|requeridas| {
+                let claves = public_keys
+                    .split(|caracter: char| caracter == ',' || caracter.is_whitespace())
+                    .filter(|clave| !clave.is_empty())
+                    .map(desde_hexa)
+                    .collect::<Result<Vec<Vec<u8>>, NodoBitcoinError>>()?;
+                Multisig::new(requeridas, claves, tipo)
+            })
+            .and_then(|multisig| Account::desde_multisig(multisig, secret_key.trim(), name));
+        let new_account = match new_account {
+            Ok(account) => account,
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::MultisigNotValid));
+                return Err(error);
+            }
+        };
+        let new_account = self.add_account(new_account)?;
+
+        _ = self.sender_frontend.send(ViewObject::Message(
+            InterfaceMessage::MultisigAccountCreated(new_account.public_key.clone()),
+        ));
+
+        Ok(new_account)
+    }
+
+    /// Genera una clave privada nueva en el nodo y registra la cuenta con su dirección, P2WPKH
+    /// si se pide segwit
+    pub fn generate_account(
+        &mut self,
+        name: String,
+        segwit: bool,
+    ) -> Result<Account, NodoBitcoinError> {
+        let new_account = match Account::generar(name, segwit) {
+            Ok(account) => account,
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::CreateAccount));
+                return Err(error);
+            }
+        };
+        let new_account = self.add_account(new_account)?;
+
+        _ = self
+            .sender_frontend
+            .send(ViewObject::Message(InterfaceMessage::AccountGenerated(
+                new_account.public_key.clone(),
+                new_account.secret_key.clone(),
+            )));
+
+        Ok(new_account)
+    }
+
+    /// Genera las palabras BIP39 de una wallet nueva. No se guardan, se muestran una sola vez
+    /// para que el usuario las anote y después cree la cuenta con ellas
+    pub fn generate_mnemonic(&self, words: String) -> Result<String, NodoBitcoinError> {
+        let words = match words.parse::<usize>() {
+            Ok(words) => words,
+            Err(_) => return Err(NodoBitcoinError::MnemonicoInvalido),
+        };
+        match generar_mnemonico(words) {
+            Ok(mnemonic) => Ok(mnemonic),
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::MnemonicNotValid));
+                Err(error)
+            }
+        }
+    }
+
+    /// Crea o restaura la cuenta HD de un mnemónico BIP39. Al agregarla el transaction manager
+    /// vuelve a recorrer los bloques guardados para reconstruir sus UTXOs
+    pub fn restore_account(
+        &mut self,
+        mnemonic: String,
+        passphrase: String,
+        name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let new_account = match Account::desde_mnemonico(&mnemonic, &passphrase, name) {
+            Ok(account) => account,
+            Err(error) => {
+                _ = self
+                    .sender_frontend
+                    .send(ViewObject::Error(InterfaceError::MnemonicNotValid));
+                return Err(error);
+            }
+        };
+        log_info_message(
+            self.logger.clone(),
+            format!(
+                "Restaurando la cuenta {} desde el mnemónico ...",
+                new_account.public_key
+            ),
+        );
+        let new_account = self.add_account(new_account)?;
+
+        _ = self
+            .sender_frontend
+            .send(ViewObject::Message(InterfaceMessage::AccountRestored));
+
+        Ok(new_account)
+    }
+
+    fn add_account(&mut self, new_account: Account) -> Result<Account, NodoBitcoinError> {
+        let mut billetera = self.billetera()?;
         let is_valid =
-            ApplicationManager::account_validator(new_account.clone(), self.accounts.clone());
+            ApplicationManager::account_validator(new_account.clone(), billetera.accounts.clone());
         if !is_valid {
             _ = self
                 .sender_frontend
                 .send(ViewObject::Error(InterfaceError::CreateAccount));
         }
 
-        self.accounts.push(new_account.clone());
+        // con passphrase la wallet tiene que estar desbloqueada para cifrar la clave nueva
+        if let Err(error) = billetera.agregar(new_account.clone()) {
+            let interface_error = match error {
+                NodoBitcoinError::WalletBloqueada => InterfaceError::WalletLocked,
+                _ => InterfaceError::CreateAccount,
+            };
+            _ = self
+                .sender_frontend
+                .send(ViewObject::Error(interface_error));
+            return Err(error);
+        }
 
         let _ = self.tx_manager.send(TransactionMessages::AddAccount(
-            self.accounts.clone(),
+            billetera.cuentas_sin_secretos(),
             self.logger.clone(),
         ));
+        drop(billetera);
+        self.save_accounts();
+        self.advertir_si_sin_cifrar();
 
-        _ = self
-            .sender_frontend
-            .send(ViewObject::Message(InterfaceMessage::CreateAccount));
-
-        new_account
+        Ok(new_account)
     }
 
     fn account_validator(new_account: Account, accounts: Vec<Account>) -> bool {
@@ -471,7 +1361,7 @@ impl ApplicationManager {
     }
 
     pub fn select_current_account(&mut self, name: String) -> Result<(), NodoBitcoinError> {
-        let accounts = self.accounts.clone();
+        let accounts = self.billetera()?.cuentas_sin_secretos();
         let mut current_account = None;
         for account in accounts.iter() {
             if account.wallet_name == name {
@@ -490,3 +1380,19 @@ impl ApplicationManager {
             .send(TransactionMessages::GetMerklePath(block_hash, tx_id));
     }
 }
+
+/// Lee los destinatarios `direccion=monto` separados por comas o saltos de línea, con el
+/// monto en BTC. Devuelve None si alguno no tiene ese formato
+fn leer_destinatarios(texto: &str) -> Option<Vec<(String, u64)>> {
+    let destinos = texto
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|destino| !destino.is_empty())
+        .map(|destino| {
+            let (direccion, monto) = destino.split_once('=')?;
+            let monto = monto.trim().parse::<f64>().ok()?;
+            Some((direccion.trim().to_string(), (monto * 100_000_000.0) as u64))
+        })
+        .collect::<Option<Vec<(String, u64)>>>()?;
+    (!destinos.is_empty()).then_some(destinos)
+}
@@ -273,6 +273,7 @@ mod tests {
             },
             script_bytes: 31,
             script_bytes_amount: 1,
+            witness: vec![],
             signature_script: vec![
                 4, 231, 190, 191, 79, 1, 34, 23, 47, 80, 50, 83, 72, 47, 73, 99, 101, 73, 114, 111,
                 110, 45, 81, 67, 45, 109, 105, 110, 101, 114, 47,
@@ -0,0 +1,397 @@
+use crate::blockchain::blockheader::BlockHeader;
+use crate::blockchain::file::{get_file_size, leer_bytes};
+use crate::common::red::red_configurada;
+use crate::errores::NodoBitcoinError;
+use bitcoin_hashes::{sha256d, Hash};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    mem,
+};
+
+/// Formato de cada registro de los archivos de bloques (blk00000.dat, blk00001.dat, ...):
+/// magic de la red (4) + largo del bloque en u32 little endian (4) + checksum (4) + bytes del bloque
+pub const TAMANIO_CABECERA_REGISTRO: u64 = 12;
+
+/// Tamaño de un header, que es lo primero que hay en los bytes de cada bloque
+const TAMANIO_HEADER: u64 = 80;
+
+/// Tamaño máximo por defecto de cada archivo de bloques antes de rotar al siguiente
+pub const TAMANIO_MAXIMO_ARCHIVO_DEFAULT: u64 = 128 * 1024 * 1024;
+
+/// Ubicación de un bloque: número de archivo y offset del registro dentro del archivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct UbicacionBloque {
+    pub archivo: u32,
+    pub offset: u64,
+}
+
+impl UbicacionBloque {
+    pub const TAMANIO: usize = 12;
+
+    pub fn serialize(&self) -> [u8; 12] {
+        let mut bytes = [0u8; 12];
+        bytes[0..4].copy_from_slice(&self.archivo.to_le_bytes());
+        bytes[4..12].copy_from_slice(&self.offset.to_le_bytes());
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<UbicacionBloque, NodoBitcoinError> {
+        if bytes.len() < Self::TAMANIO {
+            return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+        }
+        let archivo = u32::from_le_bytes(
+            bytes[0..4]
+                .try_into()
+                .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
+        );
+        let offset = u64::from_le_bytes(
+            bytes[4..12]
+                .try_into()
+                .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
+        );
+        Ok(UbicacionBloque { archivo, offset })
+    }
+}
+
+/// Devuelve el path del archivo de bloques con el número recibido
+pub fn nombre_archivo_bloques(dir: &str, numero: u32) -> String {
+    format!("{}/blk{:05}.dat", dir, numero)
+}
+
+fn checksum(datos: &[u8]) -> [u8; 4] {
+    let hash = sha256d::Hash::hash(datos);
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&hash[..4]);
+    checksum
+}
+
+/// Recibe los bytes de un bloque
+/// Devuelve el registro con magic, largo y checksum listo para escribir en el archivo
+pub fn crear_registro(datos: &[u8]) -> Result<Vec<u8>, NodoBitcoinError> {
+    let len = u32::try_from(datos.len()).map_err(|_| NodoBitcoinError::ValorFueraDeRango)?;
+    let mut registro = Vec::with_capacity(datos.len() + TAMANIO_CABECERA_REGISTRO as usize);
+    registro.extend_from_slice(&red_configurada().magic_number());
+    registro.extend_from_slice(&len.to_le_bytes());
+    registro.extend_from_slice(&checksum(datos));
+    registro.extend_from_slice(datos);
+    Ok(registro)
+}
+
+/// Recibe la cabecera de un registro
+/// Devuelve el largo del bloque y su checksum si el magic es el de la red
+fn parsear_cabecera(cabecera: &[u8]) -> Result<(u64, [u8; 4]), NodoBitcoinError> {
+    if cabecera.len() < TAMANIO_CABECERA_REGISTRO as usize {
+        return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+    }
+    if cabecera[0..4] != red_configurada().magic_number() {
+        return Err(NodoBitcoinError::MagicNumberIncorrecto);
+    }
+    let len = u32::from_le_bytes(
+        cabecera[4..8]
+            .try_into()
+            .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
+    );
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&cabecera[8..12]);
+    Ok((len as u64, checksum))
+}
+
+/// Devuelve ordenados los números de los archivos de bloques que hay en el directorio
+pub fn numeros_de_archivos(dir: &str) -> Vec<u32> {
+    let entradas = match fs::read_dir(dir) {
+        Ok(entradas) => entradas,
+        Err(_) => return vec![],
+    };
+    let mut numeros = entradas
+        .filter_map(|entrada| entrada.ok())
+        .filter_map(|entrada| {
+            let nombre = entrada.file_name().into_string().ok()?;
+            let numero = nombre.strip_prefix("blk")?.strip_suffix(".dat")?;
+            numero.parse::<u32>().ok()
+        })
+        .collect::<Vec<u32>>();
+    numeros.sort();
+    numeros
+}
+
+/// Recibe el directorio de bloques, los bytes del bloque y el tamaño máximo de cada archivo
+/// Escribe el registro en el último archivo, o en uno nuevo si el último superaría el tamaño máximo
+/// Devuelve la ubicación donde quedó guardado el bloque
+pub fn escribir_registro(
+    dir: &str,
+    datos: &[u8],
+    tamanio_maximo: u64,
+) -> Result<UbicacionBloque, NodoBitcoinError> {
+    fs::create_dir_all(dir).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    let registro = crear_registro(datos)?;
+
+    let mut archivo = numeros_de_archivos(dir).last().copied().unwrap_or(0);
+    let mut offset = get_file_size(nombre_archivo_bloques(dir, archivo)).unwrap_or(0);
+    if offset > 0 && offset + registro.len() as u64 > tamanio_maximo {
+        archivo += 1;
+        offset = 0;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(nombre_archivo_bloques(dir, archivo))
+        .map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    file.write_all(&registro)
+        .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+    Ok(UbicacionBloque { archivo, offset })
+}
+
+/// Recibe el directorio de bloques y la ubicación del registro
+/// Devuelve los bytes del bloque, validando magic y checksum, y el offset del registro siguiente
+pub fn leer_registro(
+    dir: &str,
+    ubicacion: UbicacionBloque,
+) -> Result<(Vec<u8>, u64), NodoBitcoinError> {
+    let path = nombre_archivo_bloques(dir, ubicacion.archivo);
+    let cabecera = leer_bytes(path.clone(), ubicacion.offset, TAMANIO_CABECERA_REGISTRO)?;
+    let (len, checksum_guardado) = parsear_cabecera(&cabecera)?;
+    let datos = leer_bytes(path, ubicacion.offset + TAMANIO_CABECERA_REGISTRO, len)?;
+    if checksum(&datos) != checksum_guardado {
+        return Err(NodoBitcoinError::ChecksumIncorrecto);
+    }
+    Ok((datos, ubicacion.offset + TAMANIO_CABECERA_REGISTRO + len))
+}
+
+/// Recibe el directorio de bloques y el número de archivo
+/// Recorre las cabeceras de los registros sin leer los bloques completos
+/// Devuelve las ubicaciones de los registros completos y el offset donde termina el último de ellos
+pub fn recorrer_registros(
+    dir: &str,
+    archivo: u32,
+) -> Result<(Vec<UbicacionBloque>, u64), NodoBitcoinError> {
+    let path = nombre_archivo_bloques(dir, archivo);
+    let len_archivo = get_file_size(path.clone())?;
+    let mut ubicaciones = vec![];
+    let mut offset = 0;
+    while offset + TAMANIO_CABECERA_REGISTRO <= len_archivo {
+        let cabecera = leer_bytes(path.clone(), offset, TAMANIO_CABECERA_REGISTRO)?;
+        let len = match parsear_cabecera(&cabecera) {
+            Ok((len, _)) => len,
+            Err(_) => break,
+        };
+        let siguiente = offset + TAMANIO_CABECERA_REGISTRO + len;
+        if siguiente > len_archivo {
+            break;
+        }
+        ubicaciones.push(UbicacionBloque { archivo, offset });
+        offset = siguiente;
+    }
+    Ok((ubicaciones, offset))
+}
+
+/// Recibe el directorio de bloques y el número de archivo
+/// Devuelve los headers de los bloques guardados en el archivo, leyendo solo sus primeros 80 bytes
+pub fn headers_de_archivo(dir: &str, archivo: u32) -> Result<Vec<BlockHeader>, NodoBitcoinError> {
+    let path = nombre_archivo_bloques(dir, archivo);
+    let (ubicaciones, _) = recorrer_registros(dir, archivo)?;
+    let mut headers = Vec::with_capacity(ubicaciones.len());
+    for ubicacion in ubicaciones {
+        let bytes = leer_bytes(
+            path.clone(),
+            ubicacion.offset + TAMANIO_CABECERA_REGISTRO,
+            TAMANIO_HEADER,
+        )?;
+        headers.push(BlockHeader::deserialize(&bytes)?);
+    }
+    Ok(headers)
+}
+
+/// Recibe el directorio de bloques
+/// Devuelve todos los bloques guardados, en orden, junto a su ubicación
+pub fn leer_todos_los_registros(
+    dir: &str,
+) -> Result<Vec<(UbicacionBloque, Vec<u8>)>, NodoBitcoinError> {
+    let mut todos = vec![];
+    for archivo in numeros_de_archivos(dir) {
+        let len_archivo = get_file_size(nombre_archivo_bloques(dir, archivo))?;
+        let mut offset = 0;
+        while offset < len_archivo {
+            let ubicacion = UbicacionBloque { archivo, offset };
+            let (datos, siguiente) = leer_registro(dir, ubicacion)?;
+            todos.push((ubicacion, datos));
+            offset = siguiente;
+        }
+    }
+    Ok(todos)
+}
+
+/// Lee un bloque del archivo con el formato anterior (largo como usize nativo + bytes del bloque)
+/// Devuelve los bytes del bloque y el offset del siguiente
+fn leer_bloque_formato_anterior(
+    path: String,
+    offset: u64,
+) -> Result<(Vec<u8>, u64), NodoBitcoinError> {
+    let sizeof_usize = mem::size_of::<usize>() as u64;
+    let from_file = leer_bytes(path.clone(), offset, sizeof_usize)?;
+    let len_bytes: [u8; 8] = match from_file.as_slice().try_into() {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
+    };
+    let len_block = usize::from_ne_bytes(len_bytes) as u64;
+    let block_bytes = leer_bytes(path, offset + sizeof_usize, len_block)?;
+    Ok((block_bytes, offset + sizeof_usize + len_block))
+}
+
+/// Recibe el archivo de bloques con el formato anterior y el directorio donde guardar los nuevos
+/// Copia cada bloque al nuevo formato y llama a `al_migrar` con el bloque y su nueva ubicación
+/// Devuelve la cantidad de bloques migrados
+pub fn migrar_formato_anterior<F>(
+    path_anterior: String,
+    dir: &str,
+    tamanio_maximo: u64,
+    mut al_migrar: F,
+) -> Result<u64, NodoBitcoinError>
+where
+    F: FnMut(&[u8], UbicacionBloque),
+{
+    if !numeros_de_archivos(dir).is_empty() {
+        return Err(NodoBitcoinError::ArchivoDeBloquesExistente);
+    }
+    let len_archivo = get_file_size(path_anterior.clone())?;
+    let mut offset = 0;
+    let mut cantidad = 0;
+    while offset < len_archivo {
+        let (datos, siguiente) = leer_bloque_formato_anterior(path_anterior.clone(), offset)?;
+        let ubicacion = escribir_registro(dir, &datos, tamanio_maximo)?;
+        al_migrar(&datos, ubicacion);
+        offset = siguiente;
+        cantidad += 1;
+    }
+    Ok(cantidad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::messages_header::MAGIC_NUMBER_TESTNET;
+
+    fn dir_de_prueba(nombre: &str) -> String {
+        let dir = std::env::temp_dir().join(nombre);
+        _ = fs::remove_dir_all(&dir);
+        dir.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_registro_tiene_magic_largo_y_checksum() {
+        let datos = vec![1, 2, 3, 4, 5];
+        let registro = crear_registro(&datos).unwrap();
+        assert_eq!(registro.len(), 17);
+        assert_eq!(registro[0..4], MAGIC_NUMBER_TESTNET);
+        assert_eq!(registro[4..8], [5, 0, 0, 0]);
+        assert_eq!(registro[8..12], checksum(&datos));
+        assert_eq!(registro[12..], datos[..]);
+    }
+
+    #[test]
+    fn test_escribir_y_leer_con_rotacion() {
+        let dir = dir_de_prueba("block_file_test_rotacion");
+        // cada registro ocupa 12 + 20 bytes, entran dos por archivo
+        let primero = escribir_registro(&dir, &[1; 20], 64).unwrap();
+        let segundo = escribir_registro(&dir, &[2; 20], 64).unwrap();
+        let tercero = escribir_registro(&dir, &[3; 20], 64).unwrap();
+
+        assert_eq!(
+            primero,
+            UbicacionBloque {
+                archivo: 0,
+                offset: 0
+            }
+        );
+        assert_eq!(
+            segundo,
+            UbicacionBloque {
+                archivo: 0,
+                offset: 32
+            }
+        );
+        assert_eq!(
+            tercero,
+            UbicacionBloque {
+                archivo: 1,
+                offset: 0
+            }
+        );
+        assert_eq!(numeros_de_archivos(&dir), vec![0, 1]);
+
+        let (datos, siguiente) = leer_registro(&dir, segundo).unwrap();
+        assert_eq!(datos, vec![2; 20]);
+        assert_eq!(siguiente, 64);
+
+        let todos = leer_todos_los_registros(&dir).unwrap();
+        assert_eq!(todos.len(), 3);
+        assert_eq!(todos[2], (tercero, vec![3; 20]));
+        _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_leer_registro_con_checksum_incorrecto() {
+        let dir = dir_de_prueba("block_file_test_checksum");
+        let ubicacion = escribir_registro(&dir, &[7; 10], TAMANIO_MAXIMO_ARCHIVO_DEFAULT).unwrap();
+
+        let path = nombre_archivo_bloques(&dir, 0);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[15] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        assert_eq!(
+            leer_registro(&dir, ubicacion),
+            Err(NodoBitcoinError::ChecksumIncorrecto)
+        );
+        _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recorrer_registros_con_registro_truncado() {
+        let dir = dir_de_prueba("block_file_test_recorrer");
+        escribir_registro(&dir, &[1; 10], TAMANIO_MAXIMO_ARCHIVO_DEFAULT).unwrap();
+        escribir_registro(&dir, &[2; 10], TAMANIO_MAXIMO_ARCHIVO_DEFAULT).unwrap();
+
+        let path = nombre_archivo_bloques(&dir, 0);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 3);
+        fs::write(&path, bytes).unwrap();
+
+        let (ubicaciones, fin) = recorrer_registros(&dir, 0).unwrap();
+        assert_eq!(ubicaciones, vec![UbicacionBloque::default()]);
+        assert_eq!(fin, 22);
+        _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrar_formato_anterior() {
+        let dir = dir_de_prueba("block_file_test_migrar");
+        fs::create_dir_all(&dir).unwrap();
+        let path_anterior = format!("{}/blocks.bin", dir);
+        let mut anterior = vec![];
+        for bloque in [vec![1u8; 3], vec![2u8; 5]] {
+            anterior.extend_from_slice(&bloque.len().to_ne_bytes());
+            anterior.extend_from_slice(&bloque);
+        }
+        fs::write(&path_anterior, anterior).unwrap();
+
+        let dir_nuevo = format!("{}/blocks", dir);
+        let mut migrados = vec![];
+        let cantidad = migrar_formato_anterior(
+            path_anterior.clone(),
+            &dir_nuevo,
+            TAMANIO_MAXIMO_ARCHIVO_DEFAULT,
+            |datos, ubicacion| migrados.push((ubicacion, datos.to_vec())),
+        )
+        .unwrap();
+
+        assert_eq!(cantidad, 2);
+        assert_eq!(leer_todos_los_registros(&dir_nuevo).unwrap(), migrados);
+        assert_eq!(migrados[1].1, vec![2u8; 5]);
+
+        // no se migra sobre un directorio que ya tiene bloques
+        assert!(migrar_formato_anterior(path_anterior, &dir_nuevo, 64, |_, _| {}).is_err());
+        _ = fs::remove_dir_all(&dir);
+    }
+}
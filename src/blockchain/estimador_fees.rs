@@ -0,0 +1,292 @@
+use crate::blockchain::mempool::{EntradaMempool, Mempool};
+use crate::blockchain::politica::FEE_RATE_MINIMO_RELAY;
+use crate::common::uint256::Uint256;
+use std::collections::{HashMap, HashSet};
+
+/// Máxima cantidad de bloques que se puede pedir como objetivo de confirmación
+pub const MAXIMO_OBJETIVO: usize = 25;
+
+/// Fee en sat/vB mínimo que devuelve el estimador, el mínimo para que se retransmita
+pub const FEE_RATE_MINIMO: u64 = FEE_RATE_MINIMO_RELAY;
+
+/// Tamaño virtual máximo de un bloque (4 000 000 de peso), para estimar cuántos bloques tarda
+/// en vaciarse la mempool
+const VSIZE_MAXIMO_BLOQUE: usize = 1_000_000;
+
+/// Primer límite y separación de los buckets de sat/vB
+const PRIMER_BUCKET: f64 = 1.0;
+const ESPACIADO_BUCKETS: f64 = 1.1;
+const ULTIMO_BUCKET: f64 = 10_000.0;
+
+/// Peso que conserva en cada bloque la historia anterior, así las estimaciones siguen a la red
+const DECAIMIENTO: f64 = 0.998;
+
+/// Proporción de transacciones que se tuvieron que confirmar dentro del objetivo
+const EXITO_MINIMO: f64 = 0.85;
+
+/// Cantidad mínima de transacciones observadas para usar un grupo de buckets
+const MINIMO_DATOS: f64 = 5.0;
+
+/// Estimador de fees a partir de cuánto tardan en confirmarse las transacciones de la mempool
+/// Agrupa las transacciones en buckets de fee por byte virtual y para cada uno cuenta cuántas se
+/// confirmaron dentro de cada cantidad de bloques
+#[derive(Debug, Clone)]
+pub struct EstimadorFees {
+    // sat/vB mínimo de cada bucket, de menor a mayor
+    limites: Vec<f64>,
+    // por bucket, las confirmadas en a lo sumo i + 1 bloques
+    confirmadas: Vec<Vec<f64>>,
+    // por bucket, todas las confirmadas
+    totales: Vec<f64>,
+    // transacciones de la mempool que se están siguiendo, con la altura en la que entraron y su bucket
+    sin_confirmar: HashMap<Uint256, (u32, usize)>,
+    altura: u32,
+}
+
+impl Default for EstimadorFees {
+    fn default() -> Self {
+        EstimadorFees::new()
+    }
+}
+
+impl EstimadorFees {
+    pub fn new() -> EstimadorFees {
+        let mut limites = vec![];
+        let mut limite = PRIMER_BUCKET;
+        while limite <= ULTIMO_BUCKET {
+            limites.push(limite);
+            limite *= ESPACIADO_BUCKETS;
+        }
+        EstimadorFees {
+            confirmadas: vec![vec![0.0; MAXIMO_OBJETIVO]; limites.len()],
+            totales: vec![0.0; limites.len()],
+            limites,
+            sin_confirmar: HashMap::new(),
+            altura: 0,
+        }
+    }
+
+    fn bucket(&self, fee_rate: f64) -> usize {
+        self.limites
+            .iter()
+            .rposition(|limite| *limite <= fee_rate)
+            .unwrap_or(0)
+    }
+
+    /// Empieza a seguir una transacción que se acaba de aceptar en la mempool
+    pub fn registrar_tx(&mut self, txid: Uint256, entrada: &EntradaMempool) {
+        if entrada.tamanio == 0 {
+            return;
+        }
+        let bucket = self.bucket(entrada.fee as f64 / entrada.tamanio as f64);
+        self.sin_confirmar
+            .entry(txid)
+            .or_insert((self.altura, bucket));
+    }
+
+    /// Deja de seguir una transacción que salió de la mempool sin confirmarse
+    pub fn quitar_tx(&mut self, txid: &Uint256) {
+        self.sin_confirmar.remove(txid);
+    }
+
+    /// Deja de seguir las transacciones que ya no están en la mempool
+    pub fn quitar_ausentes(&mut self, en_mempool: &HashSet<Uint256>) {
+        self.sin_confirmar
+            .retain(|txid, _| en_mempool.contains(txid));
+    }
+
+    /// Recibe los txids de un bloque nuevo y registra cuántos bloques tardaron en confirmarse
+    /// las transacciones que se estaban siguiendo
+    pub fn procesar_bloque(&mut self, txids: &[Uint256]) {
+        self.altura += 1;
+        for (confirmadas, total) in self.confirmadas.iter_mut().zip(self.totales.iter_mut()) {
+            for cantidad in confirmadas.iter_mut() {
+                *cantidad *= DECAIMIENTO;
+            }
+            *total *= DECAIMIENTO;
+        }
+        for txid in txids {
+            let (altura_entrada, bucket) = match self.sin_confirmar.remove(txid) {
+                Some(seguida) => seguida,
+                None => continue,
+            };
+            let bloques = (self.altura - altura_entrada).max(1) as usize;
+            self.totales[bucket] += 1.0;
+            for objetivo in bloques..=MAXIMO_OBJETIVO {
+                self.confirmadas[bucket][objetivo - 1] += 1.0;
+            }
+        }
+    }
+
+    /// Devuelve el menor sat/vB con el que, según lo observado, al menos el 85% de las
+    /// transacciones se confirmaron dentro del objetivo de bloques
+    /// Si no hay suficientes datos devuelve None
+    pub fn estimar_por_historial(&self, objetivo: usize) -> Option<f64> {
+        let objetivo = objetivo.clamp(1, MAXIMO_OBJETIVO);
+        // las que siguen sin confirmar después del objetivo también cuentan como fallidas
+        let mut esperando = vec![0.0; self.limites.len()];
+        for (altura_entrada, bucket) in self.sin_confirmar.values() {
+            if (self.altura - altura_entrada) as usize >= objetivo {
+                esperando[*bucket] += 1.0;
+            }
+        }
+
+        let mut estimacion = None;
+        let mut datos = 0.0;
+        let mut exitos = 0.0;
+        for bucket in (0..self.limites.len()).rev() {
+            datos += self.totales[bucket] + esperando[bucket];
+            exitos += self.confirmadas[bucket][objetivo - 1];
+            if datos < MINIMO_DATOS {
+                continue;
+            }
+            if exitos / datos < EXITO_MINIMO {
+                break;
+            }
+            estimacion = Some(self.limites[bucket]);
+            datos = 0.0;
+            exitos = 0.0;
+        }
+        estimacion
+    }
+
+    /// Devuelve el sat/vB de la transacción que quedaría última en entrar en los próximos
+    /// bloques del objetivo si se minara la mempool de mayor a menor fee por byte virtual
+    pub fn estimar_por_mempool(objetivo: usize, mempool: &Mempool) -> f64 {
+        let entradas: Vec<&EntradaMempool> = mempool
+            .txids()
+            .iter()
+            .filter_map(|txid| mempool.obtener(txid))
+            .collect();
+        fee_rate_de_corte(
+            entradas,
+            objetivo.clamp(1, MAXIMO_OBJETIVO) * VSIZE_MAXIMO_BLOQUE,
+        )
+    }
+
+    /// Devuelve el fee en satoshis por byte virtual (sat/vB) para confirmarse dentro del
+    /// objetivo de bloques
+    /// Usa lo observado en los bloques y, si todavía no hay suficientes datos, la mempool actual
+    pub fn estimar_sat_por_vbyte(&self, objetivo: usize, mempool: &Mempool) -> u64 {
+        let fee_rate = match self.estimar_por_historial(objetivo) {
+            Some(fee_rate) => fee_rate,
+            None => EstimadorFees::estimar_por_mempool(objetivo, mempool),
+        };
+        (fee_rate.ceil() as u64).max(FEE_RATE_MINIMO)
+    }
+}
+
+/// Ordena las entradas de mayor a menor fee por byte virtual y devuelve el de la primera que no entra
+/// en el espacio, o el mínimo si entran todas
+fn fee_rate_de_corte(mut entradas: Vec<&EntradaMempool>, espacio: usize) -> f64 {
+    entradas.sort_by(|a, b| b.comparar_fee_rate(a));
+    let mut ocupado = 0;
+    for entrada in entradas {
+        ocupado += entrada.tamanio;
+        if ocupado > espacio {
+            return entrada.fee as f64 / entrada.tamanio as f64;
+        }
+    }
+    FEE_RATE_MINIMO as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::transaction::{Transaction, TxIn, TxOut};
+
+    fn entrada(fee: u64, tamanio: usize) -> EntradaMempool {
+        let tx = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([1; 32]), 0)],
+            vec![TxOut {
+                value: 10,
+                pk_len: 0,
+                pk_script: vec![],
+                pk_len_bytes: 1,
+            }],
+            0,
+        )
+        .unwrap();
+        EntradaMempool {
+            tx,
+            fee,
+            tamanio,
+            hora: 0,
+        }
+    }
+
+    fn txid(semilla: u8, indice: u8) -> Uint256 {
+        let mut bytes = [semilla; 32];
+        bytes[0] = indice;
+        Uint256::from_be_bytes(bytes)
+    }
+
+    #[test]
+    fn test_estimar_por_historial() {
+        let mut estimador = EstimadorFees::new();
+        assert_eq!(estimador.estimar_por_historial(1), None);
+
+        // las de 50 sat/vB se confirman en el bloque siguiente y las de 5 en el tercero
+        let rapidas: Vec<Uint256> = (0..10).map(|i| txid(1, i)).collect();
+        let lentas: Vec<Uint256> = (0..10).map(|i| txid(2, i)).collect();
+        for txid in rapidas.iter() {
+            estimador.registrar_tx(*txid, &entrada(5000, 100));
+        }
+        for txid in lentas.iter() {
+            estimador.registrar_tx(*txid, &entrada(500, 100));
+        }
+        estimador.procesar_bloque(&rapidas);
+        estimador.procesar_bloque(&[]);
+        estimador.procesar_bloque(&lentas);
+
+        let rapido = estimador.estimar_por_historial(1).unwrap();
+        assert!((45.0..=50.0).contains(&rapido));
+        let lento = estimador.estimar_por_historial(3).unwrap();
+        assert!((4.5..=5.0).contains(&lento));
+        assert!(estimador.estimar_por_historial(2).unwrap() >= rapido);
+    }
+
+    #[test]
+    fn test_estimar_con_testigos_usa_vsize() {
+        // la mayor parte de la transacción es testigo, así que pesa mucho menos que su tamaño
+        let mut tx = entrada(0, 0).tx;
+        tx.input[0].witness = vec![vec![1; 72], vec![2; 33], vec![3; 400]];
+        let vsize = tx.vsize().unwrap();
+        assert!(tx.size() > 2 * vsize);
+
+        let mut estimador = EstimadorFees::new();
+        let segwit: Vec<Uint256> = (0..10).map(|i| txid(3, i)).collect();
+        for txid in segwit.iter() {
+            let entrada = EntradaMempool {
+                tx: tx.clone(),
+                fee: 20 * vsize as u64,
+                tamanio: vsize,
+                hora: 0,
+            };
+            estimador.registrar_tx(*txid, &entrada);
+        }
+        estimador.procesar_bloque(&segwit);
+
+        let sat_por_vbyte = estimador.estimar_por_historial(1).unwrap();
+        assert!((18.0..=20.0).contains(&sat_por_vbyte));
+        assert_eq!(
+            estimador.estimar_sat_por_vbyte(1, &Mempool::default()),
+            sat_por_vbyte.ceil() as u64
+        );
+    }
+
+    #[test]
+    fn test_estimar_por_mempool() {
+        let estimador = EstimadorFees::new();
+        assert_eq!(
+            estimador.estimar_sat_por_vbyte(6, &Mempool::default()),
+            FEE_RATE_MINIMO
+        );
+
+        let entradas = [entrada(300, 100), entrada(2000, 100), entrada(1000, 100)];
+        let entradas: Vec<&EntradaMempool> = entradas.iter().collect();
+        assert_eq!(fee_rate_de_corte(entradas.clone(), 150), 10.0);
+        assert_eq!(fee_rate_de_corte(entradas.clone(), 250), 3.0);
+        assert_eq!(fee_rate_de_corte(entradas, 300), FEE_RATE_MINIMO as f64);
+    }
+}
@@ -1,8 +1,15 @@
-use crate::blockchain::index::dump_hash_in_the_index;
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::block_file::{
+    escribir_registro, leer_registro, leer_todos_los_registros, migrar_formato_anterior,
+    UbicacionBloque, TAMANIO_MAXIMO_ARCHIVO_DEFAULT,
+};
+use crate::blockchain::index::{
+    borrar_indices_opcionales, dump_hash_in_the_index, indexar_bloque_guardado,
+};
+use crate::blockchain::journal::NOMBRE_ARCHIVO_JOURNAL_DEFAULT;
 use std::{
     fs::{File, OpenOptions},
     io::{Read, Seek, SeekFrom, Write},
-    mem,
     path::Path,
 };
 
@@ -11,27 +18,48 @@ use crate::{config, errores::NodoBitcoinError};
 use super::blockheader::BlockHeader;
 
 /// Devuelve el nombre del archivo de heades guardado en el config
-/// 
+///
 pub fn get_headers_filename() -> Result<String, NodoBitcoinError> {
     config::get_valor("NOMBRE_ARCHIVO_HEADERS".to_string())
 }
 
-/// Devuelve el nombre del archivo de bloques guardado en el config
+/// Devuelve el nombre del archivo de bloques con el formato anterior guardado en el config
+/// Solo se usa para migrar al formato de archivos numerados
 pub fn get_blocks_filename() -> Result<String, NodoBitcoinError> {
     config::get_valor("NOMBRE_ARCHIVO_BLOQUES".to_string())
 }
 
-/// Lee todos los bytes de los bloques del archivo y los guarda en un vector
+/// Devuelve el directorio de los archivos de bloques (blk00000.dat, ...) guardado en el config
+pub fn get_blocks_dirname() -> Result<String, NodoBitcoinError> {
+    config::get_valor("DIRECTORIO_BLOQUES".to_string())
+}
+
+/// Devuelve el nombre del journal de escrituras guardado en el config, o el valor por defecto
+pub fn get_journal_filename() -> String {
+    match config::get_valor("NOMBRE_ARCHIVO_JOURNAL".to_string()) {
+        Ok(valor) => valor,
+        Err(_) => NOMBRE_ARCHIVO_JOURNAL_DEFAULT.to_string(),
+    }
+}
+
+/// Devuelve el tamaño máximo de cada archivo de bloques guardado en el config, o el valor por defecto
+pub fn tamanio_maximo_archivo_bloques() -> u64 {
+    match config::get_valor("TAMANIO_MAXIMO_ARCHIVO_BLOQUES".to_string()) {
+        Ok(valor) => valor
+            .parse::<u64>()
+            .unwrap_or(TAMANIO_MAXIMO_ARCHIVO_DEFAULT),
+        Err(_) => TAMANIO_MAXIMO_ARCHIVO_DEFAULT,
+    }
+}
+
+/// Lee todos los bytes de los bloques de los archivos y los guarda en un vector
 /// Devuelve el vector de bytes de los bloques
 pub fn leer_todos_blocks() -> Result<Vec<Vec<u8>>, NodoBitcoinError> {
-    let mut todos = vec![];
-    let mut offset = 0;
-    let block_file_len = get_file_blocks_size()?;
-    while offset < block_file_len {
-        let (bytes, new_offset) = leer_bloque(offset)?;
-        todos.push(bytes);
-        offset = new_offset;
-    }
+    let dir = get_blocks_dirname()?;
+    let todos = leer_todos_los_registros(&dir)?
+        .into_iter()
+        .map(|(_, bytes)| bytes)
+        .collect();
     Ok(todos)
 }
 
@@ -56,25 +84,38 @@ pub fn escribir_archivo(path: String, datos: &[u8]) -> Result<u64, NodoBitcoinEr
     Ok(actual_file_size + 1)
 }
 
-/// Recibe el path del archivo donde escribir y los datos del bloque a escribir
-/// Escribe el bloque en el archivo, primero el tamaño del bloque (porque varía) y luego los bytes del bloque
-pub fn escribir_archivo_bloque(path: String, datos: &[u8]) -> Result<(), NodoBitcoinError> {
-    let mut archivo = match OpenOptions::new().create(true).append(true).open(path) {
-        Ok(archivo) => archivo,
-        Err(_) => return Err(NodoBitcoinError::NoExisteArchivo),
-    };
-
-    let datos_len = datos.len();
-    let datos_len_bytes: [u8; 8] = datos_len.to_ne_bytes();
-
-    let mut datos_con_len = datos_len_bytes.to_vec();
-    datos_con_len.extend_from_slice(datos);
-    let bytes_para_guardar = datos_con_len.as_slice();
-
+/// Recibe el path del archivo y el tamaño que tiene que quedar
+/// Trunca el archivo y lo sincroniza con el disco
+pub fn truncar_archivo(path: String, tamanio: u64) -> Result<(), NodoBitcoinError> {
+    let archivo = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
     archivo
-        .write_all(bytes_para_guardar)
+        .set_len(tamanio)
         .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
-    Ok(())
+    archivo
+        .sync_all()
+        .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)
+}
+
+/// Recibe el path del archivo
+/// Fuerza la escritura en disco (fsync) de lo que se haya escrito en el archivo
+pub fn sincronizar_archivo(path: String) -> Result<(), NodoBitcoinError> {
+    let archivo = File::open(path).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    archivo
+        .sync_all()
+        .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)
+}
+
+/// Recibe el directorio de los archivos de bloques y los datos del bloque a escribir
+/// Escribe el bloque como un registro con magic, largo y checksum, rotando de archivo si es necesario
+/// Devuelve la ubicación donde comienza el bloque
+pub fn escribir_archivo_bloque(
+    dir: String,
+    datos: &[u8],
+) -> Result<UbicacionBloque, NodoBitcoinError> {
+    escribir_registro(&dir, datos, tamanio_maximo_archivo_bloques())
 }
 
 /// Lee del archivo de headeres el último header guardado y devuelve sus bytes
@@ -111,19 +152,11 @@ pub fn leer_bytes(path: String, offset: u64, length: u64) -> Result<Vec<u8>, Nod
     Ok(buffer)
 }
 
-/// Recibe desde dónde leer
-/// Devuelve los bytes del bloque leído y la cantidad de bytes leídos
-pub fn leer_bloque(offset: u64) -> Result<(Vec<u8>, u64), NodoBitcoinError> {
-    let path = get_blocks_filename()?;
-    let sizeof_usize = mem::size_of::<usize>() as u64;
-    let from_file = leer_bytes(path.clone(), offset, sizeof_usize)?;
-    let len_bytes: [u8; 8] = match from_file.as_slice().try_into() {
-        Ok(bytes) => bytes,
-        Err(_) => return Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
-    };
-    let len_block = usize::from_ne_bytes(len_bytes);
-    let block_bytes = leer_bytes(path, offset + sizeof_usize, len_block as u64)?;
-    Ok((block_bytes, offset + sizeof_usize + len_block as u64))
+/// Recibe la ubicación del bloque a leer
+/// Devuelve los bytes del bloque leído y el offset del siguiente registro en el mismo archivo
+pub fn leer_bloque(ubicacion: UbicacionBloque) -> Result<(Vec<u8>, u64), NodoBitcoinError> {
+    let dir = get_blocks_dirname()?;
+    leer_registro(&dir, ubicacion)
 }
 
 /// Recibe el path del archivo del cual se quiere saber el tamaño
@@ -147,12 +180,6 @@ pub fn get_file_header_size() -> Result<u64, NodoBitcoinError> {
     get_file_size(path)
 }
 
-/// Devuelve el tamaño del archivo de bloques
-fn get_file_blocks_size() -> Result<u64, NodoBitcoinError> {
-    let path = get_blocks_filename()?;
-    get_file_size(path)
-}
-
 /// Recibe el index del header que se quiere leer
 /// Devuelve los bytes del header leído
 pub fn leer_header_desde_archivo(index: u64) -> Result<Vec<u8>, NodoBitcoinError> {
@@ -165,23 +192,15 @@ pub fn leer_header_desde_archivo(index: u64) -> Result<Vec<u8>, NodoBitcoinError
 /// Devuelve los bytes de los bloques leídos
 /// Función usada en tests de bloques
 pub fn _leer_algunos_blocks(cantidad: u32) -> Result<Vec<Vec<u8>>, NodoBitcoinError> {
-    let mut algunos = vec![];
-    let mut offset = 0;
-    let block_file_len = get_file_blocks_size()?;
-    let mut i = 0;
-    while offset < block_file_len && i < cantidad {
-        let (bytes, new_offset) = leer_bloque(offset)?;
-        algunos.push(bytes);
-        offset = new_offset;
-        i += 1;
-    }
-    Ok(algunos)
+    let mut todos = leer_todos_blocks()?;
+    todos.truncate(cantidad as usize);
+    Ok(todos)
 }
 
 /// Lee el primer bloque del archivo y devuelve sus bytes
 /// Función usada en tests
 pub fn _leer_primer_block() -> Result<Vec<u8>, NodoBitcoinError> {
-    let (bytes, _) = leer_bloque(0)?;
+    let (bytes, _) = leer_bloque(UbicacionBloque::default())?;
     Ok(bytes)
 }
 
@@ -215,6 +234,33 @@ pub fn _create_all_indexes() -> Result<(), NodoBitcoinError> {
     Ok(())
 }
 
+/// Migra el archivo de bloques con el formato anterior a archivos numerados con el formato nuevo
+/// Como cambian las ubicaciones de los bloques, vuelve a generar los índices opcionales
+/// Devuelve la cantidad de bloques migrados
+pub fn migrar_bloques() -> Result<u64, NodoBitcoinError> {
+    let path_anterior = get_blocks_filename()?;
+    let dir = get_blocks_dirname()?;
+    borrar_indices_opcionales()?;
+
+    let mut error_indice = None;
+    let cantidad = migrar_formato_anterior(
+        path_anterior,
+        &dir,
+        tamanio_maximo_archivo_bloques(),
+        |datos, ubicacion| {
+            let resultado = SerializedBlock::deserialize(datos)
+                .and_then(|bloque| indexar_bloque_guardado(&bloque, ubicacion));
+            if let Err(error) = resultado {
+                error_indice = Some(error);
+            }
+        },
+    )?;
+    match error_indice {
+        Some(error) => Err(error),
+        None => Ok(cantidad),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::config;
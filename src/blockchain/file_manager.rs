@@ -1,25 +1,25 @@
 use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::block_file::UbicacionBloque;
 use crate::blockchain::blockheader::BlockHeader;
-use crate::blockchain::file::get_file_header_size;
-use crate::blockchain::file::leer_bytes;
-use crate::blockchain::file::{
-    escribir_archivo, escribir_archivo_bloque, get_blocks_filename, get_headers_filename,
-    leer_todos_blocks,
-};
-use crate::blockchain::index::dump_hash_in_the_index;
-use crate::blockchain::index::get_start_index;
+use crate::blockchain::file::{get_blocks_dirname, get_headers_filename, get_journal_filename};
+use crate::blockchain::index::indexar_bloque_guardado;
+use crate::blockchain::index::HEADERS_INDEX_DIR;
+use crate::blockchain::journal::{actualizar_indices, verificar_consistencia};
+use crate::blockchain::script_index::{historial_de_script, MovimientoScript};
+use crate::blockchain::transaction::Transaction;
+use crate::common::red::red_configurada;
+use crate::common::uint256::Uint256;
 use crate::errores::NodoBitcoinError;
-use crate::log::{log_info_message, LogMessages};
-use crate::protocol::initial_block_download::GENESIS_BLOCK;
+use crate::log::{log_error_message, log_info_message, LogMessages};
+use crate::storage::file_storage::{FileStorage, WALLET_DIR};
+use crate::storage::Storage;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
 
-#[derive(Clone)]
 pub struct FileManager {
-    headers_file_name: String,
-    block_file_name: String,
+    storage: Box<dyn Storage>,
     logger: Sender<LogMessages>,
 }
 
@@ -31,10 +31,20 @@ pub type WriteHeadersAndBlockFileParams = (
     Sender<Result<(), NodoBitcoinError>>,
 );
 
+pub type PruneBlocksParams = (u32, Sender<Result<Vec<[u8; 32]>, NodoBitcoinError>>);
+
 pub enum FileMessages {
     ReadAllBlocks(Sender<Result<Vec<Vec<u8>>, NodoBitcoinError>>),
     WriteHeadersAndBlockFile(WriteHeadersAndBlockFileParams),
     GetHeaders(([u8; 32], Sender<Result<Vec<u8>, NodoBitcoinError>>)),
+    GetTransaction((Uint256, Sender<Result<Transaction, NodoBitcoinError>>)),
+    GetHistorialScript(
+        (
+            Vec<u8>,
+            Sender<Result<Vec<MovimientoScript>, NodoBitcoinError>>,
+        ),
+    ),
+    PruneBlocks(PruneBlocksParams),
     _ShutDown(),
 }
 
@@ -45,18 +55,39 @@ impl FileManager {
             Err(_) => "".to_string(),
         };
 
-        let block_file_name = match get_blocks_filename() {
-            Ok(real_block_file_name) => real_block_file_name,
+        let blocks_dir = match get_blocks_dirname() {
+            Ok(real_blocks_dir) => real_blocks_dir,
             Err(_) => "".to_string(),
         };
 
-        let (sender, receiver) = channel();
+        if let Err(error) = verificar_consistencia(logger.clone()) {
+            log_error_message(
+                logger.clone(),
+                format!(
+                    "No se pudo verificar la consistencia de los archivos: {}",
+                    error
+                ),
+            );
+        }
 
-        let file_manager = Arc::new(Mutex::new(FileManager {
-            logger,
+        let storage = FileStorage::new(
             headers_file_name,
-            block_file_name,
-        }));
+            blocks_dir,
+            HEADERS_INDEX_DIR.to_string(),
+            WALLET_DIR.to_string(),
+            get_journal_filename(),
+        );
+        FileManager::create_with_storage(logger, Box::new(storage))
+    }
+
+    /// Crea el file manager sobre el almacenamiento recibido
+    pub fn create_with_storage(
+        logger: Sender<LogMessages>,
+        storage: Box<dyn Storage>,
+    ) -> Sender<FileMessages> {
+        let (sender, receiver) = channel();
+
+        let file_manager = Arc::new(Mutex::new(FileManager { logger, storage }));
 
         thread::spawn(move || {
             let fm = file_manager.clone();
@@ -85,52 +116,47 @@ impl FileManager {
                     self.logger.clone(),
                     "Guardando headers y bloques...".to_string(),
                 );
-                match escribir_archivo_bloque(self.block_file_name.clone(), &block_bytes) {
-                    Ok(index) => index,
-                    Err(error) => {
-                        _ = result.send(Err(error));
-                        return;
-                    }
-                };
-                log_info_message(self.logger.clone(), "Bloque nuevo guardado".to_string());
-
-                let index_header =
-                    match escribir_archivo(self.headers_file_name.clone(), &header_bytes) {
-                        Ok(index) => index - 1,
-                        Err(error) => {
-                            _ = result.send(Err(error));
-                            return;
-                        }
-                    };
-                log_info_message(self.logger.clone(), "Header nuevo guardado".to_string());
-                match dump_hash_in_the_index(
-                    self.headers_file_name.clone(),
-                    header_hash,
-                    index_header,
-                ) {
-                    Ok(_) => {}
-                    Err(error) => {
-                        _ = result.send(Err(error));
-                        return;
-                    }
-                };
-                log_info_message(
-                    self.logger.clone(),
-                    format!("Indice de header guardado {}", index_header),
-                );
-
-                _ = result.send(Ok(()));
+                let resultado =
+                    self.guardar_header_y_bloque(&block_bytes, header_hash, &header_bytes);
+                if resultado.is_err() {
+                    self.deshacer_escritura();
+                }
+                _ = result.send(resultado);
             }
             FileMessages::ReadAllBlocks(result) => {
-                _ = result.send(leer_todos_blocks());
+                let bloques = self.storage.leer_todos_los_bloques().map(|bloques| {
+                    bloques
+                        .into_iter()
+                        .map(|(_, bytes)| bytes)
+                        .collect::<Vec<Vec<u8>>>()
+                });
+                _ = result.send(bloques);
             }
             FileMessages::_ShutDown() => {}
+            FileMessages::PruneBlocks((ultimo_timestamp_aplicado, result)) => {
+                let podados = self.storage.podar_bloques(ultimo_timestamp_aplicado);
+                if let Ok(podados) = &podados {
+                    if !podados.is_empty() {
+                        log_info_message(
+                            self.logger.clone(),
+                            format!("Prune: {} bloques borrados", podados.len()),
+                        );
+                    }
+                }
+                _ = result.send(podados);
+            }
+            FileMessages::GetTransaction((txid, result)) => {
+                _ = result.send(Transaction::_get_tx_from_file(txid));
+            }
+            FileMessages::GetHistorialScript((pk_script, result)) => {
+                _ = result.send(historial_de_script(&pk_script));
+            }
 
             FileMessages::GetHeaders((hash_id, result)) => {
                 let mut header_index = 0;
 
-                if hash_id != GENESIS_BLOCK {
-                    header_index = match get_start_index(self.headers_file_name.clone(), hash_id) {
+                if hash_id != red_configurada().bloque_genesis() {
+                    header_index = match self.storage.buscar_en_indice(hash_id) {
                         Ok(index) => index + 80,
                         Err(error) => {
                             _ = result.send(Err(error));
@@ -139,7 +165,7 @@ impl FileManager {
                     };
                 }
 
-                let file_size = match get_file_header_size() {
+                let file_size = match self.storage.tamanio_headers() {
                     Ok(size) => size,
                     Err(error) => {
                         _ = result.send(Err(error));
@@ -158,7 +184,7 @@ impl FileManager {
                     return;
                 };
 
-                let bytes = match leer_bytes(self.headers_file_name.clone(), header_index, length) {
+                let bytes = match self.storage.leer_headers(header_index, length) {
                     Ok(data) => data,
                     Err(error) => {
                         _ = result.send(Err(error));
@@ -169,6 +195,55 @@ impl FileManager {
             }
         }
     }
+
+    /// Actualiza los índices opcionales (txindex y de scripts) con el bloque guardado
+    /// Guarda el bloque y después su header dentro de una escritura del storage
+    fn guardar_header_y_bloque(
+        &mut self,
+        block_bytes: &[u8],
+        header_hash: [u8; 32],
+        header_bytes: &[u8],
+    ) -> Result<(), NodoBitcoinError> {
+        self.storage.iniciar_escritura()?;
+        let ubicacion_bloque = self.storage.escribir_bloque(block_bytes)?;
+        log_info_message(self.logger.clone(), "Bloque nuevo guardado".to_string());
+        self.indexar_bloque(block_bytes, ubicacion_bloque);
+        self.storage.bloques_escritos()?;
+
+        let index_header = self.storage.escribir_header(header_bytes)?;
+        log_info_message(self.logger.clone(), "Header nuevo guardado".to_string());
+        self.storage.guardar_en_indice(header_hash, index_header)?;
+        log_info_message(
+            self.logger.clone(),
+            format!("Indice de header guardado {}", index_header),
+        );
+        self.storage.finalizar_escritura()
+    }
+
+    /// Descarta la escritura que falló a la mitad y limpia los índices de lo descartado
+    fn deshacer_escritura(&mut self) {
+        let resultado = self
+            .storage
+            .deshacer_escritura()
+            .and_then(|reparaciones| actualizar_indices(&reparaciones, self.logger.clone()));
+        if let Err(error) = resultado {
+            log_error_message(
+                self.logger.clone(),
+                format!("No se pudo deshacer la escritura incompleta: {}", error),
+            );
+        }
+    }
+
+    fn indexar_bloque(&self, block_bytes: &[u8], ubicacion_bloque: UbicacionBloque) {
+        let resultado = SerializedBlock::deserialize(block_bytes)
+            .and_then(|bloque| indexar_bloque_guardado(&bloque, ubicacion_bloque));
+        if let Err(error) = resultado {
+            log_error_message(
+                self.logger.clone(),
+                format!("No se pudo indexar el bloque: {}", error),
+            );
+        }
+    }
 }
 
 pub fn read_blocks_from_file(
@@ -209,6 +284,51 @@ pub fn get_headers_from_file(
     }
 }
 
+/// Recibe el txid buscado
+/// Devuelve la transacción guardada, usando el txindex si está habilitado
+pub fn get_transaction_from_file(
+    file_manager: Sender<FileMessages>,
+    txid: Uint256,
+) -> Result<Transaction, NodoBitcoinError> {
+    let (result_sender, result_receiver) = channel();
+    _ = file_manager.send(FileMessages::GetTransaction((txid, result_sender)));
+    match result_receiver.recv() {
+        Ok(result) => result,
+        Err(_) => Err(NodoBitcoinError::IndexNoEncontrado),
+    }
+}
+
+/// Recibe un scriptPubKey
+/// Devuelve los outputs que le pagaron y sus gastos, usando el índice de scripts
+pub fn get_historial_script_from_file(
+    file_manager: Sender<FileMessages>,
+    pk_script: Vec<u8>,
+) -> Result<Vec<MovimientoScript>, NodoBitcoinError> {
+    let (result_sender, result_receiver) = channel();
+    _ = file_manager.send(FileMessages::GetHistorialScript((pk_script, result_sender)));
+    match result_receiver.recv() {
+        Ok(result) => result,
+        Err(_) => Err(NodoBitcoinError::IndexNoEncontrado),
+    }
+}
+
+/// Recibe el timestamp del último bloque aplicado a las UTXOs
+/// Borra los archivos de bloques viejos si está habilitado el modo prune y devuelve los hashes borrados
+pub fn prune_blocks_from_file(
+    file_manager: Sender<FileMessages>,
+    ultimo_timestamp_aplicado: u32,
+) -> Result<Vec<[u8; 32]>, NodoBitcoinError> {
+    let (result_sender, result_receiver) = channel();
+    _ = file_manager.send(FileMessages::PruneBlocks((
+        ultimo_timestamp_aplicado,
+        result_sender,
+    )));
+    match result_receiver.recv() {
+        Ok(result) => result,
+        Err(_) => Err(NodoBitcoinError::NoSePuedeEscribirLosBytes),
+    }
+}
+
 pub fn write_headers_and_block_file(
     file_manager: Sender<FileMessages>,
     block: SerializedBlock,
@@ -228,7 +348,7 @@ pub fn write_headers_and_block_file(
     )));
 
     match result_receiver.recv() {
-        Ok(_) => Ok(()),
+        Ok(result) => result,
         Err(_) => {
             // todo handle
             Err(NodoBitcoinError::InvalidAccount)
@@ -243,10 +363,19 @@ pub fn _shutdown(file_manager: Sender<FileMessages>) {
 #[cfg(test)]
 mod tests {
     use crate::{
-        blockchain::file_manager::{get_headers_from_file, FileManager},
+        blockchain::{
+            block::SerializedBlock,
+            blockheader::BlockHeader,
+            file_manager::{
+                get_headers_from_file, read_blocks_from_file, write_headers_and_block_file,
+                FileManager,
+            },
+            transaction::{Outpoint, Transaction, TxIn, TxOut},
+        },
         config,
         log::create_logger_actor,
         protocol::initial_block_download::GENESIS_BLOCK,
+        storage::memory_storage::MemoryStorage,
     };
 
     fn init_config() {
@@ -269,4 +398,56 @@ mod tests {
         let result = get_headers_from_file(file_manager, genesis);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_file_manager_en_memoria() {
+        let logger = create_logger_actor(Err(crate::errores::NodoBitcoinError::NoExisteArchivo));
+        let file_manager = FileManager::create_with_storage(logger, Box::new(MemoryStorage::new()));
+        let header = BlockHeader {
+            version: 1,
+            previous_block_hash: [0; 32],
+            merkle_root_hash: [0; 32],
+            time: 10,
+            n_bits: 0,
+            nonce: 0,
+        };
+        let block = SerializedBlock {
+            header,
+            txn_amount: 1,
+            txns: vec![Transaction {
+                version: 1,
+                input: vec![TxIn {
+                    previous_output: Outpoint {
+                        hash: [1; 32],
+                        index: 0,
+                    },
+                    script_bytes: 0,
+                    signature_script: vec![],
+                    sequence: 0xffffffff,
+                    script_bytes_amount: 1,
+                    witness: vec![],
+                }],
+                output: vec![TxOut {
+                    value: 1000,
+                    pk_len: 0,
+                    pk_script: vec![],
+                    pk_len_bytes: 1,
+                }],
+                lock_time: 0,
+                tx_in_count: 1,
+                tx_out_count: 1,
+            }],
+        };
+
+        write_headers_and_block_file(file_manager.clone(), block.clone(), header).unwrap();
+
+        let headers = get_headers_from_file(file_manager.clone(), GENESIS_BLOCK).unwrap();
+        assert_eq!(headers, header.serialize().unwrap());
+        let siguientes = get_headers_from_file(file_manager.clone(), header.hash().unwrap());
+        assert_eq!(siguientes.unwrap(), Vec::<u8>::new());
+
+        let blocks = read_blocks_from_file(file_manager).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].header.hash().unwrap(), header.hash().unwrap());
+    }
 }
@@ -0,0 +1,239 @@
+use crate::blockchain::transaction::Transaction;
+use crate::blockchain::utxos_nodo::ClaveUtxo;
+use crate::common::uint256::Uint256;
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use std::collections::{HashMap, HashSet};
+
+/// Cantidad máxima de transacciones huérfanas si no se indica en el config, igual que en Bitcoin Core
+pub const MAXIMO_HUERFANAS_DEFAULT: usize = 100;
+
+/// Segundos que se guarda una huérfana esperando a sus padres
+pub const EXPIRACION_HUERFANAS: u32 = 20 * 60;
+
+/// Tamaño máximo de una huérfana, para que no se pueda llenar la memoria con transacciones
+/// que todavía no se pueden validar
+pub const TAMANIO_MAXIMO_HUERFANA: usize = 100_000;
+
+/// Devuelve la cantidad máxima de huérfanas guardada en el config, o el valor por defecto
+pub fn maximo_huerfanas() -> usize {
+    match config::get_valor("MAXIMO_HUERFANAS".to_string()) {
+        Ok(valor) => valor.parse::<usize>().unwrap_or(MAXIMO_HUERFANAS_DEFAULT),
+        Err(_) => MAXIMO_HUERFANAS_DEFAULT,
+    }
+}
+
+/// Transacción que gasta salidas que el nodo todavía no conoce
+/// Guarda el id de la conexión del peer que la mandó para pedirle los padres
+#[derive(Debug, Clone)]
+pub struct Huerfana {
+    pub tx: Transaction,
+    pub hora: u32,
+    pub peer: Option<i32>,
+    faltantes: Vec<ClaveUtxo>,
+}
+
+/// Transacciones huérfanas indexadas por las salidas que les faltan
+#[derive(Debug, Clone)]
+pub struct TxsHuerfanas {
+    huerfanas: HashMap<Uint256, Huerfana>,
+    // para cada salida que falta, las huérfanas que la gastan
+    por_faltante: HashMap<ClaveUtxo, HashSet<Uint256>>,
+    maximo: usize,
+}
+
+impl TxsHuerfanas {
+    pub fn new(maximo: usize) -> TxsHuerfanas {
+        TxsHuerfanas {
+            huerfanas: HashMap::new(),
+            por_faltante: HashMap::new(),
+            maximo,
+        }
+    }
+
+    /// Crea el pool de huérfanas con el máximo del config
+    pub fn desde_config() -> TxsHuerfanas {
+        TxsHuerfanas::new(maximo_huerfanas())
+    }
+
+    /// Recibe la transacción, las salidas que gasta y que el nodo no conoce, la hora y el peer
+    /// que la mandó
+    /// Si el pool está lleno, desaloja la huérfana más vieja
+    /// Devuelve el txid de la huérfana
+    pub fn agregar(
+        &mut self,
+        tx: Transaction,
+        faltantes: Vec<ClaveUtxo>,
+        hora: u32,
+        peer: Option<i32>,
+    ) -> Result<Uint256, NodoBitcoinError> {
+        let txid = tx.txid()?;
+        if self.huerfanas.contains_key(&txid) {
+            return Ok(txid);
+        }
+        if faltantes.is_empty() || tx.serialize()?.len() > TAMANIO_MAXIMO_HUERFANA {
+            return Err(NodoBitcoinError::TxInvalida);
+        }
+        while self.huerfanas.len() >= self.maximo {
+            match self.mas_vieja() {
+                Some(mas_vieja) => {
+                    self.quitar(&mas_vieja);
+                }
+                None => break,
+            }
+        }
+        if self.maximo == 0 {
+            return Err(NodoBitcoinError::TxInvalida);
+        }
+        for faltante in faltantes.iter() {
+            self.por_faltante.entry(*faltante).or_default().insert(txid);
+        }
+        self.huerfanas.insert(
+            txid,
+            Huerfana {
+                tx,
+                hora,
+                peer,
+                faltantes,
+            },
+        );
+        Ok(txid)
+    }
+
+    fn mas_vieja(&self) -> Option<Uint256> {
+        self.huerfanas
+            .iter()
+            .min_by_key(|(txid, huerfana)| (huerfana.hora, **txid))
+            .map(|(txid, _)| *txid)
+    }
+
+    /// Quita la huérfana y devuelve la entrada si estaba
+    pub fn quitar(&mut self, txid: &Uint256) -> Option<Huerfana> {
+        let huerfana = self.huerfanas.remove(txid)?;
+        for faltante in huerfana.faltantes.iter() {
+            if let Some(txids) = self.por_faltante.get_mut(faltante) {
+                txids.remove(txid);
+                if txids.is_empty() {
+                    self.por_faltante.remove(faltante);
+                }
+            }
+        }
+        Some(huerfana)
+    }
+
+    /// Recibe una transacción que se acaba de aceptar o confirmar
+    /// Quita y devuelve, de la más vieja a la más nueva, las huérfanas que gastan alguna de sus
+    /// salidas para volver a evaluarlas
+    pub fn quitar_hijas_de(&mut self, tx: &Transaction) -> Vec<Huerfana> {
+        let txid = match tx.txid() {
+            Ok(txid) => txid,
+            Err(_) => return vec![],
+        };
+        let mut hijas: HashSet<Uint256> = HashSet::new();
+        for index in 0..tx.output.len() {
+            if let Some(txids) = self.por_faltante.get(&(txid, index as u32)) {
+                hijas.extend(txids.iter());
+            }
+        }
+        let mut hijas: Vec<Huerfana> = hijas.iter().filter_map(|hija| self.quitar(hija)).collect();
+        hijas.sort_by_key(|hija| hija.hora);
+        hijas
+    }
+
+    /// Quita las huérfanas que llevan más de EXPIRACION_HUERFANAS segundos esperando
+    /// Devuelve los txids quitados
+    pub fn expirar(&mut self, ahora: u32) -> Vec<Uint256> {
+        let expiradas: Vec<Uint256> = self
+            .huerfanas
+            .iter()
+            .filter(|(_, huerfana)| huerfana.hora.saturating_add(EXPIRACION_HUERFANAS) < ahora)
+            .map(|(txid, _)| *txid)
+            .collect();
+        for txid in expiradas.iter() {
+            self.quitar(txid);
+        }
+        expiradas
+    }
+
+    /// Devuelve los txids de los padres que le faltan a la huérfana, sin repetir
+    pub fn padres_faltantes(&self, txid: &Uint256) -> Vec<Uint256> {
+        let mut padres = vec![];
+        if let Some(huerfana) = self.huerfanas.get(txid) {
+            for (padre, _) in huerfana.faltantes.iter() {
+                if !padres.contains(padre) {
+                    padres.push(*padre);
+                }
+            }
+        }
+        padres
+    }
+
+    pub fn contiene(&self, txid: &Uint256) -> bool {
+        self.huerfanas.contains_key(txid)
+    }
+
+    pub fn cantidad(&self) -> usize {
+        self.huerfanas.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::utxos_nodo::tests::tx;
+
+    #[test]
+    fn test_huerfanas_se_reevaluan_cuando_llega_el_padre() {
+        let padre = tx(vec![(Uint256::from_be_bytes([1; 32]), 0)], vec![100, 200]);
+        let id_padre = padre.txid().unwrap();
+        let otro_padre = Uint256::from_be_bytes([2; 32]);
+        let mut huerfanas = TxsHuerfanas::new(10);
+
+        let primera = tx(vec![(id_padre, 1)], vec![150]);
+        let id_primera = huerfanas
+            .agregar(primera, vec![(id_padre, 1)], 20, Some(3))
+            .unwrap();
+        let segunda = tx(vec![(id_padre, 0), (otro_padre, 0)], vec![50]);
+        let id_segunda = huerfanas
+            .agregar(segunda, vec![(id_padre, 0), (otro_padre, 0)], 10, None)
+            .unwrap();
+        assert_eq!(huerfanas.cantidad(), 2);
+        assert_eq!(huerfanas.padres_faltantes(&id_segunda).len(), 2);
+
+        let hijas = huerfanas.quitar_hijas_de(&padre);
+        assert_eq!(hijas.len(), 2);
+        assert_eq!(hijas[0].tx.txid().unwrap(), id_segunda);
+        assert_eq!(hijas[1].tx.txid().unwrap(), id_primera);
+        assert_eq!(hijas[1].peer, Some(3));
+        assert_eq!(huerfanas.cantidad(), 0);
+        assert!(huerfanas.quitar_hijas_de(&padre).is_empty());
+    }
+
+    #[test]
+    fn test_huerfanas_limite_y_expiracion() {
+        let mut huerfanas = TxsHuerfanas::new(2);
+        let mut ids = vec![];
+        for (semilla, hora) in [(1, 100), (2, 50), (3, 200)] {
+            let padre = Uint256::from_be_bytes([semilla; 32]);
+            let huerfana = tx(vec![(padre, 0)], vec![10]);
+            ids.push(
+                huerfanas
+                    .agregar(huerfana, vec![(padre, 0)], hora, None)
+                    .unwrap(),
+            );
+        }
+        // se desalojó la más vieja
+        assert_eq!(huerfanas.cantidad(), 2);
+        assert!(!huerfanas.contiene(&ids[1]));
+
+        let expiradas = huerfanas.expirar(150 + EXPIRACION_HUERFANAS);
+        assert_eq!(expiradas, vec![ids[0]]);
+        assert!(huerfanas.contiene(&ids[2]));
+
+        let sin_faltantes = tx(vec![(Uint256::from_be_bytes([4; 32]), 0)], vec![10]);
+        assert_eq!(
+            huerfanas.agregar(sin_faltantes, vec![], 0, None),
+            Err(NodoBitcoinError::TxInvalida)
+        );
+    }
+}
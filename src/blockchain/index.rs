@@ -1,14 +1,26 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::block_file::leer_todos_los_registros;
+use crate::blockchain::block_file::UbicacionBloque;
+use crate::blockchain::blockheader::BlockHeader;
 use crate::blockchain::file::escribir_archivo;
+use crate::blockchain::file::get_blocks_dirname;
 use crate::blockchain::file::leer_bytes;
+use crate::blockchain::script_index::{
+    borrar_indice_de_scripts, indexar_scripts_del_bloque, scriptindex_habilitado,
+};
+use crate::blockchain::tx_index::{
+    borrar_txindex, indexar_transacciones_del_bloque, txindex_habilitado,
+};
 use crate::errores::NodoBitcoinError;
 use std::collections::hash_map::DefaultHasher;
+use std::fs;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::mem;
 
 use super::file::get_file_size;
 
-fn create_hash_to_find_index(hash: [u8; 32]) -> usize {
+pub fn create_hash_to_find_index(hash: [u8; 32]) -> usize {
     let mut hasher = DefaultHasher::new();
     hash.hash(&mut hasher);
     let hash_value = hasher.finish();
@@ -16,11 +28,14 @@ fn create_hash_to_find_index(hash: [u8; 32]) -> usize {
     (hash_value % range as u64) as usize
 }
 
-fn create_path(hash: [u8; 32]) -> String {
-    format!(
-        "src/indexes/headers/ix-{}.bin",
-        create_hash_to_find_index(hash)
-    )
+/// Directorio del índice de headers
+pub const HEADERS_INDEX_DIR: &str = "src/indexes/headers";
+
+/// Tamaño de cada registro del índice de headers: hash (32) + offset en el archivo de headers (8)
+const TAMANIO_REGISTRO_HEADERS: usize = 40;
+
+fn create_path(dir: &str, hash: [u8; 32]) -> String {
+    format!("{}/ix-{}.bin", dir, create_hash_to_find_index(hash))
 }
 
 fn is_hash_searched(vec: Vec<u8>, slice: &[u8; 32]) -> bool {
@@ -32,7 +47,17 @@ pub fn dump_hash_in_the_index(
     hash: [u8; 32],
     real_index: u64,
 ) -> Result<(), NodoBitcoinError> {
-    let index_path = create_path(hash);
+    dump_hash_in_the_index_en(HEADERS_INDEX_DIR, hash, real_index)
+}
+
+/// Igual que `dump_hash_in_the_index` pero con el índice en el directorio recibido
+pub fn dump_hash_in_the_index_en(
+    dir: &str,
+    hash: [u8; 32],
+    real_index: u64,
+) -> Result<(), NodoBitcoinError> {
+    fs::create_dir_all(dir).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    let index_path = create_path(dir, hash);
 
     if let Err(error) = escribir_archivo(index_path.clone(), &hash) {
         println!(
@@ -51,7 +76,12 @@ pub fn dump_hash_in_the_index(
 }
 
 pub fn get_start_index(_path: String, hash: [u8; 32]) -> Result<u64, NodoBitcoinError> {
-    let index_path = create_path(hash);
+    get_start_index_en(HEADERS_INDEX_DIR, hash)
+}
+
+/// Igual que `get_start_index` pero con el índice en el directorio recibido
+pub fn get_start_index_en(dir: &str, hash: [u8; 32]) -> Result<u64, NodoBitcoinError> {
+    let index_path = create_path(dir, hash);
     let len_archivo = get_file_size(index_path.clone())?;
     let mut offset = 0;
     let size_of_u8 = mem::size_of::<u8>() as u64;
@@ -75,6 +105,102 @@ pub fn get_start_index(_path: String, hash: [u8; 32]) -> Result<u64, NodoBitcoin
     Err(NodoBitcoinError::IndexNoEncontrado)
 }
 
+/// Recibe el bloque recién guardado y su ubicación en los archivos de bloques
+/// Actualiza los índices opcionales (txindex y de scripts) que estén habilitados en el config
+pub fn indexar_bloque_guardado(
+    bloque: &SerializedBlock,
+    ubicacion_bloque: UbicacionBloque,
+) -> Result<(), NodoBitcoinError> {
+    if txindex_habilitado() {
+        indexar_transacciones_del_bloque(bloque, ubicacion_bloque)?;
+    }
+    if scriptindex_habilitado() {
+        indexar_scripts_del_bloque(bloque, ubicacion_bloque)?;
+    }
+    Ok(())
+}
+
+/// Borra los índices opcionales para volver a generarlos desde los bloques
+pub fn borrar_indices_opcionales() -> Result<(), NodoBitcoinError> {
+    borrar_txindex()?;
+    borrar_indice_de_scripts()
+}
+
+/// Vuelve a generar los índices opcionales leyendo todos los bloques guardados
+/// Devuelve la cantidad de bloques indexados
+pub fn reconstruir_indices_opcionales() -> Result<u64, NodoBitcoinError> {
+    borrar_indices_opcionales()?;
+    if !txindex_habilitado() && !scriptindex_habilitado() {
+        return Ok(0);
+    }
+    let dir = get_blocks_dirname()?;
+    let mut cantidad = 0;
+    for (ubicacion_bloque, datos) in leer_todos_los_registros(&dir)? {
+        let bloque = SerializedBlock::deserialize(&datos)?;
+        indexar_bloque_guardado(&bloque, ubicacion_bloque)?;
+        cantidad += 1;
+    }
+    Ok(cantidad)
+}
+
+/// Recibe el tamaño con el que quedó el archivo de headers
+/// Borra del índice de headers las entradas que apuntan más allá del final del archivo
+/// Devuelve la cantidad de entradas borradas
+pub fn purgar_indice_de_headers(tamanio_headers: u64) -> Result<u64, NodoBitcoinError> {
+    purgar_indice_de_headers_en(HEADERS_INDEX_DIR, tamanio_headers)
+}
+
+fn purgar_indice_de_headers_en(dir: &str, tamanio_headers: u64) -> Result<u64, NodoBitcoinError> {
+    let entradas = match fs::read_dir(dir) {
+        Ok(entradas) => entradas,
+        Err(_) => return Ok(0),
+    };
+    let mut borradas = 0;
+    for entrada in entradas.filter_map(|entrada| entrada.ok()) {
+        let path = entrada.path();
+        let bytes = fs::read(&path).map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?;
+        let mut conservados = Vec::with_capacity(bytes.len());
+        for registro in bytes.chunks_exact(TAMANIO_REGISTRO_HEADERS) {
+            let mut offset_bytes = [0u8; 8];
+            offset_bytes.copy_from_slice(&registro[32..40]);
+            if u64::from_le_bytes(offset_bytes) < tamanio_headers {
+                conservados.extend_from_slice(registro);
+            } else {
+                borradas += 1;
+            }
+        }
+        if conservados.len() != bytes.len() {
+            fs::write(&path, conservados)
+                .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        }
+    }
+    Ok(borradas)
+}
+
+/// Borra el índice de headers y lo vuelve a generar recorriendo el archivo de headers
+/// Devuelve la cantidad de headers indexados
+pub fn reconstruir_indice_de_headers(headers_path: String) -> Result<u64, NodoBitcoinError> {
+    reconstruir_indice_de_headers_en(headers_path, HEADERS_INDEX_DIR)
+}
+
+fn reconstruir_indice_de_headers_en(
+    headers_path: String,
+    dir: &str,
+) -> Result<u64, NodoBitcoinError> {
+    if fs::metadata(dir).is_ok() {
+        fs::remove_dir_all(dir).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+    }
+    let tamanio = get_file_size(headers_path.clone())?;
+    let headers = leer_bytes(headers_path, 0, tamanio)?;
+    let mut cantidad = 0;
+    for (posicion, bytes) in headers.chunks_exact(80).enumerate() {
+        let header = BlockHeader::deserialize(bytes)?;
+        dump_hash_in_the_index_en(dir, header.hash()?, posicion as u64 * 80)?;
+        cantidad += 1;
+    }
+    Ok(cantidad)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,4 +237,59 @@ mod tests {
         ];
         assert_eq!(is_hash_searched(vec, slice), false);
     }
+
+    #[test]
+    fn test_purgar_indice_de_headers() {
+        let dir = std::env::temp_dir().join("index_test_purgar_headers");
+        let dir = dir.to_str().unwrap();
+        _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        let mut bytes = vec![];
+        for (hash, offset) in [([1u8; 32], 0u64), ([2u8; 32], 80), ([3u8; 32], 160)] {
+            bytes.extend_from_slice(&hash);
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+        let path = format!("{}/ix-1.bin", dir);
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(purgar_indice_de_headers_en(dir, 160).unwrap(), 1);
+        assert_eq!(fs::read(&path).unwrap(), bytes[..80].to_vec());
+        _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_reconstruir_indice_de_headers() {
+        let dir = std::env::temp_dir().join("index_test_reconstruir_headers");
+        let dir = dir.to_str().unwrap();
+        _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        let mut hashes = vec![];
+        let mut bytes = vec![];
+        for time in [10, 20] {
+            let header = BlockHeader {
+                version: 1,
+                previous_block_hash: [0; 32],
+                merkle_root_hash: [0; 32],
+                time,
+                n_bits: 0,
+                nonce: 0,
+            };
+            hashes.push(header.hash().unwrap());
+            bytes.extend(header.serialize().unwrap());
+        }
+        let headers_path = format!("{}/headers.bin", dir);
+        fs::write(&headers_path, &bytes).unwrap();
+        let indice = format!("{}/indexes", dir);
+        dump_hash_in_the_index_en(&indice, [9; 32], 0).unwrap();
+
+        assert_eq!(
+            reconstruir_indice_de_headers_en(headers_path, &indice).unwrap(),
+            2
+        );
+        assert_eq!(get_start_index_en(&indice, hashes[1]).unwrap(), 80);
+        assert!(get_start_index_en(&indice, [9; 32]).is_err());
+        _ = fs::remove_dir_all(dir);
+    }
 }
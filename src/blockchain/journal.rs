@@ -0,0 +1,606 @@
+use crate::blockchain::block_file::{
+    headers_de_archivo, leer_registro, nombre_archivo_bloques, numeros_de_archivos,
+    recorrer_registros,
+};
+use crate::blockchain::blockheader::BlockHeader;
+use crate::blockchain::file::{
+    get_blocks_dirname, get_file_size, get_headers_filename, get_journal_filename, leer_bytes,
+    sincronizar_archivo, truncar_archivo,
+};
+use crate::blockchain::index::{purgar_indice_de_headers, reconstruir_indices_opcionales};
+use crate::errores::NodoBitcoinError;
+use crate::log::{log_info_message, LogMessages};
+use crate::protocol::initial_block_download::get_timestamp_inicial;
+use bitcoin_hashes::{sha256d, Hash};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::mpsc::Sender;
+
+/// Nombre por defecto del journal si no está en el config
+pub const NOMBRE_ARCHIVO_JOURNAL_DEFAULT: &str = "journal.bin";
+
+/// Formato del journal:
+/// tamaño del archivo de headers (8) + último archivo de bloques (4) + tamaño de ese archivo (8) + checksum (4)
+const TAMANIO_JOURNAL: usize = 24;
+
+const TAMANIO_HEADER: u64 = 80;
+
+/// Tamaño de los archivos de la cadena antes de comenzar una escritura
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstadoArchivos {
+    pub tamanio_headers: u64,
+    pub ultimo_archivo: u32,
+    pub tamanio_ultimo_archivo: u64,
+}
+
+impl EstadoArchivos {
+    /// Recibe el archivo de headers y el directorio de bloques
+    /// Devuelve el tamaño actual de cada uno
+    pub fn actual(headers_path: &str, blocks_dir: &str) -> EstadoArchivos {
+        let ultimo_archivo = numeros_de_archivos(blocks_dir).last().copied().unwrap_or(0);
+        EstadoArchivos {
+            tamanio_headers: get_file_size(headers_path.to_string()).unwrap_or(0),
+            ultimo_archivo,
+            tamanio_ultimo_archivo: get_file_size(nombre_archivo_bloques(
+                blocks_dir,
+                ultimo_archivo,
+            ))
+            .unwrap_or(0),
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(TAMANIO_JOURNAL);
+        bytes.extend_from_slice(&self.tamanio_headers.to_le_bytes());
+        bytes.extend_from_slice(&self.ultimo_archivo.to_le_bytes());
+        bytes.extend_from_slice(&self.tamanio_ultimo_archivo.to_le_bytes());
+        let hash = sha256d::Hash::hash(&bytes);
+        bytes.extend_from_slice(&hash[..4]);
+        bytes
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<EstadoArchivos, NodoBitcoinError> {
+        if bytes.len() != TAMANIO_JOURNAL {
+            return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+        }
+        let hash = sha256d::Hash::hash(&bytes[..20]);
+        if hash[..4] != bytes[20..24] {
+            return Err(NodoBitcoinError::ChecksumIncorrecto);
+        }
+        let mut tamanio_headers = [0u8; 8];
+        tamanio_headers.copy_from_slice(&bytes[0..8]);
+        let mut ultimo_archivo = [0u8; 4];
+        ultimo_archivo.copy_from_slice(&bytes[8..12]);
+        let mut tamanio_ultimo_archivo = [0u8; 8];
+        tamanio_ultimo_archivo.copy_from_slice(&bytes[12..20]);
+        Ok(EstadoArchivos {
+            tamanio_headers: u64::from_le_bytes(tamanio_headers),
+            ultimo_archivo: u32::from_le_bytes(ultimo_archivo),
+            tamanio_ultimo_archivo: u64::from_le_bytes(tamanio_ultimo_archivo),
+        })
+    }
+}
+
+/// Journal de una escritura de headers y bloques
+/// Mientras exista el archivo del journal, la escritura no terminó y se puede deshacer
+/// Si se descarta sin finalizar, por ejemplo al volver antes con un error, deshace la escritura
+pub struct Journal {
+    path: String,
+    headers_path: String,
+    blocks_dir: String,
+    estado: EstadoArchivos,
+    terminado: bool,
+}
+
+impl Journal {
+    /// Recibe el path del journal, el archivo de headers y el directorio de bloques
+    /// Guarda en disco el tamaño actual de los archivos antes de empezar a escribir
+    pub fn iniciar(
+        path: String,
+        headers_path: String,
+        blocks_dir: String,
+    ) -> Result<Journal, NodoBitcoinError> {
+        let estado = EstadoArchivos::actual(&headers_path, &blocks_dir);
+        let mut archivo = File::create(&path).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+        archivo
+            .write_all(&estado.serialize())
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        archivo
+            .sync_all()
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        Ok(Journal {
+            path,
+            headers_path,
+            blocks_dir,
+            estado,
+            terminado: false,
+        })
+    }
+
+    /// Inicia el journal con los archivos del config
+    pub fn iniciar_escritura() -> Result<Journal, NodoBitcoinError> {
+        Journal::iniciar(
+            get_journal_filename(),
+            get_headers_filename()?,
+            get_blocks_dirname()?,
+        )
+    }
+
+    /// Fuerza a disco los archivos de bloques escritos desde que se inició el journal
+    /// Se llama antes de escribir los headers, para que nunca quede un header sin su bloque
+    pub fn sincronizar_bloques(&self) -> Result<(), NodoBitcoinError> {
+        for archivo in numeros_de_archivos(&self.blocks_dir) {
+            if archivo >= self.estado.ultimo_archivo {
+                sincronizar_archivo(nombre_archivo_bloques(&self.blocks_dir, archivo))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fuerza a disco el archivo de headers y borra el journal dando por terminada la escritura
+    /// Si falla, la escritura sigue abierta y se puede deshacer
+    pub fn finalizar(&mut self) -> Result<(), NodoBitcoinError> {
+        if fs::metadata(&self.headers_path).is_ok() {
+            sincronizar_archivo(self.headers_path.clone())?;
+        }
+        fs::remove_file(&self.path).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        self.terminado = true;
+        Ok(())
+    }
+
+    /// Vuelve los archivos al tamaño que tenían al iniciar el journal y lo borra
+    /// Devuelve las reparaciones hechas, para actualizar los índices
+    pub fn deshacer(mut self) -> Result<Reparaciones, NodoBitcoinError> {
+        let reparaciones = self.deshacer_archivos()?;
+        self.terminado = true;
+        Ok(reparaciones)
+    }
+
+    fn deshacer_archivos(&self) -> Result<Reparaciones, NodoBitcoinError> {
+        let mut reparaciones = Reparaciones::default();
+        volver_a_estado(
+            &self.estado,
+            &self.headers_path,
+            &self.blocks_dir,
+            &mut reparaciones,
+        )?;
+        fs::remove_file(&self.path).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        Ok(reparaciones)
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        // si no se puede deshacer queda el archivo del journal y se deshace al iniciar
+        if !self.terminado {
+            _ = self.deshacer_archivos();
+        }
+    }
+}
+
+/// Resultado de la verificación de consistencia de los archivos de la cadena
+#[derive(Debug, Default, PartialEq)]
+pub struct Reparaciones {
+    pub mensajes: Vec<String>,
+    pub headers_truncados: bool,
+    pub bloques_truncados: bool,
+    pub tamanio_headers: u64,
+}
+
+impl Reparaciones {
+    fn truncar_headers(
+        &mut self,
+        headers_path: &str,
+        tamanio: u64,
+    ) -> Result<(), NodoBitcoinError> {
+        truncar_archivo(headers_path.to_string(), tamanio)?;
+        self.headers_truncados = true;
+        self.tamanio_headers = tamanio;
+        Ok(())
+    }
+}
+
+/// Verifica al iniciar que los archivos de headers y bloques sean consistentes entre sí
+/// Si no lo son, los trunca hasta el último punto consistente, limpia los índices afectados
+/// y deja en el log cada reparación
+pub fn verificar_consistencia(logger: Sender<LogMessages>) -> Result<(), NodoBitcoinError> {
+    let headers_path = get_headers_filename()?;
+    let reparaciones = verificar_consistencia_en(
+        &get_journal_filename(),
+        &headers_path,
+        &get_blocks_dirname()?,
+        get_timestamp_inicial(),
+    )?;
+
+    actualizar_indices(&reparaciones, logger)
+}
+
+/// Deja en el log las reparaciones y limpia los índices de los headers y bloques descartados
+pub fn actualizar_indices(
+    reparaciones: &Reparaciones,
+    logger: Sender<LogMessages>,
+) -> Result<(), NodoBitcoinError> {
+    for mensaje in reparaciones.mensajes.iter() {
+        log_info_message(logger.clone(), format!("Reparación: {}", mensaje));
+    }
+    if reparaciones.headers_truncados {
+        let borradas = purgar_indice_de_headers(reparaciones.tamanio_headers)?;
+        log_info_message(
+            logger.clone(),
+            format!(
+                "Reparación: {} entradas borradas del índice de headers",
+                borradas
+            ),
+        );
+    }
+    if reparaciones.bloques_truncados {
+        let indexados = reconstruir_indices_opcionales()?;
+        log_info_message(
+            logger,
+            format!(
+                "Reparación: índices opcionales reconstruidos con {} bloques",
+                indexados
+            ),
+        );
+    }
+    Ok(())
+}
+
+fn verificar_consistencia_en(
+    journal_path: &str,
+    headers_path: &str,
+    blocks_dir: &str,
+    timestamp_inicial: u32,
+) -> Result<Reparaciones, NodoBitcoinError> {
+    let mut reparaciones = Reparaciones::default();
+    deshacer_escritura_incompleta(journal_path, headers_path, blocks_dir, &mut reparaciones)?;
+    truncar_header_incompleto(headers_path, &mut reparaciones)?;
+    truncar_registro_incompleto(blocks_dir, &mut reparaciones)?;
+    truncar_headers_sin_bloque(
+        headers_path,
+        blocks_dir,
+        timestamp_inicial,
+        &mut reparaciones,
+    )?;
+    Ok(reparaciones)
+}
+
+/// Si quedó un journal es porque la última escritura no terminó: se vuelve al estado guardado en él
+fn deshacer_escritura_incompleta(
+    journal_path: &str,
+    headers_path: &str,
+    blocks_dir: &str,
+    reparaciones: &mut Reparaciones,
+) -> Result<(), NodoBitcoinError> {
+    let bytes = match fs::read(journal_path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Ok(()),
+    };
+    // si el journal está incompleto no se llegó a escribir nada después de él
+    if let Ok(estado) = EstadoArchivos::deserialize(&bytes) {
+        volver_a_estado(&estado, headers_path, blocks_dir, reparaciones)?;
+    }
+    fs::remove_file(journal_path).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)
+}
+
+/// Trunca los headers y el último archivo de bloques al tamaño guardado y borra los archivos
+/// de bloques creados después
+fn volver_a_estado(
+    estado: &EstadoArchivos,
+    headers_path: &str,
+    blocks_dir: &str,
+    reparaciones: &mut Reparaciones,
+) -> Result<(), NodoBitcoinError> {
+    let tamanio_headers = get_file_size(headers_path.to_string()).unwrap_or(0);
+    if tamanio_headers > estado.tamanio_headers {
+        reparaciones.truncar_headers(headers_path, estado.tamanio_headers)?;
+        reparaciones.mensajes.push(format!(
+            "escritura incompleta, headers truncados de {} a {} bytes",
+            tamanio_headers, estado.tamanio_headers
+        ));
+    }
+    for archivo in numeros_de_archivos(blocks_dir) {
+        let path = nombre_archivo_bloques(blocks_dir, archivo);
+        if archivo > estado.ultimo_archivo {
+            fs::remove_file(&path).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+            reparaciones.bloques_truncados = true;
+            reparaciones
+                .mensajes
+                .push(format!("escritura incompleta, borrado {}", path));
+        } else if archivo == estado.ultimo_archivo {
+            let tamanio = get_file_size(path.clone())?;
+            if tamanio > estado.tamanio_ultimo_archivo {
+                truncar_archivo(path.clone(), estado.tamanio_ultimo_archivo)?;
+                reparaciones.bloques_truncados = true;
+                reparaciones.mensajes.push(format!(
+                    "escritura incompleta, {} truncado de {} a {} bytes",
+                    path, tamanio, estado.tamanio_ultimo_archivo
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Trunca el archivo de headers al último header completo
+fn truncar_header_incompleto(
+    headers_path: &str,
+    reparaciones: &mut Reparaciones,
+) -> Result<(), NodoBitcoinError> {
+    let tamanio = match get_file_size(headers_path.to_string()) {
+        Ok(tamanio) => tamanio,
+        Err(_) => return Ok(()),
+    };
+    let sobrante = tamanio % TAMANIO_HEADER;
+    if sobrante != 0 {
+        reparaciones.truncar_headers(headers_path, tamanio - sobrante)?;
+        reparaciones.mensajes.push(format!(
+            "header incompleto, se descartaron {} bytes del archivo de headers",
+            sobrante
+        ));
+    }
+    Ok(())
+}
+
+/// Trunca el último archivo de bloques al último registro completo y con checksum correcto
+fn truncar_registro_incompleto(
+    blocks_dir: &str,
+    reparaciones: &mut Reparaciones,
+) -> Result<(), NodoBitcoinError> {
+    let archivo = match numeros_de_archivos(blocks_dir).last() {
+        Some(archivo) => *archivo,
+        None => return Ok(()),
+    };
+    let path = nombre_archivo_bloques(blocks_dir, archivo);
+    let tamanio = get_file_size(path.clone())?;
+    let (ubicaciones, mut fin) = recorrer_registros(blocks_dir, archivo)?;
+    if let Some(ultima) = ubicaciones.last() {
+        if leer_registro(blocks_dir, *ultima).is_err() {
+            fin = ultima.offset;
+        }
+    }
+    if fin < tamanio {
+        truncar_archivo(path.clone(), fin)?;
+        reparaciones.bloques_truncados = true;
+        reparaciones.mensajes.push(format!(
+            "registro de bloque incompleto, {} truncado de {} a {} bytes",
+            path, tamanio, fin
+        ));
+    }
+    Ok(())
+}
+
+/// Devuelve los hashes de los bloques de los dos últimos archivos de bloques
+/// Los bloques de una misma descarga se guardan ordenados por timestamp y pueden quedar
+/// repartidos entre dos archivos, por eso no alcanza con mirar solo el último bloque
+fn hashes_de_ultimos_bloques(blocks_dir: &str) -> Result<HashSet<[u8; 32]>, NodoBitcoinError> {
+    let mut hashes = HashSet::new();
+    for archivo in numeros_de_archivos(blocks_dir).iter().rev().take(2) {
+        for header in headers_de_archivo(blocks_dir, *archivo)? {
+            hashes.insert(header.hash()?);
+        }
+    }
+    Ok(hashes)
+}
+
+/// Los bloques se guardan antes que sus headers, así que los últimos headers a partir del
+/// día inicial tienen que tener su bloque guardado. Descarta los que quedaron sin él.
+fn truncar_headers_sin_bloque(
+    headers_path: &str,
+    blocks_dir: &str,
+    timestamp_inicial: u32,
+    reparaciones: &mut Reparaciones,
+) -> Result<(), NodoBitcoinError> {
+    let cantidad = match get_file_size(headers_path.to_string()) {
+        Ok(tamanio) => tamanio / TAMANIO_HEADER,
+        Err(_) => return Ok(()),
+    };
+    let hashes = hashes_de_ultimos_bloques(blocks_dir)?;
+    let mut consistentes = cantidad;
+    while consistentes > 0 {
+        let bytes = leer_bytes(
+            headers_path.to_string(),
+            (consistentes - 1) * TAMANIO_HEADER,
+            TAMANIO_HEADER,
+        )?;
+        let header = BlockHeader::deserialize(&bytes)?;
+        if header.time < timestamp_inicial || hashes.contains(&header.hash()?) {
+            break;
+        }
+        consistentes -= 1;
+    }
+    if consistentes < cantidad {
+        reparaciones.truncar_headers(headers_path, consistentes * TAMANIO_HEADER)?;
+        reparaciones.mensajes.push(format!(
+            "{} headers sin su bloque descartados",
+            cantidad - consistentes
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::block_file::{escribir_registro, TAMANIO_MAXIMO_ARCHIVO_DEFAULT};
+    use crate::blockchain::file::escribir_archivo;
+
+    struct Archivos {
+        dir: String,
+        journal: String,
+        headers: String,
+        bloques: String,
+    }
+
+    fn archivos_de_prueba(nombre: &str) -> Archivos {
+        let dir = std::env::temp_dir().join(nombre);
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap().to_string();
+        Archivos {
+            journal: format!("{}/journal.bin", dir),
+            headers: format!("{}/headers.bin", dir),
+            bloques: format!("{}/blocks", dir),
+            dir,
+        }
+    }
+
+    fn header(nonce: u32) -> BlockHeader {
+        BlockHeader {
+            version: 1,
+            previous_block_hash: [0; 32],
+            merkle_root_hash: [0; 32],
+            time: 1000 + nonce,
+            n_bits: 0,
+            nonce,
+        }
+    }
+
+    /// Guarda el bloque (su header seguido de bytes cualquiera) y luego el header
+    fn guardar(archivos: &Archivos, nonce: u32) {
+        let header = header(nonce);
+        let mut bloque = header.serialize().unwrap();
+        bloque.extend_from_slice(&[0; 10]);
+        escribir_registro(&archivos.bloques, &bloque, TAMANIO_MAXIMO_ARCHIVO_DEFAULT).unwrap();
+        escribir_archivo(archivos.headers.clone(), &header.serialize().unwrap()).unwrap();
+    }
+
+    fn verificar(archivos: &Archivos) -> Reparaciones {
+        verificar_consistencia_en(&archivos.journal, &archivos.headers, &archivos.bloques, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_archivos_consistentes_no_se_reparan() {
+        let archivos = archivos_de_prueba("journal_test_consistentes");
+        guardar(&archivos, 1);
+        guardar(&archivos, 2);
+
+        assert_eq!(verificar(&archivos), Reparaciones::default());
+        _ = fs::remove_dir_all(&archivos.dir);
+    }
+
+    #[test]
+    fn test_journal_deshace_escritura_incompleta() {
+        let archivos = archivos_de_prueba("journal_test_deshacer");
+        guardar(&archivos, 1);
+        let antes = EstadoArchivos::actual(&archivos.headers, &archivos.bloques);
+
+        let journal = Journal::iniciar(
+            archivos.journal.clone(),
+            archivos.headers.clone(),
+            archivos.bloques.clone(),
+        )
+        .unwrap();
+        guardar(&archivos, 2);
+        // se corta el proceso sin que el journal llegue a deshacer nada
+        std::mem::forget(journal);
+
+        let reparaciones = verificar(&archivos);
+        assert!(reparaciones.headers_truncados);
+        assert!(reparaciones.bloques_truncados);
+        assert_eq!(
+            EstadoArchivos::actual(&archivos.headers, &archivos.bloques),
+            antes
+        );
+        assert!(fs::metadata(&archivos.journal).is_err());
+
+        let mut journal = Journal::iniciar(
+            archivos.journal.clone(),
+            archivos.headers.clone(),
+            archivos.bloques.clone(),
+        )
+        .unwrap();
+        guardar(&archivos, 2);
+        journal.finalizar().unwrap();
+        assert_eq!(verificar(&archivos), Reparaciones::default());
+        _ = fs::remove_dir_all(&archivos.dir);
+    }
+
+    #[test]
+    fn test_journal_deshace_si_falla_entre_el_bloque_y_el_header() {
+        let archivos = archivos_de_prueba("journal_test_falla");
+        guardar(&archivos, 1);
+        let antes = EstadoArchivos::actual(&archivos.headers, &archivos.bloques);
+
+        let escribir = || -> Result<(), NodoBitcoinError> {
+            let journal = Journal::iniciar(
+                archivos.journal.clone(),
+                archivos.headers.clone(),
+                archivos.bloques.clone(),
+            )?;
+            let mut bloque = header(2).serialize()?;
+            bloque.extend_from_slice(&[0; 10]);
+            escribir_registro(&archivos.bloques, &bloque, TAMANIO_MAXIMO_ARCHIVO_DEFAULT)?;
+            journal.sincronizar_bloques()?;
+            // falla la escritura del header
+            Err(NodoBitcoinError::NoSePuedeEscribirLosBytes)
+        };
+        assert!(escribir().is_err());
+
+        assert_eq!(
+            EstadoArchivos::actual(&archivos.headers, &archivos.bloques),
+            antes
+        );
+        assert!(fs::metadata(&archivos.journal).is_err());
+        assert_eq!(verificar(&archivos), Reparaciones::default());
+
+        // al deshacer explícitamente se devuelven las reparaciones para actualizar los índices
+        let journal = Journal::iniciar(
+            archivos.journal.clone(),
+            archivos.headers.clone(),
+            archivos.bloques.clone(),
+        )
+        .unwrap();
+        guardar(&archivos, 2);
+        let reparaciones = journal.deshacer().unwrap();
+        assert!(reparaciones.headers_truncados);
+        assert!(reparaciones.bloques_truncados);
+        assert_eq!(
+            EstadoArchivos::actual(&archivos.headers, &archivos.bloques),
+            antes
+        );
+        _ = fs::remove_dir_all(&archivos.dir);
+    }
+
+    #[test]
+    fn test_trunca_header_y_registro_incompletos() {
+        let archivos = archivos_de_prueba("journal_test_incompletos");
+        guardar(&archivos, 1);
+        guardar(&archivos, 2);
+        let antes = EstadoArchivos::actual(&archivos.headers, &archivos.bloques);
+
+        let path_bloques = nombre_archivo_bloques(&archivos.bloques, 0);
+        let mut bloques = fs::read(&path_bloques).unwrap();
+        bloques.extend_from_slice(&crate::messages::messages_header::MAGIC_NUMBER_TESTNET);
+        fs::write(&path_bloques, bloques).unwrap();
+        escribir_archivo(archivos.headers.clone(), &[1; 30]).unwrap();
+
+        let reparaciones = verificar(&archivos);
+        assert_eq!(reparaciones.mensajes.len(), 2);
+        assert_eq!(
+            EstadoArchivos::actual(&archivos.headers, &archivos.bloques),
+            antes
+        );
+        _ = fs::remove_dir_all(&archivos.dir);
+    }
+
+    #[test]
+    fn test_descarta_headers_sin_bloque() {
+        let archivos = archivos_de_prueba("journal_test_sin_bloque");
+        guardar(&archivos, 1);
+        let tamanio_consistente = get_file_size(archivos.headers.clone()).unwrap();
+        escribir_archivo(archivos.headers.clone(), &header(2).serialize().unwrap()).unwrap();
+        escribir_archivo(archivos.headers.clone(), &header(3).serialize().unwrap()).unwrap();
+
+        let reparaciones = verificar(&archivos);
+        assert!(reparaciones.headers_truncados);
+        assert_eq!(reparaciones.tamanio_headers, tamanio_consistente);
+        assert_eq!(
+            get_file_size(archivos.headers.clone()).unwrap(),
+            tamanio_consistente
+        );
+        _ = fs::remove_dir_all(&archivos.dir);
+    }
+}
@@ -0,0 +1,1118 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::politica::{requerir_estandar, validar_relay};
+use crate::blockchain::transaction::Transaction;
+use crate::blockchain::utxos_nodo::{clave_de_input, ClaveUtxo, UtxosNodo};
+use crate::common::uint256::Uint256;
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tamaño máximo de la mempool en bytes si no se indica en el config, igual que `-maxmempool` en Bitcoin Core
+pub const TAMANIO_MAXIMO_MEMPOOL_DEFAULT: usize = 300 * 1024 * 1024;
+
+/// Nombre del archivo de la mempool si no se indica en el config
+pub const NOMBRE_ARCHIVO_MEMPOOL_DEFAULT: &str = "mempool.dat";
+
+const VERSION_ARCHIVO_MEMPOOL: u32 = 1;
+
+/// Máxima cantidad de transacciones, contando descendientes, que puede desalojar un reemplazo (BIP125)
+pub const MAXIMO_REEMPLAZADAS: usize = 100;
+
+/// Fee por byte que tiene que pagar un reemplazo además del fee de las transacciones que desaloja
+pub const FEE_INCREMENTAL_POR_BYTE: u64 = 1;
+
+/// Máxima cantidad de ancestros sin confirmar de una transacción, contándola a ella, igual que en Bitcoin Core
+pub const MAXIMO_ANCESTROS: usize = 25;
+
+/// Devuelve el tamaño máximo de la mempool guardado en el config, o el valor por defecto
+pub fn tamanio_maximo_mempool() -> usize {
+    match config::get_valor("TAMANIO_MAXIMO_MEMPOOL".to_string()) {
+        Ok(valor) => valor
+            .parse::<usize>()
+            .unwrap_or(TAMANIO_MAXIMO_MEMPOOL_DEFAULT),
+        Err(_) => TAMANIO_MAXIMO_MEMPOOL_DEFAULT,
+    }
+}
+
+/// Transacción aceptada en la mempool junto con su fee y su tamaño virtual, que para las
+/// transacciones sin testigos es el tamaño serializada
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntradaMempool {
+    pub tx: Transaction,
+    pub fee: u64,
+    pub tamanio: usize,
+    pub hora: u32,
+}
+
+impl EntradaMempool {
+    /// Compara el fee por byte de las dos entradas sin perder precisión
+    pub fn comparar_fee_rate(&self, otra: &EntradaMempool) -> Ordering {
+        comparar_fee_rate(self.fee, self.tamanio, otra.fee, otra.tamanio)
+    }
+}
+
+/// Transacciones de la mempool que se tienen que confirmar juntas, con su fee y tamaño sumados
+/// El paquete de ancestros de una transacción la incluye a ella y a todos sus ancestros sin
+/// confirmar, y el de descendientes a ella y a todas las que gastan sus salidas
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paquete {
+    pub txids: Vec<Uint256>,
+    pub fee: u64,
+    pub tamanio: usize,
+}
+
+impl Paquete {
+    /// Compara el fee por byte de los dos paquetes sin perder precisión
+    pub fn comparar_fee_rate(&self, otro: &Paquete) -> Ordering {
+        comparar_fee_rate(self.fee, self.tamanio, otro.fee, otro.tamanio)
+    }
+
+    /// Devuelve el fee por byte del paquete
+    pub fn fee_rate(&self) -> f64 {
+        if self.tamanio == 0 {
+            return 0.0;
+        }
+        self.fee as f64 / self.tamanio as f64
+    }
+}
+
+fn comparar_fee_rate(fee: u64, tamanio: usize, otro_fee: u64, otro_tamanio: usize) -> Ordering {
+    let propio = fee as u128 * otro_tamanio as u128;
+    let ajeno = otro_fee as u128 * tamanio as u128;
+    propio.cmp(&ajeno)
+}
+
+/// Fee y tamaño por los que se evalúa una transacción al desalojar: el mayor fee rate entre el
+/// suyo y el de su paquete de descendientes, así no se desaloja un padre cuyo fee paga una hija
+/// (CPFP). Se ordenan por fee rate y después por txid
+#[derive(Debug, Clone, Copy)]
+struct PuntajeDesalojo {
+    txid: Uint256,
+    fee: u64,
+    tamanio: usize,
+}
+
+impl PuntajeDesalojo {
+    fn comparar_fee_rate(&self, otro: &PuntajeDesalojo) -> Ordering {
+        comparar_fee_rate(self.fee, self.tamanio, otro.fee, otro.tamanio)
+    }
+}
+
+impl PartialEq for PuntajeDesalojo {
+    fn eq(&self, otro: &PuntajeDesalojo) -> bool {
+        self.cmp(otro) == Ordering::Equal
+    }
+}
+
+impl Eq for PuntajeDesalojo {}
+
+impl PartialOrd for PuntajeDesalojo {
+    fn partial_cmp(&self, otro: &PuntajeDesalojo) -> Option<Ordering> {
+        Some(self.cmp(otro))
+    }
+}
+
+impl Ord for PuntajeDesalojo {
+    fn cmp(&self, otro: &PuntajeDesalojo) -> Ordering {
+        self.comparar_fee_rate(otro)
+            .then_with(|| self.txid.cmp(&otro.txid))
+    }
+}
+
+/// Transacciones válidas todavía no confirmadas que conoce el nodo
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    entradas: HashMap<Uint256, EntradaMempool>,
+    // para cada salida gastada por la mempool, la transacción que la gasta
+    gastadas: HashMap<ClaveUtxo, Uint256>,
+    tamanio_total: usize,
+    tamanio_maximo: usize,
+    // el puntaje de desalojo de cada transacción y los mismos puntajes ordenados, el primero es
+    // el de la próxima a desalojar
+    puntajes: HashMap<Uint256, PuntajeDesalojo>,
+    desalojo: BTreeSet<PuntajeDesalojo>,
+    // si rechaza las transacciones que no cumplen la política de retransmisión
+    requerir_estandar: bool,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT)
+    }
+}
+
+impl Mempool {
+    pub fn new(tamanio_maximo: usize) -> Mempool {
+        Mempool {
+            entradas: HashMap::new(),
+            gastadas: HashMap::new(),
+            tamanio_total: 0,
+            tamanio_maximo,
+            puntajes: HashMap::new(),
+            desalojo: BTreeSet::new(),
+            requerir_estandar: false,
+        }
+    }
+
+    /// Crea la mempool con el tamaño máximo y la política de retransmisión del config
+    pub fn desde_config() -> Mempool {
+        Mempool::new(tamanio_maximo_mempool()).con_politica(requerir_estandar())
+    }
+
+    /// Indica si la mempool sólo acepta transacciones estándar
+    pub fn con_politica(mut self, requerir_estandar: bool) -> Mempool {
+        self.requerir_estandar = requerir_estandar;
+        self
+    }
+
+    /// Recibe la transacción y las UTXOs del nodo
+    /// Valida que sus inputs gasten salidas sin gastar de los bloques o de otras transacciones
+    /// de la mempool y que no gaste más de lo que recibe. Si la mempool se pasa del tamaño
+    /// máximo, desaloja las transacciones con menor fee rate. Si la nueva no entra, la mempool
+    /// queda como estaba.
+    /// Devuelve el txid de la transacción agregada
+    pub fn agregar(
+        &mut self,
+        tx: Transaction,
+        utxos: &UtxosNodo,
+    ) -> Result<Uint256, NodoBitcoinError> {
+        let (txid, _) = self.agregar_con_hora(tx, utxos, ahora())?;
+        Ok(txid)
+    }
+
+    /// Igual que agregar, pero devuelve también los txids de las transacciones que reemplazó
+    pub fn agregar_reemplazando(
+        &mut self,
+        tx: Transaction,
+        utxos: &UtxosNodo,
+    ) -> Result<(Uint256, Vec<Uint256>), NodoBitcoinError> {
+        self.agregar_con_hora(tx, utxos, ahora())
+    }
+
+    fn agregar_con_hora(
+        &mut self,
+        tx: Transaction,
+        utxos: &UtxosNodo,
+        hora: u32,
+    ) -> Result<(Uint256, Vec<Uint256>), NodoBitcoinError> {
+        let txid = tx.txid()?;
+        if self.entradas.contains_key(&txid) {
+            return Err(NodoBitcoinError::TxYaEnMempool);
+        }
+        if tx.input.is_empty() || tx.output.is_empty() {
+            return Err(NodoBitcoinError::TxInvalida);
+        }
+
+        let mut claves = HashSet::new();
+        let mut en_conflicto = HashSet::new();
+        let mut total_inputs: u64 = 0;
+        for tx_in in tx.input.iter() {
+            let clave = clave_de_input(tx_in);
+            if !claves.insert(clave) {
+                return Err(NodoBitcoinError::TxInvalida);
+            }
+            if let Some(gastada_por) = self.gastadas.get(&clave) {
+                en_conflicto.insert(*gastada_por);
+            }
+            let valor = match self.salida_sin_confirmar(&clave) {
+                Some(valor) => valor,
+                None => match utxos.obtener(&clave) {
+                    Some(tx_out) => tx_out.value,
+                    None => return Err(NodoBitcoinError::InputsDesconocidos),
+                },
+            };
+            total_inputs = total_inputs
+                .checked_add(valor)
+                .ok_or(NodoBitcoinError::TxInvalida)?;
+        }
+
+        let mut total_outputs: u64 = 0;
+        for tx_out in tx.output.iter() {
+            total_outputs = total_outputs
+                .checked_add(tx_out.value)
+                .ok_or(NodoBitcoinError::TxInvalida)?;
+        }
+        if total_outputs > total_inputs {
+            return Err(NodoBitcoinError::TxInvalida);
+        }
+
+        let entrada = EntradaMempool {
+            fee: total_inputs - total_outputs,
+            tamanio: tx.vsize()?,
+            hora,
+            tx,
+        };
+        if self.requerir_estandar {
+            validar_relay(&entrada.tx, entrada.fee, entrada.tamanio)?;
+        }
+        if self.ancestros_de(&entrada.tx).len() + 1 > MAXIMO_ANCESTROS {
+            return Err(NodoBitcoinError::DemasiadosAncestros);
+        }
+        let reemplazadas = self.validar_reemplazo(&entrada, &en_conflicto)?;
+        // todo lo que se quita se decide antes de tocar la mempool
+        let mut quitadas = reemplazadas.clone();
+        quitadas.extend(self.desalojadas_para(txid, &entrada, &reemplazadas)?);
+        self.quitar_entradas(&quitadas);
+        self.insertar(txid, entrada);
+        Ok((txid, reemplazadas))
+    }
+
+    /// Recibe la transacción nueva y las que reemplaza
+    /// Devuelve las que hay que desalojar para que entre sin pasarse del tamaño máximo: las de
+    /// menor puntaje junto con sus descendientes. Los ancestros de la nueva se evalúan con ella
+    /// como descendiente, así una hija puede pagar por su padre
+    /// Si la nueva o uno de sus ancestros se tendría que desalojar antes que las otras, no entra
+    fn desalojadas_para(
+        &self,
+        txid: Uint256,
+        entrada: &EntradaMempool,
+        reemplazadas: &[Uint256],
+    ) -> Result<Vec<Uint256>, NodoBitcoinError> {
+        let mut quitadas: HashSet<Uint256> = reemplazadas.iter().copied().collect();
+        let tamanio_reemplazadas: usize = quitadas
+            .iter()
+            .filter_map(|txid| self.entradas.get(txid))
+            .map(|reemplazada| reemplazada.tamanio)
+            .sum();
+        let mut tamanio_total = self.tamanio_total - tamanio_reemplazadas + entrada.tamanio;
+        let mut desalojadas = vec![];
+        if tamanio_total <= self.tamanio_maximo {
+            return Ok(desalojadas);
+        }
+
+        let ancestros = self.ancestros_de(&entrada.tx);
+        let mut con_la_nueva: Vec<PuntajeDesalojo> = ancestros
+            .iter()
+            .filter_map(|ancestro| self.puntaje_desalojo(ancestro, Some(entrada)))
+            .collect();
+        con_la_nueva.push(PuntajeDesalojo {
+            txid,
+            fee: entrada.fee,
+            tamanio: entrada.tamanio,
+        });
+        con_la_nueva.sort();
+        let mut con_la_nueva = con_la_nueva.into_iter().peekable();
+        let mut resto = self
+            .desalojo
+            .iter()
+            .filter(|puntaje| !ancestros.contains(&puntaje.txid))
+            .copied()
+            .peekable();
+        while tamanio_total > self.tamanio_maximo {
+            // con el mismo fee rate se desaloja primero a la nueva
+            let peor = match (con_la_nueva.peek(), resto.peek()) {
+                (Some(nueva), Some(otra)) if nueva.comparar_fee_rate(otra) == Ordering::Greater => {
+                    resto.next()
+                }
+                (Some(_), _) => con_la_nueva.next(),
+                (None, _) => resto.next(),
+            };
+            let peor = match peor {
+                Some(peor) => peor,
+                None => break,
+            };
+            if peor.txid == txid || ancestros.contains(&peor.txid) {
+                return Err(NodoBitcoinError::FeeRateInsuficiente);
+            }
+            for desalojada in self.descendientes(&HashSet::from([peor.txid])) {
+                if !quitadas.insert(desalojada) {
+                    continue;
+                }
+                if let Some(entrada) = self.entradas.get(&desalojada) {
+                    tamanio_total -= entrada.tamanio;
+                }
+                desalojadas.push(desalojada);
+            }
+        }
+        Ok(desalojadas)
+    }
+
+    /// Recibe la entrada nueva y las transacciones de la mempool que gastan alguno de sus inputs
+    /// Aplica las reglas de BIP125: todas las transacciones en conflicto tienen que señalizar RBF,
+    /// la nueva solo puede gastar salidas sin confirmar que ya gastaban ellas, tiene que pagar
+    /// un fee rate mayor que cada una y un fee que cubra el de todas las que desaloja más el fee
+    /// incremental, sin desalojar más de MAXIMO_REEMPLAZADAS
+    /// Devuelve los txids de las transacciones que se desalojarían
+    fn validar_reemplazo(
+        &self,
+        entrada: &EntradaMempool,
+        en_conflicto: &HashSet<Uint256>,
+    ) -> Result<Vec<Uint256>, NodoBitcoinError> {
+        if en_conflicto.is_empty() {
+            return Ok(vec![]);
+        }
+        for txid in en_conflicto.iter() {
+            if !self.senaliza_rbf(txid) {
+                return Err(NodoBitcoinError::TxEnConflictoConMempool);
+            }
+            if let Some(original) = self.entradas.get(txid) {
+                if entrada.comparar_fee_rate(original) != Ordering::Greater {
+                    return Err(NodoBitcoinError::FeeInsuficienteParaReemplazo);
+                }
+            }
+        }
+        let padres: HashSet<Uint256> = en_conflicto
+            .iter()
+            .filter_map(|txid| self.entradas.get(txid))
+            .flat_map(|original| original.tx.input.iter())
+            .map(|tx_in| clave_de_input(tx_in).0)
+            .collect();
+        if entrada.tx.input.iter().any(|tx_in| {
+            let padre = clave_de_input(tx_in).0;
+            self.entradas.contains_key(&padre) && !padres.contains(&padre)
+        }) {
+            return Err(NodoBitcoinError::ReemplazoConInputsSinConfirmar);
+        }
+
+        let reemplazadas = self.descendientes(en_conflicto);
+        if reemplazadas.len() > MAXIMO_REEMPLAZADAS {
+            return Err(NodoBitcoinError::DemasiadasTransaccionesReemplazadas);
+        }
+        // no puede gastar salidas de las transacciones que desaloja
+        if entrada
+            .tx
+            .input
+            .iter()
+            .any(|tx_in| reemplazadas.contains(&clave_de_input(tx_in).0))
+        {
+            return Err(NodoBitcoinError::TxInvalida);
+        }
+
+        let fee_reemplazadas: u64 = reemplazadas
+            .iter()
+            .filter_map(|txid| self.entradas.get(txid))
+            .map(|reemplazada| reemplazada.fee)
+            .sum();
+        let fee_minimo = fee_reemplazadas + FEE_INCREMENTAL_POR_BYTE * entrada.tamanio as u64;
+        if entrada.fee < fee_minimo {
+            return Err(NodoBitcoinError::FeeInsuficienteParaReemplazo);
+        }
+        Ok(reemplazadas)
+    }
+
+    /// Devuelve true si la transacción o alguno de sus ancestros sin confirmar señaliza RBF
+    fn senaliza_rbf(&self, txid: &Uint256) -> bool {
+        let mut visitadas = HashSet::new();
+        let mut pendientes = vec![*txid];
+        while let Some(txid) = pendientes.pop() {
+            if !visitadas.insert(txid) {
+                continue;
+            }
+            let entrada = match self.entradas.get(&txid) {
+                Some(entrada) => entrada,
+                None => continue,
+            };
+            if entrada.tx.senaliza_rbf() {
+                return true;
+            }
+            for tx_in in entrada.tx.input.iter() {
+                pendientes.push(clave_de_input(tx_in).0);
+            }
+        }
+        false
+    }
+
+    /// Devuelve los txids de las transacciones de la mempool de las que depende la transacción,
+    /// directa o indirectamente
+    fn ancestros_de(&self, tx: &Transaction) -> HashSet<Uint256> {
+        let mut ancestros = HashSet::new();
+        let mut pendientes: Vec<Uint256> = tx
+            .input
+            .iter()
+            .map(|tx_in| clave_de_input(tx_in).0)
+            .collect();
+        while let Some(txid) = pendientes.pop() {
+            let entrada = match self.entradas.get(&txid) {
+                Some(entrada) => entrada,
+                None => continue,
+            };
+            if !ancestros.insert(txid) {
+                continue;
+            }
+            for tx_in in entrada.tx.input.iter() {
+                pendientes.push(clave_de_input(tx_in).0);
+            }
+        }
+        ancestros
+    }
+
+    /// Devuelve el paquete con la transacción y todos sus ancestros sin confirmar, o None si
+    /// no está en la mempool
+    pub fn paquete_de_ancestros(&self, txid: &Uint256) -> Option<Paquete> {
+        let entrada = self.entradas.get(txid)?;
+        let mut txids = vec![*txid];
+        txids.extend(self.ancestros_de(&entrada.tx));
+        Some(self.paquete(txids))
+    }
+
+    /// Devuelve el paquete con la transacción y todas las que gastan sus salidas, o None si
+    /// no está en la mempool
+    pub fn paquete_de_descendientes(&self, txid: &Uint256) -> Option<Paquete> {
+        if !self.entradas.contains_key(txid) {
+            return None;
+        }
+        let raiz = HashSet::from([*txid]);
+        Some(self.paquete(self.descendientes(&raiz)))
+    }
+
+    fn paquete(&self, txids: Vec<Uint256>) -> Paquete {
+        let mut fee = 0;
+        let mut tamanio = 0;
+        for entrada in txids.iter().filter_map(|txid| self.entradas.get(txid)) {
+            fee += entrada.fee;
+            tamanio += entrada.tamanio;
+        }
+        Paquete {
+            txids,
+            fee,
+            tamanio,
+        }
+    }
+
+    /// Devuelve true si alguna transacción de la mempool gasta la salida
+    pub fn esta_gastada(&self, clave: &ClaveUtxo) -> bool {
+        self.gastadas.contains_key(clave)
+    }
+
+    /// Devuelve los txids recibidos que están en la mempool junto con todos sus descendientes
+    fn descendientes(&self, txids: &HashSet<Uint256>) -> Vec<Uint256> {
+        let mut descendientes = vec![];
+        let mut visitadas = HashSet::new();
+        let mut pendientes: Vec<Uint256> = txids.iter().copied().collect();
+        while let Some(txid) = pendientes.pop() {
+            if !visitadas.insert(txid) {
+                continue;
+            }
+            let entrada = match self.entradas.get(&txid) {
+                Some(entrada) => entrada,
+                None => continue,
+            };
+            for index in 0..entrada.tx.output.len() {
+                if let Some(hijo) = self.gastadas.get(&(txid, index as u32)) {
+                    pendientes.push(*hijo);
+                }
+            }
+            descendientes.push(txid);
+        }
+        descendientes
+    }
+
+    /// Devuelve las salidas que gasta la transacción y que no están ni en las UTXOs del nodo ni
+    /// en la mempool, o sea las de los padres que todavía no se recibieron
+    pub fn salidas_faltantes(&self, tx: &Transaction, utxos: &UtxosNodo) -> Vec<ClaveUtxo> {
+        tx.input
+            .iter()
+            .map(clave_de_input)
+            .filter(|clave| {
+                self.salida_sin_confirmar(clave).is_none() && utxos.obtener(clave).is_none()
+            })
+            .collect()
+    }
+
+    fn salida_sin_confirmar(&self, clave: &ClaveUtxo) -> Option<u64> {
+        let entrada = self.entradas.get(&clave.0)?;
+        entrada
+            .tx
+            .output
+            .get(clave.1 as usize)
+            .map(|tx_out| tx_out.value)
+    }
+
+    fn insertar(&mut self, txid: Uint256, entrada: EntradaMempool) {
+        let ancestros = self.ancestros_de(&entrada.tx);
+        for tx_in in entrada.tx.input.iter() {
+            self.gastadas.insert(clave_de_input(tx_in), txid);
+        }
+        self.tamanio_total += entrada.tamanio;
+        self.entradas.insert(txid, entrada);
+        // la nueva agranda el paquete de descendientes de sus ancestros
+        self.actualizar_puntajes(ancestros.into_iter().chain([txid]));
+    }
+
+    /// Quita las transacciones y actualiza el puntaje de los ancestros que quedan
+    fn quitar_entradas(&mut self, txids: &[Uint256]) {
+        let mut ancestros = HashSet::new();
+        for txid in txids {
+            if let Some(entrada) = self.entradas.get(txid) {
+                ancestros.extend(self.ancestros_de(&entrada.tx));
+            }
+        }
+        for txid in txids {
+            let entrada = match self.entradas.remove(txid) {
+                Some(entrada) => entrada,
+                None => continue,
+            };
+            for tx_in in entrada.tx.input.iter() {
+                self.gastadas.remove(&clave_de_input(tx_in));
+            }
+            self.tamanio_total -= entrada.tamanio;
+        }
+        self.actualizar_puntajes(ancestros.into_iter().chain(txids.iter().copied()));
+    }
+
+    /// Vuelve a calcular el puntaje de desalojo de las transacciones, las que ya no están en la
+    /// mempool salen del índice
+    fn actualizar_puntajes(&mut self, txids: impl IntoIterator<Item = Uint256>) {
+        for txid in txids {
+            if let Some(anterior) = self.puntajes.remove(&txid) {
+                self.desalojo.remove(&anterior);
+            }
+            if let Some(puntaje) = self.puntaje_desalojo(&txid, None) {
+                self.puntajes.insert(txid, puntaje);
+                self.desalojo.insert(puntaje);
+            }
+        }
+    }
+
+    /// Devuelve el puntaje de desalojo de la transacción, contando entre sus descendientes a la
+    /// hija que todavía no está en la mempool si se recibe, o None si no está en la mempool
+    fn puntaje_desalojo(
+        &self,
+        txid: &Uint256,
+        hija: Option<&EntradaMempool>,
+    ) -> Option<PuntajeDesalojo> {
+        let entrada = self.entradas.get(txid)?;
+        let mut descendientes = self.paquete_de_descendientes(txid)?;
+        if let Some(hija) = hija {
+            descendientes.fee += hija.fee;
+            descendientes.tamanio += hija.tamanio;
+        }
+        let propio = PuntajeDesalojo {
+            txid: *txid,
+            fee: entrada.fee,
+            tamanio: entrada.tamanio,
+        };
+        let con_descendientes = PuntajeDesalojo {
+            txid: *txid,
+            fee: descendientes.fee,
+            tamanio: descendientes.tamanio,
+        };
+        Some(propio.max(con_descendientes))
+    }
+
+    /// Quita la transacción y todas las que gastan sus salidas
+    /// Devuelve los txids quitados
+    pub fn quitar_con_descendientes(&mut self, txid: Uint256) -> Vec<Uint256> {
+        let quitadas = self.descendientes(&HashSet::from([txid]));
+        self.quitar_entradas(&quitadas);
+        quitadas
+    }
+
+    /// Recibe un bloque recién conectado
+    /// Quita las transacciones que confirma y las que gastan las mismas salidas que él
+    /// Devuelve los txids quitados
+    pub fn quitar_confirmadas(&mut self, bloque: &SerializedBlock) -> Vec<Uint256> {
+        let mut quitadas = vec![];
+        for tx in bloque.txns.iter() {
+            let txid = match tx.txid() {
+                Ok(txid) => txid,
+                Err(_) => continue,
+            };
+            // las hijas de una confirmada siguen siendo válidas
+            if self.entradas.contains_key(&txid) {
+                self.quitar_entradas(&[txid]);
+                quitadas.push(txid);
+            }
+            for tx_in in tx.input.iter() {
+                if let Some(en_conflicto) = self.gastadas.get(&clave_de_input(tx_in)) {
+                    let en_conflicto = *en_conflicto;
+                    quitadas.extend(self.quitar_con_descendientes(en_conflicto));
+                }
+            }
+        }
+        quitadas
+    }
+
+    pub fn obtener(&self, txid: &Uint256) -> Option<&EntradaMempool> {
+        self.entradas.get(txid)
+    }
+
+    /// Devuelve los txids de todas las transacciones de la mempool
+    pub fn txids(&self) -> Vec<Uint256> {
+        self.entradas.keys().copied().collect()
+    }
+
+    pub fn contiene(&self, txid: &Uint256) -> bool {
+        self.entradas.contains_key(txid)
+    }
+
+    pub fn cantidad(&self) -> usize {
+        self.entradas.len()
+    }
+
+    pub fn tamanio_total(&self) -> usize {
+        self.tamanio_total
+    }
+
+    /// Recibe las transacciones propias todavía sin confirmar
+    /// Devuelve los bytes a guardar: las transacciones de la mempool con la hora en que
+    /// entraron y después las propias
+    pub fn serializar(&self, propias: &[Transaction]) -> Result<Vec<u8>, NodoBitcoinError> {
+        let mut bytes = VERSION_ARCHIVO_MEMPOOL.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(self.entradas.len() as u32).to_le_bytes());
+        for entrada in self.entradas.values() {
+            bytes.extend_from_slice(&entrada.hora.to_le_bytes());
+            serializar_tx(&entrada.tx, &mut bytes)?;
+        }
+        bytes.extend_from_slice(&(propias.len() as u32).to_le_bytes());
+        for tx in propias {
+            serializar_tx(tx, &mut bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Recibe las transacciones guardadas y las UTXOs del nodo
+    /// Vuelve a validar cada transacción contra el estado actual, en el orden en que habían
+    /// entrado, y descarta las que ya no son válidas
+    /// Devuelve la cantidad de transacciones agregadas
+    pub fn recargar(&mut self, mut guardadas: Vec<(Transaction, u32)>, utxos: &UtxosNodo) -> usize {
+        guardadas.sort_by_key(|(_, hora)| *hora);
+        let mut agregadas = 0;
+        // una hija con la misma hora que su padre puede quedar antes, se reintenta mientras se avance
+        loop {
+            let mut rechazadas = vec![];
+            let mut avance = false;
+            for (tx, hora) in guardadas {
+                match self.agregar_con_hora(tx.clone(), utxos, hora) {
+                    Ok(_) => {
+                        agregadas += 1;
+                        avance = true;
+                    }
+                    Err(NodoBitcoinError::InputsDesconocidos) => rechazadas.push((tx, hora)),
+                    Err(_) => {}
+                }
+            }
+            if !avance || rechazadas.is_empty() {
+                return agregadas;
+            }
+            guardadas = rechazadas;
+        }
+    }
+}
+
+/// Transacciones leídas del archivo de la mempool
+#[derive(Debug, Default, PartialEq)]
+pub struct MempoolGuardada {
+    pub transacciones: Vec<(Transaction, u32)>,
+    pub propias: Vec<Transaction>,
+}
+
+impl MempoolGuardada {
+    pub fn deserializar(bytes: &[u8]) -> Result<MempoolGuardada, NodoBitcoinError> {
+        let mut offset = 0;
+        if leer_u32(bytes, &mut offset)? != VERSION_ARCHIVO_MEMPOOL {
+            return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+        }
+        let mut guardada = MempoolGuardada::default();
+        let cantidad = leer_u32(bytes, &mut offset)?;
+        for _ in 0..cantidad {
+            let hora = leer_u32(bytes, &mut offset)?;
+            let tx = deserializar_tx(bytes, &mut offset)?;
+            guardada.transacciones.push((tx, hora));
+        }
+        let cantidad = leer_u32(bytes, &mut offset)?;
+        for _ in 0..cantidad {
+            guardada.propias.push(deserializar_tx(bytes, &mut offset)?);
+        }
+        Ok(guardada)
+    }
+}
+
+/// Devuelve el nombre del archivo donde se guarda la mempool al cerrar el nodo
+pub fn get_mempool_filename() -> String {
+    match config::get_valor("NOMBRE_ARCHIVO_MEMPOOL".to_string()) {
+        Ok(valor) => valor,
+        Err(_) => NOMBRE_ARCHIVO_MEMPOOL_DEFAULT.to_string(),
+    }
+}
+
+/// Guarda la mempool y las transacciones propias sin confirmar en el archivo del config
+pub fn guardar_mempool(mempool: &Mempool, propias: &[Transaction]) -> Result<(), NodoBitcoinError> {
+    let bytes = mempool.serializar(propias)?;
+    fs::write(get_mempool_filename(), bytes)
+        .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)
+}
+
+/// Lee la mempool guardada en el archivo del config
+/// Si no hay archivo devuelve una mempool guardada vacía
+pub fn leer_mempool_guardada() -> Result<MempoolGuardada, NodoBitcoinError> {
+    match fs::read(get_mempool_filename()) {
+        Ok(bytes) => MempoolGuardada::deserializar(&bytes),
+        Err(_) => Ok(MempoolGuardada::default()),
+    }
+}
+
+fn serializar_tx(tx: &Transaction, bytes: &mut Vec<u8>) -> Result<(), NodoBitcoinError> {
+    let tx_bytes = tx.serialize()?;
+    bytes.extend_from_slice(&(tx_bytes.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&tx_bytes);
+    Ok(())
+}
+
+fn deserializar_tx(bytes: &[u8], offset: &mut usize) -> Result<Transaction, NodoBitcoinError> {
+    let largo = leer_u32(bytes, offset)? as usize;
+    let tx_bytes = bytes
+        .get(*offset..*offset + largo)
+        .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)?;
+    *offset += largo;
+    Transaction::deserialize(tx_bytes)
+}
+
+fn leer_u32(bytes: &[u8], offset: &mut usize) -> Result<u32, NodoBitcoinError> {
+    let mut valor = [0u8; 4];
+    valor.copy_from_slice(
+        bytes
+            .get(*offset..*offset + 4)
+            .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)?,
+    );
+    *offset += 4;
+    Ok(u32::from_le_bytes(valor))
+}
+
+/// Devuelve la hora actual en segundos desde el epoch
+pub fn ahora() -> u32 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(n) => n.as_secs() as u32,
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockheader::BlockHeader;
+    use crate::blockchain::politica::MotivoRechazo;
+    use crate::blockchain::transaction::SEQUENCE_RBF;
+    use crate::blockchain::utxos_nodo::tests::tx;
+
+    fn tx_rbf(inputs: Vec<(Uint256, usize)>, valores: Vec<u64>) -> Transaction {
+        let mut tx = tx(inputs, valores);
+        for tx_in in tx.input.iter_mut() {
+            tx_in.sequence = SEQUENCE_RBF;
+        }
+        tx
+    }
+
+    fn bloque(txns: Vec<Transaction>) -> SerializedBlock {
+        SerializedBlock {
+            header: BlockHeader {
+                version: 1,
+                previous_block_hash: [0; 32],
+                merkle_root_hash: [0; 32],
+                time: 10,
+                n_bits: 0,
+                nonce: 0,
+            },
+            txn_amount: txns.len(),
+            txns,
+        }
+    }
+
+    /// UTXOs con una salida de 10000 satoshis por cada txid recibido
+    fn utxos(txids: &[[u8; 32]]) -> (UtxosNodo, Vec<Uint256>) {
+        let mut ids = vec![];
+        let mut txns = vec![];
+        for txid in txids {
+            let coinbase = tx(vec![(Uint256::from_be_bytes(*txid), 0)], vec![10000]);
+            ids.push(coinbase.txid().unwrap());
+            txns.push(coinbase);
+        }
+        (UtxosNodo::desde_bloques(&[bloque(txns)]), ids)
+    }
+
+    #[test]
+    fn test_agregar_valida_inputs_y_calcula_fee() {
+        let (utxos, ids) = utxos(&[[1; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+
+        let desconocida = tx(
+            vec![(Uint256::from_be_bytes([9; 32]), 0), (ids[0], 0)],
+            vec![10],
+        );
+        assert_eq!(
+            mempool.salidas_faltantes(&desconocida, &utxos),
+            vec![(Uint256::from_be_bytes([9; 32]), 0)]
+        );
+        assert_eq!(
+            mempool.agregar(desconocida, &utxos),
+            Err(NodoBitcoinError::InputsDesconocidos)
+        );
+        let gasta_de_mas = tx(vec![(ids[0], 0)], vec![20000]);
+        assert_eq!(
+            mempool.agregar(gasta_de_mas, &utxos),
+            Err(NodoBitcoinError::TxInvalida)
+        );
+
+        let valida = tx(vec![(ids[0], 0)], vec![9000]);
+        let txid = mempool.agregar(valida.clone(), &utxos).unwrap();
+        let entrada = mempool.obtener(&txid).unwrap();
+        assert_eq!(entrada.fee, 1000);
+        assert_eq!(entrada.tamanio, valida.serialize().unwrap().len());
+        assert_eq!(
+            mempool.agregar(valida, &utxos),
+            Err(NodoBitcoinError::TxYaEnMempool)
+        );
+
+        let doble_gasto = tx(vec![(ids[0], 0)], vec![8000]);
+        assert_eq!(
+            mempool.agregar(doble_gasto, &utxos),
+            Err(NodoBitcoinError::TxEnConflictoConMempool)
+        );
+
+        // una hija puede gastar las salidas sin confirmar de la mempool
+        let hija = tx(vec![(txid, 0)], vec![8500]);
+        let id_hija = mempool.agregar(hija, &utxos).unwrap();
+        assert_eq!(mempool.obtener(&id_hija).unwrap().fee, 500);
+    }
+
+    #[test]
+    fn test_politica_de_retransmision() {
+        let (utxos, ids) = utxos(&[[1; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT).con_politica(true);
+
+        // los outputs del helper tienen el script vacío
+        let no_estandar = tx(vec![(ids[0], 0)], vec![9000]);
+        assert_eq!(
+            mempool.agregar(no_estandar, &utxos),
+            Err(NodoBitcoinError::TxNoEstandar(MotivoRechazo::ScriptPubKey))
+        );
+
+        let mut p2pkh = vec![0x76, 0xa9, 0x14];
+        p2pkh.extend_from_slice(&[7; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xac]);
+        let mut sin_fee = tx(vec![(ids[0], 0)], vec![9990]);
+        sin_fee.output[0].pk_script = p2pkh.clone();
+        assert_eq!(
+            mempool.agregar(sin_fee, &utxos),
+            Err(NodoBitcoinError::TxNoEstandar(MotivoRechazo::FeeMinimo))
+        );
+
+        let mut estandar = tx(vec![(ids[0], 0)], vec![9000]);
+        estandar.output[0].pk_script = p2pkh;
+        assert!(mempool.agregar(estandar, &utxos).is_ok());
+    }
+
+    #[test]
+    fn test_desalojar_por_fee_rate() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32], [3; 32]]);
+        let barata = tx(vec![(ids[0], 0)], vec![9900]);
+        let tamanio = barata.serialize().unwrap().len();
+        let mut mempool = Mempool::new(tamanio * 2);
+
+        let id_barata = mempool.agregar(barata, &utxos).unwrap();
+        let hija_barata = tx(vec![(id_barata, 0)], vec![9000]);
+        let id_hija = mempool.agregar(hija_barata, &utxos).unwrap();
+        let cara = tx(vec![(ids[1], 0)], vec![5000]);
+        let id_cara = mempool.agregar(cara, &utxos).unwrap();
+
+        // al desalojar la más barata se va también su hija
+        assert!(!mempool.contiene(&id_barata));
+        assert!(!mempool.contiene(&id_hija));
+        assert!(mempool.contiene(&id_cara));
+        assert_eq!(mempool.tamanio_total(), tamanio);
+
+        let muy_barata = tx(vec![(ids[2], 0)], vec![9999]);
+        mempool.agregar(muy_barata.clone(), &utxos).unwrap();
+        let otra = tx(vec![(ids[0], 0)], vec![9999]);
+        assert_eq!(
+            mempool.agregar(otra, &utxos),
+            Err(NodoBitcoinError::FeeRateInsuficiente)
+        );
+    }
+
+    #[test]
+    fn test_quitar_confirmadas_y_en_conflicto() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        let confirmada = tx(vec![(ids[0], 0)], vec![9000]);
+        let id_confirmada = mempool.agregar(confirmada.clone(), &utxos).unwrap();
+        let hija = tx(vec![(id_confirmada, 0)], vec![8000]);
+        let id_hija = mempool.agregar(hija, &utxos).unwrap();
+        let en_conflicto = tx(vec![(ids[1], 0)], vec![9000]);
+        let id_en_conflicto = mempool.agregar(en_conflicto, &utxos).unwrap();
+
+        let otro_gasto = tx(vec![(ids[1], 0)], vec![9500]);
+        let quitadas = mempool.quitar_confirmadas(&bloque(vec![confirmada, otro_gasto]));
+
+        assert_eq!(quitadas, vec![id_confirmada, id_en_conflicto]);
+        assert!(mempool.contiene(&id_hija));
+        assert_eq!(mempool.cantidad(), 1);
+    }
+
+    #[test]
+    fn test_serializar_y_recargar_mempool() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        let padre = tx(vec![(ids[0], 0)], vec![9000]);
+        let id_padre = mempool.agregar(padre, &utxos).unwrap();
+        let hija = tx(vec![(id_padre, 0)], vec![8000]);
+        let id_hija = mempool.agregar(hija, &utxos).unwrap();
+        let propia = tx(vec![(ids[1], 0)], vec![9500]);
+
+        let bytes = mempool.serializar(std::slice::from_ref(&propia)).unwrap();
+        let guardada = MempoolGuardada::deserializar(&bytes).unwrap();
+        assert_eq!(guardada.transacciones.len(), 2);
+        assert_eq!(guardada.propias.len(), 1);
+        assert_eq!(guardada.propias[0].txid(), propia.txid());
+
+        // la hija puede quedar antes que el padre, igual entran las dos
+        let mut transacciones = guardada.transacciones;
+        transacciones.sort_by_key(|(tx, _)| tx.txid().unwrap() != id_hija);
+        let mut recargada = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        assert_eq!(recargada.recargar(transacciones.clone(), &utxos), 2);
+        assert!(recargada.contiene(&id_padre) && recargada.contiene(&id_hija));
+
+        // si el padre ya no es válido, la hija se descarta
+        let mut sin_utxos = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        assert_eq!(sin_utxos.recargar(transacciones, &UtxosNodo::new()), 0);
+        assert!(MempoolGuardada::deserializar(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_reemplazo_por_fee() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32], [3; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        let original = tx_rbf(vec![(ids[0], 0)], vec![9000]);
+        let id_original = mempool.agregar(original, &utxos).unwrap();
+        let hija = tx(vec![(id_original, 0)], vec![8500]);
+        let id_hija = mempool.agregar(hija, &utxos).unwrap();
+
+        // tiene que cubrir el fee de la original y de su hija más el fee incremental
+        let barata = tx_rbf(vec![(ids[0], 0)], vec![8600]);
+        assert_eq!(
+            mempool.agregar(barata, &utxos),
+            Err(NodoBitcoinError::FeeInsuficienteParaReemplazo)
+        );
+        let reemplazo = tx_rbf(vec![(ids[0], 0)], vec![8000]);
+        let (id_reemplazo, mut reemplazadas) =
+            mempool.agregar_reemplazando(reemplazo, &utxos).unwrap();
+        reemplazadas.sort();
+        let mut esperadas = vec![id_original, id_hija];
+        esperadas.sort();
+        assert_eq!(reemplazadas, esperadas);
+        assert!(mempool.contiene(&id_reemplazo));
+        assert_eq!(mempool.cantidad(), 1);
+
+        // sin señalizar RBF no se puede reemplazar
+        let no_reemplazable = tx(vec![(ids[1], 0)], vec![9000]);
+        mempool.agregar(no_reemplazable, &utxos).unwrap();
+        let reemplazo = tx_rbf(vec![(ids[1], 0)], vec![5000]);
+        assert_eq!(
+            mempool.agregar(reemplazo, &utxos),
+            Err(NodoBitcoinError::TxEnConflictoConMempool)
+        );
+
+        // no puede desalojar más de MAXIMO_REEMPLAZADAS transacciones
+        let padre = tx_rbf(vec![(ids[2], 0)], vec![90; MAXIMO_REEMPLAZADAS]);
+        let id_padre = mempool.agregar(padre, &utxos).unwrap();
+        for index in 0..MAXIMO_REEMPLAZADAS {
+            let hija = tx(vec![(id_padre, index)], vec![80]);
+            mempool.agregar(hija, &utxos).unwrap();
+        }
+        let reemplazo = tx_rbf(vec![(ids[2], 0)], vec![1000]);
+        assert_eq!(
+            mempool.agregar(reemplazo, &utxos),
+            Err(NodoBitcoinError::DemasiadasTransaccionesReemplazadas)
+        );
+    }
+
+    #[test]
+    fn test_reemplazo_sin_inputs_nuevos_sin_confirmar() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32], [3; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        let original = tx_rbf(vec![(ids[0], 0)], vec![9000]);
+        mempool.agregar(original, &utxos).unwrap();
+        let otra = tx(vec![(ids[1], 0)], vec![9000]);
+        let id_otra = mempool.agregar(otra, &utxos).unwrap();
+
+        // no puede sumar una salida sin confirmar que la original no gastaba
+        let reemplazo = tx_rbf(vec![(ids[0], 0), (id_otra, 0)], vec![15000]);
+        assert_eq!(
+            mempool.agregar(reemplazo, &utxos),
+            Err(NodoBitcoinError::ReemplazoConInputsSinConfirmar)
+        );
+        assert_eq!(mempool.cantidad(), 2);
+
+        // una salida confirmada sí
+        let reemplazo = tx_rbf(vec![(ids[0], 0), (ids[2], 0)], vec![15000]);
+        assert!(mempool.agregar(reemplazo, &utxos).is_ok());
+        assert_eq!(mempool.cantidad(), 2);
+    }
+
+    #[test]
+    fn test_reemplazo_que_no_entra_no_quita_la_original() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32]]);
+        let original = tx_rbf(vec![(ids[0], 0)], vec![9000]);
+        let tamanio = original.serialize().unwrap().len();
+        let mut mempool = Mempool::new(tamanio * 2);
+        let id_original = mempool.agregar(original, &utxos).unwrap();
+        let cara = tx(vec![(ids[1], 0)], vec![1000]);
+        let id_cara = mempool.agregar(cara, &utxos).unwrap();
+
+        // el reemplazo es más grande y paga menos por byte que la otra, habría que desalojarlo
+        let reemplazo = tx_rbf(vec![(ids[0], 0)], vec![4000, 4000]);
+        assert_eq!(
+            mempool.agregar(reemplazo, &utxos),
+            Err(NodoBitcoinError::FeeRateInsuficiente)
+        );
+        assert!(mempool.contiene(&id_original) && mempool.contiene(&id_cara));
+        assert_eq!(mempool.tamanio_total(), tamanio * 2);
+    }
+
+    #[test]
+    fn test_paquetes_y_desalojo_con_cpfp() {
+        let (utxos, ids) = utxos(&[[1; 32], [2; 32], [3; 32]]);
+        let padre = tx(vec![(ids[0], 0)], vec![9990]);
+        let tamanio = padre.serialize().unwrap().len();
+        let mut mempool = Mempool::new(tamanio * 3);
+
+        let id_padre = mempool.agregar(padre, &utxos).unwrap();
+        let hija = tx(vec![(id_padre, 0)], vec![7990]);
+        let id_hija = mempool.agregar(hija, &utxos).unwrap();
+
+        let ancestros = mempool.paquete_de_ancestros(&id_hija).unwrap();
+        assert_eq!(ancestros.fee, 2010);
+        assert_eq!(ancestros.tamanio, tamanio * 2);
+        assert_eq!(ancestros.txids.len(), 2);
+        let descendientes = mempool.paquete_de_descendientes(&id_padre).unwrap();
+        assert_eq!(descendientes.fee, ancestros.fee);
+        assert!(mempool
+            .paquete_de_descendientes(&Uint256::from_be_bytes([9; 32]))
+            .is_none());
+
+        // el padre paga menos que la otra, pero con su hija el paquete paga más
+        let media = tx(vec![(ids[1], 0)], vec![9500]);
+        let id_media = mempool.agregar(media, &utxos).unwrap();
+        let otra = tx(vec![(ids[2], 0)], vec![9400]);
+        let id_otra = mempool.agregar(otra, &utxos).unwrap();
+        assert!(!mempool.contiene(&id_media));
+        assert!(mempool.contiene(&id_padre) && mempool.contiene(&id_hija));
+        assert!(mempool.contiene(&id_otra));
+
+        // con la mempool llena una hija puede pagar por su padre aunque él sea el de menor fee
+        // rate, se desaloja al siguiente paquete
+        let hija_de_otra = tx(vec![(id_otra, 0)], vec![5000]);
+        let id_hija_de_otra = mempool.agregar(hija_de_otra, &utxos).unwrap();
+        assert!(mempool.contiene(&id_otra) && mempool.contiene(&id_hija_de_otra));
+        assert!(!mempool.contiene(&id_padre) && !mempool.contiene(&id_hija));
+    }
+
+    #[test]
+    fn test_limite_de_ancestros() {
+        let (utxos, ids) = utxos(&[[1; 32]]);
+        let mut mempool = Mempool::new(TAMANIO_MAXIMO_MEMPOOL_DEFAULT);
+        let mut padre = ids[0];
+        for index in 0..MAXIMO_ANCESTROS as u64 {
+            let hija = tx(vec![(padre, 0)], vec![9000 - index]);
+            padre = mempool.agregar(hija, &utxos).unwrap();
+        }
+        let hija = tx(vec![(padre, 0)], vec![8000]);
+        assert_eq!(
+            mempool.agregar(hija, &utxos),
+            Err(NodoBitcoinError::DemasiadosAncestros)
+        );
+    }
+}
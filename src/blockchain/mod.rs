@@ -1,7 +1,19 @@
 pub mod block;
+pub mod block_file;
 pub mod blockheader;
+pub mod estimador_fees;
 pub mod file;
 pub mod file_manager;
+pub mod huerfanas;
 pub mod index;
+pub mod journal;
+pub mod mempool;
+pub mod politica;
 pub mod proof_of_work;
+pub mod prune;
+pub mod reindex;
+pub mod script_index;
 pub mod transaction;
+pub mod tx_index;
+pub mod utxos_nodo;
+pub mod verify_chain;
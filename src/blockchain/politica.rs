@@ -0,0 +1,363 @@
+use crate::blockchain::transaction::{Transaction, TxOut};
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use std::fmt;
+
+/// Máxima versión de transacción que se retransmite
+pub const VERSION_MAXIMA_ESTANDAR: u32 = 2;
+
+/// Peso máximo de una transacción estándar, igual que en Bitcoin Core (100 000 bytes virtuales)
+pub const PESO_MAXIMO_TX_ESTANDAR: usize = 400_000;
+
+/// Tamaño máximo del script de firma de un input estándar, alcanza para un multisig de 15 claves
+pub const TAMANIO_MAXIMO_SCRIPT_SIG: usize = 1650;
+
+/// Tamaño máximo del script de una salida OP_RETURN: el opcode más 80 bytes de datos con sus pushes
+pub const TAMANIO_MAXIMO_OP_RETURN: usize = 83;
+
+/// Máxima cantidad de claves de un multisig sin P2SH estándar
+pub const MAXIMO_CLAVES_MULTISIG: u8 = 3;
+
+/// Fee por byte mínimo para retransmitir una transacción
+pub const FEE_RATE_MINIMO_RELAY: u64 = 1;
+
+/// Fee por kilobyte con el que se calcula si una salida es dust
+pub const FEE_DUST_POR_KB: u64 = 3000;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// Devuelve si el nodo sólo acepta transacciones estándar según el config. Por defecto sí
+pub fn requerir_estandar() -> bool {
+    match config::get_valor("REQUERIR_ESTANDAR".to_string()) {
+        Ok(valor) => valor != "false",
+        Err(_) => true,
+    }
+}
+
+/// Plantillas de script de salida que conoce el nodo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoScript {
+    P2PK,
+    P2PKH,
+    P2SH,
+    Multisig,
+    P2WPKH,
+    P2WSH,
+    P2TR,
+    TestigoDesconocido,
+    NullData,
+    NoEstandar,
+}
+
+impl TipoScript {
+    /// Devuelve si el script es un programa de testigo (segwit), que se gasta más barato
+    pub fn es_testigo(&self) -> bool {
+        matches!(
+            self,
+            TipoScript::P2WPKH
+                | TipoScript::P2WSH
+                | TipoScript::P2TR
+                | TipoScript::TestigoDesconocido
+        )
+    }
+}
+
+/// Motivo por el que una transacción no se retransmite, con el texto del mensaje reject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotivoRechazo {
+    Version,
+    Tamanio,
+    ScriptSigTamanio,
+    ScriptSigNoPush,
+    ScriptPubKey,
+    Dust,
+    MultiOpReturn,
+    FeeMinimo,
+}
+
+impl fmt::Display for MotivoRechazo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MotivoRechazo::Version => write!(f, "version"),
+            MotivoRechazo::Tamanio => write!(f, "tx-size"),
+            MotivoRechazo::ScriptSigTamanio => write!(f, "scriptsig-size"),
+            MotivoRechazo::ScriptSigNoPush => write!(f, "scriptsig-not-pushonly"),
+            MotivoRechazo::ScriptPubKey => write!(f, "scriptpubkey"),
+            MotivoRechazo::Dust => write!(f, "dust"),
+            MotivoRechazo::MultiOpReturn => write!(f, "multi-op-return"),
+            MotivoRechazo::FeeMinimo => write!(f, "min relay fee not met"),
+        }
+    }
+}
+
+/// Recibe el script de una salida y devuelve a qué plantilla corresponde
+pub fn tipo_de_script(script: &[u8]) -> TipoScript {
+    match script {
+        [OP_DUP, OP_HASH160, 0x14, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG] if hash.len() == 20 => {
+            TipoScript::P2PKH
+        }
+        [OP_HASH160, 0x14, hash @ .., OP_EQUAL] if hash.len() == 20 => TipoScript::P2SH,
+        [0x21, clave @ .., OP_CHECKSIG] if clave.len() == 33 => TipoScript::P2PK,
+        [0x41, clave @ .., OP_CHECKSIG] if clave.len() == 65 => TipoScript::P2PK,
+        [OP_0, 0x14, programa @ ..] if programa.len() == 20 => TipoScript::P2WPKH,
+        [OP_0, 0x20, programa @ ..] if programa.len() == 32 => TipoScript::P2WSH,
+        [OP_1, 0x20, programa @ ..] if programa.len() == 32 => TipoScript::P2TR,
+        [version, largo, programa @ ..]
+            if (OP_1..=OP_16).contains(version)
+                && (2..=40).contains(largo)
+                && programa.len() == *largo as usize =>
+        {
+            TipoScript::TestigoDesconocido
+        }
+        [OP_RETURN, datos @ ..]
+            if script.len() <= TAMANIO_MAXIMO_OP_RETURN && es_solo_push(datos) =>
+        {
+            TipoScript::NullData
+        }
+        _ if es_multisig_estandar(script) => TipoScript::Multisig,
+        _ => TipoScript::NoEstandar,
+    }
+}
+
+/// Devuelve si el script sólo tiene operaciones que agregan datos al stack
+pub fn es_solo_push(script: &[u8]) -> bool {
+    leer_pushes(script).is_some()
+}
+
+/// Devuelve los datos de cada push del script, o None si tiene otra operación o está cortado
+pub fn leer_pushes(script: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut pushes = vec![];
+    let mut offset = 0;
+    while offset < script.len() {
+        let opcode = script[offset];
+        offset += 1;
+        let largo = match opcode {
+            0x01..=0x4b => opcode as usize,
+            OP_PUSHDATA1 => {
+                let largo = *script.get(offset)? as usize;
+                offset += 1;
+                largo
+            }
+            OP_PUSHDATA2 => {
+                let bytes = script.get(offset..offset + 2)?;
+                offset += 2;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as usize
+            }
+            OP_PUSHDATA4 => {
+                let bytes = script.get(offset..offset + 4)?;
+                offset += 4;
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+            }
+            // OP_0, OP_1NEGATE, OP_RESERVED y OP_1 a OP_16
+            _ if opcode <= OP_16 => 0,
+            _ => return None,
+        };
+        pushes.push(script.get(offset..offset + largo)?);
+        offset += largo;
+    }
+    Some(pushes)
+}
+
+// OP_m <clave>... OP_n OP_CHECKMULTISIG con 1 <= m <= n <= MAXIMO_CLAVES_MULTISIG
+fn es_multisig_estandar(script: &[u8]) -> bool {
+    let (requeridas, resto) = match script.split_first() {
+        Some((opcode, resto)) if (OP_1..=OP_16).contains(opcode) => (opcode - OP_1 + 1, resto),
+        _ => return false,
+    };
+    let (claves, total) = match resto {
+        [claves @ .., total, OP_CHECKMULTISIG] if (OP_1..=OP_16).contains(total) => {
+            (claves, total - OP_1 + 1)
+        }
+        _ => return false,
+    };
+    let claves = match leer_pushes(claves) {
+        Some(claves) => claves,
+        None => return false,
+    };
+    claves.len() == total as usize
+        && claves
+            .iter()
+            .all(|clave| clave.len() == 33 || clave.len() == 65)
+        && requeridas <= total
+        && total <= MAXIMO_CLAVES_MULTISIG
+}
+
+/// Devuelve el valor mínimo que tiene que tener la salida para no ser dust, o sea para que
+/// gastarla no cueste más de lo que vale con el fee de dust
+/// Las salidas OP_RETURN no se pueden gastar y no tienen mínimo
+pub fn umbral_dust(tx_out: &TxOut) -> u64 {
+    let tipo = tipo_de_script(&tx_out.pk_script);
+    if tipo == TipoScript::NullData {
+        return 0;
+    }
+    // outpoint, largo del script, script de firma y sequence del input que la gaste
+    let tamanio_input = if tipo.es_testigo() {
+        32 + 4 + 1 + 107 / 4 + 4
+    } else {
+        32 + 4 + 1 + 107 + 4
+    };
+    (tx_out.size() + tamanio_input) as u64 * FEE_DUST_POR_KB / 1000
+}
+
+/// Devuelve el fee mínimo para retransmitir una transacción del tamaño recibido
+pub fn fee_minimo_relay(tamanio: usize) -> u64 {
+    tamanio as u64 * FEE_RATE_MINIMO_RELAY
+}
+
+/// Valida que la transacción cumpla la política de retransmisión: versión, tamaño, scripts de
+/// firma sólo con pushes, salidas con plantillas estándar, sin dust y con un solo OP_RETURN
+pub fn validar_estandar(tx: &Transaction) -> Result<(), MotivoRechazo> {
+    if !(1..=VERSION_MAXIMA_ESTANDAR).contains(&tx.version) {
+        return Err(MotivoRechazo::Version);
+    }
+    // los testigos cuentan una vez y el resto cuatro, como en el límite de los bloques
+    let peso = tx.peso().map_err(|_| MotivoRechazo::Tamanio)?;
+    if peso > PESO_MAXIMO_TX_ESTANDAR {
+        return Err(MotivoRechazo::Tamanio);
+    }
+    for tx_in in tx.input.iter() {
+        if tx_in.signature_script.len() > TAMANIO_MAXIMO_SCRIPT_SIG {
+            return Err(MotivoRechazo::ScriptSigTamanio);
+        }
+        if !es_solo_push(&tx_in.signature_script) {
+            return Err(MotivoRechazo::ScriptSigNoPush);
+        }
+    }
+    let mut op_returns = 0;
+    for tx_out in tx.output.iter() {
+        match tipo_de_script(&tx_out.pk_script) {
+            TipoScript::NoEstandar => return Err(MotivoRechazo::ScriptPubKey),
+            TipoScript::NullData => op_returns += 1,
+            _ => {
+                if tx_out.value < umbral_dust(tx_out) {
+                    return Err(MotivoRechazo::Dust);
+                }
+            }
+        }
+    }
+    if op_returns > 1 {
+        return Err(MotivoRechazo::MultiOpReturn);
+    }
+    Ok(())
+}
+
+/// Valida la política de retransmisión completa, incluyendo el fee mínimo
+pub fn validar_relay(tx: &Transaction, fee: u64, tamanio: usize) -> Result<(), NodoBitcoinError> {
+    validar_estandar(tx).map_err(NodoBitcoinError::TxNoEstandar)?;
+    if fee < fee_minimo_relay(tamanio) {
+        return Err(NodoBitcoinError::TxNoEstandar(MotivoRechazo::FeeMinimo));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::transaction::TxIn;
+    use crate::common::uint256::Uint256;
+
+    fn tx_out(value: u64, pk_script: Vec<u8>) -> TxOut {
+        TxOut {
+            value,
+            pk_len: pk_script.len(),
+            pk_script,
+            pk_len_bytes: 1,
+        }
+    }
+
+    fn p2pkh() -> Vec<u8> {
+        let mut script = vec![OP_DUP, OP_HASH160, 0x14];
+        script.extend_from_slice(&[7; 20]);
+        script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+        script
+    }
+
+    #[test]
+    fn test_tipos_de_script_y_dust() {
+        assert_eq!(tipo_de_script(&p2pkh()), TipoScript::P2PKH);
+        let mut p2sh = vec![OP_HASH160, 0x14];
+        p2sh.extend_from_slice(&[1; 20]);
+        p2sh.push(OP_EQUAL);
+        assert_eq!(tipo_de_script(&p2sh), TipoScript::P2SH);
+        let mut p2wpkh = vec![OP_0, 0x14];
+        p2wpkh.extend_from_slice(&[1; 20]);
+        assert_eq!(tipo_de_script(&p2wpkh), TipoScript::P2WPKH);
+        let mut p2tr = vec![OP_1, 0x20];
+        p2tr.extend_from_slice(&[1; 32]);
+        assert_eq!(tipo_de_script(&p2tr), TipoScript::P2TR);
+        let mut multisig = vec![OP_1, 0x21];
+        multisig.extend_from_slice(&[2; 33]);
+        multisig.push(0x21);
+        multisig.extend_from_slice(&[3; 33]);
+        multisig.extend_from_slice(&[OP_1 + 1, OP_CHECKMULTISIG]);
+        assert_eq!(tipo_de_script(&multisig), TipoScript::Multisig);
+        let mut op_return = vec![OP_RETURN, 0x04];
+        op_return.extend_from_slice(b"hola");
+        assert_eq!(tipo_de_script(&op_return), TipoScript::NullData);
+        let mut op_return_largo = vec![OP_RETURN, OP_PUSHDATA1, 81];
+        op_return_largo.extend_from_slice(&[0; 81]);
+        assert_eq!(tipo_de_script(&op_return_largo), TipoScript::NoEstandar);
+        assert_eq!(tipo_de_script(&[OP_CHECKSIG]), TipoScript::NoEstandar);
+
+        assert_eq!(umbral_dust(&tx_out(0, p2pkh())), 546);
+        assert_eq!(umbral_dust(&tx_out(0, p2wpkh)), 294);
+        assert_eq!(umbral_dust(&tx_out(0, p2tr)), 330);
+        assert_eq!(umbral_dust(&tx_out(0, op_return)), 0);
+    }
+
+    #[test]
+    fn test_validar_relay() {
+        let input = TxIn::new(Uint256::from_be_bytes([1; 32]), 0);
+        let estandar =
+            Transaction::new(vec![input.clone()], vec![tx_out(1000, p2pkh())], 0).unwrap();
+        let tamanio = estandar.size();
+        assert_eq!(validar_relay(&estandar, tamanio as u64, tamanio), Ok(()));
+        assert_eq!(
+            validar_relay(&estandar, tamanio as u64 - 1, tamanio),
+            Err(NodoBitcoinError::TxNoEstandar(MotivoRechazo::FeeMinimo))
+        );
+
+        let dust = Transaction::new(vec![input.clone()], vec![tx_out(545, p2pkh())], 0).unwrap();
+        assert_eq!(validar_estandar(&dust), Err(MotivoRechazo::Dust));
+
+        let op_returns = vec![tx_out(0, vec![OP_RETURN]), tx_out(0, vec![OP_RETURN])];
+        let dos_op_return = Transaction::new(vec![input.clone()], op_returns, 0).unwrap();
+        assert_eq!(
+            validar_estandar(&dos_op_return),
+            Err(MotivoRechazo::MultiOpReturn)
+        );
+
+        let mut no_push = estandar.clone();
+        no_push.input[0].signature_script = vec![OP_DUP];
+        assert_eq!(
+            validar_estandar(&no_push),
+            Err(MotivoRechazo::ScriptSigNoPush)
+        );
+
+        let mut version = estandar.clone();
+        version.version = 3;
+        assert_eq!(validar_estandar(&version), Err(MotivoRechazo::Version));
+
+        // más de 100 000 bytes, pero casi todos de testigo: el peso queda debajo del límite
+        let mut con_testigo = estandar;
+        con_testigo.input[0].witness = vec![vec![0; 150_000]];
+        assert!(con_testigo.size() > 100_000);
+        assert_eq!(validar_estandar(&con_testigo), Ok(()));
+
+        let salidas = (0..3000).map(|_| tx_out(1000, p2pkh())).collect();
+        let grande = Transaction::new(vec![input], salidas, 0).unwrap();
+        assert_eq!(validar_estandar(&grande), Err(MotivoRechazo::Tamanio));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::blockchain::block_file::{
+    headers_de_archivo, nombre_archivo_bloques, numeros_de_archivos,
+};
+use crate::blockchain::file::get_file_size;
+use crate::blockchain::script_index::scriptindex_habilitado;
+use crate::blockchain::tx_index::txindex_habilitado;
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use std::{cmp, fs};
+
+/// Objetivo mínimo de espacio para los archivos de bloques en MiB, igual que en Bitcoin Core
+pub const PRUNE_MINIMO_MIB: u64 = 550;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Devuelve, si está habilitado el modo prune (PRUNE=<MiB> distinto de 0 en el config),
+/// el espacio en bytes que pueden ocupar los archivos de bloques
+pub fn objetivo_prune() -> Option<u64> {
+    let valor = config::get_valor("PRUNE".to_string()).ok()?;
+    let mib = valor.trim().parse::<u64>().ok()?;
+    if mib == 0 {
+        return None;
+    }
+    Some(cmp::max(mib, PRUNE_MINIMO_MIB) * MIB)
+}
+
+/// Devuelve true si está habilitado el modo prune
+pub fn prune_habilitado() -> bool {
+    objetivo_prune().is_some()
+}
+
+/// Recibe el directorio de bloques y el timestamp del último bloque validado y aplicado al estado de UTXOs
+/// Borra los archivos de bloques más viejos mientras se supere el objetivo de espacio.
+/// Los headers y su índice no se tocan.
+/// Devuelve los hashes de los bloques borrados
+pub fn podar_bloques(
+    dir: &str,
+    ultimo_timestamp_aplicado: u32,
+) -> Result<Vec<[u8; 32]>, NodoBitcoinError> {
+    let objetivo = match objetivo_prune() {
+        Some(objetivo) => objetivo,
+        None => return Ok(vec![]),
+    };
+    // los índices opcionales apuntan a los bloques, no pueden quedar apuntando a archivos borrados
+    if txindex_habilitado() || scriptindex_habilitado() {
+        return Err(NodoBitcoinError::PruneIncompatibleConIndices);
+    }
+    podar_bloques_en(dir, objetivo, ultimo_timestamp_aplicado)
+}
+
+fn podar_bloques_en(
+    dir: &str,
+    objetivo: u64,
+    ultimo_timestamp_aplicado: u32,
+) -> Result<Vec<[u8; 32]>, NodoBitcoinError> {
+    let archivos = numeros_de_archivos(dir);
+    let mut total = 0;
+    for archivo in archivos.iter() {
+        total += get_file_size(nombre_archivo_bloques(dir, *archivo))?;
+    }
+
+    let mut podados = vec![];
+    // el último archivo nunca se borra porque es en el que se siguen guardando bloques
+    for archivo in archivos.iter().take(archivos.len().saturating_sub(1)) {
+        if total <= objetivo {
+            break;
+        }
+        let headers = headers_de_archivo(dir, *archivo)?;
+        if headers
+            .iter()
+            .any(|header| header.time > ultimo_timestamp_aplicado)
+        {
+            break;
+        }
+        let path = nombre_archivo_bloques(dir, *archivo);
+        let tamanio = get_file_size(path.clone())?;
+        fs::remove_file(path).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        total -= tamanio;
+        for header in headers {
+            podados.push(header.hash()?);
+        }
+    }
+    Ok(podados)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::block_file::escribir_registro;
+    use crate::blockchain::blockheader::BlockHeader;
+
+    fn bloque(time: u32) -> Vec<u8> {
+        let header = BlockHeader {
+            version: 1,
+            previous_block_hash: [0; 32],
+            merkle_root_hash: [0; 32],
+            time,
+            n_bits: 0,
+            nonce: 0,
+        };
+        let mut bytes = header.serialize().unwrap();
+        bytes.extend_from_slice(&[0; 8]);
+        bytes
+    }
+
+    #[test]
+    fn test_podar_bloques_respeta_objetivo_y_bloques_aplicados() {
+        let dir = std::env::temp_dir().join("prune_test_podar");
+        _ = fs::remove_dir_all(&dir);
+        let dir = dir.to_str().unwrap();
+
+        // cada registro ocupa 12 + 88 bytes, queda un bloque por archivo
+        for time in [10, 20, 30, 40] {
+            escribir_registro(dir, &bloque(time), 150).unwrap();
+        }
+        assert_eq!(numeros_de_archivos(dir), vec![0, 1, 2, 3]);
+
+        // el bloque del archivo 1 todavía no se aplicó a las UTXOs
+        let podados = podar_bloques_en(dir, 100, 15).unwrap();
+        assert_eq!(podados.len(), 1);
+        assert_eq!(numeros_de_archivos(dir), vec![1, 2, 3]);
+
+        // nunca se borra el último archivo
+        let podados = podar_bloques_en(dir, 0, 100).unwrap();
+        assert_eq!(podados.len(), 2);
+        assert_eq!(numeros_de_archivos(dir), vec![3]);
+
+        _ = fs::remove_dir_all(dir);
+    }
+}
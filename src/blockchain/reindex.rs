@@ -0,0 +1,86 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::block_file::numeros_de_archivos;
+use crate::blockchain::file::{get_blocks_dirname, get_headers_filename};
+use crate::blockchain::index::{reconstruir_indice_de_headers, reconstruir_indices_opcionales};
+use crate::errores::NodoBitcoinError;
+use crate::storage::file_storage::FileStorage;
+use crate::storage::Storage;
+use crate::wallet::user::Account;
+use crate::wallet::uxto_set::UTXOSet;
+
+/// Resultado de `reindexar`
+#[derive(Debug, PartialEq)]
+pub struct ResumenReindexado {
+    pub headers_indexados: u64,
+    pub bloques_indexados: u64,
+    pub bloques_aplicados: u64,
+}
+
+/// Vuelve a generar, a partir de los archivos de headers y de bloques ya descargados,
+/// el índice de headers, los índices opcionales habilitados y el estado de UTXOs de las
+/// cuentas de la wallet, que se guarda reemplazando los archivos anteriores.
+/// No se puede reindexar si el modo prune ya borró archivos de bloques
+pub fn reindexar() -> Result<ResumenReindexado, NodoBitcoinError> {
+    let dir = get_blocks_dirname()?;
+    if numeros_de_archivos(&dir)
+        .first()
+        .is_some_and(|primero| *primero != 0)
+    {
+        return Err(NodoBitcoinError::ReindexConBloquesPodados);
+    }
+
+    let headers_indexados = reconstruir_indice_de_headers(get_headers_filename()?)?;
+    let bloques_indexados = reconstruir_indices_opcionales()?;
+
+    let mut storage = FileStorage::desde_config()?;
+    // para reconocer las salidas de las cuentas alcanza con sus direcciones, no hace falta
+    // desbloquear la wallet
+    let accounts = Account::get_all_accounts()
+        .map(|(accounts, _)| accounts)
+        .unwrap_or_default();
+    let (utxos, bloques_aplicados) = reconstruir_utxos(&storage, accounts)?;
+    utxos.save_en(&mut storage)?;
+
+    Ok(ResumenReindexado {
+        headers_indexados,
+        bloques_indexados,
+        bloques_aplicados,
+    })
+}
+
+/// Arma el estado de UTXOs de las cuentas desde cero aplicando todos los bloques guardados
+/// Devuelve el estado y la cantidad de bloques aplicados
+fn reconstruir_utxos(
+    storage: &dyn Storage,
+    accounts: Vec<Account>,
+) -> Result<(UTXOSet, u64), NodoBitcoinError> {
+    let mut bloques = vec![];
+    for (_, bytes) in storage.leer_todos_los_bloques()? {
+        let bloque = SerializedBlock::deserialize(&bytes)?;
+        if !bloque.txns.is_empty() {
+            bloques.push(bloque);
+        }
+    }
+    // update_from_blocks descarta los bloques con timestamp anterior al último aplicado
+    bloques.sort();
+    let cantidad = bloques.len() as u64;
+
+    let mut utxos = UTXOSet::new();
+    utxos.update_from_blocks(bloques, accounts)?;
+    Ok((utxos, cantidad))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory_storage::MemoryStorage;
+
+    #[test]
+    fn test_reconstruir_utxos_sin_bloques() {
+        let storage = MemoryStorage::new();
+        let (utxos, cantidad) = reconstruir_utxos(&storage, vec![]).unwrap();
+        assert_eq!(cantidad, 0);
+        assert_eq!(utxos.last_timestamp, 0);
+        assert!(utxos.utxos_for_account.is_empty());
+    }
+}
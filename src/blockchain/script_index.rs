@@ -0,0 +1,393 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::block_file::UbicacionBloque;
+use crate::blockchain::file::{escribir_archivo, get_file_size, leer_bytes};
+use crate::blockchain::index::create_hash_to_find_index;
+use crate::blockchain::transaction::Transaction;
+use crate::blockchain::tx_index::{borrar_directorio, leer_transaccion_de_bloque};
+use crate::common::uint256::Uint256;
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use bitcoin_hashes::{sha256, Hash};
+use std::fs;
+
+const SALIDAS_INDEX_DIR: &str = "src/indexes/scripts";
+const GASTOS_INDEX_DIR: &str = "src/indexes/spends";
+
+/// hash del script (32) + txid (32) + índice del output (4) + valor (8) + timestamp del bloque (4)
+/// + ubicación del bloque (12) + posición de la tx en el bloque (4)
+const TAMANIO_REGISTRO_SALIDA: usize = 96;
+
+/// txid previo (32) + índice del output gastado (4) + txid que lo gasta (32) + índice del input (4)
+/// + timestamp del bloque (4)
+const TAMANIO_REGISTRO_GASTO: usize = 76;
+
+/// Output registrado en el índice de scripts
+#[derive(Debug, Clone, PartialEq)]
+pub struct SalidaIndexada {
+    pub tx_id: Uint256,
+    pub output_index: u32,
+    pub value: u64,
+    pub timestamp: u32,
+    pub ubicacion_bloque: UbicacionBloque,
+    pub posicion: u32,
+}
+
+/// Input que gasta un output registrado
+#[derive(Debug, Clone, PartialEq)]
+pub struct GastoIndexado {
+    pub tx_id: Uint256,
+    pub input_index: u32,
+    pub timestamp: u32,
+}
+
+/// Output que paga a un script, con la transacción que lo contiene y el input que lo gastó (si existe)
+#[derive(Debug, Clone)]
+pub struct MovimientoScript {
+    pub salida: SalidaIndexada,
+    pub tx: Transaction,
+    pub gasto: Option<GastoIndexado>,
+}
+
+/// Devuelve true si en el config está habilitado el índice de scripts (SCRIPTINDEX=true)
+pub fn scriptindex_habilitado() -> bool {
+    match config::get_valor("SCRIPTINDEX".to_string()) {
+        Ok(valor) => valor.trim() == "true" || valor.trim() == "1",
+        Err(_) => false,
+    }
+}
+
+fn hash_script(pk_script: &[u8]) -> [u8; 32] {
+    *sha256::Hash::hash(pk_script).as_byte_array()
+}
+
+fn create_path(dir: &str, hash: [u8; 32]) -> String {
+    format!("{}/ix-{}.bin", dir, create_hash_to_find_index(hash))
+}
+
+fn serializar_salida(script_hash: [u8; 32], salida: &SalidaIndexada) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(TAMANIO_REGISTRO_SALIDA);
+    bytes.extend_from_slice(&script_hash);
+    bytes.extend_from_slice(&salida.tx_id.get_bytes());
+    bytes.extend_from_slice(&salida.output_index.to_le_bytes());
+    bytes.extend_from_slice(&salida.value.to_le_bytes());
+    bytes.extend_from_slice(&salida.timestamp.to_le_bytes());
+    bytes.extend_from_slice(&salida.ubicacion_bloque.serialize());
+    bytes.extend_from_slice(&salida.posicion.to_le_bytes());
+    bytes
+}
+
+fn deserializar_salida(bytes: &[u8]) -> Result<([u8; 32], SalidaIndexada), NodoBitcoinError> {
+    if bytes.len() < TAMANIO_REGISTRO_SALIDA {
+        return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+    }
+    let leer = |desde: usize, hasta: usize| bytes[desde..hasta].to_vec();
+    let mut script_hash = [0u8; 32];
+    script_hash.copy_from_slice(&bytes[0..32]);
+    let mut tx_id = [0u8; 32];
+    tx_id.copy_from_slice(&bytes[32..64]);
+    let output_index = u32::from_le_bytes(a_array(leer(64, 68))?);
+    let value = u64::from_le_bytes(a_array(leer(68, 76))?);
+    let timestamp = u32::from_le_bytes(a_array(leer(76, 80))?);
+    let ubicacion_bloque = UbicacionBloque::deserialize(&bytes[80..92])?;
+    let posicion = u32::from_le_bytes(a_array(leer(92, 96))?);
+    Ok((
+        script_hash,
+        SalidaIndexada {
+            tx_id: Uint256::from_be_bytes(tx_id),
+            output_index,
+            value,
+            timestamp,
+            ubicacion_bloque,
+            posicion,
+        },
+    ))
+}
+
+fn serializar_gasto(
+    previous_tx_id: [u8; 32],
+    previous_index: u32,
+    gasto: &GastoIndexado,
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(TAMANIO_REGISTRO_GASTO);
+    bytes.extend_from_slice(&previous_tx_id);
+    bytes.extend_from_slice(&previous_index.to_le_bytes());
+    bytes.extend_from_slice(&gasto.tx_id.get_bytes());
+    bytes.extend_from_slice(&gasto.input_index.to_le_bytes());
+    bytes.extend_from_slice(&gasto.timestamp.to_le_bytes());
+    bytes
+}
+
+fn deserializar_gasto(bytes: &[u8]) -> Result<([u8; 32], u32, GastoIndexado), NodoBitcoinError> {
+    if bytes.len() < TAMANIO_REGISTRO_GASTO {
+        return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+    }
+    let mut previous_tx_id = [0u8; 32];
+    previous_tx_id.copy_from_slice(&bytes[0..32]);
+    let previous_index = u32::from_le_bytes(a_array(bytes[32..36].to_vec())?);
+    let mut tx_id = [0u8; 32];
+    tx_id.copy_from_slice(&bytes[36..68]);
+    let input_index = u32::from_le_bytes(a_array(bytes[68..72].to_vec())?);
+    let timestamp = u32::from_le_bytes(a_array(bytes[72..76].to_vec())?);
+    Ok((
+        previous_tx_id,
+        previous_index,
+        GastoIndexado {
+            tx_id: Uint256::from_be_bytes(tx_id),
+            input_index,
+            timestamp,
+        },
+    ))
+}
+
+fn a_array<const N: usize>(bytes: Vec<u8>) -> Result<[u8; N], NodoBitcoinError> {
+    bytes
+        .try_into()
+        .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)
+}
+
+/// Recibe el bloque recién guardado y su ubicación en los archivos de bloques
+/// Registra los outputs de cada transacción por hash de script y los outpoints que gastan sus inputs
+pub fn indexar_scripts_del_bloque(
+    bloque: &SerializedBlock,
+    ubicacion_bloque: UbicacionBloque,
+) -> Result<(), NodoBitcoinError> {
+    indexar_scripts_en(
+        SALIDAS_INDEX_DIR,
+        GASTOS_INDEX_DIR,
+        bloque,
+        ubicacion_bloque,
+    )
+}
+
+/// Borra el índice de scripts para volver a generarlo
+pub fn borrar_indice_de_scripts() -> Result<(), NodoBitcoinError> {
+    borrar_directorio(SALIDAS_INDEX_DIR)?;
+    borrar_directorio(GASTOS_INDEX_DIR)
+}
+
+fn indexar_scripts_en(
+    dir_salidas: &str,
+    dir_gastos: &str,
+    bloque: &SerializedBlock,
+    ubicacion_bloque: UbicacionBloque,
+) -> Result<(), NodoBitcoinError> {
+    fs::create_dir_all(dir_salidas).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    fs::create_dir_all(dir_gastos).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    let timestamp = bloque.header.time;
+    for (posicion, tx) in bloque.txns.iter().enumerate() {
+        let tx_id = tx.txid()?;
+        for (output_index, tx_out) in tx.output.iter().enumerate() {
+            let script_hash = hash_script(&tx_out.pk_script);
+            let salida = SalidaIndexada {
+                tx_id,
+                output_index: output_index as u32,
+                value: tx_out.value,
+                timestamp,
+                ubicacion_bloque,
+                posicion: posicion as u32,
+            };
+            escribir_archivo(
+                create_path(dir_salidas, script_hash),
+                &serializar_salida(script_hash, &salida),
+            )?;
+        }
+        for (input_index, tx_in) in tx.input.iter().enumerate() {
+            let gasto = GastoIndexado {
+                tx_id,
+                input_index: input_index as u32,
+                timestamp,
+            };
+            let previous = tx_in.previous_output.hash;
+            escribir_archivo(
+                create_path(dir_gastos, previous),
+                &serializar_gasto(previous, tx_in.previous_output.index, &gasto),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn leer_registros(path: String) -> Result<Vec<u8>, NodoBitcoinError> {
+    let len_archivo = match get_file_size(path.clone()) {
+        Ok(len) => len,
+        Err(_) => return Ok(vec![]),
+    };
+    leer_bytes(path, 0, len_archivo)
+}
+
+fn buscar_salidas(dir: &str, pk_script: &[u8]) -> Result<Vec<SalidaIndexada>, NodoBitcoinError> {
+    let script_hash = hash_script(pk_script);
+    let bytes = leer_registros(create_path(dir, script_hash))?;
+    let mut salidas = vec![];
+    for registro in bytes.chunks_exact(TAMANIO_REGISTRO_SALIDA) {
+        let (hash_registro, salida) = deserializar_salida(registro)?;
+        if hash_registro == script_hash {
+            salidas.push(salida);
+        }
+    }
+    Ok(salidas)
+}
+
+fn buscar_gasto(
+    dir: &str,
+    tx_id: Uint256,
+    output_index: u32,
+) -> Result<Option<GastoIndexado>, NodoBitcoinError> {
+    let tx_id_bytes = tx_id.get_bytes();
+    let bytes = leer_registros(create_path(dir, tx_id_bytes))?;
+    for registro in bytes.chunks_exact(TAMANIO_REGISTRO_GASTO) {
+        let (previous_tx_id, previous_index, gasto) = deserializar_gasto(registro)?;
+        if previous_tx_id == tx_id_bytes && previous_index == output_index {
+            return Ok(Some(gasto));
+        }
+    }
+    Ok(None)
+}
+
+/// Recibe un scriptPubKey
+/// Devuelve todos los outputs que le pagaron, con su transacción y el gasto si ya fueron gastados
+pub fn historial_de_script(pk_script: &[u8]) -> Result<Vec<MovimientoScript>, NodoBitcoinError> {
+    let mut movimientos = vec![];
+    for salida in buscar_salidas(SALIDAS_INDEX_DIR, pk_script)? {
+        let (_, tx) = leer_transaccion_de_bloque(salida.ubicacion_bloque, salida.posicion)?;
+        let gasto = buscar_gasto(GASTOS_INDEX_DIR, salida.tx_id, salida.output_index)?;
+        movimientos.push(MovimientoScript { salida, tx, gasto });
+    }
+    Ok(movimientos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockheader::BlockHeader;
+    use crate::blockchain::transaction::{Outpoint, TxIn, TxOut};
+
+    fn crear_tx(previous: Outpoint, pk_script: Vec<u8>, value: u64) -> Transaction {
+        Transaction {
+            version: 1,
+            input: vec![TxIn {
+                previous_output: previous,
+                script_bytes: 0,
+                signature_script: vec![],
+                sequence: 0xffffffff,
+                script_bytes_amount: 1,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value,
+                pk_len: pk_script.len(),
+                pk_script,
+                pk_len_bytes: 1,
+            }],
+            lock_time: 0,
+            tx_in_count: 1,
+            tx_out_count: 1,
+        }
+    }
+
+    fn crear_bloque(time: u32, txns: Vec<Transaction>) -> SerializedBlock {
+        SerializedBlock {
+            header: BlockHeader {
+                version: 1,
+                previous_block_hash: [0; 32],
+                merkle_root_hash: [0; 32],
+                time,
+                n_bits: 0,
+                nonce: 0,
+            },
+            txn_amount: 1,
+            txns,
+        }
+    }
+
+    #[test]
+    fn test_serializar_deserializar_registros() {
+        let salida = SalidaIndexada {
+            tx_id: Uint256::from_be_bytes([3; 32]),
+            output_index: 2,
+            value: 5000,
+            timestamp: 1681095630,
+            ubicacion_bloque: UbicacionBloque {
+                archivo: 0,
+                offset: 77,
+            },
+            posicion: 4,
+        };
+        let bytes = serializar_salida([1; 32], &salida);
+        assert_eq!(bytes.len(), TAMANIO_REGISTRO_SALIDA);
+        assert_eq!(deserializar_salida(&bytes).unwrap(), ([1; 32], salida));
+
+        let gasto = GastoIndexado {
+            tx_id: Uint256::from_be_bytes([8; 32]),
+            input_index: 1,
+            timestamp: 1681095700,
+        };
+        let bytes = serializar_gasto([3; 32], 2, &gasto);
+        assert_eq!(bytes.len(), TAMANIO_REGISTRO_GASTO);
+        assert_eq!(deserializar_gasto(&bytes).unwrap(), ([3; 32], 2, gasto));
+    }
+
+    #[test]
+    fn test_indexar_salidas_y_gastos() {
+        let base = std::env::temp_dir().join("scriptindex_test_indexar");
+        let dir_salidas = base.join("scripts");
+        let dir_gastos = base.join("spends");
+        let dir_salidas = dir_salidas.to_str().unwrap();
+        let dir_gastos = dir_gastos.to_str().unwrap();
+        _ = fs::remove_dir_all(&base);
+
+        let script = vec![0x76, 0xa9, 0x14, 1, 2, 3];
+        let tx_recibida = crear_tx(
+            Outpoint {
+                hash: [9; 32],
+                index: 0,
+            },
+            script.clone(),
+            1000,
+        );
+        let tx_id_recibida = tx_recibida.txid().unwrap();
+        indexar_scripts_en(
+            dir_salidas,
+            dir_gastos,
+            &crear_bloque(10, vec![tx_recibida]),
+            UbicacionBloque::default(),
+        )
+        .unwrap();
+
+        let salidas = buscar_salidas(dir_salidas, &script).unwrap();
+        assert_eq!(salidas.len(), 1);
+        assert_eq!(salidas[0].tx_id, tx_id_recibida);
+        assert_eq!(salidas[0].value, 1000);
+        assert_eq!(buscar_gasto(dir_gastos, tx_id_recibida, 0).unwrap(), None);
+
+        let tx_gasto = crear_tx(
+            Outpoint {
+                hash: tx_id_recibida.get_bytes(),
+                index: 0,
+            },
+            vec![0x51],
+            900,
+        );
+        let tx_id_gasto = tx_gasto.txid().unwrap();
+        let ubicacion_bloque = UbicacionBloque {
+            archivo: 0,
+            offset: 100,
+        };
+        indexar_scripts_en(
+            dir_salidas,
+            dir_gastos,
+            &crear_bloque(20, vec![tx_gasto]),
+            ubicacion_bloque,
+        )
+        .unwrap();
+
+        let gasto = buscar_gasto(dir_gastos, tx_id_recibida, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(gasto.tx_id, tx_id_gasto);
+        assert_eq!(gasto.timestamp, 20);
+        assert!(buscar_salidas(dir_salidas, &[0x00]).unwrap().is_empty());
+
+        _ = fs::remove_dir_all(&base);
+    }
+}
@@ -1,15 +1,54 @@
+use crate::common::address::Address;
 use crate::common::decoder::{
-    decode_base58, point_sec, script_serialized, signature_der, wif_to_hex,
+    p2pkh_script_serialized, point_sec, script_de_direccion, script_serialized, signature_der,
+    verificar_firma, wif_to_hex,
 };
+use crate::common::red::red_configurada;
+use crate::common::uint256::Uint256;
 use crate::common::utils_bytes;
-use crate::common::{decoder::p2pkh_script_serialized, uint256::Uint256};
+use crate::config;
 use crate::errores::NodoBitcoinError;
+use crate::wallet::multisig::{Multisig, TipoMultisig};
+use crate::wallet::psbt::Psbt;
 use crate::wallet::user::Account;
 use crate::wallet::uxto_set::Utxo;
-use bitcoin_hashes::{sha256d, Hash};
+use bitcoin_hashes::{hash160, sha256d, Hash};
 use std::{collections::HashMap, fmt, io::Write, vec};
 
 use super::block::SerializedBlock;
+use super::mempool::FEE_INCREMENTAL_POR_BYTE;
+use super::politica::{leer_pushes, tipo_de_script, validar_relay, TipoScript};
+use super::tx_index::{get_transaction, txindex_habilitado};
+
+/// Sequence de un input que no señaliza nada
+pub const SEQUENCE_FINAL: u32 = 0xffffffff;
+
+/// Sequence con la que los inputs de la wallet señalizan que la transacción
+/// se puede reemplazar por otra con más fee (BIP125)
+pub const SEQUENCE_RBF: u32 = 0xfffffffd;
+
+/// Marca y flag que siguen a la versión en las transacciones serializadas con testigos (BIP144)
+const MARCA_SEGWIT: [u8; 2] = [0x00, 0x01];
+
+/// Largo del testigo de un input P2WPKH: la cantidad de elementos, la firma DER de hasta 72
+/// bytes con el sighash y la clave pública comprimida, cada una con su largo
+const TAMANIO_TESTIGO_P2WPKH: usize = 108;
+
+/// Largo del script de desbloqueo de un input P2PKH: la firma DER de hasta 72 bytes con el
+/// sighash y la clave pública comprimida, cada una con su push
+const TAMANIO_SCRIPT_SIG_P2PKH: usize = 107;
+
+/// Largo de una firma DER de hasta 72 bytes con el sighash, con su push
+const TAMANIO_FIRMA_CON_PUSH: usize = 73;
+
+/// Devuelve la sequence de los inputs de las transacciones que arma la wallet
+/// Señalizan RBF salvo que el config tenga SENALIZAR_RBF=false
+pub fn sequence_de_la_wallet() -> u32 {
+    match config::get_valor("SENALIZAR_RBF".to_string()) {
+        Ok(valor) if valor.trim() == "false" => SEQUENCE_FINAL,
+        _ => SEQUENCE_RBF,
+    }
+}
 
 /// A struct representing a Bitcoin transaction
 /// ### Bitcoin Core References
@@ -46,11 +85,27 @@ impl fmt::Display for Transaction {
 }
 
 impl Transaction {
+    /// Serializa la transacción como se envía a los peers. Si algún input tiene testigo usa el
+    /// formato de BIP144, con la marca y los testigos de cada input antes del lock time
     pub fn serialize(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        self.serializar(self.tiene_testigos())
+    }
+
+    /// Serializa la transacción sin los testigos, que es lo que se hashea para el txid
+    pub fn serialize_sin_testigos(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        self.serializar(false)
+    }
+
+    fn serializar(&self, con_testigos: bool) -> Result<Vec<u8>, NodoBitcoinError> {
         let mut bytes = Vec::new();
         bytes
             .write_all(&(self.version).to_le_bytes())
             .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        if con_testigos {
+            bytes
+                .write_all(&MARCA_SEGWIT)
+                .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        }
 
         let tx_in_count_prefix = utils_bytes::from_amount_bytes_to_prefix(self.tx_in_count);
         bytes
@@ -72,6 +127,13 @@ impl Transaction {
                 .write_all(&tx_out.serialize()?)
                 .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
         }
+        if con_testigos {
+            for tx_in in &self.input {
+                bytes
+                    .write_all(&tx_in.serialize_witness())
+                    .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+            }
+        }
         bytes
             .write_all(&self.lock_time.to_le_bytes())
             .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
@@ -81,33 +143,45 @@ impl Transaction {
     pub fn deserialize(block_bytes: &[u8]) -> Result<Transaction, NodoBitcoinError> {
         let mut offset = 0;
         let version = u32::from_le_bytes(
-            block_bytes[offset..offset + 4]
+            leer_bytes(block_bytes, offset, 4)?
                 .try_into()
                 .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
         );
         offset += 4;
-        let (tx_in_count, tx_in_amount) = utils_bytes::parse_varint(&block_bytes[offset..]);
+        // ninguna transacción válida tiene cero inputs, así que un cero seguido de un uno es
+        // la marca de BIP144
+        let con_testigos = block_bytes.get(offset..offset + 2) == Some(&MARCA_SEGWIT[..]);
+        if con_testigos {
+            offset += 2;
+        }
+        let (tx_in_count, tx_in_amount) = utils_bytes::leer_varint(resto(block_bytes, offset)?)?;
         offset += tx_in_count;
 
         let mut input = Vec::new();
         for _v in 0..tx_in_amount {
-            let tx_in = TxIn::deserialize(&block_bytes[offset..])?;
+            let tx_in = TxIn::deserialize(resto(block_bytes, offset)?)?;
             offset += tx_in.size();
             input.push(tx_in);
         }
 
-        let (tx_out_count, tx_out_amount) = utils_bytes::parse_varint(&block_bytes[offset..]);
+        let (tx_out_count, tx_out_amount) = utils_bytes::leer_varint(resto(block_bytes, offset)?)?;
         offset += tx_out_count;
 
         let mut output = Vec::new();
         for _v in 0..tx_out_amount {
-            let tx_out = TxOut::deserialize(&block_bytes[offset..])?;
+            let tx_out = TxOut::deserialize(resto(block_bytes, offset)?)?;
             offset += tx_out.size();
             output.push(tx_out);
         }
 
+        if con_testigos {
+            for tx_in in input.iter_mut() {
+                offset += tx_in.deserialize_witness(resto(block_bytes, offset)?)?;
+            }
+        }
+
         let lock_time = u32::from_le_bytes(
-            block_bytes[offset..offset + 4]
+            leer_bytes(block_bytes, offset, 4)?
                 .try_into()
                 .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
         );
@@ -122,6 +196,7 @@ impl Transaction {
         })
     }
 
+    /// Devuelve el tamaño en bytes de la transacción serializada, con los testigos
     pub fn size(&self) -> usize {
         let input_size = self.input.iter().map(|tx_in| tx_in.size()).sum::<usize>();
         let output_size = self
@@ -129,17 +204,56 @@ impl Transaction {
             .iter()
             .map(|tx_out| tx_out.size())
             .sum::<usize>();
-        8 + input_size + output_size + self.tx_in_count + self.tx_out_count
+        let witness_size = match self.tiene_testigos() {
+            true => {
+                MARCA_SEGWIT.len()
+                    + self
+                        .input
+                        .iter()
+                        .map(|tx_in| tx_in.serialize_witness().len())
+                        .sum::<usize>()
+            }
+            false => 0,
+        };
+        8 + input_size + output_size + self.tx_in_count + self.tx_out_count + witness_size
+    }
+
+    /// Devuelve si algún input tiene testigo
+    pub fn tiene_testigos(&self) -> bool {
+        self.input.iter().any(|tx_in| !tx_in.witness.is_empty())
+    }
+
+    /// Devuelve el peso de la transacción (BIP141): los bytes sin testigos cuentan cuatro
+    /// veces y los de los testigos una
+    pub fn peso(&self) -> Result<usize, NodoBitcoinError> {
+        Ok(self.serialize_sin_testigos()?.len() * 3 + self.serialize()?.len())
+    }
+
+    /// Devuelve el tamaño virtual, el peso dividido cuatro redondeado para arriba. Es el
+    /// tamaño con el que se calcula el fee rate
+    pub fn vsize(&self) -> Result<usize, NodoBitcoinError> {
+        Ok(self.peso()?.div_ceil(4))
     }
 
     pub fn txid(&self) -> Result<Uint256, NodoBitcoinError> {
-        let bytes = self.serialize()?;
+        // el txid no cambia con los testigos
+        let bytes = self.serialize_sin_testigos()?;
         let hash = sha256d::Hash::hash(&bytes);
         let u256 = Uint256::from_be_bytes(*hash.as_byte_array());
         Ok(u256)
     }
 
+    /// Busca la transacción en los bloques guardados.
+    /// Si está habilitado el txindex lee solo el bloque que la contiene, sino recorre todos los bloques
+    /// Las que no están en el índice, como las de bloques guardados antes de habilitarlo,
+    /// también se buscan recorriendo los bloques
     pub fn _get_tx_from_file(txid: Uint256) -> Result<Transaction, NodoBitcoinError> {
+        if txindex_habilitado() {
+            match get_transaction(txid) {
+                Err(NodoBitcoinError::IndexNoEncontrado) => {}
+                resultado => return resultado,
+            }
+        }
         let blocks = SerializedBlock::read_blocks_from_file()?;
         let mut txs = HashMap::new();
         for block in blocks {
@@ -172,31 +286,37 @@ impl Transaction {
         })
     }
 
-    fn get_new_tx_in(tx_in_base: TxIn, previous_tx: Transaction) -> TxIn {
-        let previous_output = tx_in_base.clone().previous_output;
-        let previous_index = previous_output.index;
-        let previous_outpts = previous_tx.output;
-        let previous_tx_out = &previous_outpts[previous_index as usize];
-
-        let script_bytes = previous_tx_out.pk_len;
-        let script_bytes_amount = previous_tx_out.pk_len_bytes;
-        let signature_script = previous_tx_out.pk_script.clone();
-
-        let sequence = tx_in_base.sequence;
-
-        TxIn {
-            previous_output,
-            script_bytes,
-            signature_script,
-            sequence,
-            script_bytes_amount,
-        }
+    /// Devuelve true si algún input tiene una sequence que permite reemplazar la transacción (BIP125)
+    pub fn senaliza_rbf(&self) -> bool {
+        self.input
+            .iter()
+            .any(|tx_in| tx_in.sequence <= SEQUENCE_RBF)
     }
 
     fn sig_hash(
         &self,
         index: usize,
         previous_tx: Transaction,
+    ) -> Result<Vec<u8>, NodoBitcoinError> {
+        let previous_index = self
+            .input
+            .get(index)
+            .ok_or(NodoBitcoinError::TxInvalida)?
+            .previous_output
+            .index as usize;
+        let previous_tx_out = previous_tx
+            .output
+            .get(previous_index)
+            .ok_or(NodoBitcoinError::TxInvalida)?;
+        self.sig_hash_legacy(index, &previous_tx_out.pk_script)
+    }
+
+    /// Hash de la firma legacy con SIGHASH_ALL: el input que se firma lleva el script code, que
+    /// es el script de la salida que gasta o el redeem script en P2SH, y los demás van vacíos
+    pub fn sig_hash_legacy(
+        &self,
+        index: usize,
+        script_code: &[u8],
     ) -> Result<Vec<u8>, NodoBitcoinError> {
         let mut bytes = Vec::new();
         bytes
@@ -210,7 +330,14 @@ impl Transaction {
 
         for (i, tx_in) in self.input.iter().enumerate() {
             if i == index {
-                let tx_new = Self::get_new_tx_in(tx_in.clone(), previous_tx.clone());
+                let tx_new = TxIn {
+                    previous_output: tx_in.clone().previous_output,
+                    script_bytes: script_code.len(),
+                    signature_script: script_code.to_vec(),
+                    sequence: tx_in.sequence,
+                    script_bytes_amount: utils_bytes::encode_varint(script_code.len()).len(),
+                    witness: vec![],
+                };
                 bytes
                     .write_all(&tx_new.serialize()?)
                     .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
@@ -221,6 +348,7 @@ impl Transaction {
                     signature_script: vec![],
                     sequence: tx_in.clone().sequence,
                     script_bytes_amount: 0,
+                    witness: vec![],
                 };
 
                 bytes
@@ -282,25 +410,524 @@ impl Transaction {
         let private_key_hexa = wif_to_hex(private_key_compresed)?;
         self.sign_with_hexa_key(input_index, private_key_hexa, previous_tx)
     }
+
+    /// Hash de la firma de BIP143 para un input segwit versión 0 con SIGHASH_ALL
+    /// A diferencia del legacy compromete el monto de la salida que se gasta, y los hashes de
+    /// los outpoints, las sequences y las salidas son los mismos para todos los inputs
+    pub fn sig_hash_segwit(
+        &self,
+        index: usize,
+        script_code: &[u8],
+        amount: u64,
+    ) -> Result<Vec<u8>, NodoBitcoinError> {
+        let tx_in = self.input.get(index).ok_or(NodoBitcoinError::TxInvalida)?;
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for input in &self.input {
+            prevouts.extend(input.previous_output.serialize()?);
+            sequences.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        let mut outputs = Vec::new();
+        for tx_out in &self.output {
+            outputs.extend(tx_out.serialize()?);
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(sha256d::Hash::hash(&prevouts).as_byte_array());
+        bytes.extend_from_slice(sha256d::Hash::hash(&sequences).as_byte_array());
+        bytes.extend(tx_in.previous_output.serialize()?);
+        bytes.extend(utils_bytes::encode_varint(script_code.len()));
+        bytes.extend_from_slice(script_code);
+        bytes.extend_from_slice(&amount.to_le_bytes());
+        bytes.extend_from_slice(&tx_in.sequence.to_le_bytes());
+        bytes.extend_from_slice(sha256d::Hash::hash(&outputs).as_byte_array());
+        bytes.extend_from_slice(&self.lock_time.to_le_bytes());
+        // SIGHASH_ALL
+        let sighash_all: u32 = 1;
+        bytes.extend_from_slice(&sighash_all.to_le_bytes());
+
+        let hash = sha256d::Hash::hash(&bytes);
+        Ok(hash.as_byte_array().to_vec())
+    }
+
+    /// Firma un input que gasta una salida P2WPKH de amount satoshis. La firma y la clave
+    /// pública van en el testigo y el script de firma queda vacío
+    pub fn sign_p2wpkh_with_hexa_key(
+        &mut self,
+        input_index: usize,
+        private_key_hexa: Vec<u8>,
+        amount: u64,
+    ) -> Result<(), NodoBitcoinError> {
+        let sec = point_sec(&private_key_hexa)?;
+        // el script code de P2WPKH es el script P2PKH del hash de la clave
+        let script_code = p2pkh_script_serialized(hash160::Hash::hash(&sec).as_byte_array())?;
+        let sign_hash = self.sig_hash_segwit(input_index, &script_code, amount)?;
+        let signature_der = signature_der(&private_key_hexa, &sign_hash);
+        let signature_der_bytes = signature_der.serialize_der().as_ref().to_vec();
+
+        let sighash_all: u8 = 1;
+        let sig = [&signature_der_bytes[..], &sighash_all.to_be_bytes()[..]].concat();
+
+        let tx_in = self
+            .input
+            .get_mut(input_index)
+            .ok_or(NodoBitcoinError::TxInvalida)?;
+        tx_in.sign(vec![])?;
+        tx_in.witness = vec![sig, sec];
+        Ok(())
+    }
+
+    /// Agrega la firma de la clave a un input que gasta una salida del multisig, conservando
+    /// las firmas de otros participantes que ya tenía. En P2SH las firmas y el redeem script
+    /// van en el script de firma, y en P2WSH van en el testigo y la firma compromete amount,
+    /// el monto de la salida
+    /// Devuelve si el input quedó con todas las firmas requeridas
+    pub fn sign_multisig_with_hexa_key(
+        &mut self,
+        input_index: usize,
+        private_key_hexa: Vec<u8>,
+        multisig: &Multisig,
+        amount: u64,
+    ) -> Result<bool, NodoBitcoinError> {
+        let sec = point_sec(&private_key_hexa)?;
+        let posicion = multisig
+            .claves_publicas
+            .iter()
+            .position(|clave| *clave == sec)
+            .ok_or(NodoBitcoinError::InvalidAccount)?;
+        let redeem_script = multisig.redeem_script()?;
+        let sign_hash = match multisig.tipo {
+            TipoMultisig::P2SH => self.sig_hash_legacy(input_index, &redeem_script)?,
+            TipoMultisig::P2WSH => self.sig_hash_segwit(input_index, &redeem_script, amount)?,
+        };
+        let signature_der = signature_der(&private_key_hexa, &sign_hash);
+        let sighash_all: u8 = 1;
+        let sig = [
+            signature_der.serialize_der().as_ref(),
+            &sighash_all.to_be_bytes()[..],
+        ]
+        .concat();
+
+        let mut firmas = self.firmas_multisig(input_index, multisig, &sign_hash)?;
+        firmas[posicion] = Some(sig);
+        // OP_CHECKMULTISIG recorre las firmas en el orden de las claves y no acepta de más
+        let firmas: Vec<Vec<u8>> = firmas
+            .into_iter()
+            .flatten()
+            .take(multisig.requeridas)
+            .collect();
+        let completa = firmas.len() == multisig.requeridas;
+
+        // el primer elemento vacío es el que OP_CHECKMULTISIG saca de más del stack
+        let mut elementos = vec![vec![]];
+        elementos.extend(firmas);
+        elementos.push(redeem_script);
+        let tx_in = self
+            .input
+            .get_mut(input_index)
+            .ok_or(NodoBitcoinError::TxInvalida)?;
+        match multisig.tipo {
+            TipoMultisig::P2SH => tx_in.sign(elementos)?,
+            TipoMultisig::P2WSH => {
+                tx_in.sign(vec![])?;
+                tx_in.witness = elementos;
+            }
+        }
+        Ok(completa)
+    }
+
+    // devuelve las firmas que ya tiene el input ordenadas por la clave que las valida, las que
+    // no valida ninguna clave del multisig se descartan
+    fn firmas_multisig(
+        &self,
+        input_index: usize,
+        multisig: &Multisig,
+        sign_hash: &[u8],
+    ) -> Result<Vec<Option<Vec<u8>>>, NodoBitcoinError> {
+        let tx_in = self
+            .input
+            .get(input_index)
+            .ok_or(NodoBitcoinError::TxInvalida)?;
+        let elementos: Vec<Vec<u8>> = match multisig.tipo {
+            TipoMultisig::P2SH => leer_pushes(&tx_in.signature_script)
+                .ok_or(NodoBitcoinError::TxInvalida)?
+                .into_iter()
+                .map(|push| push.to_vec())
+                .collect(),
+            TipoMultisig::P2WSH => tx_in.witness.clone(),
+        };
+        let mut firmas = vec![None; multisig.claves_publicas.len()];
+        if elementos.len() > 2 {
+            for firma in &elementos[1..elementos.len() - 1] {
+                let posicion = multisig
+                    .claves_publicas
+                    .iter()
+                    .position(|clave| verificar_firma(clave, sign_hash, firma));
+                if let Some(posicion) = posicion {
+                    firmas[posicion] = Some(firma.clone());
+                }
+            }
+        }
+        Ok(firmas)
+    }
+
+    /// Firma el input según el tipo de la salida que gasta: P2PKH con el hash de firma legacy
+    /// y P2WPKH con el de BIP143
+    pub fn sign_input_with_wif(
+        &mut self,
+        input_index: usize,
+        private_key_wif: &str,
+        previous_tx: Transaction,
+    ) -> Result<(), NodoBitcoinError> {
+        let previous_index = self
+            .input
+            .get(input_index)
+            .ok_or(NodoBitcoinError::TxInvalida)?
+            .previous_output
+            .index as usize;
+        let previous_tx_out = previous_tx
+            .output
+            .get(previous_index)
+            .ok_or(NodoBitcoinError::TxInvalida)?;
+        match tipo_de_script(&previous_tx_out.pk_script) {
+            TipoScript::P2PKH => {
+                self.sign_with_wif_compressed_key(input_index, private_key_wif, previous_tx)
+            }
+            TipoScript::P2WPKH => {
+                let amount = previous_tx_out.value;
+                self.sign_p2wpkh_with_hexa_key(input_index, wif_to_hex(private_key_wif)?, amount)
+            }
+            _ => Err(NodoBitcoinError::InvalidAccount),
+        }
+    }
+}
+
+/// Fee de una transacción nueva: el total en satoshis o un fee rate en sat/vB, que se multiplica
+/// por el tamaño virtual que va a tener la transacción firmada
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fee {
+    Total(u64),
+    PorVbyte(u64),
 }
 
+/// Arma y firma una transacción que paga value a target_address y devuelve el cambio a la cuenta
+/// Si la transacción no cumple la política de retransmisión, por ejemplo si el pago o el cambio
+/// son dust, devuelve el motivo en el error
+/// Las utxos elegidas, por ejemplo salidas sin confirmar de una transacción que se quiere
+/// acelerar con una hija (CPFP), se gastan siempre y las otras solo si hacen falta
 pub fn create_tx_to_send(
     account: Account,
-    target_public_key: String,
+    target_address: Address,
+    value: u64,
+    fee: Fee,
+    utxos: Vec<Utxo>,
+    chosen_utxos: Vec<Utxo>,
+) -> Result<Transaction, NodoBitcoinError> {
+    let (mut tx_obj, utxos, fee) =
+        armar_tx_a_enviar(&account, target_address, value, fee, utxos, chosen_utxos)?;
+    let mut private_keys_wif = vec![];
+    for utxo in utxos.iter() {
+        match account.wif_para_script(&utxo.pk_script) {
+            Some(private_key_wif) => private_keys_wif.push(private_key_wif),
+            None => return Err(NodoBitcoinError::InvalidAccount),
+        }
+    }
+    for (index, (utxo, private_key_wif)) in utxos.iter().zip(private_keys_wif).enumerate() {
+        tx_obj.sign_input_with_wif(index, &private_key_wif, utxo.tx.clone())?;
+    }
+    // los peers no retransmiten las transacciones que no son estándar, el fee mínimo se
+    // calcula con el tamaño virtual
+    validar_relay(&tx_obj, fee, tx_obj.vsize()?)?;
+    Ok(tx_obj)
+}
+
+/// Arma la misma transacción que `create_tx_to_send` pero sin firmar, para las cuentas de
+/// solo lectura que firman en otro lado. El fee mínimo se valida con el tamaño virtual que
+/// va a tener la transacción firmada
+pub fn create_unsigned_tx_to_send(
+    account: Account,
+    target_address: Address,
+    value: u64,
+    fee: Fee,
+    utxos: Vec<Utxo>,
+) -> Result<Transaction, NodoBitcoinError> {
+    let (tx_obj, utxos, fee) =
+        armar_tx_a_enviar(&account, target_address, value, fee, utxos, vec![])?;
+    validar_relay(&tx_obj, fee, vsize_firmada(&tx_obj, &utxos, &account)?)?;
+    Ok(tx_obj)
+}
+
+/// Arma la PSBT de una transacción sin firmar que le paga a cada dirección de destinos su
+/// monto y devuelve el cambio a la cuenta. El fee mínimo se valida con el tamaño virtual que
+/// va a tener la transacción firmada
+pub fn create_psbt_to_send(
+    account: Account,
+    destinos: Vec<(Address, u64)>,
+    fee: Fee,
+    utxos: Vec<Utxo>,
+) -> Result<Psbt, NodoBitcoinError> {
+    let (tx_obj, utxos, fee) = armar_tx_con_destinos(&account, destinos, fee, utxos, vec![])?;
+    validar_relay(&tx_obj, fee, vsize_firmada(&tx_obj, &utxos, &account)?)?;
+    Psbt::crear(tx_obj, &utxos, &account)
+}
+
+/// Arma una transacción desde una cuenta multisig y le agrega la firma de la clave de la
+/// cuenta. Devuelve la transacción y si ya tiene todas las firmas para enviarla, si no hay
+/// que pasársela a los otros participantes para que agreguen las suyas
+pub fn create_multisig_tx_to_send(
+    account: Account,
+    target_address: Address,
+    value: u64,
+    fee: Fee,
+    utxos: Vec<Utxo>,
+) -> Result<(Transaction, bool), NodoBitcoinError> {
+    let (mut tx_obj, utxos, fee) =
+        armar_tx_a_enviar(&account, target_address, value, fee, utxos, vec![])?;
+    // el fee mínimo se valida con el tamaño que va a tener con todas las firmas
+    validar_relay(&tx_obj, fee, vsize_firmada(&tx_obj, &utxos, &account)?)?;
+    let completa = sign_multisig_tx(&mut tx_obj, &account, &utxos)?;
+    Ok((tx_obj, completa))
+}
+
+/// Agrega la firma de la clave de la cuenta multisig a todos los inputs de la transacción,
+/// que tienen que gastar utxos de la cuenta. Las utxos dan los montos que firman los inputs
+/// P2WSH
+/// Devuelve si todos los inputs quedaron con las firmas requeridas
+pub fn sign_multisig_tx(
+    tx: &mut Transaction,
+    account: &Account,
+    utxos: &[Utxo],
+) -> Result<bool, NodoBitcoinError> {
+    let multisig = account
+        .multisig
+        .as_ref()
+        .ok_or(NodoBitcoinError::InvalidAccount)?;
+    let pk_script = multisig.pk_script()?;
+    let private_key_wif = account
+        .wif_para_script(&pk_script)
+        .ok_or(NodoBitcoinError::InvalidAccount)?;
+    let private_key_hexa = wif_to_hex(&private_key_wif)?;
+    let mut completa = true;
+    for index in 0..tx.input.len() {
+        let previous_output = &tx.input[index].previous_output;
+        let utxo = utxos
+            .iter()
+            .find(|utxo| {
+                utxo.tx_id.get_bytes() == previous_output.hash
+                    && utxo.output_index == previous_output.index
+                    && utxo.pk_script == pk_script
+            })
+            .ok_or(NodoBitcoinError::InputsDesconocidos)?;
+        let amount = utxo.tx_out.value;
+        completa &=
+            tx.sign_multisig_with_hexa_key(index, private_key_hexa.clone(), multisig, amount)?;
+    }
+    Ok(completa)
+}
+
+// estima el tamaño virtual que va a tener la transacción cuando se firmen sus inputs
+fn vsize_firmada(
+    tx: &Transaction,
+    utxos: &[Utxo],
+    account: &Account,
+) -> Result<usize, NodoBitcoinError> {
+    let mut peso = tx.peso()?;
+    let mut con_testigos = false;
+    for utxo in utxos {
+        match (tipo_de_script(&utxo.pk_script), &account.multisig) {
+            (TipoScript::P2WPKH, _) => {
+                peso += TAMANIO_TESTIGO_P2WPKH;
+                con_testigos = true;
+            }
+            (TipoScript::P2SH, Some(multisig)) => {
+                // OP_0, las firmas y el push del redeem script, y el largo del script que
+                // pasa a ocupar más de un byte
+                let tamanio = 1
+                    + multisig.requeridas * TAMANIO_FIRMA_CON_PUSH
+                    + script_serialized(&multisig.redeem_script()?)?.len();
+                peso += (tamanio + utils_bytes::encode_varint(tamanio).len() - 1) * 4;
+            }
+            (TipoScript::P2WSH, Some(multisig)) => {
+                let redeem_script = multisig.redeem_script()?;
+                peso += utils_bytes::encode_varint(multisig.requeridas + 2).len()
+                    + 1
+                    + multisig.requeridas * TAMANIO_FIRMA_CON_PUSH
+                    + utils_bytes::encode_varint(redeem_script.len()).len()
+                    + redeem_script.len();
+                con_testigos = true;
+            }
+            _ => peso += TAMANIO_SCRIPT_SIG_P2PKH * 4,
+        }
+    }
+    if con_testigos {
+        peso += MARCA_SEGWIT.len();
+    }
+    Ok(peso.div_ceil(4))
+}
+
+// elige las utxos y arma la transacción sin firmar con el pago y el cambio
+// devuelve también las utxos que gasta, en el orden de los inputs, y el fee total que paga
+fn armar_tx_a_enviar(
+    account: &Account,
+    target_address: Address,
     value: u64,
+    fee: Fee,
+    utxos: Vec<Utxo>,
+    chosen_utxos: Vec<Utxo>,
+) -> Result<(Transaction, Vec<Utxo>, u64), NodoBitcoinError> {
+    armar_tx_con_destinos(
+        account,
+        vec![(target_address, value)],
+        fee,
+        utxos,
+        chosen_utxos,
+    )
+}
+
+// igual que armar_tx_a_enviar pero con una salida por cada dirección y monto de destinos
+// con un fee rate se vuelve a armar con el fee del tamaño firmado de la anterior hasta que
+// alcance, como el fee solo sube termina cuando alcanza o cuando no hay más utxos
+fn armar_tx_con_destinos(
+    account: &Account,
+    destinos: Vec<(Address, u64)>,
+    fee: Fee,
+    utxos: Vec<Utxo>,
+    chosen_utxos: Vec<Utxo>,
+) -> Result<(Transaction, Vec<Utxo>, u64), NodoBitcoinError> {
+    let sat_por_vbyte = match fee {
+        Fee::Total(fee) => {
+            let (tx, utxos) = armar_con_fee_total(account, &destinos, fee, utxos, chosen_utxos)?;
+            return Ok((tx, utxos, fee));
+        }
+        Fee::PorVbyte(sat_por_vbyte) => sat_por_vbyte,
+    };
+    let mut fee = 0;
+    loop {
+        let (tx, elegidas) =
+            armar_con_fee_total(account, &destinos, fee, utxos.clone(), chosen_utxos.clone())?;
+        let necesario = sat_por_vbyte
+            .checked_mul(vsize_firmada(&tx, &elegidas, account)? as u64)
+            .ok_or(NodoBitcoinError::ValorFueraDeRango)?;
+        if fee >= necesario {
+            return Ok((tx, elegidas, fee));
+        }
+        fee = necesario;
+    }
+}
+
+// arma la transacción con un fee total fijo
+fn armar_con_fee_total(
+    account: &Account,
+    destinos: &[(Address, u64)],
     fee: u64,
     utxos: Vec<Utxo>,
+    chosen_utxos: Vec<Utxo>,
+) -> Result<(Transaction, Vec<Utxo>), NodoBitcoinError> {
+    if destinos.is_empty() {
+        return Err(NodoBitcoinError::TxInvalida);
+    }
+    let value = destinos
+        .iter()
+        .try_fold(0u64, |suma, (_, value)| suma.checked_add(*value))
+        .ok_or(NodoBitcoinError::ValorFueraDeRango)?;
+    let value_con_fee = value
+        .checked_add(fee)
+        .ok_or(NodoBitcoinError::ValorFueraDeRango)?;
+    let (utxos, tx_in_value_sum) = get_utxos_for_value(utxos, chosen_utxos, value_con_fee)?;
+    let tx_ins = crear_tx_ins(&utxos);
+    let mut tx_outs = vec![];
+    for (target_address, value) in destinos {
+        tx_outs.push(TxOut::pagar_a(*value, target_address)?);
+    }
+    let change_value = tx_in_value_sum - value_con_fee;
+    if change_value > 0 {
+        tx_outs.push(crear_change_txout(account, change_value)?);
+    }
+    Ok((Transaction::new(tx_ins, tx_outs, 0)?, utxos))
+}
+
+/// Recibe una transacción propia todavía sin confirmar, las transacciones previas de sus
+/// inputs en el mismo orden y el nuevo fee total
+/// Arma la transacción que la reemplaza gastando los mismos inputs: descuenta del cambio
+/// la diferencia de fee y la vuelve a firmar
+/// Igual que la mempool con BIP125, el nuevo fee tiene que superar al anterior por lo menos en
+/// el fee incremental por cada byte virtual del reemplazo
+pub fn create_tx_to_bump_fee(
+    account: Account,
+    original: &Transaction,
+    previous_txs: Vec<Transaction>,
+    new_fee: u64,
 ) -> Result<Transaction, NodoBitcoinError> {
-    let private_key_wif = account.clone().secret_key;
-    let (utxos, tx_in_value_sum) = get_utxos_for_value(utxos, value + fee)?;
-    let (tx_ins, previous_txs) = crear_tx_ins(utxos)?;
-    let tx_out_target = TxOut::new(value, target_public_key)?;
-    let change_value = tx_in_value_sum - value - fee;
-    let tx_out_change = crear_change_txout(account, change_value)?;
-    let tx_outs = vec![tx_out_target, tx_out_change];
-    let mut tx_obj = Transaction::new(tx_ins, tx_outs, 0)?;
-    for (index, previous_tx) in previous_txs.iter().enumerate() {
-        tx_obj.sign_with_wif_compressed_key(index, &private_key_wif, previous_tx.clone())?;
+    if previous_txs.len() != original.input.len() {
+        return Err(NodoBitcoinError::TxInvalida);
+    }
+    let mut tx_in_value_sum: u64 = 0;
+    let mut private_keys_wif = vec![];
+    let mut utxos = vec![];
+    for (tx_in, previous_tx) in original.input.iter().zip(previous_txs.iter()) {
+        let previous_tx_out = previous_tx
+            .output
+            .get(tx_in.previous_output.index as usize)
+            .ok_or(NodoBitcoinError::TxInvalida)?;
+        tx_in_value_sum += previous_tx_out.value;
+        match account.wif_para_script(&previous_tx_out.pk_script) {
+            Some(private_key_wif) => private_keys_wif.push(private_key_wif),
+            None => return Err(NodoBitcoinError::InvalidAccount),
+        }
+        utxos.push(Utxo {
+            tx_id: previous_tx.txid()?,
+            output_index: tx_in.previous_output.index,
+            tx_out: previous_tx_out.clone(),
+            pk_script: previous_tx_out.pk_script.clone(),
+            tx: previous_tx.clone(),
+        });
+    }
+    let tx_out_value_sum: u64 = original.output.iter().map(|tx_out| tx_out.value).sum();
+    let fee = tx_in_value_sum
+        .checked_sub(tx_out_value_sum)
+        .ok_or(NodoBitcoinError::TxInvalida)?;
+    if new_fee <= fee {
+        return Err(NodoBitcoinError::FeeInsuficienteParaReemplazo);
+    }
+
+    // el cambio es la última salida que vuelve a la cuenta
+    let mut tx_outs = original.output.clone();
+    let change_index = tx_outs
+        .iter()
+        .rposition(|tx_out| account.es_propio(tx_out))
+        .ok_or(NodoBitcoinError::NoHaySuficientesUtxos)?;
+    let difference = new_fee - fee;
+    match tx_outs[change_index].value.cmp(&difference) {
+        std::cmp::Ordering::Less => return Err(NodoBitcoinError::NoHaySuficientesUtxos),
+        std::cmp::Ordering::Equal => {
+            tx_outs.remove(change_index);
+        }
+        std::cmp::Ordering::Greater => tx_outs[change_index].value -= difference,
+    }
+
+    let tx_ins = original
+        .input
+        .iter()
+        .map(|tx_in| {
+            let mut new_tx_in = TxIn::new(
+                Uint256::from_be_bytes(tx_in.previous_output.hash),
+                tx_in.previous_output.index as usize,
+            );
+            new_tx_in.sequence = SEQUENCE_RBF;
+            new_tx_in
+        })
+        .collect();
+    let mut tx_obj = Transaction::new(tx_ins, tx_outs, original.lock_time)?;
+    let vsize = vsize_firmada(&tx_obj, &utxos, &account)?;
+    if new_fee < fee + FEE_INCREMENTAL_POR_BYTE * vsize as u64 {
+        return Err(NodoBitcoinError::FeeInsuficienteParaReemplazo);
+    }
+    validar_relay(&tx_obj, new_fee, vsize)?;
+    for (index, (previous_tx, private_key_wif)) in
+        previous_txs.into_iter().zip(private_keys_wif).enumerate()
+    {
+        tx_obj.sign_input_with_wif(index, &private_key_wif, previous_tx)?;
     }
     Ok(tx_obj)
 }
@@ -315,32 +942,63 @@ fn _crear_tx_outs(
     Ok(vec![tx_out_target, tx_out_fee])
 }
 
-fn crear_tx_ins(utxos: Vec<Utxo>) -> Result<(Vec<TxIn>, Vec<Transaction>), NodoBitcoinError> {
-    let mut tx_in_vec = Vec::new();
-    let mut previous_tx_vec = Vec::new();
-    for utxo in utxos {
-        let tx_in = TxIn::new(utxo.tx_id, utxo.output_index as usize);
-        tx_in_vec.push(tx_in);
-        previous_tx_vec.push(utxo.tx.clone());
-    }
-    Ok((tx_in_vec, previous_tx_vec))
+// devuelve los largo bytes desde offset, o un error si la transacción está cortada
+fn leer_bytes(bytes: &[u8], offset: usize, largo: usize) -> Result<&[u8], NodoBitcoinError> {
+    offset
+        .checked_add(largo)
+        .and_then(|fin| bytes.get(offset..fin))
+        .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)
+}
+
+// devuelve los bytes desde offset hasta el final
+fn resto(bytes: &[u8], offset: usize) -> Result<&[u8], NodoBitcoinError> {
+    bytes
+        .get(offset..)
+        .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)
 }
 
-fn crear_change_txout(account: Account, value: u64) -> Result<TxOut, NodoBitcoinError> {
-    let change_address = account.public_key;
+fn crear_tx_ins(utxos: &[Utxo]) -> Vec<TxIn> {
+    let sequence = sequence_de_la_wallet();
+    utxos
+        .iter()
+        .map(|utxo| {
+            let mut tx_in = TxIn::new(utxo.tx_id, utxo.output_index as usize);
+            tx_in.sequence = sequence;
+            tx_in
+        })
+        .collect()
+}
+
+fn crear_change_txout(account: &Account, value: u64) -> Result<TxOut, NodoBitcoinError> {
+    let change_address = account.direccion_de_cambio();
     let tx_out_change = TxOut::new(value, change_address)?;
     Ok(tx_out_change)
 }
 
-fn get_utxos_for_value(utxos: Vec<Utxo>, value: u64) -> Result<(Vec<Utxo>, u64), NodoBitcoinError> {
-    let mut utxos_for_value = Vec::new();
-    let mut value_sum = 0;
+fn get_utxos_for_value(
+    utxos: Vec<Utxo>,
+    chosen_utxos: Vec<Utxo>,
+    value: u64,
+) -> Result<(Vec<Utxo>, u64), NodoBitcoinError> {
+    let mut value_sum = chosen_utxos
+        .iter()
+        .try_fold(0u64, |suma, utxo| suma.checked_add(utxo.tx_out.value))
+        .ok_or(NodoBitcoinError::ValorFueraDeRango)?;
+    let mut utxos_for_value = chosen_utxos;
     for utxo in utxos {
-        value_sum += utxo.tx_out.value;
-        utxos_for_value.push(utxo);
         if value_sum >= value {
             break;
         }
+        let already_chosen = utxos_for_value
+            .iter()
+            .any(|chosen| chosen.tx_id == utxo.tx_id && chosen.output_index == utxo.output_index);
+        if already_chosen {
+            continue;
+        }
+        value_sum = value_sum
+            .checked_add(utxo.tx_out.value)
+            .ok_or(NodoBitcoinError::ValorFueraDeRango)?;
+        utxos_for_value.push(utxo);
     }
     if value_sum < value {
         return Err(NodoBitcoinError::NoHaySuficientesUtxos);
@@ -356,6 +1014,7 @@ fn get_utxos_for_value(utxos: Vec<Utxo>, value: u64) -> Result<(Vec<Utxo>, u64),
 /// * script_bytes - The number of bytes in the signature script.
 /// * signature_script - The signature script for the input.
 /// * sequence - The sequence number for the input.
+/// * witness - The witness stack for the input, empty for non-segwit inputs.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TxIn {
     pub previous_output: Outpoint,
@@ -363,6 +1022,7 @@ pub struct TxIn {
     pub signature_script: Vec<u8>,
     pub sequence: u32,
     pub script_bytes_amount: usize,
+    pub witness: Vec<Vec<u8>>,
 }
 
 impl fmt::Display for TxIn {
@@ -399,18 +1059,18 @@ impl TxIn {
     pub fn deserialize(block_bytes: &[u8]) -> Result<TxIn, NodoBitcoinError> {
         let mut offset = 0;
 
-        let previous_output = Outpoint::deserialize(&block_bytes[offset..offset + 36])?;
+        let previous_output = Outpoint::deserialize(leer_bytes(block_bytes, offset, 36)?)?;
         offset += 36;
 
-        let (script_bytes_amount, script_bytes) = utils_bytes::parse_varint(&block_bytes[offset..]);
+        let (script_bytes_amount, script_bytes) =
+            utils_bytes::leer_varint(resto(block_bytes, offset)?)?;
         offset += script_bytes_amount;
 
-        let mut signature_script = vec![0u8; script_bytes];
-        signature_script.copy_from_slice(&block_bytes[offset..offset + script_bytes]);
+        let signature_script = leer_bytes(block_bytes, offset, script_bytes)?.to_vec();
         offset += script_bytes;
 
         let sequence = u32::from_le_bytes(
-            block_bytes[offset..offset + 4]
+            leer_bytes(block_bytes, offset, 4)?
                 .try_into()
                 .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
         );
@@ -422,21 +1082,50 @@ impl TxIn {
             signature_script,
             sequence,
             script_bytes_amount,
+            witness: vec![],
         })
     }
 
+    /// Tamaño del input sin el testigo, que se serializa aparte
     pub fn size(&self) -> usize {
         40 + self.script_bytes_amount + self.signature_script.len()
     }
 
+    /// Serializa el testigo: la cantidad de elementos y cada uno con su largo
+    pub fn serialize_witness(&self) -> Vec<u8> {
+        let mut bytes = utils_bytes::encode_varint(self.witness.len());
+        for elemento in &self.witness {
+            bytes.extend(utils_bytes::encode_varint(elemento.len()));
+            bytes.extend_from_slice(elemento);
+        }
+        bytes
+    }
+
+    /// Lee el testigo del input y devuelve la cantidad de bytes que ocupaba
+    pub fn deserialize_witness(&mut self, bytes: &[u8]) -> Result<usize, NodoBitcoinError> {
+        let mut offset = 0;
+        let (largo_cantidad, cantidad) = utils_bytes::leer_varint(resto(bytes, offset)?)?;
+        offset += largo_cantidad;
+        let mut witness = Vec::new();
+        for _ in 0..cantidad {
+            let (largo_prefijo, largo) = utils_bytes::leer_varint(resto(bytes, offset)?)?;
+            offset += largo_prefijo;
+            witness.push(leer_bytes(bytes, offset, largo)?.to_vec());
+            offset += largo;
+        }
+        self.witness = witness;
+        Ok(offset)
+    }
+
     pub fn new(hash: Uint256, index: usize) -> TxIn {
         let previous_output = Outpoint::new(hash, index);
         TxIn {
             previous_output,
             script_bytes: 0,
             signature_script: vec![],
-            sequence: 0xffffffff,
+            sequence: SEQUENCE_FINAL,
             script_bytes_amount: 0,
+            witness: vec![],
         }
     }
 
@@ -447,9 +1136,9 @@ impl TxIn {
             pk_script.append(&mut partial_script.clone());
         }
         let pk_len = pk_script.len();
-        let pk_len_bytes = utils_bytes::from_amount_bytes_to_prefix(pk_len);
 
-        self.script_bytes_amount = pk_len_bytes as usize;
+        // los scripts de firma de más de 252 bytes, como los de multisig, llevan el largo en 3
+        self.script_bytes_amount = utils_bytes::encode_varint(pk_len).len();
         self.script_bytes = pk_len;
         self.signature_script = pk_script;
         Ok(())
@@ -494,11 +1183,11 @@ impl Outpoint {
         let mut offset = 0;
 
         let mut hash = [0u8; 32];
-        hash.copy_from_slice(&block_bytes[offset..offset + 32]);
+        hash.copy_from_slice(leer_bytes(block_bytes, offset, 32)?);
         offset += 32;
 
         let index = u32::from_le_bytes(
-            block_bytes[offset..offset + 4]
+            leer_bytes(block_bytes, offset, 4)?
                 .try_into()
                 .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
         );
@@ -559,16 +1248,15 @@ impl TxOut {
         let mut offset = 0;
 
         let value = u64::from_le_bytes(
-            block_bytes[offset..offset + 8]
+            leer_bytes(block_bytes, offset, 8)?
                 .try_into()
                 .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?,
         );
         offset += 8;
-        let (pk_len_bytes, pk_len) = utils_bytes::parse_varint(&block_bytes[offset..]);
+        let (pk_len_bytes, pk_len) = utils_bytes::leer_varint(resto(block_bytes, offset)?)?;
         offset += pk_len_bytes;
 
-        let mut pk_script = vec![0u8; pk_len];
-        pk_script.copy_from_slice(&block_bytes[offset..offset + pk_len]);
+        let pk_script = leer_bytes(block_bytes, offset, pk_len)?.to_vec();
         Ok(TxOut {
             value,
             pk_len,
@@ -581,35 +1269,56 @@ impl TxOut {
         8 + self.pk_len_bytes + self.pk_script.len()
     }
 
+    /// Crea la salida que le paga a la dirección en texto. La dirección tiene que tener el
+    /// checksum correcto y ser de la red configurada, así un error de tipeo no le paga a otro
+    /// script. Para las direcciones ya validadas se usa `pagar_a`
     pub fn new(amount: u64, account: String) -> Result<TxOut, NodoBitcoinError> {
-        let script = decode_base58(account)?;
-        let p2pkh_script = p2pkh_script_serialized(&script)?;
-        let pk_len = p2pkh_script.len();
+        let direccion = Address::en_red(&account, red_configurada())?;
+        TxOut::pagar_a(amount, &direccion)
+    }
+
+    /// Crea la salida que le paga a una dirección ya decodificada. Las direcciones base58
+    /// reciben en P2PKH o P2SH según su prefijo y las segwit en P2WPKH, P2WSH o P2TR según
+    /// su testigo
+    pub fn pagar_a(amount: u64, direccion: &Address) -> Result<TxOut, NodoBitcoinError> {
+        let pk_script = direccion.pk_script()?;
+        let pk_len = pk_script.len();
         let pk_len_bytes = utils_bytes::from_amount_bytes_to_prefix(pk_len);
         Ok(TxOut {
             value: amount,
             pk_len,
-            pk_script: p2pkh_script,
+            pk_script,
             pk_len_bytes: pk_len_bytes.into(),
         })
     }
 
+    /// Devuelve si la salida le paga a la dirección, con el tipo de salida que corresponde a
+    /// la dirección
     pub fn is_user_account_output(&self, account: String) -> bool {
-        let script = match decode_base58(account) {
-            Ok(script) => script,
-            Err(_) => return false,
-        };
-        let p2pkh_script = match p2pkh_script_serialized(&script) {
+        let script = match script_de_direccion(&account) {
             Ok(script) => script,
             Err(_) => return false,
         };
-        self.pk_script == p2pkh_script
+        let tipo = tipo_de_script(&self.pk_script);
+        matches!(
+            tipo,
+            TipoScript::P2PKH
+                | TipoScript::P2SH
+                | TipoScript::P2WPKH
+                | TipoScript::P2WSH
+                | TipoScript::P2TR
+        ) && self.pk_script == script
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blockchain::politica::MotivoRechazo;
+    use crate::common::decoder::codificar_direccion_segwit;
+    use crate::common::red::Red;
+    use crate::wallet::cifrado::desde_hexa;
+    use crate::wallet::multisig::tests::{cuenta_multisig, utxo_de};
 
     #[test]
     fn test_serialize_transaction() {
@@ -621,6 +1330,7 @@ mod tests {
             },
             script_bytes: 31,
             script_bytes_amount: 1,
+            witness: vec![],
             signature_script: vec![
                 4, 231, 190, 191, 79, 1, 34, 23, 47, 80, 50, 83, 72, 47, 73, 99, 101, 73, 114, 111,
                 110, 45, 81, 67, 45, 109, 105, 110, 101, 114, 47,
@@ -708,6 +1418,7 @@ mod tests {
                 },
                 script_bytes: 31,
                 script_bytes_amount: 1,
+                witness: vec![],
                 signature_script: vec![
                     4, 231, 190, 191, 79, 1, 34, 23, 47, 80, 50, 83, 72, 47, 73, 99, 101, 73, 114,
                     111, 110, 45, 81, 67, 45, 109, 105, 110, 101, 114, 47
@@ -765,6 +1476,7 @@ mod tests {
             },
             script_bytes: 31,
             script_bytes_amount: 1,
+            witness: vec![],
             signature_script: vec![
                 4, 231, 190, 191, 79, 1, 34, 23, 47, 80, 50, 83, 72, 47, 73, 99, 101, 73, 114, 111,
                 110, 45, 81, 67, 45, 109, 105, 110, 101, 114, 47,
@@ -839,6 +1551,7 @@ mod tests {
             previous_output,
             script_bytes,
             script_bytes_amount: 1,
+            witness: vec![],
             signature_script: signature_script.clone(),
             sequence,
         };
@@ -893,6 +1606,7 @@ mod tests {
             signature_script: signature_script.clone(),
             sequence,
             script_bytes_amount,
+            witness: vec![],
         };
 
         let expected_size = 40 + script_bytes_amount + signature_script.len();
@@ -1337,4 +2051,494 @@ mod tests {
         ];
         assert_eq!(tx_obj_bytes, expected_obj_bytes);
     }
+
+    #[test]
+    fn test_bump_fee_reemplaza_con_los_mismos_inputs() {
+        let account = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "test".to_string(),
+        );
+        let tx_out = TxOut::new(100000, account.public_key.clone()).unwrap();
+        let previous_tx = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([1; 32]), 0)],
+            vec![tx_out.clone()],
+            0,
+        )
+        .unwrap();
+        let utxo = Utxo {
+            tx_id: previous_tx.txid().unwrap(),
+            output_index: 0,
+            pk_script: tx_out.pk_script.clone(),
+            tx_out,
+            tx: previous_tx.clone(),
+        };
+        let target_address = Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap();
+        let original = create_tx_to_send(
+            account.clone(),
+            target_address,
+            50000,
+            Fee::Total(1000),
+            vec![utxo],
+            vec![],
+        )
+        .unwrap();
+        assert!(original.senaliza_rbf());
+
+        let replacement =
+            create_tx_to_bump_fee(account.clone(), &original, vec![previous_tx.clone()], 3000)
+                .unwrap();
+        assert!(replacement.senaliza_rbf());
+        assert_ne!(replacement.txid().unwrap(), original.txid().unwrap());
+        assert_eq!(
+            replacement.input[0].previous_output,
+            original.input[0].previous_output
+        );
+        assert_eq!(replacement.output[0], original.output[0]);
+        assert_eq!(replacement.output[1].value, 47000);
+
+        assert_eq!(
+            create_tx_to_bump_fee(account.clone(), &original, vec![previous_tx.clone()], 1000),
+            Err(NodoBitcoinError::FeeInsuficienteParaReemplazo)
+        );
+        // más fee que la original pero sin cubrir el fee incremental por byte del reemplazo
+        assert_eq!(
+            create_tx_to_bump_fee(account.clone(), &original, vec![previous_tx.clone()], 1001),
+            Err(NodoBitcoinError::FeeInsuficienteParaReemplazo)
+        );
+        assert_eq!(
+            create_tx_to_bump_fee(account, &original, vec![previous_tx], 60000),
+            Err(NodoBitcoinError::NoHaySuficientesUtxos)
+        );
+    }
+
+    #[test]
+    fn test_create_tx_to_send_gasta_las_utxos_elegidas() {
+        let account = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "test".to_string(),
+        );
+        let utxo = |seed: u8, value: u64| {
+            let tx_out = TxOut::new(value, account.public_key.clone()).unwrap();
+            let tx = Transaction::new(
+                vec![TxIn::new(Uint256::from_be_bytes([seed; 32]), 0)],
+                vec![tx_out.clone()],
+                0,
+            )
+            .unwrap();
+            Utxo {
+                tx_id: tx.txid().unwrap(),
+                output_index: 0,
+                pk_script: tx_out.pk_script.clone(),
+                tx_out,
+                tx,
+            }
+        };
+        let confirmed = utxo(1, 100000);
+        let unconfirmed = utxo(2, 30000);
+        let propia = Address::desde_string(&account.public_key).unwrap();
+
+        // la hija gasta la salida sin confirmar aunque alcance con la confirmada
+        let child = create_tx_to_send(
+            account.clone(),
+            propia.clone(),
+            25000,
+            Fee::Total(5000),
+            vec![confirmed.clone()],
+            vec![unconfirmed.clone()],
+        )
+        .unwrap();
+        assert_eq!(child.input.len(), 1);
+        assert_eq!(
+            Uint256::from_be_bytes(child.input[0].previous_output.hash),
+            unconfirmed.tx_id
+        );
+        // sin cambio no se agrega la salida de cambio
+        assert_eq!(child.output.len(), 1);
+
+        let with_change = create_tx_to_send(
+            account.clone(),
+            propia.clone(),
+            50000,
+            Fee::Total(5000),
+            vec![confirmed, unconfirmed.clone()],
+            vec![unconfirmed],
+        )
+        .unwrap();
+        assert_eq!(with_change.input.len(), 2);
+        assert_eq!(with_change.output[1].value, 75000);
+
+        // la wallet no arma transacciones que los peers no retransmiten
+        assert_eq!(
+            create_tx_to_send(
+                account.clone(),
+                propia,
+                500,
+                Fee::Total(5000),
+                vec![],
+                vec![utxo(3, 5600)],
+            ),
+            Err(NodoBitcoinError::TxNoEstandar(MotivoRechazo::Dust))
+        );
+    }
+
+    #[test]
+    fn test_create_tx_to_send_con_fee_por_vbyte() {
+        let account = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "test".to_string(),
+        );
+        let utxo = |seed: u8, value: u64| {
+            let tx_out = TxOut::new(value, account.public_key.clone()).unwrap();
+            let tx = Transaction::new(
+                vec![TxIn::new(Uint256::from_be_bytes([seed; 32]), 0)],
+                vec![tx_out.clone()],
+                0,
+            )
+            .unwrap();
+            Utxo {
+                tx_id: tx.txid().unwrap(),
+                output_index: 0,
+                pk_script: tx_out.pk_script.clone(),
+                tx_out,
+                tx,
+            }
+        };
+        let utxos = vec![utxo(1, 20000), utxo(2, 20000)];
+        let destino = Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap();
+
+        // con un input no alcanza para el fee de la transacción, se agrega el segundo y el fee
+        // se calcula con el tamaño de la transacción con los dos inputs firmados
+        let tx = create_tx_to_send(
+            account.clone(),
+            destino.clone(),
+            18000,
+            Fee::PorVbyte(10),
+            utxos.clone(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(tx.input.len(), 2);
+        let salidas: u64 = tx.output.iter().map(|tx_out| tx_out.value).sum();
+        let fee = 40000 - salidas;
+        let vsize = tx.vsize().unwrap() as u64;
+        assert!(fee >= 10 * vsize);
+        assert!(fee <= 10 * (vsize + tx.input.len() as u64));
+
+        // los montos que no entran en un u64 son un error y no un panic
+        assert_eq!(
+            create_tx_to_send(
+                account.clone(),
+                destino.clone(),
+                u64::MAX,
+                Fee::Total(1),
+                utxos.clone(),
+                vec![],
+            ),
+            Err(NodoBitcoinError::ValorFueraDeRango)
+        );
+        assert_eq!(
+            create_psbt_to_send(
+                account.clone(),
+                vec![(destino.clone(), u64::MAX), (destino.clone(), 1)],
+                Fee::Total(1000),
+                utxos.clone(),
+            )
+            .map(|_| ()),
+            Err(NodoBitcoinError::ValorFueraDeRango)
+        );
+        assert_eq!(
+            create_tx_to_send(
+                account,
+                destino,
+                18000,
+                Fee::PorVbyte(u64::MAX),
+                utxos,
+                vec![]
+            ),
+            Err(NodoBitcoinError::ValorFueraDeRango)
+        );
+    }
+
+    #[test]
+    fn test_create_unsigned_tx_to_send_sin_clave() {
+        let account = Account::new(
+            String::new(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "frio".to_string(),
+        );
+        let tx_out = TxOut::new(100000, account.public_key.clone()).unwrap();
+        let previous_tx = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([1; 32]), 0)],
+            vec![tx_out.clone()],
+            0,
+        )
+        .unwrap();
+        let utxo = Utxo {
+            tx_id: previous_tx.txid().unwrap(),
+            output_index: 0,
+            pk_script: tx_out.pk_script.clone(),
+            tx_out,
+            tx: previous_tx,
+        };
+        let target_address = Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap();
+
+        // sin la clave privada no se puede firmar, pero sí armar la transacción sin firmar
+        assert_eq!(
+            create_tx_to_send(
+                account.clone(),
+                target_address.clone(),
+                50000,
+                Fee::Total(1000),
+                vec![utxo.clone()],
+                vec![],
+            ),
+            Err(NodoBitcoinError::InvalidAccount)
+        );
+        let unsigned = create_unsigned_tx_to_send(
+            account,
+            target_address,
+            50000,
+            Fee::Total(1000),
+            vec![utxo.clone()],
+        )
+        .unwrap();
+        assert_eq!(
+            Uint256::from_be_bytes(unsigned.input[0].previous_output.hash),
+            utxo.tx_id
+        );
+        assert!(unsigned.input[0].signature_script.is_empty());
+        assert_eq!(unsigned.output[0].value, 50000);
+        assert_eq!(unsigned.output[1].value, 49000);
+        assert_eq!(
+            Transaction::deserialize(&unsigned.serialize().unwrap())
+                .unwrap()
+                .txid()
+                .unwrap(),
+            unsigned.txid().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tx_out_direcciones_segwit() {
+        // P2WPKH de la misma clave que mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun
+        let p2pkh = TxOut::new(1000, "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string()).unwrap();
+        let direccion = codificar_direccion_segwit("tb", 0, &p2pkh.pk_script[3..23]).unwrap();
+        let p2wpkh = TxOut::new(1000, direccion.clone()).unwrap();
+        assert_eq!(tipo_de_script(&p2wpkh.pk_script), TipoScript::P2WPKH);
+        assert_eq!(p2wpkh.pk_script[2..], p2pkh.pk_script[3..23]);
+        assert!(p2wpkh.is_user_account_output(direccion.clone()));
+        assert!(!p2pkh.is_user_account_output(direccion));
+
+        // las direcciones de otra red se rechazan aunque sean válidas
+        let p2tr = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+        assert_eq!(
+            TxOut::new(1000, p2tr.to_string()),
+            Err(NodoBitcoinError::DireccionDeOtraRed)
+        );
+        let p2tr = TxOut::pagar_a(1000, &Address::desde_string(p2tr).unwrap()).unwrap();
+        assert_eq!(tipo_de_script(&p2tr.pk_script), TipoScript::P2TR);
+        assert_eq!(p2tr.size(), 8 + 1 + 34);
+        // solo se paga a direcciones segwit de mainnet o testnet
+        let otra_red = codificar_direccion_segwit("xx", 0, &p2pkh.pk_script[3..23]).unwrap();
+        assert!(TxOut::new(1000, otra_red).is_err());
+        // un error de tipeo no pasa el checksum
+        assert_eq!(
+            TxOut::new(1000, "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVuo".to_string()),
+            Err(NodoBitcoinError::DireccionInvalida)
+        );
+    }
+
+    #[test]
+    fn test_sig_hash_segwit_bip143() {
+        // ejemplo de P2WPKH nativo de BIP143: se firma el segundo input, de 6 BTC
+        let unsigned = desde_hexa("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let mut tx = Transaction::deserialize(&unsigned).unwrap();
+        let script_code = p2pkh_script_serialized(
+            &desde_hexa("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            tx.sig_hash_segwit(1, &script_code, 600000000).unwrap(),
+            desde_hexa("c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670").unwrap()
+        );
+
+        let txid = tx.txid().unwrap();
+        let private_key =
+            desde_hexa("619c335025c7f4012e556c2a58b2506e30b8511b53ade95ea316fd8c3286feb9").unwrap();
+        tx.sign_p2wpkh_with_hexa_key(1, private_key, 600000000)
+            .unwrap();
+        assert_eq!(
+            tx.input[1].witness,
+            vec![
+                desde_hexa("304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01").unwrap(),
+                desde_hexa("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357").unwrap(),
+            ]
+        );
+
+        // con testigos cambia la serialización pero no el txid
+        let signed = tx.serialize().unwrap();
+        assert_eq!(signed[4..6], MARCA_SEGWIT);
+        assert_eq!(tx.txid().unwrap(), txid);
+        assert_eq!(tx.size(), signed.len());
+        let leida = Transaction::deserialize(&signed).unwrap();
+        assert_eq!(leida.input[1].witness, tx.input[1].witness);
+        assert_eq!(leida.serialize().unwrap(), signed);
+        assert_eq!(tx.peso().unwrap(), unsigned.len() * 3 + signed.len());
+        assert!(tx.vsize().unwrap() < signed.len());
+    }
+
+    #[test]
+    fn test_create_tx_to_send_desde_p2wpkh() {
+        let wif = "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw";
+        let private_key = wif_to_hex(wif).unwrap();
+        let sec = point_sec(&private_key).unwrap();
+        let address = Red::Testnet.direccion_p2wpkh(&sec).unwrap();
+        let account = Account::new(wif.to_string(), address.clone(), "segwit".to_string());
+        let tx_out = TxOut::new(100000, address).unwrap();
+        assert!(account.es_propio(&tx_out));
+        let previous_tx = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([1; 32]), 0)],
+            vec![tx_out.clone()],
+            0,
+        )
+        .unwrap();
+        let utxo = Utxo {
+            tx_id: previous_tx.txid().unwrap(),
+            output_index: 0,
+            pk_script: tx_out.pk_script.clone(),
+            tx_out,
+            tx: previous_tx,
+        };
+
+        let tx = create_tx_to_send(
+            account.clone(),
+            Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+            50000,
+            Fee::Total(200),
+            vec![utxo],
+            vec![],
+        )
+        .unwrap();
+        assert!(tx.input[0].signature_script.is_empty());
+        assert_eq!(tx.input[0].witness[1], sec);
+        // el cambio vuelve a la dirección segwit de la cuenta
+        assert!(account.es_propio(&tx.output[1]));
+
+        // la firma del testigo valida contra el hash de BIP143
+        let script_code =
+            p2pkh_script_serialized(hash160::Hash::hash(&sec).as_byte_array()).unwrap();
+        let sig_hash = tx.sig_hash_segwit(0, &script_code, 100000).unwrap();
+        let firma = &tx.input[0].witness[0];
+        let signature = secp256k1::ecdsa::Signature::from_der(&firma[..firma.len() - 1]).unwrap();
+        let public_key = secp256k1::PublicKey::from_slice(&sec).unwrap();
+        let message = secp256k1::Message::from_slice(&sig_hash).unwrap();
+        assert!(secp256k1::Secp256k1::new()
+            .verify_ecdsa(&message, &signature, &public_key)
+            .is_ok());
+
+        // el fee mínimo se calcula con el tamaño virtual, que es menor que el serializado
+        assert!(tx.vsize().unwrap() < tx.serialize().unwrap().len());
+        assert!(tx.vsize().unwrap() <= 200);
+    }
+
+    #[test]
+    fn test_firma_multisig_p2wsh_en_dos_pasos() {
+        let primera = cuenta_multisig(TipoMultisig::P2WSH, 3);
+        let segunda = cuenta_multisig(TipoMultisig::P2WSH, 1);
+        assert_eq!(primera.public_key, segunda.public_key);
+        let utxos = vec![utxo_de(&primera, 100000)];
+        assert!(primera.es_propio(&utxos[0].tx_out));
+
+        let (mut tx, completa) = create_multisig_tx_to_send(
+            primera.clone(),
+            Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+            50000,
+            Fee::Total(1000),
+            utxos.clone(),
+        )
+        .unwrap();
+        assert!(!completa);
+        assert_eq!(tx.input[0].witness.len(), 3);
+        let txid = tx.txid().unwrap();
+
+        // el otro participante lee la transacción parcial y agrega su firma
+        let mut tx_leida = Transaction::deserialize(&tx.serialize().unwrap()).unwrap();
+        assert!(sign_multisig_tx(&mut tx_leida, &segunda, &utxos).unwrap());
+        // volver a firmar con la misma clave no agrega otra firma
+        assert!(!sign_multisig_tx(&mut tx, &primera, &utxos).unwrap());
+        tx = tx_leida;
+        assert_eq!(tx.txid().unwrap(), txid);
+        assert!(tx.input[0].signature_script.is_empty());
+
+        // las firmas quedan en el orden de las claves del redeem script
+        let multisig = primera.multisig.clone().unwrap();
+        let redeem_script = multisig.redeem_script().unwrap();
+        let sig_hash = tx.sig_hash_segwit(0, &redeem_script, 100000).unwrap();
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 4);
+        assert!(witness[0].is_empty());
+        assert!(verificar_firma(
+            &multisig.claves_publicas[0],
+            &sig_hash,
+            &witness[1]
+        ));
+        assert!(verificar_firma(
+            &multisig.claves_publicas[2],
+            &sig_hash,
+            &witness[2]
+        ));
+        assert_eq!(witness[3], redeem_script);
+        assert!(tx.vsize().unwrap() <= 1000);
+    }
+
+    #[test]
+    fn test_firma_multisig_p2sh() {
+        let primera = cuenta_multisig(TipoMultisig::P2SH, 2);
+        let segunda = cuenta_multisig(TipoMultisig::P2SH, 1);
+        let utxos = vec![utxo_de(&primera, 100000)];
+        assert!(primera.es_propio(&utxos[0].tx_out));
+
+        let (tx, completa) = create_multisig_tx_to_send(
+            primera.clone(),
+            Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+            50000,
+            Fee::Total(1000),
+            utxos.clone(),
+        )
+        .unwrap();
+        assert!(!completa);
+        let mut tx = Transaction::deserialize(&tx.serialize().unwrap()).unwrap();
+        assert!(sign_multisig_tx(&mut tx, &segunda, &utxos).unwrap());
+
+        // OP_0, las dos firmas y el redeem script van en el script de firma
+        let multisig = primera.multisig.clone().unwrap();
+        let redeem_script = multisig.redeem_script().unwrap();
+        let sig_hash = tx.sig_hash_legacy(0, &redeem_script).unwrap();
+        let pushes = leer_pushes(&tx.input[0].signature_script).unwrap();
+        assert_eq!(pushes.len(), 4);
+        assert!(pushes[0].is_empty());
+        assert!(verificar_firma(
+            &multisig.claves_publicas[0],
+            &sig_hash,
+            pushes[1]
+        ));
+        assert!(verificar_firma(
+            &multisig.claves_publicas[1],
+            &sig_hash,
+            pushes[2]
+        ));
+        assert_eq!(pushes[3], redeem_script);
+        assert!(!tx.tiene_testigos());
+        assert_eq!(
+            Transaction::deserialize(&tx.serialize().unwrap())
+                .unwrap()
+                .input[0]
+                .signature_script,
+            tx.input[0].signature_script
+        );
+        assert!(tx.size() <= 1000);
+    }
 }
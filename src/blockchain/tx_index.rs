@@ -0,0 +1,253 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::block_file::UbicacionBloque;
+use crate::blockchain::file::{escribir_archivo, get_file_size, leer_bloque, leer_bytes};
+use crate::blockchain::index::create_hash_to_find_index;
+use crate::blockchain::transaction::Transaction;
+use crate::common::uint256::Uint256;
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use std::fs;
+
+const TX_INDEX_DIR: &str = "src/indexes/transactions";
+
+/// Tamaño de cada registro del índice:
+/// txid (32) + hash del bloque (32) + ubicación del bloque (12) + posición de la tx en el bloque (4)
+const TAMANIO_REGISTRO: u64 = 80;
+
+/// Ubicación de una transacción dentro del archivo de bloques
+#[derive(Debug, Clone, PartialEq)]
+pub struct UbicacionTx {
+    pub block_hash: [u8; 32],
+    pub ubicacion_bloque: UbicacionBloque,
+    pub posicion: u32,
+}
+
+impl UbicacionTx {
+    fn serialize(&self, txid: [u8; 32]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(TAMANIO_REGISTRO as usize);
+        bytes.extend_from_slice(&txid);
+        bytes.extend_from_slice(&self.block_hash);
+        bytes.extend_from_slice(&self.ubicacion_bloque.serialize());
+        bytes.extend_from_slice(&self.posicion.to_le_bytes());
+        bytes
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<([u8; 32], UbicacionTx), NodoBitcoinError> {
+        if bytes.len() < TAMANIO_REGISTRO as usize {
+            return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+        }
+        let mut txid = [0u8; 32];
+        txid.copy_from_slice(&bytes[0..32]);
+        let mut block_hash = [0u8; 32];
+        block_hash.copy_from_slice(&bytes[32..64]);
+        let ubicacion_bloque = UbicacionBloque::deserialize(&bytes[64..76])?;
+        let mut posicion_bytes = [0u8; 4];
+        posicion_bytes.copy_from_slice(&bytes[76..80]);
+        Ok((
+            txid,
+            UbicacionTx {
+                block_hash,
+                ubicacion_bloque,
+                posicion: u32::from_le_bytes(posicion_bytes),
+            },
+        ))
+    }
+}
+
+/// Devuelve true si en el config está habilitado el índice de transacciones (TXINDEX=true)
+pub fn txindex_habilitado() -> bool {
+    match config::get_valor("TXINDEX".to_string()) {
+        Ok(valor) => valor.trim() == "true" || valor.trim() == "1",
+        Err(_) => false,
+    }
+}
+
+fn create_path(dir: &str, txid: [u8; 32]) -> String {
+    format!("{}/ix-{}.bin", dir, create_hash_to_find_index(txid))
+}
+
+/// Recibe el bloque recién guardado y su ubicación en los archivos de bloques
+/// Agrega al índice la ubicación de cada una de sus transacciones
+pub fn indexar_transacciones_del_bloque(
+    bloque: &SerializedBlock,
+    ubicacion_bloque: UbicacionBloque,
+) -> Result<(), NodoBitcoinError> {
+    indexar_transacciones_en(TX_INDEX_DIR, bloque, ubicacion_bloque)
+}
+
+/// Borra el índice de transacciones para volver a generarlo
+pub fn borrar_txindex() -> Result<(), NodoBitcoinError> {
+    borrar_directorio(TX_INDEX_DIR)
+}
+
+/// Borra el directorio recibido si existe
+pub fn borrar_directorio(dir: &str) -> Result<(), NodoBitcoinError> {
+    match fs::remove_dir_all(dir) {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(_) => Err(NodoBitcoinError::NoSePuedeEscribirLosBytes),
+    }
+}
+
+fn indexar_transacciones_en(
+    dir: &str,
+    bloque: &SerializedBlock,
+    ubicacion_bloque: UbicacionBloque,
+) -> Result<(), NodoBitcoinError> {
+    fs::create_dir_all(dir).map_err(|_| NodoBitcoinError::NoExisteArchivo)?;
+    let block_hash = bloque.header.hash()?;
+    for (posicion, tx) in bloque.txns.iter().enumerate() {
+        let txid = tx.txid()?.get_bytes();
+        let ubicacion = UbicacionTx {
+            block_hash,
+            ubicacion_bloque,
+            posicion: posicion as u32,
+        };
+        escribir_archivo(create_path(dir, txid), &ubicacion.serialize(txid))?;
+    }
+    Ok(())
+}
+
+/// Recibe el txid buscado
+/// Devuelve la ubicación de la transacción si se encuentra en el índice
+pub fn buscar_en_txindex(txid: Uint256) -> Result<UbicacionTx, NodoBitcoinError> {
+    buscar_en(TX_INDEX_DIR, txid)
+}
+
+fn buscar_en(dir: &str, txid: Uint256) -> Result<UbicacionTx, NodoBitcoinError> {
+    let txid_bytes = txid.get_bytes();
+    let index_path = create_path(dir, txid_bytes);
+    // si no existe el archivo de ese prefijo la transacción no está indexada
+    let len_archivo =
+        get_file_size(index_path.clone()).map_err(|_| NodoBitcoinError::IndexNoEncontrado)?;
+    let bytes = leer_bytes(index_path, 0, len_archivo)?;
+
+    for registro in bytes.chunks_exact(TAMANIO_REGISTRO as usize) {
+        let (txid_registro, ubicacion) = UbicacionTx::deserialize(registro)?;
+        if txid_registro == txid_bytes {
+            return Ok(ubicacion);
+        }
+    }
+    Err(NodoBitcoinError::IndexNoEncontrado)
+}
+
+/// Recibe el txid buscado
+/// Devuelve la transacción leyendo únicamente el bloque que la contiene
+pub fn get_transaction(txid: Uint256) -> Result<Transaction, NodoBitcoinError> {
+    let ubicacion = buscar_en_txindex(txid)?;
+    let (block_hash, tx) =
+        leer_transaccion_de_bloque(ubicacion.ubicacion_bloque, ubicacion.posicion)?;
+    if block_hash != ubicacion.block_hash {
+        return Err(NodoBitcoinError::IndexNoEncontrado);
+    }
+    Ok(tx)
+}
+
+/// Recibe la ubicación del bloque y la posición de la transacción dentro del bloque
+/// Devuelve el hash del bloque leído y la transacción
+pub fn leer_transaccion_de_bloque(
+    ubicacion_bloque: UbicacionBloque,
+    posicion: u32,
+) -> Result<([u8; 32], Transaction), NodoBitcoinError> {
+    let (block_bytes, _) = leer_bloque(ubicacion_bloque)?;
+    let bloque = SerializedBlock::deserialize(&block_bytes)?;
+    match bloque.txns.get(posicion as usize) {
+        Some(tx) => Ok((bloque.header.hash()?, tx.clone())),
+        None => Err(NodoBitcoinError::IndexNoEncontrado),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::blockheader::BlockHeader;
+    use crate::blockchain::transaction::{Outpoint, TxIn, TxOut};
+
+    fn crear_bloque(lock_times: Vec<u32>) -> SerializedBlock {
+        let txns = lock_times
+            .into_iter()
+            .map(|lock_time| Transaction {
+                version: 1,
+                input: vec![TxIn {
+                    previous_output: Outpoint {
+                        hash: [1; 32],
+                        index: 0,
+                    },
+                    script_bytes: 0,
+                    signature_script: vec![],
+                    sequence: 0xffffffff,
+                    script_bytes_amount: 1,
+                    witness: vec![],
+                }],
+                output: vec![TxOut {
+                    value: 1000,
+                    pk_len: 0,
+                    pk_script: vec![],
+                    pk_len_bytes: 1,
+                }],
+                lock_time,
+                tx_in_count: 1,
+                tx_out_count: 1,
+            })
+            .collect::<Vec<Transaction>>();
+        SerializedBlock {
+            header: BlockHeader {
+                version: 1,
+                previous_block_hash: [0; 32],
+                merkle_root_hash: [0; 32],
+                time: 0,
+                n_bits: 0,
+                nonce: 0,
+            },
+            txn_amount: 1,
+            txns,
+        }
+    }
+
+    #[test]
+    fn test_serialize_deserialize_ubicacion() {
+        let ubicacion = UbicacionTx {
+            block_hash: [7; 32],
+            ubicacion_bloque: UbicacionBloque {
+                archivo: 2,
+                offset: 123456,
+            },
+            posicion: 3,
+        };
+        let bytes = ubicacion.serialize([9; 32]);
+        assert_eq!(bytes.len() as u64, TAMANIO_REGISTRO);
+
+        let (txid, leida) = UbicacionTx::deserialize(&bytes).unwrap();
+        assert_eq!(txid, [9; 32]);
+        assert_eq!(leida, ubicacion);
+    }
+
+    #[test]
+    fn test_indexar_y_buscar_transacciones() {
+        let dir = std::env::temp_dir().join("txindex_test_indexar_y_buscar");
+        let dir = dir.to_str().unwrap();
+        _ = fs::remove_dir_all(dir);
+
+        let bloque = crear_bloque(vec![1, 2, 3]);
+        let ubicacion_bloque = UbicacionBloque {
+            archivo: 1,
+            offset: 500,
+        };
+        indexar_transacciones_en(dir, &bloque, ubicacion_bloque).unwrap();
+
+        let txid = bloque.txns[2].txid().unwrap();
+        let ubicacion = buscar_en(dir, txid).unwrap();
+        assert_eq!(ubicacion.block_hash, bloque.header.hash().unwrap());
+        assert_eq!(ubicacion.ubicacion_bloque, ubicacion_bloque);
+        assert_eq!(ubicacion.posicion, 2);
+
+        let otra = crear_bloque(vec![4]).txns[0].txid().unwrap();
+        // las que no están en el índice se distinguen para poder buscarlas en los bloques
+        assert_eq!(
+            buscar_en(dir, otra),
+            Err(NodoBitcoinError::IndexNoEncontrado)
+        );
+
+        _ = fs::remove_dir_all(dir);
+    }
+}
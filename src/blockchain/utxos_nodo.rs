@@ -0,0 +1,248 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::transaction::{Transaction, TxIn, TxOut};
+use crate::common::uint256::Uint256;
+use crate::errores::NodoBitcoinError;
+use crate::storage::file_storage::FileStorage;
+use crate::storage::Storage;
+use std::collections::HashMap;
+
+const UTXOS_NODO_FILENAME: &str = "utxos_nodo.dat";
+
+/// Outpoint de una salida: txid y número de salida
+pub type ClaveUtxo = (Uint256, u32);
+
+/// Devuelve la clave de la salida que gasta el input
+pub fn clave_de_input(tx_in: &TxIn) -> ClaveUtxo {
+    (
+        Uint256::from_be_bytes(tx_in.previous_output.hash),
+        tx_in.previous_output.index,
+    )
+}
+
+/// Salidas sin gastar de todas las transacciones de los bloques guardados por el nodo,
+/// no solo las de las cuentas de la wallet
+/// Como solo se descargan los bloques desde la fecha inicial, las salidas creadas antes no están
+#[derive(Debug, Clone, Default)]
+pub struct UtxosNodo {
+    salidas: HashMap<ClaveUtxo, TxOut>,
+    // timestamp del bloque más nuevo aplicado
+    ultimo_timestamp: u32,
+}
+
+impl UtxosNodo {
+    pub fn new() -> UtxosNodo {
+        UtxosNodo::default()
+    }
+
+    /// Arma las salidas sin gastar aplicando los bloques en orden de timestamp
+    pub fn desde_bloques(bloques: &[SerializedBlock]) -> UtxosNodo {
+        let mut ordenados: Vec<&SerializedBlock> = bloques.iter().collect();
+        ordenados.sort();
+        let mut utxos = UtxosNodo::new();
+        for bloque in ordenados {
+            utxos.aplicar_bloque(bloque);
+        }
+        utxos
+    }
+
+    /// Aplica a las salidas guardadas solo los bloques posteriores al último que tenían
+    /// aplicado. Con prune es la única forma de volver a armarlas, porque los bloques viejos
+    /// ya no están
+    pub fn con_bloques_posteriores(mut self, bloques: &[SerializedBlock]) -> UtxosNodo {
+        let mut posteriores: Vec<&SerializedBlock> = bloques
+            .iter()
+            .filter(|bloque| bloque.header.time > self.ultimo_timestamp)
+            .collect();
+        posteriores.sort();
+        for bloque in posteriores {
+            self.aplicar_bloque(bloque);
+        }
+        self
+    }
+
+    /// Agrega las salidas de las transacciones del bloque y quita las que gastan sus inputs
+    pub fn aplicar_bloque(&mut self, bloque: &SerializedBlock) {
+        for tx in bloque.txns.iter() {
+            self.aplicar_tx(tx);
+        }
+        self.ultimo_timestamp = self.ultimo_timestamp.max(bloque.header.time);
+    }
+
+    fn aplicar_tx(&mut self, tx: &Transaction) {
+        for tx_in in tx.input.iter() {
+            self.salidas.remove(&clave_de_input(tx_in));
+        }
+        let txid = match tx.txid() {
+            Ok(txid) => txid,
+            Err(_) => return,
+        };
+        for (index, tx_out) in tx.output.iter().enumerate() {
+            self.salidas.insert((txid, index as u32), tx_out.clone());
+        }
+    }
+
+    /// Devuelve la salida sin gastar con la clave recibida
+    pub fn obtener(&self, clave: &ClaveUtxo) -> Option<&TxOut> {
+        self.salidas.get(clave)
+    }
+
+    pub fn cantidad(&self) -> usize {
+        self.salidas.len()
+    }
+
+    /// Serializa el timestamp del último bloque aplicado y cada salida con su outpoint
+    pub fn serializar(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.ultimo_timestamp.to_le_bytes());
+        bytes.extend_from_slice(&(self.salidas.len() as u32).to_le_bytes());
+        for ((txid, index), tx_out) in self.salidas.iter() {
+            bytes.extend_from_slice(&txid.get_bytes());
+            bytes.extend_from_slice(&index.to_le_bytes());
+            bytes.extend(tx_out.serialize()?);
+        }
+        Ok(bytes)
+    }
+
+    pub fn deserializar(bytes: &[u8]) -> Result<UtxosNodo, NodoBitcoinError> {
+        let mut offset = 0;
+        let ultimo_timestamp = leer_u32(bytes, &mut offset)?;
+        let cantidad = leer_u32(bytes, &mut offset)?;
+        let mut salidas = HashMap::new();
+        for _ in 0..cantidad {
+            let txid: [u8; 32] = bytes
+                .get(offset..offset + 32)
+                .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)?
+                .try_into()
+                .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)?;
+            offset += 32;
+            let index = leer_u32(bytes, &mut offset)?;
+            let tx_out = TxOut::deserialize(
+                bytes
+                    .get(offset..)
+                    .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)?,
+            )?;
+            offset += tx_out.size();
+            salidas.insert((Uint256::from_be_bytes(txid), index), tx_out);
+        }
+        Ok(UtxosNodo {
+            salidas,
+            ultimo_timestamp,
+        })
+    }
+
+    /// Guarda las salidas en los archivos de la wallet, antes de borrar bloques con prune
+    pub fn guardar(&self) -> Result<(), NodoBitcoinError> {
+        self.guardar_en(&mut FileStorage::desde_config()?)
+    }
+
+    pub fn guardar_en(&self, storage: &mut dyn Storage) -> Result<(), NodoBitcoinError> {
+        storage.guardar_estado_wallet(UTXOS_NODO_FILENAME, &self.serializar()?)
+    }
+
+    /// Carga las salidas guardadas antes del último prune
+    pub fn cargar() -> Result<UtxosNodo, NodoBitcoinError> {
+        UtxosNodo::cargar_de(&FileStorage::desde_config()?)
+    }
+
+    pub fn cargar_de(storage: &dyn Storage) -> Result<UtxosNodo, NodoBitcoinError> {
+        UtxosNodo::deserializar(&storage.leer_estado_wallet(UTXOS_NODO_FILENAME)?)
+    }
+}
+
+fn leer_u32(bytes: &[u8], offset: &mut usize) -> Result<u32, NodoBitcoinError> {
+    let valor = bytes
+        .get(*offset..*offset + 4)
+        .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)?;
+    *offset += 4;
+    Ok(u32::from_le_bytes([valor[0], valor[1], valor[2], valor[3]]))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::blockchain::blockheader::BlockHeader;
+    use crate::storage::memory_storage::MemoryStorage;
+
+    /// Bloque de prueba con las transacciones recibidas, ordenado por el timestamp
+    pub(crate) fn bloque(time: u32, txns: Vec<Transaction>) -> SerializedBlock {
+        SerializedBlock {
+            header: BlockHeader {
+                version: 1,
+                previous_block_hash: [0; 32],
+                merkle_root_hash: [0; 32],
+                time,
+                n_bits: 0,
+                nonce: 0,
+            },
+            txn_amount: txns.len(),
+            txns,
+        }
+    }
+
+    /// Transacción de prueba que gasta los outpoints recibidos y tiene una salida vacía por
+    /// cada valor
+    pub(crate) fn tx(inputs: Vec<(Uint256, usize)>, valores: Vec<u64>) -> Transaction {
+        let inputs = inputs
+            .into_iter()
+            .map(|(txid, index)| TxIn::new(txid, index))
+            .collect();
+        let outputs = valores
+            .into_iter()
+            .map(|valor| TxOut {
+                value: valor,
+                pk_len: 0,
+                pk_script: vec![],
+                pk_len_bytes: 1,
+            })
+            .collect();
+        Transaction::new(inputs, outputs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_utxos_nodo_aplica_bloques_en_orden() {
+        let padre = tx(vec![(Uint256::from_be_bytes([7; 32]), 0)], vec![100, 200]);
+        let id_padre = padre.txid().unwrap();
+        let hijo = tx(vec![(id_padre, 0)], vec![90]);
+        let id_hijo = hijo.txid().unwrap();
+
+        // el bloque del hijo viene primero pero tiene un timestamp posterior
+        let utxos = UtxosNodo::desde_bloques(&[bloque(20, vec![hijo]), bloque(10, vec![padre])]);
+
+        assert_eq!(utxos.cantidad(), 2);
+        assert!(utxos.obtener(&(id_padre, 0)).is_none());
+        assert_eq!(utxos.obtener(&(id_padre, 1)).unwrap().value, 200);
+        assert_eq!(utxos.obtener(&(id_hijo, 0)).unwrap().value, 90);
+    }
+
+    #[test]
+    fn test_utxos_nodo_guardadas_antes_del_prune() {
+        let padre = tx(vec![(Uint256::from_be_bytes([7; 32]), 0)], vec![100, 200]);
+        let id_padre = padre.txid().unwrap();
+        let hijo = tx(vec![(id_padre, 1)], vec![150]);
+        let id_hijo = hijo.txid().unwrap();
+        let bloques = vec![bloque(10, vec![padre]), bloque(20, vec![hijo])];
+
+        let mut storage = MemoryStorage::new();
+        assert!(UtxosNodo::cargar_de(&storage).is_err());
+        UtxosNodo::desde_bloques(&bloques[..1])
+            .guardar_en(&mut storage)
+            .unwrap();
+
+        // el bloque viejo se podó: las salidas guardadas lo tienen y no se aplica dos veces
+        let guardadas = UtxosNodo::cargar_de(&storage).unwrap();
+        assert_eq!(guardadas.cantidad(), 2);
+        let utxos = guardadas.con_bloques_posteriores(&bloques[1..]);
+        let completas = UtxosNodo::desde_bloques(&bloques);
+        assert_eq!(utxos.salidas, completas.salidas);
+        assert_eq!(utxos.obtener(&(id_padre, 0)).unwrap().value, 100);
+        assert!(utxos.obtener(&(id_padre, 1)).is_none());
+        assert_eq!(utxos.obtener(&(id_hijo, 0)).unwrap().value, 150);
+
+        let bytes = utxos.serializar().unwrap();
+        assert_eq!(
+            UtxosNodo::deserializar(&bytes).unwrap().salidas,
+            utxos.salidas
+        );
+        assert!(UtxosNodo::deserializar(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
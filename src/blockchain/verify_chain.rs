@@ -0,0 +1,180 @@
+use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::blockheader::BlockHeader;
+use crate::blockchain::proof_of_work::pow_validation;
+use crate::errores::NodoBitcoinError;
+use crate::storage::file_storage::FileStorage;
+use crate::storage::Storage;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Cantidad de bloques que se verifican si no se indica otra, igual que `-checkblocks` en Bitcoin Core
+pub const BLOQUES_A_VERIFICAR_DEFAULT: u64 = 6;
+
+const TAMANIO_HEADER: u64 = 80;
+
+/// Motivo por el que un bloque no pasó la verificación
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotivoBloqueInvalido {
+    ProofOfWork,
+    Encadenamiento,
+    MerkleRoot,
+}
+
+impl fmt::Display for MotivoBloqueInvalido {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MotivoBloqueInvalido::ProofOfWork => write!(f, "la proof of work no es válida"),
+            MotivoBloqueInvalido::Encadenamiento => {
+                write!(f, "no apunta al header anterior de la cadena")
+            }
+            MotivoBloqueInvalido::MerkleRoot => {
+                write!(f, "el merkle root no coincide con sus transacciones")
+            }
+        }
+    }
+}
+
+/// Primer bloque inválido encontrado por `verificar_cadena`
+/// La altura es la posición del header en el archivo de headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloqueInvalido {
+    pub altura: u64,
+    pub hash: [u8; 32],
+    pub motivo: MotivoBloqueInvalido,
+}
+
+impl fmt::Display for BloqueInvalido {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut hash = self.hash;
+        hash.reverse();
+        let hash_hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        write!(
+            f,
+            "Bloque {} (altura {}) inválido: {}.",
+            hash_hex, self.altura, self.motivo
+        )
+    }
+}
+
+/// Recibe la cantidad de bloques a verificar
+/// Verifica la proof of work y el encadenamiento de los últimos headers de la cadena y,
+/// para los que tienen el bloque guardado, el merkle root de sus transacciones.
+/// Devuelve el primer bloque inválido, o None si están todos bien
+pub fn verificar_cadena(cantidad: u64) -> Result<Option<BloqueInvalido>, NodoBitcoinError> {
+    verificar_cadena_en(&FileStorage::desde_config()?, cantidad)
+}
+
+fn verificar_cadena_en(
+    storage: &dyn Storage,
+    cantidad: u64,
+) -> Result<Option<BloqueInvalido>, NodoBitcoinError> {
+    let total = storage.tamanio_headers()? / TAMANIO_HEADER;
+    let desde = total.saturating_sub(cantidad);
+
+    let mut headers = vec![];
+    for altura in desde..total {
+        let bytes = storage.leer_headers(altura * TAMANIO_HEADER, TAMANIO_HEADER)?;
+        let header = BlockHeader::deserialize(&bytes)?;
+        headers.push((altura, header.hash()?, header));
+    }
+
+    // solo se guardan en memoria los bloques de los headers a verificar
+    let mut bloques = HashMap::new();
+    for (_, bytes) in storage.leer_todos_los_bloques()? {
+        let hash = BlockHeader::deserialize(&bytes[..bytes.len().min(80)])?.hash()?;
+        if headers
+            .iter()
+            .any(|(_, hash_header, _)| *hash_header == hash)
+        {
+            bloques.insert(hash, bytes);
+        }
+    }
+
+    let mut hash_anterior = match desde {
+        0 => None,
+        _ => {
+            let bytes = storage.leer_headers((desde - 1) * TAMANIO_HEADER, TAMANIO_HEADER)?;
+            Some(BlockHeader::deserialize(&bytes)?.hash()?)
+        }
+    };
+    for (altura, hash, header) in headers {
+        let motivo = if !pow_validation(&header)? {
+            Some(MotivoBloqueInvalido::ProofOfWork)
+        } else if hash_anterior.is_some_and(|anterior| anterior != header.previous_block_hash) {
+            Some(MotivoBloqueInvalido::Encadenamiento)
+        } else {
+            match bloques.get(&hash) {
+                Some(bytes) if !SerializedBlock::deserialize(bytes)?.is_valid_merkle() => {
+                    Some(MotivoBloqueInvalido::MerkleRoot)
+                }
+                _ => None,
+            }
+        };
+        if let Some(motivo) = motivo {
+            return Ok(Some(BloqueInvalido {
+                altura,
+                hash,
+                motivo,
+            }));
+        }
+        hash_anterior = Some(hash);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory_storage::MemoryStorage;
+
+    fn header_valido() -> BlockHeader {
+        let bytes = [
+            0x02, 0x00, 0x00, 0x20, 0x8e, 0xc3, 0x94, 0x28, 0xb1, 0x73, 0x23, 0xfa, 0x0d, 0xde,
+            0xc8, 0xe8, 0x87, 0xb4, 0xa7, 0xc5, 0x3b, 0x8c, 0x0a, 0x0a, 0x22, 0x0c, 0xfd, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x07, 0x50, 0xfc, 0xe0, 0xa8,
+            0x89, 0x50, 0x2d, 0x40, 0x50, 0x8d, 0x39, 0x57, 0x68, 0x21, 0x15, 0x5e, 0x9c, 0x9e,
+            0x3f, 0x5c, 0x31, 0x57, 0xf9, 0x61, 0xdb, 0x38, 0xfd, 0x8b, 0x25, 0xbe, 0x1e, 0x77,
+            0xa7, 0x59, 0xe9, 0x3c, 0x01, 0x18, 0xa4, 0xff, 0xd7, 0x1d,
+        ];
+        BlockHeader::deserialize(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_verificar_cadena_reporta_el_primer_bloque_invalido() {
+        let mut storage = MemoryStorage::new();
+        let valido = header_valido();
+        storage
+            .escribir_header(&valido.serialize().unwrap())
+            .unwrap();
+        assert_eq!(verificar_cadena_en(&storage, 6).unwrap(), None);
+
+        let mut invalido = valido;
+        invalido.nonce = 0xFFFFFFFF;
+        storage
+            .escribir_header(&invalido.serialize().unwrap())
+            .unwrap();
+        let resultado = verificar_cadena_en(&storage, 6).unwrap().unwrap();
+        assert_eq!(resultado.altura, 1);
+        assert_eq!(resultado.motivo, MotivoBloqueInvalido::ProofOfWork);
+
+        // un bloque sin transacciones no puede tener el merkle root del header
+        let mut bloque = valido.serialize().unwrap();
+        bloque.push(0);
+        storage.escribir_bloque(&bloque).unwrap();
+        let resultado = verificar_cadena_en(&storage, 6).unwrap().unwrap();
+        assert_eq!(resultado.altura, 0);
+        assert_eq!(resultado.motivo, MotivoBloqueInvalido::MerkleRoot);
+
+        // si solo se verifica el último header se controla que apunte al anterior
+        let mut sin_encadenar = MemoryStorage::new();
+        sin_encadenar
+            .escribir_header(&invalido.serialize().unwrap())
+            .unwrap();
+        sin_encadenar
+            .escribir_header(&valido.serialize().unwrap())
+            .unwrap();
+        let resultado = verificar_cadena_en(&sin_encadenar, 1).unwrap().unwrap();
+        assert_eq!(resultado.altura, 1);
+        assert_eq!(resultado.motivo, MotivoBloqueInvalido::Encadenamiento);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::common::decoder::{
+    codificar_direccion_segwit, decodificar_direccion_segwit, p2pkh_script_serialized,
+    p2sh_script_serialized, segwit_script_serialized,
+};
+use crate::common::red::{codificar_base58check, decodificar_base58check, Red};
+use crate::errores::NodoBitcoinError;
+use std::fmt;
+
+const REDES: [Red; 2] = [Red::Mainnet, Red::Testnet];
+
+/// Tipo de salida al que le paga la dirección, con el hash o el programa del testigo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TipoDireccion {
+    P2PKH([u8; 20]),
+    P2SH([u8; 20]),
+    Segwit { version: u8, programa: Vec<u8> },
+}
+
+/// Dirección de destino ya decodificada: tiene el checksum correcto y el prefijo o el hrp de
+/// una red conocida
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub red: Red,
+    pub tipo: TipoDireccion,
+}
+
+impl Address {
+    /// Decodifica una dirección base58check (P2PKH o P2SH) o segwit (bech32 o bech32m) de
+    /// mainnet o testnet. La red sale del byte de versión o del hrp
+    pub fn desde_string(texto: &str) -> Result<Address, NodoBitcoinError> {
+        if let Ok((hrp, version, programa)) = decodificar_direccion_segwit(texto) {
+            let red = REDES
+                .into_iter()
+                .find(|red| red.hrp_segwit() == hrp)
+                .ok_or(NodoBitcoinError::DireccionInvalida)?;
+            return Ok(Address {
+                red,
+                tipo: TipoDireccion::Segwit { version, programa },
+            });
+        }
+        let datos =
+            decodificar_base58check(texto).map_err(|_| NodoBitcoinError::DireccionInvalida)?;
+        let (version, hash) = datos
+            .split_first()
+            .ok_or(NodoBitcoinError::DireccionInvalida)?;
+        let hash: [u8; 20] = hash
+            .try_into()
+            .map_err(|_| NodoBitcoinError::DireccionInvalida)?;
+        for red in REDES {
+            if *version == red.prefijo_p2pkh() {
+                return Ok(Address {
+                    red,
+                    tipo: TipoDireccion::P2PKH(hash),
+                });
+            }
+            if *version == red.prefijo_p2sh() {
+                return Ok(Address {
+                    red,
+                    tipo: TipoDireccion::P2SH(hash),
+                });
+            }
+        }
+        Err(NodoBitcoinError::DireccionInvalida)
+    }
+
+    /// Decodifica la dirección y además exige que sea de la red indicada
+    pub fn en_red(texto: &str, red: Red) -> Result<Address, NodoBitcoinError> {
+        let direccion = Address::desde_string(texto)?;
+        if direccion.red != red {
+            return Err(NodoBitcoinError::DireccionDeOtraRed);
+        }
+        Ok(direccion)
+    }
+
+    /// Script de la salida que le paga a la dirección: P2PKH, P2SH o P2WPKH, P2WSH o P2TR
+    /// según la versión y el largo del programa
+    pub fn pk_script(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        match &self.tipo {
+            TipoDireccion::P2PKH(hash) => p2pkh_script_serialized(hash),
+            TipoDireccion::P2SH(hash) => p2sh_script_serialized(hash),
+            TipoDireccion::Segwit { version, programa } => {
+                segwit_script_serialized(*version, programa)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (prefijo, hash) = match &self.tipo {
+            TipoDireccion::P2PKH(hash) => (self.red.prefijo_p2pkh(), hash),
+            TipoDireccion::P2SH(hash) => (self.red.prefijo_p2sh(), hash),
+            TipoDireccion::Segwit { version, programa } => {
+                let texto = codificar_direccion_segwit(self.red.hrp_segwit(), *version, programa)
+                    .map_err(|_| fmt::Error)?;
+                return write!(f, "{}", texto);
+            }
+        };
+        let mut bytes = vec![prefijo];
+        bytes.extend_from_slice(hash);
+        write!(f, "{}", codificar_base58check(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_prefijos_y_redes() {
+        let p2pkh = Address::desde_string("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun").unwrap();
+        assert_eq!(p2pkh.red, Red::Testnet);
+        assert!(matches!(p2pkh.tipo, TipoDireccion::P2PKH(_)));
+        assert_eq!(p2pkh.to_string(), "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun");
+
+        // el mismo script hash en testnet y en mainnet
+        let p2sh_testnet = Address::desde_string("2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS").unwrap();
+        let p2sh_mainnet = Address::desde_string("33hG2q39jRi2NqicRJB4ggY1J8EJm97Szz").unwrap();
+        assert_eq!(p2sh_testnet.red, Red::Testnet);
+        assert_eq!(p2sh_mainnet.red, Red::Mainnet);
+        assert_eq!(p2sh_testnet.tipo, p2sh_mainnet.tipo);
+        assert_eq!(
+            p2sh_testnet.pk_script().unwrap(),
+            p2sh_mainnet.pk_script().unwrap()
+        );
+
+        let p2tr = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+        let segwit = Address::desde_string(p2tr).unwrap();
+        assert_eq!(segwit.red, Red::Mainnet);
+        assert_eq!(segwit.to_string(), p2tr);
+    }
+
+    #[test]
+    fn test_address_invalidas() {
+        // un caracter cambiado no pasa el checksum
+        assert_eq!(
+            Address::desde_string("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVuo"),
+            Err(NodoBitcoinError::DireccionInvalida)
+        );
+        // checksum correcto pero prefijo de WIF en lugar de dirección
+        assert_eq!(
+            Address::desde_string("cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw"),
+            Err(NodoBitcoinError::DireccionInvalida)
+        );
+        assert_eq!(
+            Address::desde_string(""),
+            Err(NodoBitcoinError::DireccionInvalida)
+        );
+
+        assert_eq!(
+            Address::en_red("33hG2q39jRi2NqicRJB4ggY1J8EJm97Szz", Red::Testnet),
+            Err(NodoBitcoinError::DireccionDeOtraRed)
+        );
+        assert_eq!(
+            Address::en_red("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun", Red::Mainnet),
+            Err(NodoBitcoinError::DireccionDeOtraRed)
+        );
+        assert!(Address::en_red("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun", Red::Testnet).is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::errores::NodoBitcoinError;
+
+/// Alfabeto estándar de base64 (RFC 4648), el valor de cada caracter es su posición
+const ALFABETO_BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Codifica los bytes en base64 con relleno
+pub fn codificar_base64(bytes: &[u8]) -> String {
+    let mut texto = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for grupo in bytes.chunks(3) {
+        let valor = (grupo[0] as u32) << 16
+            | (*grupo.get(1).unwrap_or(&0) as u32) << 8
+            | *grupo.get(2).unwrap_or(&0) as u32;
+        // cada grupo de 3 bytes son 4 caracteres de 6 bits, los que faltan se rellenan con =
+        for indice in 0..4 {
+            if indice <= grupo.len() {
+                let posicion = (valor >> (18 - 6 * indice)) & 0x3f;
+                texto.push(ALFABETO_BASE64[posicion as usize] as char);
+            } else {
+                texto.push('=');
+            }
+        }
+    }
+    texto
+}
+
+/// Decodifica un texto en base64 con relleno. Ignora los espacios y saltos de línea
+pub fn decodificar_base64(texto: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+    let caracteres: Vec<u8> = texto
+        .bytes()
+        .filter(|caracter| !caracter.is_ascii_whitespace())
+        .collect();
+    if !caracteres.len().is_multiple_of(4) {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    let mut bytes = Vec::with_capacity(caracteres.len() / 4 * 3);
+    let grupos = caracteres.len() / 4;
+    for (numero, grupo) in caracteres.chunks(4).enumerate() {
+        let relleno = grupo
+            .iter()
+            .rev()
+            .take_while(|caracter| **caracter == b'=')
+            .count();
+        // el relleno sólo puede estar al final del texto
+        if relleno > 2 || (relleno > 0 && numero + 1 != grupos) {
+            return Err(NodoBitcoinError::DecodeError);
+        }
+        let mut valor: u32 = 0;
+        for caracter in &grupo[..4 - relleno] {
+            let posicion = ALFABETO_BASE64
+                .iter()
+                .position(|letra| letra == caracter)
+                .ok_or(NodoBitcoinError::DecodeError)?;
+            valor = valor << 6 | posicion as u32;
+        }
+        valor <<= 6 * relleno;
+        let decodificados = [(valor >> 16) as u8, (valor >> 8) as u8, valor as u8];
+        bytes.extend_from_slice(&decodificados[..3 - relleno]);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        // vectores de RFC 4648
+        let vectores = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, texto) in vectores {
+            assert_eq!(codificar_base64(bytes.as_bytes()), texto);
+            assert_eq!(decodificar_base64(texto).unwrap(), bytes.as_bytes());
+        }
+        assert_eq!(
+            decodificar_base64("cHNidP8=\n").unwrap(),
+            [b'p', b's', b'b', b't', 0xff]
+        );
+        assert!(decodificar_base64("Zm9").is_err());
+        assert!(decodificar_base64("Zg==Zm9v").is_err());
+        assert!(decodificar_base64("Zm9*").is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use std::vec;
 
+use crate::common::address::Address;
+use crate::common::red::decodificar_base58check;
 use crate::errores::NodoBitcoinError;
 
 use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
@@ -50,30 +52,29 @@ pub fn wif_to_hex(wif: &str) -> Result<Vec<u8>, NodoBitcoinError> {
     Ok(hex)
 }
 
+/// Decodifica una dirección base58check validando el checksum
+/// Devuelve los datos sin el byte de versión
 pub fn decode_base58(input: String) -> Result<Vec<u8>, NodoBitcoinError> {
-    let base_58 = bs58::decode(input);
-    if let Ok(base_vec) = base_58.into_vec() {
-        // quitar el primer byte
-        let mut base_vec = base_vec[1..].to_vec();
-        // quitar los ultimos 4 bytes
-        base_vec.truncate(base_vec.len() - 4);
-        Ok(base_vec)
-    } else {
-        Err(NodoBitcoinError::DecodeError)
+    // el checksum se valida antes de quitar el byte de versión
+    let datos = decodificar_base58check(&input)?;
+    match datos.split_first() {
+        Some((_, base_vec)) => Ok(base_vec.to_vec()),
+        None => Err(NodoBitcoinError::DecodeError),
     }
 }
 
 pub fn script_serialized(key: &[u8]) -> Result<Vec<u8>, NodoBitcoinError> {
     let mut script = vec![];
     let length = key.len();
-    if length < 75 {
+    if length <= 75 {
         script.push(length as u8);
-    } else if length > 75 && length < 0x100 {
+    } else if length < 0x100 {
         script.push(76);
         script.push(length as u8);
-    } else if (0x100..=520).contains(&length) {
+    } else if length <= 520 {
+        // OP_PUSHDATA2 lleva el largo en dos bytes
         script.push(77);
-        script.extend_from_slice(&length.to_le_bytes());
+        script.extend_from_slice(&(length as u16).to_le_bytes());
     } else {
         return Err(NodoBitcoinError::DecodeError);
     }
@@ -100,11 +101,260 @@ pub fn p2pkh_script_serialized(pubkey_hash: &[u8]) -> Result<Vec<u8>, NodoBitcoi
     Ok(script)
 }
 
+/// Arma el script P2SH que le paga al hash160 del redeem script
+pub fn p2sh_script_serialized(script_hash: &[u8]) -> Result<Vec<u8>, NodoBitcoinError> {
+    if script_hash.len() != 20 {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    let mut script = vec![0xa9, 0x14]; // OP_HASH160 y el push de 20 bytes
+    script.extend_from_slice(script_hash);
+    script.push(0x87); // OP_EQUAL
+    Ok(script)
+}
+
+/// Verifica una firma DER seguida del byte de sighash contra la clave pública y el hash
+/// firmado
+pub fn verificar_firma(clave_publica: &[u8], mensaje: &[u8], firma: &[u8]) -> bool {
+    let der = match firma.split_last() {
+        Some((_, der)) => der,
+        None => return false,
+    };
+    match (
+        PublicKey::from_slice(clave_publica),
+        Signature::from_der(der),
+        Message::from_slice(mensaje),
+    ) {
+        (Ok(clave_publica), Ok(firma), Ok(mensaje)) => Secp256k1::verification_only()
+            .verify_ecdsa(&mensaje, &firma, &clave_publica)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// Caracteres de bech32, el valor de cada uno es su posición
+const CHARSET_BECH32: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Largo máximo de un texto bech32 (BIP173)
+const LARGO_MAXIMO_BECH32: usize = 90;
+
+/// Variante del checksum: bech32 para los testigos versión 0 (BIP173) y bech32m para
+/// las versiones 1 a 16 (BIP350)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarianteBech32 {
+    Bech32,
+    Bech32m,
+}
+
+impl VarianteBech32 {
+    fn constante(&self) -> u32 {
+        match self {
+            VarianteBech32::Bech32 => 1,
+            VarianteBech32::Bech32m => 0x2bc830a3,
+        }
+    }
+}
+
+fn polymod_bech32(valores: &[u8]) -> u32 {
+    const GENERADOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for valor in valores {
+        let tope = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *valor as u32;
+        for (i, generador) in GENERADOR.iter().enumerate() {
+            if (tope >> i) & 1 == 1 {
+                checksum ^= generador;
+            }
+        }
+    }
+    checksum
+}
+
+// el hrp entra al checksum con los bits altos de cada caracter, un cero y los bits bajos
+fn expandir_hrp(hrp: &str) -> Vec<u8> {
+    let mut valores: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    valores.push(0);
+    valores.extend(hrp.bytes().map(|c| c & 31));
+    valores
+}
+
+/// Codifica el hrp y los datos, en grupos de 5 bits, como bech32 o bech32m
+pub fn codificar_bech32(
+    hrp: &str,
+    datos: &[u8],
+    variante: VarianteBech32,
+) -> Result<String, NodoBitcoinError> {
+    if hrp.is_empty() || datos.iter().any(|valor| *valor > 31) {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    let mut valores = expandir_hrp(hrp);
+    valores.extend_from_slice(datos);
+    valores.extend_from_slice(&[0; 6]);
+    let polymod = polymod_bech32(&valores) ^ variante.constante();
+    let checksum = (0..6).map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8);
+
+    let mut texto = format!("{}1", hrp.to_lowercase());
+    for valor in datos.iter().copied().chain(checksum) {
+        texto.push(CHARSET_BECH32[valor as usize] as char);
+    }
+    if texto.len() > LARGO_MAXIMO_BECH32 {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    Ok(texto)
+}
+
+/// Decodifica un texto bech32 o bech32m y valida el checksum
+/// Devuelve el hrp en minúsculas, los datos en grupos de 5 bits sin el checksum y la variante
+pub fn decodificar_bech32(
+    texto: &str,
+) -> Result<(String, Vec<u8>, VarianteBech32), NodoBitcoinError> {
+    let tiene_minusculas = texto.bytes().any(|c| c.is_ascii_lowercase());
+    let tiene_mayusculas = texto.bytes().any(|c| c.is_ascii_uppercase());
+    if texto.len() > LARGO_MAXIMO_BECH32
+        || (tiene_minusculas && tiene_mayusculas)
+        || texto.bytes().any(|c| !(33..=126).contains(&c))
+    {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    let texto = texto.to_lowercase();
+    // el separador es el último 1, el hrp puede tener otros
+    let separador = texto.rfind('1').ok_or(NodoBitcoinError::DecodeError)?;
+    if separador == 0 || separador + 7 > texto.len() {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    let (hrp, resto) = texto.split_at(separador);
+    let datos = resto[1..]
+        .bytes()
+        .map(|c| {
+            CHARSET_BECH32
+                .iter()
+                .position(|valor| *valor == c)
+                .map(|valor| valor as u8)
+                .ok_or(NodoBitcoinError::DecodeError)
+        })
+        .collect::<Result<Vec<u8>, NodoBitcoinError>>()?;
+
+    let mut valores = expandir_hrp(hrp);
+    valores.extend_from_slice(&datos);
+    let variante = match polymod_bech32(&valores) {
+        1 => VarianteBech32::Bech32,
+        0x2bc830a3 => VarianteBech32::Bech32m,
+        _ => return Err(NodoBitcoinError::DecodeError),
+    };
+    Ok((hrp.to_string(), datos[..datos.len() - 6].to_vec(), variante))
+}
+
+/// Reagrupa los bits de los datos, por ejemplo de bytes a grupos de 5 bits para bech32
+/// Con relleno completa con ceros el último grupo; sin relleno los bits que sobran tienen
+/// que ser ceros y no alcanzar para un grupo
+pub fn convertir_bits(
+    datos: &[u8],
+    desde: u32,
+    hacia: u32,
+    relleno: bool,
+) -> Result<Vec<u8>, NodoBitcoinError> {
+    let mut acumulador: u32 = 0;
+    let mut bits: u32 = 0;
+    let maximo: u32 = (1 << hacia) - 1;
+    let mut resultado = vec![];
+    for valor in datos {
+        let valor = *valor as u32;
+        if valor >> desde != 0 {
+            return Err(NodoBitcoinError::DecodeError);
+        }
+        acumulador = (acumulador << desde) | valor;
+        bits += desde;
+        while bits >= hacia {
+            bits -= hacia;
+            resultado.push(((acumulador >> bits) & maximo) as u8);
+        }
+    }
+    if relleno {
+        if bits > 0 {
+            resultado.push(((acumulador << (hacia - bits)) & maximo) as u8);
+        }
+    } else if bits >= desde || ((acumulador << (hacia - bits)) & maximo) != 0 {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    Ok(resultado)
+}
+
+/// Codifica una dirección segwit con la versión y el programa del testigo. La versión 0 usa
+/// bech32 y las demás bech32m
+pub fn codificar_direccion_segwit(
+    hrp: &str,
+    version: u8,
+    programa: &[u8],
+) -> Result<String, NodoBitcoinError> {
+    validar_programa_testigo(version, programa)?;
+    let variante = match version {
+        0 => VarianteBech32::Bech32,
+        _ => VarianteBech32::Bech32m,
+    };
+    let mut datos = vec![version];
+    datos.extend(convertir_bits(programa, 8, 5, true)?);
+    codificar_bech32(hrp, &datos, variante)
+}
+
+/// Decodifica una dirección segwit (bc1 o tb1)
+/// Devuelve el hrp, la versión del testigo y el programa
+pub fn decodificar_direccion_segwit(
+    direccion: &str,
+) -> Result<(String, u8, Vec<u8>), NodoBitcoinError> {
+    let (hrp, datos, variante) = decodificar_bech32(direccion)?;
+    let (version, programa) = datos.split_first().ok_or(NodoBitcoinError::DecodeError)?;
+    let programa = convertir_bits(programa, 5, 8, false)?;
+    validar_programa_testigo(*version, &programa)?;
+    let variante_esperada = match version {
+        0 => VarianteBech32::Bech32,
+        _ => VarianteBech32::Bech32m,
+    };
+    if variante != variante_esperada {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    Ok((hrp, *version, programa))
+}
+
+fn validar_programa_testigo(version: u8, programa: &[u8]) -> Result<(), NodoBitcoinError> {
+    let largo_valido = match version {
+        0 => programa.len() == 20 || programa.len() == 32,
+        1..=16 => (2..=40).contains(&programa.len()),
+        _ => false,
+    };
+    if !largo_valido {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    Ok(())
+}
+
+/// Arma el script de una salida segwit: el opcode de la versión y el push del programa
+pub fn segwit_script_serialized(version: u8, programa: &[u8]) -> Result<Vec<u8>, NodoBitcoinError> {
+    validar_programa_testigo(version, programa)?;
+    // OP_0 para la versión 0 y de OP_1 (0x51) a OP_16 para las demás
+    let opcode = match version {
+        0 => 0x00,
+        _ => 0x50 + version,
+    };
+    let mut script = vec![opcode, programa.len() as u8];
+    script.extend_from_slice(programa);
+    Ok(script)
+}
+
+/// Arma el script de salida que le paga a la dirección: P2PKH o P2SH según el prefijo de las
+/// direcciones base58check y P2WPKH, P2WSH o P2TR para las direcciones segwit. Acepta
+/// direcciones de mainnet y de testnet, ver `Address::en_red` para exigir la red
+pub fn script_de_direccion(direccion: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+    Address::desde_string(direccion)?.pk_script()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::decoder::{point_sec, signature_der, wif_to_hex};
 
-    use super::{decode_base58, p2pkh_script_serialized};
+    use super::{
+        codificar_direccion_segwit, decode_base58, decodificar_direccion_segwit,
+        p2pkh_script_serialized, script_de_direccion, script_serialized,
+    };
+    use crate::wallet::cifrado::desde_hexa;
 
     #[test]
     fn test_compress_public_key() {
@@ -176,6 +426,10 @@ mod tests {
         let decode = result.unwrap();
         let bytes_decoded = decode.as_slice();
         assert_ne!(bytes_decoded, decode_ok.as_ref());
+
+        // con un caracter cambiado no coincide el checksum
+        let typo = "mzx5YhAH9kNHtcN481u6WkjeHjYtVeKVh3".to_string();
+        assert!(decode_base58(typo).is_err());
     }
 
     #[test]
@@ -242,4 +496,71 @@ mod tests {
         let serialized_bytes = binding.as_ref();
         assert_eq!(serialized_bytes, &expected_der);
     }
+
+    #[test]
+    fn test_direcciones_segwit() {
+        let p2wpkh = desde_hexa("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(
+            decodificar_direccion_segwit("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").unwrap(),
+            ("bc".to_string(), 0, p2wpkh.clone())
+        );
+        assert_eq!(
+            codificar_direccion_segwit("bc", 0, &p2wpkh).unwrap(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+
+        let p2wsh = "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7";
+        assert_eq!(
+            script_de_direccion(p2wsh).unwrap(),
+            desde_hexa("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262")
+                .unwrap()
+        );
+        let p2tr = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+        let script_p2tr = script_de_direccion(p2tr).unwrap();
+        assert_eq!(
+            script_p2tr,
+            desde_hexa("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .unwrap()
+        );
+        assert_eq!(
+            codificar_direccion_segwit("bc", 1, &script_p2tr[2..]).unwrap(),
+            p2tr
+        );
+
+        // checksum inválido, versión 2 con checksum bech32 y mayúsculas mezcladas
+        assert!(
+            decodificar_direccion_segwit("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err()
+        );
+        assert!(decodificar_direccion_segwit(
+            "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf"
+        )
+        .is_err());
+        assert!(decodificar_direccion_segwit(
+            "tb1Qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7"
+        )
+        .is_err());
+        assert!(script_de_direccion("").is_err());
+    }
+
+    #[test]
+    fn test_script_de_direccion_p2sh() {
+        assert_eq!(
+            script_de_direccion("2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS").unwrap(),
+            desde_hexa("a91415fc0754e73eb85d1cbce08786fadb7320ecb8dc87").unwrap()
+        );
+        assert_eq!(
+            script_de_direccion("33hG2q39jRi2NqicRJB4ggY1J8EJm97Szz").unwrap(),
+            desde_hexa("a91415fc0754e73eb85d1cbce08786fadb7320ecb8dc87").unwrap()
+        );
+
+        // pushes directos hasta 75 bytes, OP_PUSHDATA1 hasta 255 y OP_PUSHDATA2 hasta 520
+        assert_eq!(script_serialized(&[1; 75]).unwrap()[0], 75);
+        assert_eq!(script_serialized(&[1; 105]).unwrap()[..2], [0x4c, 105]);
+        assert_eq!(
+            script_serialized(&[1; 513]).unwrap()[..3],
+            [0x4d, 0x01, 0x02]
+        );
+        assert_eq!(script_serialized(&[1; 513]).unwrap().len(), 516);
+        assert!(script_serialized(&[1; 521]).is_err());
+    }
 }
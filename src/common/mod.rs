@@ -1,4 +1,7 @@
+pub mod address;
+pub mod base64;
 pub mod decoder;
+pub mod red;
 pub mod uint256;
 pub mod utils_bytes;
 pub mod utils_data;
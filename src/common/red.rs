@@ -0,0 +1,169 @@
+use crate::common::decoder::codificar_direccion_segwit;
+use crate::config;
+use crate::errores::NodoBitcoinError;
+use crate::messages::messages_header::{MAGIC_NUMBER_MAINNET, MAGIC_NUMBER_TESTNET};
+use crate::protocol::initial_block_download::{GENESIS_BLOCK, GENESIS_BLOCK_MAINNET};
+use bitcoin_hashes::{hash160, sha256d, Hash};
+
+/// Red de Bitcoin en la que funciona el nodo, define los prefijos de las claves y direcciones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Red {
+    Mainnet,
+    Testnet,
+}
+
+impl Red {
+    /// Magic number de los mensajes P2P y de los registros de los archivos de bloques
+    pub fn magic_number(&self) -> [u8; 4] {
+        match self {
+            Red::Mainnet => MAGIC_NUMBER_MAINNET,
+            Red::Testnet => MAGIC_NUMBER_TESTNET,
+        }
+    }
+
+    /// Hash del bloque génesis, desde donde se piden los headers
+    pub fn bloque_genesis(&self) -> [u8; 32] {
+        match self {
+            Red::Mainnet => GENESIS_BLOCK_MAINNET,
+            Red::Testnet => GENESIS_BLOCK,
+        }
+    }
+
+    /// Prefijo de las direcciones P2PKH
+    pub fn prefijo_p2pkh(&self) -> u8 {
+        match self {
+            Red::Mainnet => 0x00,
+            Red::Testnet => 0x6f,
+        }
+    }
+
+    /// Prefijo de las direcciones P2SH
+    pub fn prefijo_p2sh(&self) -> u8 {
+        match self {
+            Red::Mainnet => 0x05,
+            Red::Testnet => 0xc4,
+        }
+    }
+
+    /// Parte legible (hrp) de las direcciones segwit en bech32
+    pub fn hrp_segwit(&self) -> &'static str {
+        match self {
+            Red::Mainnet => "bc",
+            Red::Testnet => "tb",
+        }
+    }
+
+    /// Prefijo de las claves privadas en formato WIF
+    pub fn prefijo_wif(&self) -> u8 {
+        match self {
+            Red::Mainnet => 0x80,
+            Red::Testnet => 0xef,
+        }
+    }
+
+    /// Versión de las claves privadas extendidas (xprv o tprv)
+    pub fn version_xprv(&self) -> [u8; 4] {
+        match self {
+            Red::Mainnet => [0x04, 0x88, 0xad, 0xe4],
+            Red::Testnet => [0x04, 0x35, 0x83, 0x94],
+        }
+    }
+
+    /// Versión de las claves públicas extendidas (xpub o tpub)
+    pub fn version_xpub(&self) -> [u8; 4] {
+        match self {
+            Red::Mainnet => [0x04, 0x88, 0xb2, 0x1e],
+            Red::Testnet => [0x04, 0x35, 0x87, 0xcf],
+        }
+    }
+
+    /// Tipo de moneda de BIP44 que se usa en los caminos de derivación
+    pub fn tipo_moneda(&self) -> u32 {
+        match self {
+            Red::Mainnet => 0,
+            Red::Testnet => 1,
+        }
+    }
+
+    /// Codifica la clave privada en formato WIF comprimido
+    pub fn codificar_wif(&self, clave: &[u8; 32]) -> String {
+        let mut bytes = vec![self.prefijo_wif()];
+        bytes.extend_from_slice(clave);
+        bytes.push(0x01);
+        codificar_base58check(&bytes)
+    }
+
+    /// Devuelve la dirección P2PKH de la clave pública
+    pub fn direccion_p2pkh(&self, clave_publica: &[u8]) -> String {
+        let hash = hash160::Hash::hash(clave_publica);
+        let mut bytes = vec![self.prefijo_p2pkh()];
+        bytes.extend_from_slice(hash.as_byte_array());
+        codificar_base58check(&bytes)
+    }
+
+    /// Devuelve la dirección P2WPKH (bech32) de la clave pública comprimida
+    pub fn direccion_p2wpkh(&self, clave_publica: &[u8]) -> Result<String, NodoBitcoinError> {
+        let hash = hash160::Hash::hash(clave_publica);
+        codificar_direccion_segwit(self.hrp_segwit(), 0, hash.as_byte_array())
+    }
+}
+
+/// Devuelve la red guardada en el config. Por defecto testnet, que es la red del nodo
+pub fn red_configurada() -> Red {
+    match config::get_valor("RED".to_string()) {
+        Ok(valor) if valor == "mainnet" => Red::Mainnet,
+        _ => Red::Testnet,
+    }
+}
+
+/// Codifica los datos en base58 agregando los 4 bytes de checksum
+pub fn codificar_base58check(datos: &[u8]) -> String {
+    let checksum = sha256d::Hash::hash(datos);
+    let mut bytes = datos.to_vec();
+    bytes.extend_from_slice(&checksum.as_byte_array()[..4]);
+    bs58::encode(bytes).into_string()
+}
+
+/// Decodifica un string base58 y valida el checksum
+/// Devuelve los datos sin el checksum
+pub fn decodificar_base58check(texto: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+    let bytes = bs58::decode(texto)
+        .into_vec()
+        .map_err(|_| NodoBitcoinError::DecodeError)?;
+    if bytes.len() < 4 {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    let (datos, checksum) = bytes.split_at(bytes.len() - 4);
+    if sha256d::Hash::hash(datos).as_byte_array()[..4] != *checksum {
+        return Err(NodoBitcoinError::DecodeError);
+    }
+    Ok(datos.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base58check() {
+        let direccion = "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun";
+        let datos = decodificar_base58check(direccion).unwrap();
+        assert_eq!(datos.len(), 21);
+        assert_eq!(datos[0], Red::Testnet.prefijo_p2pkh());
+        assert_eq!(codificar_base58check(&datos), direccion);
+
+        // un caracter cambiado no pasa el checksum
+        assert_eq!(
+            decodificar_base58check("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVuo"),
+            Err(NodoBitcoinError::DecodeError)
+        );
+    }
+
+    #[test]
+    fn test_parametros_de_la_red() {
+        assert_eq!(red_configurada(), Red::Testnet);
+        assert_eq!(red_configurada().magic_number(), [0x0b, 0x11, 0x09, 0x07]);
+        assert_eq!(Red::Mainnet.magic_number(), [0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_ne!(Red::Mainnet.bloque_genesis(), Red::Testnet.bloque_genesis());
+    }
+}
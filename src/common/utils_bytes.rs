@@ -22,6 +22,22 @@ pub fn parse_varint(bytes: &[u8]) -> (usize, usize) {
     }
 }
 
+/// Igual que parse_varint pero devuelve un error si los bytes no alcanzan para el varint, para
+/// leer datos que vienen de afuera sin entrar en pánico
+pub fn leer_varint(bytes: &[u8]) -> Result<(usize, usize), NodoBitcoinError> {
+    let largo = match bytes.first() {
+        Some(&PREFIX_FD) => 3,
+        Some(&PREFIX_FE) => 5,
+        Some(&PREFIX_FF) => 9,
+        Some(_) => 1,
+        None => return Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
+    };
+    if bytes.len() < largo {
+        return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+    }
+    Ok(parse_varint(bytes))
+}
+
 pub fn string_to_bytes(s: &String, fixed_size: usize) -> Vec<u8> {
     let mut bytes = s.as_bytes().to_vec();
     match bytes.len() < fixed_size {
@@ -90,6 +106,17 @@ pub fn build_varint_bytes(prefix: u8, value: usize) -> Result<Vec<u8>, NodoBitco
         }
     }
 }
+
+/// Codifica el valor como varint usando la menor cantidad de bytes
+pub fn encode_varint(value: usize) -> Vec<u8> {
+    match value {
+        0..=0xfc => vec![value as u8],
+        0xfd..=0xffff => [&[PREFIX_FD][..], &(value as u16).to_le_bytes()].concat(),
+        0x10000..=0xffffffff => [&[PREFIX_FE][..], &(value as u32).to_le_bytes()].concat(),
+        _ => [&[PREFIX_FF][..], &(value as u64).to_le_bytes()].concat(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -165,6 +192,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_encode_varint() {
+        assert_eq!(encode_varint(0xfc), vec![0xfc]);
+        assert_eq!(encode_varint(0xfd), vec![0xfd, 0xfd, 0x00]);
+        assert_eq!(encode_varint(102003), vec![0xfe, 0x73, 0x8e, 0x01, 0x00]);
+        assert_eq!(
+            parse_varint(&encode_varint(0x1_0000_0000)),
+            (9, 0x1_0000_0000)
+        );
+    }
+
     #[test]
     fn testfrom_amount_bytes_to_prefix() {
         assert_eq!(from_amount_bytes_to_prefix(3), PREFIX_FD);
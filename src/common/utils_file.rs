@@ -56,9 +56,11 @@ pub fn read_decoded_string_offset(
 }
 
 fn leer_bytes(buffer: Vec<u8>, offset: u64, length: u64) -> Result<Vec<u8>, NodoBitcoinError> {
-    let mut bytes = vec![0; length as usize];
-    for i in 0..length {
-        bytes[i as usize] = buffer[(offset + i) as usize];
+    let fin = offset
+        .checked_add(length)
+        .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)?;
+    match buffer.get(offset as usize..fin as usize) {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
     }
-    Ok(bytes)
 }
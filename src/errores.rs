@@ -1,8 +1,11 @@
+use crate::blockchain::politica::MotivoRechazo;
 use std::{error::Error, fmt};
 
 #[derive(Debug, PartialEq)]
 pub enum NodoBitcoinError {
     NoArgument,
+    ModoDesconocido,
+    CantidadDeBloquesInvalida,
     NoExisteArchivo,
     NoExisteClave,
     ConfigLock,
@@ -41,8 +44,38 @@ pub enum NodoBitcoinError {
     CuentaNoEncontrada,
     NoSePuedeEnviarTransaccion,
     ErrorAlActualizarUTXOS,
+    ClaveInvalida,
+    CaminoDeDerivacionInvalido,
+    MnemonicoInvalido,
+    PassphraseIncorrecta,
+    WalletBloqueada,
+    ErrorAlAccederALaWallet,
+    CuentaSoloLectura,
+    MultisigInvalido,
+    PsbtInvalida,
+    PsbtIncompleta,
+    DireccionInvalida,
+    DireccionDeOtraRed,
     // index
     IndexNoEncontrado,
+    // archivos de bloques
+    ChecksumIncorrecto,
+    ArchivoDeBloquesExistente,
+    PruneIncompatibleConIndices,
+    ReindexConBloquesPodados,
+    // mempool
+    TxYaEnMempool,
+    TxInvalida,
+    InputsDesconocidos,
+    TxEnConflictoConMempool,
+    FeeRateInsuficiente,
+    FeeInsuficienteParaReemplazo,
+    DemasiadasTransaccionesReemplazadas,
+    ReemplazoConInputsSinConfirmar,
+    TxPendienteNoEncontrada,
+    TxNoReemplazable,
+    DemasiadosAncestros,
+    TxNoEstandar(MotivoRechazo),
     // server
     ErrorEnPing,
     ErrorAlLeerSolicitudDelCliente,
@@ -60,6 +93,12 @@ impl fmt::Display for NodoBitcoinError {
                     "ERROR: No se especificó la ubicación del archivo de configuración."
                 )
             }
+            NodoBitcoinError::ModoDesconocido => {
+                write!(f, "ERROR: El modo de ejecución indicado no existe.")
+            }
+            NodoBitcoinError::CantidadDeBloquesInvalida => {
+                write!(f, "ERROR: La cantidad de bloques a verificar no es válida.")
+            }
             NodoBitcoinError::ConfigLock => {
                 write!(f, "ERROR: Error al lockear el config.")
             }
@@ -158,6 +197,151 @@ impl fmt::Display for NodoBitcoinError {
             NodoBitcoinError::IndexNoEncontrado => {
                 write!(f, "ERROR: No encontró en el indice.")
             }
+            NodoBitcoinError::ChecksumIncorrecto => {
+                write!(
+                    f,
+                    "ERROR: El checksum del registro no coincide con sus datos."
+                )
+            }
+            NodoBitcoinError::ArchivoDeBloquesExistente => {
+                write!(
+                    f,
+                    "ERROR: El directorio de bloques ya contiene archivos, no se puede migrar."
+                )
+            }
+            NodoBitcoinError::PruneIncompatibleConIndices => {
+                write!(
+                    f,
+                    "ERROR: El modo prune no se puede usar con TXINDEX o SCRIPTINDEX habilitados."
+                )
+            }
+            NodoBitcoinError::ReindexConBloquesPodados => {
+                write!(
+                    f,
+                    "ERROR: Faltan archivos de bloques borrados por el modo prune, hay que volver a descargarlos."
+                )
+            }
+            NodoBitcoinError::TxYaEnMempool => {
+                write!(f, "ERROR: La transacción ya está en la mempool.")
+            }
+            NodoBitcoinError::TxInvalida => {
+                write!(f, "ERROR: La transacción no es válida.")
+            }
+            NodoBitcoinError::InputsDesconocidos => {
+                write!(
+                    f,
+                    "ERROR: La transacción gasta salidas que no están en el conjunto de UTXOs."
+                )
+            }
+            NodoBitcoinError::TxEnConflictoConMempool => {
+                write!(
+                    f,
+                    "ERROR: La transacción gasta salidas ya gastadas por otra transacción de la mempool."
+                )
+            }
+            NodoBitcoinError::FeeRateInsuficiente => {
+                write!(
+                    f,
+                    "ERROR: El fee rate de la transacción es muy bajo para entrar en la mempool."
+                )
+            }
+            NodoBitcoinError::FeeInsuficienteParaReemplazo => {
+                write!(
+                    f,
+                    "ERROR: El fee no alcanza para reemplazar a las transacciones en conflicto."
+                )
+            }
+            NodoBitcoinError::DemasiadasTransaccionesReemplazadas => {
+                write!(
+                    f,
+                    "ERROR: El reemplazo desalojaría demasiadas transacciones de la mempool."
+                )
+            }
+            NodoBitcoinError::ReemplazoConInputsSinConfirmar => {
+                write!(
+                    f,
+                    "ERROR: El reemplazo gasta salidas sin confirmar que no gastaban las transacciones que reemplaza."
+                )
+            }
+            NodoBitcoinError::TxPendienteNoEncontrada => {
+                write!(f, "ERROR: No se encontró la transacción pendiente.")
+            }
+            NodoBitcoinError::TxNoReemplazable => {
+                write!(
+                    f,
+                    "ERROR: La transacción no señaliza que se pueda reemplazar (RBF)."
+                )
+            }
+            NodoBitcoinError::DemasiadosAncestros => {
+                write!(
+                    f,
+                    "ERROR: La transacción tiene demasiados ancestros sin confirmar en la mempool."
+                )
+            }
+            NodoBitcoinError::ClaveInvalida => {
+                write!(f, "ERROR: La clave privada no es válida.")
+            }
+            NodoBitcoinError::CaminoDeDerivacionInvalido => {
+                write!(f, "ERROR: El camino de derivación no es válido.")
+            }
+            NodoBitcoinError::MnemonicoInvalido => {
+                write!(
+                    f,
+                    "ERROR: Las palabras no forman un mnemónico BIP39 válido."
+                )
+            }
+            NodoBitcoinError::PassphraseIncorrecta => {
+                write!(f, "ERROR: La passphrase de la wallet no es correcta.")
+            }
+            NodoBitcoinError::WalletBloqueada => {
+                write!(
+                    f,
+                    "ERROR: La wallet está bloqueada, hay que desbloquearla para usar las claves."
+                )
+            }
+            NodoBitcoinError::ErrorAlAccederALaWallet => {
+                write!(f, "ERROR: No se pudo acceder a las cuentas de la wallet.")
+            }
+            NodoBitcoinError::CuentaSoloLectura => {
+                write!(
+                    f,
+                    "ERROR: La cuenta es de solo lectura, el nodo no tiene su clave privada."
+                )
+            }
+            NodoBitcoinError::MultisigInvalido => {
+                write!(
+                    f,
+                    "ERROR: El multisig tiene que tener entre 1 y 15 claves públicas comprimidas distintas y no requerir más firmas que claves."
+                )
+            }
+            NodoBitcoinError::PsbtInvalida => {
+                write!(
+                    f,
+                    "ERROR: La PSBT no es válida o no es de la misma transacción que las otras."
+                )
+            }
+            NodoBitcoinError::PsbtIncompleta => {
+                write!(
+                    f,
+                    "ERROR: A la PSBT le faltan firmas para finalizar todos sus inputs."
+                )
+            }
+            NodoBitcoinError::DireccionInvalida => {
+                write!(
+                    f,
+                    "ERROR: La dirección no es válida o no coincide su checksum."
+                )
+            }
+            NodoBitcoinError::DireccionDeOtraRed => {
+                write!(f, "ERROR: La dirección no es de la red configurada.")
+            }
+            NodoBitcoinError::TxNoEstandar(motivo) => {
+                write!(
+                    f,
+                    "ERROR: La transacción no cumple la política de retransmisión (reject: {}).",
+                    motivo
+                )
+            }
 
             NodoBitcoinError::ErrorEnPing => {
                 write!(f, "ERROR: No se puede armar el mensaje PING")
@@ -182,6 +366,24 @@ pub enum InterfaceError {
     BlockBroadcastingError,
     NodoServerError,
     MerklePathError,
+    BumpFeeNotValid,
+    TxIdNotValid,
+    FeeEstimateError,
+    NewAddressError,
+    MnemonicNotValid,
+    WrongPassphrase,
+    PassphraseNotValid,
+    WalletLocked,
+    SaveAccountsError,
+    WatchOnlyAccount,
+    UnsignedTxNotExported,
+    MultisigNotValid,
+    PartialTxNotSigned,
+    PsbtRecipientsNotValid,
+    PsbtNotValid,
+    PsbtNotSigned,
+    AddressNotValid,
+    AddressWrongNetwork,
 }
 
 impl Error for InterfaceError {}
@@ -219,6 +421,99 @@ impl fmt::Display for InterfaceError {
             InterfaceError::MerklePathError => {
                 write!(f, "POI invalido.")
             }
+            InterfaceError::BumpFeeNotValid => {
+                write!(
+                    f,
+                    "No se pudo aumentar el fee de la transaccion. El nuevo fee debe superar al anterior en al menos 1 satoshi por byte virtual."
+                )
+            }
+            InterfaceError::TxIdNotValid => {
+                write!(f, "El Tx ID seleccionado no es valido.")
+            }
+            InterfaceError::FeeEstimateError => {
+                write!(f, "No se pudo estimar el fee. Ingreselo manualmente.")
+            }
+            InterfaceError::NewAddressError => {
+                write!(f, "No se pudo generar una dirección nueva.")
+            }
+            InterfaceError::MnemonicNotValid => {
+                write!(
+                    f,
+                    "Las palabras no forman un mnemónico válido. Revise que estén completas y en orden."
+                )
+            }
+            InterfaceError::WrongPassphrase => {
+                write!(f, "La passphrase de la wallet no es correcta.")
+            }
+            InterfaceError::PassphraseNotValid => {
+                write!(
+                    f,
+                    "La passphrase nueva no puede estar vacía y tiene que coincidir con la confirmación."
+                )
+            }
+            InterfaceError::WalletLocked => {
+                write!(
+                    f,
+                    "La wallet está bloqueada. Desbloqueela desde el menú Wallet para continuar."
+                )
+            }
+            InterfaceError::SaveAccountsError => {
+                write!(f, "No se pudieron guardar las cuentas en el archivo.")
+            }
+            InterfaceError::WatchOnlyAccount => {
+                write!(
+                    f,
+                    "La cuenta es de solo lectura y el nodo no puede firmar sus transacciones."
+                )
+            }
+            InterfaceError::UnsignedTxNotExported => {
+                write!(
+                    f,
+                    "No se pudo armar la transaccion sin firmar. Revise el monto, el fee y el saldo disponible."
+                )
+            }
+            InterfaceError::MultisigNotValid => {
+                write!(
+                    f,
+                    "El multisig no es válido. Ingrese cuántas firmas requiere y las claves públicas comprimidas en hexadecimal separadas por comas."
+                )
+            }
+            InterfaceError::PartialTxNotSigned => {
+                write!(
+                    f,
+                    "No se pudo firmar la transaccion multisig. Revise que sea de la cuenta seleccionada y que sus salidas no se hayan gastado."
+                )
+            }
+            InterfaceError::PsbtRecipientsNotValid => {
+                write!(
+                    f,
+                    "Ingrese los destinatarios como direccion=monto separados por comas, con montos numéricos."
+                )
+            }
+            InterfaceError::PsbtNotValid => {
+                write!(
+                    f,
+                    "No se pudo armar o leer la PSBT. Revise los archivos, el monto, el fee y el saldo disponible."
+                )
+            }
+            InterfaceError::PsbtNotSigned => {
+                write!(
+                    f,
+                    "La cuenta seleccionada no tiene claves para firmar ningún input de la PSBT."
+                )
+            }
+            InterfaceError::AddressNotValid => {
+                write!(
+                    f,
+                    "La dirección de destino no es válida. Revise que esté bien escrita."
+                )
+            }
+            InterfaceError::AddressWrongNetwork => {
+                write!(
+                    f,
+                    "La dirección de destino es de otra red que la configurada en el nodo."
+                )
+            }
         }
     }
 }
@@ -227,6 +522,21 @@ impl fmt::Display for InterfaceError {
 pub enum InterfaceMessage {
     CreateAccount,
     TransactionSent,
+    FeeBumped,
+    ChildTxSent,
+    NewReceiveAddress(String),
+    NewMnemonic(String),
+    AccountGenerated(String, String),
+    AccountRestored,
+    WalletUnlocked(u64),
+    WalletLocked,
+    PassphraseChanged,
+    WatchOnlyAccountCreated,
+    UnsignedTxExported(String),
+    MultisigAccountCreated(String),
+    PartialTxExported(String),
+    PsbtExported(String),
+    UnencryptedWallet,
 }
 
 impl fmt::Display for InterfaceMessage {
@@ -238,6 +548,91 @@ impl fmt::Display for InterfaceMessage {
             InterfaceMessage::TransactionSent => {
                 write!(f, "Transaccion enviada.")
             }
+            InterfaceMessage::FeeBumped => {
+                write!(f, "Transaccion reemplazada con el nuevo fee.")
+            }
+            InterfaceMessage::ChildTxSent => {
+                write!(f, "Transaccion hija enviada para acelerar la seleccionada.")
+            }
+            InterfaceMessage::NewReceiveAddress(address) => {
+                write!(f, "Nueva dirección de recepción: {}", address)
+            }
+            InterfaceMessage::NewMnemonic(mnemonic) => {
+                write!(
+                    f,
+                    "Anote estas palabras en orden y guárdelas en un lugar seguro, no se vuelven a mostrar: {}",
+                    mnemonic
+                )
+            }
+            InterfaceMessage::AccountGenerated(address, secret_key) => {
+                write!(
+                    f,
+                    "Cuenta creada con la dirección {}. Guarde una copia de la clave privada: {}",
+                    address, secret_key
+                )
+            }
+            InterfaceMessage::AccountRestored => {
+                write!(
+                    f,
+                    "Cuenta restaurada. Se están recorriendo los bloques guardados para recuperar sus movimientos."
+                )
+            }
+            InterfaceMessage::WalletUnlocked(segundos) => {
+                write!(
+                    f,
+                    "Wallet desbloqueada. Se vuelve a bloquear sola en {} segundos.",
+                    segundos
+                )
+            }
+            InterfaceMessage::WalletLocked => {
+                write!(f, "Wallet bloqueada.")
+            }
+            InterfaceMessage::PassphraseChanged => {
+                write!(
+                    f,
+                    "Las claves de la wallet quedaron cifradas con la passphrase nueva. La wallet está bloqueada."
+                )
+            }
+            InterfaceMessage::WatchOnlyAccountCreated => {
+                write!(
+                    f,
+                    "Cuenta de solo lectura creada. Se están recorriendo los bloques guardados para recuperar sus movimientos."
+                )
+            }
+            InterfaceMessage::UnsignedTxExported(path) => {
+                write!(
+                    f,
+                    "La cuenta es de solo lectura, la transaccion no se envió. Se guardó sin firmar en {} para firmarla fuera del nodo.",
+                    path
+                )
+            }
+            InterfaceMessage::MultisigAccountCreated(address) => {
+                write!(
+                    f,
+                    "Cuenta multisig creada con la dirección {}. Se están recorriendo los bloques guardados para recuperar sus movimientos.",
+                    address
+                )
+            }
+            InterfaceMessage::PartialTxExported(path) => {
+                write!(
+                    f,
+                    "Faltan firmas de otros participantes, la transaccion no se envió. Se guardó firmada en parte en {} para que la firmen.",
+                    path
+                )
+            }
+            InterfaceMessage::PsbtExported(path) => {
+                write!(
+                    f,
+                    "PSBT guardada en base64 en {}. Todavía le faltan firmas para enviarla.",
+                    path
+                )
+            }
+            InterfaceMessage::UnencryptedWallet => {
+                write!(
+                    f,
+                    "ATENCIÓN: la wallet no tiene passphrase y las claves privadas se guardan sin cifrar. Configure una passphrase para cifrarlas."
+                )
+            }
         }
     }
 }
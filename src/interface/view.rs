@@ -5,13 +5,13 @@ use gtk::{
     Box, Builder, Button, Dialog, Entry, Label, MenuItem, ProgressBar, ResponseType, Spinner,
     TreeView, Window,
 };
-use gtk::{CellRendererText, ComboBox, ListStore};
+use gtk::{CellRendererText, CheckButton, ComboBox, ComboBoxText, ListStore};
 use std::sync::{Arc, Mutex};
 
 use crate::{app_manager::ApplicationManager, config};
 use crate::{common::utils_timestamp::timestamp_to_datetime, wallet::user::Account};
 use crate::{
-    errores::{InterfaceError, InterfaceMessage},
+    errores::{InterfaceError, InterfaceMessage, NodoBitcoinError},
     wallet::uxto_set::TxReport,
 };
 
@@ -32,6 +32,7 @@ pub enum ViewObject {
     UpdateButtonPoiStatus(String),
     UploadProgressBar((usize, usize, usize)),
     PoiResponse(Vec<(Uint256, ProofOrder)>),
+    UploadFeeEstimate(u64),
 }
 
 pub struct ViewObjectData {
@@ -139,6 +140,11 @@ pub fn create_view() -> Sender<ViewObject> {
                 //open_message_dialog(false, &builder_receiver_clone, message);
             }
             ViewObject::UpdateButtonPoiStatus(tx_id) => {
+                for id in ["bump_fee", "cpfp"] {
+                    if let Some(button) = builder_receiver_clone.object::<Button>(id) {
+                        button.set_sensitive(!tx_id.is_empty());
+                    }
+                }
                 if let Some(button) = builder_receiver_clone.object::<Button>("poi") {
                     if !tx_id.is_empty() {
                         button.set_sensitive(true);
@@ -192,6 +198,15 @@ pub fn create_view() -> Sender<ViewObject> {
                     .join("\n");
                 open_path_dialog(&builder_receiver_clone, concatenated_text);
             }
+            ViewObject::UploadFeeEstimate(sat_por_vbyte) => {
+                if let Some(entry) = builder_receiver_clone.object::<Entry>("transaction_fee") {
+                    entry.set_text(&sat_por_vbyte.to_string());
+                }
+                if let Some(label) = builder_receiver_clone.object::<Label>("transaction_fee_unit")
+                {
+                    label.set_text("sat/vB.");
+                }
+            }
         }
         glib::Continue(true)
     });
@@ -215,11 +230,38 @@ pub fn create_view() -> Sender<ViewObject> {
         builder.clone(),
     );
 
+    let manager_fee_preset: Arc<Mutex<ApplicationManager>> = app_manager_mutex.clone();
+    handle_fee_preset(manager_fee_preset, builder.clone());
+
+    let manager_new_address: Arc<Mutex<ApplicationManager>> = app_manager_mutex.clone();
+    handle_new_address(manager_new_address, builder.clone());
+
     handle_modal_about(builder.clone());
 
+    let manager_wallet_menu: Arc<Mutex<ApplicationManager>> = app_manager_mutex.clone();
+    handle_menu_wallet(manager_wallet_menu, builder.clone());
+
     let sender_row_transaction_clone = sender.clone();
     handle_row_transaction_selected(sender_row_transaction_clone, builder.clone());
 
+    let manager_bump_fee: Arc<Mutex<ApplicationManager>> = app_manager_mutex.clone();
+    handle_fee_dialog(
+        manager_bump_fee,
+        builder.clone(),
+        shared_tx.clone(),
+        ("bump_fee_dialog", "bump_fee", "tx_bump_fee", "new_fee"),
+        ApplicationManager::bump_fee,
+    );
+
+    let manager_cpfp: Arc<Mutex<ApplicationManager>> = app_manager_mutex.clone();
+    handle_fee_dialog(
+        manager_cpfp,
+        builder.clone(),
+        shared_tx.clone(),
+        ("cpfp_dialog", "cpfp", "tx_cpfp", "cpfp_fee"),
+        ApplicationManager::cpfp,
+    );
+
     let manager_poi: Arc<Mutex<ApplicationManager>> = app_manager_mutex;
     let shared_tx_handler = shared_tx;
     handle_poi(manager_poi, builder, shared_tx_handler);
@@ -331,6 +373,63 @@ fn open_poi_dialog(
     dialog.run();
 }
 
+/// Acción que se hace con el tx id y el fee ingresados en un diálogo de fee
+type FeeDialogAction = fn(&ApplicationManager, String, String) -> Result<(), NodoBitcoinError>;
+
+/// Conecta el botón que abre el diálogo de fee con el tx id seleccionado en la tabla.
+/// La respuesta del diálogo se conecta una sola vez.
+fn handle_fee_dialog(
+    app_manager: Arc<Mutex<ApplicationManager>>,
+    builder: Builder,
+    shared_tx: Arc<Mutex<ViewObjectData>>,
+    ids: (&str, &str, &str, &str),
+    action: FeeDialogAction,
+) {
+    let (dialog_id, button_id, tx_entry_id, fee_entry_id) = ids;
+    let dialog = match builder.object::<Dialog>(dialog_id) {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let tx_entry = match builder.object::<Entry>(tx_entry_id) {
+        Some(entry) => entry,
+        None => return,
+    };
+    let fee_entry = match builder.object::<Entry>(fee_entry_id) {
+        Some(entry) => entry,
+        None => return,
+    };
+
+    let tx_entry_response = tx_entry.clone();
+    dialog.connect_response(move |dialog, response_id| {
+        if response_id == ResponseType::Ok {
+            let tx = tx_entry_response.text().to_string();
+            let fee = fee_entry.text().to_string();
+            if !tx.is_empty() && !fee.is_empty() {
+                let app_manager_thread = match app_manager.lock() {
+                    Ok(res) => res,
+                    Err(_) => return,
+                };
+                let _ = action(&app_manager_thread, tx, fee);
+                drop(app_manager_thread);
+            }
+        }
+        fee_entry.set_text("");
+        dialog.hide();
+    });
+
+    if let Some(button) = builder.object::<Button>(button_id) {
+        button.connect_clicked(move |_| {
+            let tx_id = match shared_tx.lock() {
+                Ok(shared_tx_guard) => shared_tx_guard.text.clone(),
+                Err(_) => return,
+            };
+            tx_entry.set_text(&tx_id);
+            dialog.show_all();
+            dialog.run();
+        });
+    }
+}
+
 fn open_path_dialog(builder: &Builder, path: String) {
     let label;
     if let Some(res) = builder.object::<Label>("path") {
@@ -371,6 +470,8 @@ fn handle_modal_wallet(
     manager_open_modal_wallet: Arc<Mutex<ApplicationManager>>,
     builder: Builder,
 ) {
+    handle_generate_mnemonic(manager_open_modal_wallet.clone(), builder.clone());
+    handle_generate_key(manager_open_modal_wallet.clone(), builder.clone());
     if let Some(dialog) = builder.object::<Dialog>("wallet_dialog") {
         let dialog_clone = dialog;
         if let Some(new_wallet_button) = builder.object::<Button>("new_wallet_button") {
@@ -381,6 +482,71 @@ fn handle_modal_wallet(
     }
 }
 
+/// Genera las palabras de una wallet nueva, las muestra una vez para que se anoten y las deja
+/// en el campo de la clave para crear la cuenta al guardar
+fn handle_generate_mnemonic(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    if let Some(generate_button) = builder.object::<Button>("generate_mnemonic") {
+        generate_button.connect_clicked(move |_| {
+            let words = match builder.object::<ComboBoxText>("mnemonic_length") {
+                Some(combo) => combo.active_id().map(|id| id.to_string()),
+                None => None,
+            };
+            let app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let mnemonic =
+                app_manager_thread.generate_mnemonic(words.unwrap_or_else(|| "12".to_string()));
+            drop(app_manager_thread);
+            if let Ok(mnemonic) = mnemonic {
+                if let Some(key_entry) = builder.object::<Entry>("key") {
+                    key_entry.set_text(&mnemonic);
+                }
+                open_message_dialog(
+                    false,
+                    &builder,
+                    InterfaceMessage::NewMnemonic(mnemonic).to_string(),
+                );
+            }
+        });
+    }
+}
+
+/// Genera una clave privada nueva en el nodo y registra la cuenta con el nombre ingresado,
+/// con dirección P2WPKH si está marcada la opción segwit
+fn handle_generate_key(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    if let Some(generate_button) = builder.object::<Button>("generate_key") {
+        generate_button.connect_clicked(move |_| {
+            let name_entry = match builder.object::<Entry>("name") {
+                Some(entry) => entry,
+                None => return,
+            };
+            let name = name_entry.text().to_string();
+            if name.is_empty() {
+                open_message_dialog(true, &builder, InterfaceError::EmptyFields.to_string());
+                return;
+            }
+            let segwit = builder
+                .object::<CheckButton>("generate_segwit")
+                .map(|check| check.is_active())
+                .unwrap_or(false);
+            let mut app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let account = app_manager_thread.generate_account(name, segwit);
+            drop(app_manager_thread);
+            if let Ok(account) = account {
+                add_wallet_combobox(&builder, &account);
+                name_entry.set_text("");
+                if let Some(dialog) = builder.object::<Dialog>("wallet_dialog") {
+                    dialog.hide();
+                }
+            }
+        });
+    }
+}
+
 fn handle_combobox(
     manager_change_wallet: Arc<Mutex<ApplicationManager>>,
     sender: Sender<ViewObject>,
@@ -420,6 +586,48 @@ fn handle_transaction(
     }
 }
 
+/// Al elegir un preset pide el fee estimado en sat/vB para confirmarse en esa cantidad de
+/// bloques. Con el preset manual se vuelve a ingresar el fee total en BTC
+fn handle_fee_preset(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    if let Some(fee_preset) = builder.object::<ComboBoxText>("fee_preset") {
+        fee_preset.connect_changed(move |fee_preset| {
+            let target_blocks = match fee_preset.active_id() {
+                Some(id) if id.as_str() != "manual" => id.to_string(),
+                _ => {
+                    if let Some(entry) = builder.object::<Entry>("transaction_fee") {
+                        entry.set_text("");
+                    }
+                    if let Some(label) = builder.object::<Label>("transaction_fee_unit") {
+                        label.set_text("BTC.");
+                    }
+                    return;
+                }
+            };
+            let app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let _ = &app_manager_thread.estimate_fee(target_blocks);
+            drop(app_manager_thread);
+        });
+    }
+}
+
+/// Pide una dirección de recepción sin usar de la cuenta seleccionada.
+/// La respuesta se muestra en un mensaje
+fn handle_new_address(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    if let Some(new_address_button) = builder.object::<Button>("new_address_button") {
+        new_address_button.connect_clicked(move |_| {
+            let app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let _ = &app_manager_thread.new_receive_address();
+            drop(app_manager_thread);
+        });
+    }
+}
+
 fn handle_modal_about(builder: Builder) {
     if let Some(about_item_menu) = builder.object::<MenuItem>("about_item_menu") {
         about_item_menu.connect_activate(move |_| {
@@ -437,6 +645,261 @@ fn handle_modal_about(builder: Builder) {
     }
 }
 
+/// Conecta las opciones del menú Wallet para desbloquear, bloquear y cambiar la passphrase,
+/// crear cuentas multisig, firmar sus transacciones parciales y manejar PSBTs
+fn handle_menu_wallet(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    handle_unlock_dialog(app_manager.clone(), builder.clone());
+    handle_passphrase_dialog(app_manager.clone(), builder.clone());
+    handle_multisig_dialog(app_manager.clone(), builder.clone());
+    handle_sign_tx_dialog(app_manager.clone(), builder.clone());
+    handle_psbt_dialog(app_manager.clone(), builder.clone());
+    if let Some(lock_item_menu) = builder.object::<MenuItem>("lock_item_menu") {
+        lock_item_menu.connect_activate(move |_| {
+            let app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let _ = app_manager_thread.lock_wallet();
+            drop(app_manager_thread);
+        });
+    }
+}
+
+fn handle_unlock_dialog(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    let dialog = match builder.object::<Dialog>("unlock_dialog") {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let passphrase_entry = match builder.object::<Entry>("unlock_passphrase") {
+        Some(entry) => entry,
+        None => return,
+    };
+
+    dialog.connect_response(move |dialog, response_id| {
+        if response_id == ResponseType::Ok {
+            let passphrase = passphrase_entry.text().to_string();
+            if !passphrase.is_empty() {
+                let app_manager_thread = match app_manager.lock() {
+                    Ok(res) => res,
+                    Err(_) => return,
+                };
+                let _ = app_manager_thread.unlock_wallet(passphrase);
+                drop(app_manager_thread);
+            }
+        }
+        passphrase_entry.set_text("");
+        dialog.hide();
+    });
+
+    if let Some(unlock_item_menu) = builder.object::<MenuItem>("unlock_item_menu") {
+        unlock_item_menu.connect_activate(move |_| {
+            dialog.show_all();
+            dialog.run();
+        });
+    }
+}
+
+/// La primera vez cifra las claves de la wallet, y después cambia la passphrase pidiendo la
+/// actual
+fn handle_passphrase_dialog(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    let dialog = match builder.object::<Dialog>("passphrase_dialog") {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let mut entries = vec![];
+    for id in ["current_passphrase", "new_passphrase", "confirm_passphrase"] {
+        match builder.object::<Entry>(id) {
+            Some(entry) => entries.push(entry),
+            None => return,
+        }
+    }
+
+    dialog.connect_response(move |dialog, response_id| {
+        let texts: Vec<String> = entries
+            .iter()
+            .map(|entry| entry.text().to_string())
+            .collect();
+        for entry in entries.iter() {
+            entry.set_text("");
+        }
+        dialog.hide();
+        if response_id == ResponseType::Ok {
+            let app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let _ = app_manager_thread.change_passphrase(
+                texts[0].clone(),
+                texts[1].clone(),
+                texts[2].clone(),
+            );
+            drop(app_manager_thread);
+        }
+    });
+
+    if let Some(passphrase_item_menu) = builder.object::<MenuItem>("passphrase_item_menu") {
+        passphrase_item_menu.connect_activate(move |_| {
+            dialog.show_all();
+            dialog.run();
+        });
+    }
+}
+
+/// Crea una cuenta multisig con las firmas requeridas, las claves públicas de los
+/// participantes y opcionalmente la clave privada propia
+fn handle_multisig_dialog(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    let dialog = match builder.object::<Dialog>("multisig_dialog") {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let mut entries = vec![];
+    for id in [
+        "multisig_name",
+        "multisig_required",
+        "multisig_public_keys",
+        "multisig_key",
+    ] {
+        match builder.object::<Entry>(id) {
+            Some(entry) => entries.push(entry),
+            None => return,
+        }
+    }
+    let segwit_check = match builder.object::<CheckButton>("multisig_segwit") {
+        Some(check) => check,
+        None => return,
+    };
+
+    let builder_clone = builder.clone();
+    dialog.connect_response(move |dialog, response_id| {
+        let texts: Vec<String> = entries
+            .iter()
+            .map(|entry| entry.text().to_string())
+            .collect();
+        for entry in entries.iter() {
+            entry.set_text("");
+        }
+        let segwit = segwit_check.is_active();
+        segwit_check.set_active(false);
+        dialog.hide();
+        if response_id == ResponseType::Ok && !texts[0].is_empty() {
+            let mut app_manager_thread = match app_manager.lock() {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            let account = app_manager_thread.create_multisig_account(
+                texts[1].clone(),
+                texts[2].clone(),
+                texts[3].clone(),
+                segwit,
+                texts[0].clone(),
+            );
+            if let Ok(account) = account {
+                add_wallet_combobox(&builder_clone, &account);
+            }
+            drop(app_manager_thread);
+        }
+    });
+
+    if let Some(multisig_item_menu) = builder.object::<MenuItem>("multisig_item_menu") {
+        multisig_item_menu.connect_activate(move |_| {
+            dialog.show_all();
+            dialog.run();
+        });
+    }
+}
+
+/// Agrega la firma de la cuenta seleccionada a una transacción multisig exportada por otro
+/// participante
+fn handle_sign_tx_dialog(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    let dialog = match builder.object::<Dialog>("sign_tx_dialog") {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let path_entry = match builder.object::<Entry>("partial_tx_path") {
+        Some(entry) => entry,
+        None => return,
+    };
+
+    dialog.connect_response(move |dialog, response_id| {
+        if response_id == ResponseType::Ok {
+            let path = path_entry.text().to_string();
+            if !path.is_empty() {
+                let app_manager_thread = match app_manager.lock() {
+                    Ok(res) => res,
+                    Err(_) => return,
+                };
+                let _ = app_manager_thread.sign_partial_tx(path);
+                drop(app_manager_thread);
+            }
+        }
+        path_entry.set_text("");
+        dialog.hide();
+    });
+
+    if let Some(sign_tx_item_menu) = builder.object::<MenuItem>("sign_tx_item_menu") {
+        sign_tx_item_menu.connect_activate(move |_| {
+            dialog.show_all();
+            dialog.run();
+        });
+    }
+}
+
+// respuestas de los botones del diálogo de PSBT, que están en el window.glade
+const RESPUESTA_CREAR_PSBT: u16 = 1;
+const RESPUESTA_FIRMAR_PSBT: u16 = 2;
+const RESPUESTA_COMBINAR_PSBT: u16 = 3;
+
+/// Diálogo para armar una PSBT desde la cuenta seleccionada, firmar una PSBT guardada y
+/// combinar las firmadas por varios participantes. Las que quedan completas se envían
+fn handle_psbt_dialog(app_manager: Arc<Mutex<ApplicationManager>>, builder: Builder) {
+    let dialog = match builder.object::<Dialog>("psbt_dialog") {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let mut entries = vec![];
+    for id in ["psbt_recipients", "psbt_fee", "psbt_files"] {
+        match builder.object::<Entry>(id) {
+            Some(entry) => entries.push(entry),
+            None => return,
+        }
+    }
+
+    dialog.connect_response(move |dialog, response_id| {
+        let texts: Vec<String> = entries
+            .iter()
+            .map(|entry| entry.text().to_string())
+            .collect();
+        for entry in entries.iter() {
+            entry.set_text("");
+        }
+        dialog.hide();
+        let app_manager_thread = match app_manager.lock() {
+            Ok(res) => res,
+            Err(_) => return,
+        };
+        let _ = match response_id {
+            ResponseType::Other(RESPUESTA_CREAR_PSBT) => {
+                app_manager_thread.create_psbt(texts[0].clone(), texts[1].clone())
+            }
+            ResponseType::Other(RESPUESTA_FIRMAR_PSBT) => {
+                app_manager_thread.sign_psbt(texts[2].clone())
+            }
+            ResponseType::Other(RESPUESTA_COMBINAR_PSBT) => {
+                app_manager_thread.combine_psbts(texts[2].clone())
+            }
+            _ => Ok(()),
+        };
+        drop(app_manager_thread);
+    });
+
+    if let Some(psbt_item_menu) = builder.object::<MenuItem>("psbt_item_menu") {
+        psbt_item_menu.connect_activate(move |_| {
+            dialog.show_all();
+            dialog.run();
+        });
+    }
+}
+
 fn send_transaction(
     app_manager: Arc<Mutex<ApplicationManager>>,
     builder: Builder,
@@ -466,6 +929,13 @@ fn send_transaction(
     let to_address = to_address_entry.text().to_string();
     let transaction_amount = transaction_amount_entry.text().to_string();
     let transaction_fee = transaction_fee_entry.text().to_string();
+    // con un preset el fee del campo es el estimado en sat/vB
+    let fee_por_vbyte = match builder.object::<ComboBoxText>("fee_preset") {
+        Some(fee_preset) => fee_preset
+            .active_id()
+            .is_some_and(|id| id.as_str() != "manual"),
+        None => false,
+    };
     if to_address.is_empty() || transaction_amount.is_empty() || transaction_fee.is_empty() {
         let _ = sender.send(ViewObject::Error(InterfaceError::EmptyFields));
     } else {
@@ -473,11 +943,18 @@ fn send_transaction(
             Ok(res) => res,
             Err(_) => return,
         };
-        let _ =
-            &app_manager_thread.send_transaction(to_address, transaction_amount, transaction_fee);
+        let _ = &app_manager_thread.send_transaction(
+            to_address,
+            transaction_amount,
+            transaction_fee,
+            fee_por_vbyte,
+        );
         to_address_entry.set_text("");
         transaction_amount_entry.set_text("");
         transaction_fee_entry.set_text("");
+        if let Some(fee_preset) = builder.object::<ComboBoxText>("fee_preset") {
+            fee_preset.set_active_id(Some("manual"));
+        }
         drop(app_manager_thread);
     }
 }
@@ -537,6 +1014,13 @@ fn open_wallet_dialog(
         return;
     }
 
+    let passphrase_entry: Entry;
+    if let Some(res) = builder.object::<Entry>("passphrase") {
+        passphrase_entry = res;
+    } else {
+        return;
+    }
+
     let builder_clone = builder.clone();
 
     dialog.connect_response(move |dialog, response_id| {
@@ -545,13 +1029,28 @@ fn open_wallet_dialog(
                 let key = key_entry.text().to_string();
                 let address = address_entry.text().to_string();
                 let name = name_entry.text().to_string();
-                if !key.is_empty() && !address.is_empty() && !name.is_empty() {
+                let passphrase = passphrase_entry.text().to_string();
+                // las cuentas HD se crean solo con la clave extendida o con las palabras
+                let es_mnemonico = key.split_whitespace().count() > 1;
+                let es_hd = es_mnemonico || key.starts_with("xprv") || key.starts_with("tprv");
+                // sin clave la cuenta es de solo lectura, con la dirección o la clave pública
+                let solo_lectura = key.is_empty() && !address.is_empty();
+                let completa = solo_lectura || (!key.is_empty() && (es_hd || !address.is_empty()));
+                if completa && !name.is_empty() {
                     let mut app_manager_thread = match app_manager.lock() {
                         Ok(res) => res,
                         Err(_) => return,
                     };
-                    let account = app_manager_thread.create_account(key, address, name);
-                    add_wallet_combobox(&builder_clone, &account);
+                    let account = if solo_lectura {
+                        app_manager_thread.create_watch_only_account(address, name)
+                    } else if es_mnemonico {
+                        app_manager_thread.restore_account(key, passphrase, name)
+                    } else {
+                        app_manager_thread.create_account(key, address, name)
+                    };
+                    if let Ok(account) = account {
+                        add_wallet_combobox(&builder_clone, &account);
+                    }
                     drop(app_manager_thread);
                 }
             }
@@ -560,6 +1059,7 @@ fn open_wallet_dialog(
         key_entry.set_text("");
         address_entry.set_text("");
         name_entry.set_text("");
+        passphrase_entry.set_text("");
 
         dialog.hide();
     });
@@ -573,7 +1073,8 @@ fn create_combobox_wallet_list(builder: &Builder, app_manager: Arc<Mutex<Applica
         Ok(res) => res,
         Err(_) => return,
     };
-    let accounts = &app_manager_thread.accounts;
+    let account_names = app_manager_thread.account_names();
+    drop(app_manager_thread);
 
     let combobox_wallet: ComboBox;
     if let Some(res) = builder.object::<ComboBox>("combobox_wallet") {
@@ -589,8 +1090,8 @@ fn create_combobox_wallet_list(builder: &Builder, app_manager: Arc<Mutex<Applica
         return;
     };
 
-    for account in accounts {
-        let name = &account.wallet_name as &dyn ToValue;
+    for account_name in account_names {
+        let name = &account_name as &dyn ToValue;
         list_store.insert_with_values(None, &[(0, name)]);
     }
     list_store.insert_with_values(Some(0_u32), &[(0, &"None".to_string() as &dyn ToValue)]);
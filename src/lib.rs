@@ -9,4 +9,5 @@ pub mod merkle_tree;
 pub mod messages;
 pub mod parse_args;
 pub mod protocol;
+pub mod storage;
 pub mod wallet;
@@ -9,12 +9,17 @@ mod merkle_tree;
 mod messages;
 mod parse_args;
 mod protocol;
+mod storage;
 mod wallet;
 
 use std::{env, println};
 
+use crate::blockchain::file::migrar_bloques;
+use crate::blockchain::reindex::reindexar;
 use crate::blockchain::transaction::{Transaction, TxIn, TxOut};
+use crate::blockchain::verify_chain::verificar_cadena;
 use crate::common::uint256::Uint256;
+use crate::parse_args::{parse_modo, ModoEjecucion};
 use crate::protocol::send_tx::_mock_tx_obj;
 use crate::protocol::send_tx::send_tx;
 use crate::{log::create_logger_actor, protocol::connection::connect};
@@ -23,18 +28,47 @@ use interface::view::{self};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let modo = match parse_modo(&args) {
+        Ok(modo) => modo,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
     match config::inicializar(args) {
-        Ok(_) => match gtk::init() {
-            Ok(_) => {
-                _ = view::create_view();
-                gtk::main();
-            }
-            Err(_) => println!("No se pudo inicializar GTK."),
+        Ok(_) => match modo {
+            ModoEjecucion::Interfaz => iniciar_interfaz(),
+            ModoEjecucion::MigrarBloques => match migrar_bloques() {
+                Ok(cantidad) => println!("Se migraron {} bloques al nuevo formato.", cantidad),
+                Err(e) => println!("{}", e),
+            },
+            ModoEjecucion::Reindexar => match reindexar() {
+                Ok(resumen) => println!(
+                    "Se reindexaron {} headers, {} bloques en los índices opcionales y {} bloques en las UTXOs.",
+                    resumen.headers_indexados, resumen.bloques_indexados, resumen.bloques_aplicados
+                ),
+                Err(e) => println!("{}", e),
+            },
+            ModoEjecucion::VerificarCadena(cantidad) => match verificar_cadena(cantidad) {
+                Ok(None) => println!("Los últimos {} bloques son válidos.", cantidad),
+                Ok(Some(bloque_invalido)) => println!("{}", bloque_invalido),
+                Err(e) => println!("{}", e),
+            },
         },
         Err(e) => println!("{}", e),
     }
 }
 
+fn iniciar_interfaz() {
+    match gtk::init() {
+        Ok(_) => {
+            _ = view::create_view();
+            gtk::main();
+        }
+        Err(_) => println!("No se pudo inicializar GTK."),
+    }
+}
+
 fn _send_tx_main() {
     let args: Vec<String> = env::args().collect();
     let do_steps = || -> Result<(), NodoBitcoinError> {
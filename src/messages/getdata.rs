@@ -1,8 +1,11 @@
 use super::messages_header::make_header;
-use crate::{common::utils_bytes::parse_varint, errores::NodoBitcoinError};
+use crate::{
+    common::utils_bytes::{encode_varint, parse_varint},
+    errores::NodoBitcoinError,
+};
 
-const MSG_BLOCK: u32 = 2;
-const MSG_TX: u32 = 1;
+pub const MSG_BLOCK: u32 = 2;
+pub const MSG_TX: u32 = 1;
 
 /// Representa un inventario del protocolo Bitcoin
 ///
@@ -21,7 +24,7 @@ pub struct Inventory {
 /// * count: cantidad de inventarios
 /// * inventory: vector de inventarios
 pub struct GetDataMessage {
-    count: u8,
+    count: usize,
     inventory: Vec<Inventory>,
 }
 
@@ -35,11 +38,28 @@ impl GetDataMessage {
         };
 
         GetDataMessage {
-            count,
+            count: count.into(),
             inventory: vec![inventory],
         }
     }
 
+    /// Crea un GetDataMessage para pedir las transacciones con los hashes recibidos,
+    /// por ejemplo los padres que le faltan a una transacción huérfana
+    pub fn new_for_txs(hashes: &[[u8; 32]]) -> GetDataMessage {
+        let inventory = hashes
+            .iter()
+            .map(|hash| Inventory {
+                inv_type: MSG_TX,
+                hash: hash.to_vec(),
+            })
+            .collect::<Vec<Inventory>>();
+
+        GetDataMessage {
+            count: inventory.len(),
+            inventory,
+        }
+    }
+
     /// Crea un GetDataMessage para cuando se recibe una transacción a partir del mensaje inv
     /// Devuelve un struct del mensaje GetDataMessage
     pub fn new_for_tx(inv_msg: &[u8]) -> Result<GetDataMessage, NodoBitcoinError> {
@@ -65,10 +85,7 @@ impl GetDataMessage {
             });
         }
 
-        Ok(GetDataMessage {
-            count: count as u8,
-            inventory,
-        })
+        Ok(GetDataMessage { count, inventory })
     }
 
     /// Devuelve los hashes de los inventarios
@@ -80,12 +97,65 @@ impl GetDataMessage {
         hashes
     }
 
+    /// Devuelve los hashes de los inventarios de tipo MSG_TX
+    pub fn get_hashes_de_transacciones(&self) -> Vec<Vec<u8>> {
+        self.inventory
+            .iter()
+            .filter(|inventory| inventory.inv_type == MSG_TX)
+            .map(|inventory| inventory.hash.clone())
+            .collect()
+    }
+
+    /// Devuelve los hashes de los inventarios de tipo MSG_BLOCK
+    pub fn get_hashes_de_bloques(&self) -> Vec<Vec<u8>> {
+        self.inventory
+            .iter()
+            .filter(|inventory| inventory.inv_type == MSG_BLOCK)
+            .map(|inventory| inventory.hash.clone())
+            .collect()
+    }
+
+    /// Devuelve los hashes de los inventarios de los tipos que no se sirven, como los de
+    /// testigos, bloques filtrados o compactos, que se responden con notfound
+    pub fn get_hashes_no_soportados(&self) -> Vec<Vec<u8>> {
+        self.inventory
+            .iter()
+            .filter(|inventory| inventory.inv_type != MSG_BLOCK && inventory.inv_type != MSG_TX)
+            .map(|inventory| inventory.hash.clone())
+            .collect()
+    }
+
+    /// Recibe los hashes pedidos que no se encontraron
+    /// Devuelve el mensaje notfound con sus inventarios, que tiene el mismo formato que getdata
+    pub fn make_notfound(&self, no_encontrados: &[Vec<u8>]) -> Result<Vec<u8>, NodoBitcoinError> {
+        let inventory = self
+            .inventory
+            .iter()
+            .filter(|inventory| no_encontrados.contains(&inventory.hash))
+            .collect::<Vec<&Inventory>>();
+
+        let mut payload = Vec::new();
+        let mut msg = Vec::new();
+
+        payload.extend_from_slice(&encode_varint(inventory.len()));
+        for inventory in inventory {
+            payload.extend_from_slice(&inventory.inv_type.to_le_bytes());
+            payload.extend_from_slice(&inventory.hash);
+        }
+
+        let header = make_header("notfound".to_string(), &payload)?;
+
+        msg.extend_from_slice(&header);
+        msg.extend_from_slice(&payload);
+        Ok(msg)
+    }
+
     /// Serializa el mensaje GetData y devuelve los bytes del mismo
     pub fn serialize(&self) -> Result<Vec<u8>, NodoBitcoinError> {
         let mut payload = Vec::new();
         let mut msg = Vec::new();
 
-        payload.extend_from_slice(&(self.count).to_le_bytes());
+        payload.extend_from_slice(&encode_varint(self.count));
         for inventory in &self.inventory {
             payload.extend_from_slice(&inventory.inv_type.to_le_bytes());
             payload.extend_from_slice(&inventory.hash);
@@ -118,10 +188,7 @@ impl GetDataMessage {
             inventory.push(Inventory { inv_type, hash });
         }
 
-        Ok(GetDataMessage {
-            count: count as u8,
-            inventory,
-        })
+        Ok(GetDataMessage { count, inventory })
     }
 }
 
@@ -179,4 +246,64 @@ mod tests {
         assert_eq!(hashes.len(), 1);
         assert_eq!(hashes[0], hash_header.to_vec());
     }
+
+    #[test]
+    fn test_make_notfound() {
+        let mut get_data = GetDataMessage::new(2, [1; 32]);
+        get_data.inventory.push(super::Inventory {
+            inv_type: super::MSG_BLOCK,
+            hash: vec![2; 32],
+        });
+
+        let notfound = get_data.make_notfound(&[vec![2; 32]]).unwrap();
+        let (command, payload_len) = check_header(&notfound).unwrap();
+        assert_eq!(command, "notfound");
+        assert_eq!(payload_len, 37);
+
+        let deserealized = GetDataMessage::deserealize(&notfound[24..]).unwrap();
+        assert_eq!(deserealized.get_hashes(), vec![vec![2; 32]]);
+    }
+
+    #[test]
+    fn test_cantidad_de_inventarios_como_varint() {
+        // con más de 252 inventarios la cantidad ocupa 3 bytes
+        let hashes: Vec<[u8; 32]> = (0..300u16)
+            .map(|indice| {
+                let mut hash = [0; 32];
+                hash[..2].copy_from_slice(&indice.to_le_bytes());
+                hash
+            })
+            .collect();
+        let get_data = GetDataMessage::new_for_txs(&hashes);
+        let serializado = get_data.serialize().unwrap();
+        let (_, payload_len) = check_header(&serializado).unwrap();
+        assert_eq!(payload_len, 3 + 300 * 36);
+
+        let deserealized = GetDataMessage::deserealize(&serializado[24..]).unwrap();
+        assert_eq!(deserealized.count, 300);
+        assert_eq!(deserealized.get_hashes_de_transacciones().len(), 300);
+
+        let no_encontrados = deserealized.get_hashes();
+        let notfound = deserealized.make_notfound(&no_encontrados).unwrap();
+        let notfound = GetDataMessage::deserealize(&notfound[24..]).unwrap();
+        assert_eq!(notfound.get_hashes(), no_encontrados);
+    }
+
+    #[test]
+    fn test_hashes_por_tipo() {
+        let mut get_data = GetDataMessage::new(1, [1; 32]);
+        // MSG_WITNESS_TX y MSG_FILTERED_BLOCK no se tratan como bloques
+        for (inv_type, hash) in [(super::MSG_TX, 2), (0x40000001, 3), (3, 4)] {
+            get_data.inventory.push(super::Inventory {
+                inv_type,
+                hash: vec![hash; 32],
+            });
+        }
+        assert_eq!(get_data.get_hashes_de_bloques(), vec![vec![1; 32]]);
+        assert_eq!(get_data.get_hashes_de_transacciones(), vec![vec![2; 32]]);
+        assert_eq!(
+            get_data.get_hashes_no_soportados(),
+            vec![vec![3; 32], vec![4; 32]]
+        );
+    }
 }
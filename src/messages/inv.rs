@@ -0,0 +1,51 @@
+use super::getdata::MSG_TX;
+use super::messages_header::make_header;
+use crate::common::utils_bytes::encode_varint;
+use crate::errores::NodoBitcoinError;
+
+/// Cantidad máxima de inventarios que puede tener un mensaje inv según el protocolo
+pub const MAX_INVENTARIOS_POR_INV: usize = 50000;
+
+/// Recibe los hashes de las transacciones a anunciar
+/// Devuelve los mensajes inv con los inventarios del tipo MSG_TX,
+/// partidos en varios mensajes si superan el máximo por mensaje
+pub fn make_inv_tx(hashes: &[[u8; 32]]) -> Result<Vec<Vec<u8>>, NodoBitcoinError> {
+    let mut mensajes = vec![];
+    for hashes in hashes.chunks(MAX_INVENTARIOS_POR_INV) {
+        let mut payload = encode_varint(hashes.len());
+        for hash in hashes {
+            payload.extend_from_slice(&MSG_TX.to_le_bytes());
+            payload.extend_from_slice(hash);
+        }
+
+        let mut msg = make_header("inv".to_string(), &payload)?;
+        msg.extend_from_slice(&payload);
+        mensajes.push(msg);
+    }
+    Ok(mensajes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::getdata::GetDataMessage;
+    use crate::messages::messages_header::check_header;
+
+    #[test]
+    fn test_make_inv_tx() {
+        let mensajes = make_inv_tx(&[[1; 32], [2; 32]]).unwrap();
+        assert_eq!(mensajes.len(), 1);
+
+        let (command, payload_len) = check_header(&mensajes[0][..24]).unwrap();
+        assert_eq!(command, "inv");
+        assert_eq!(payload_len, 1 + 2 * 36);
+
+        // el inv tiene el mismo formato que el getdata
+        let inv = GetDataMessage::deserealize(&mensajes[0][24..]).unwrap();
+        assert_eq!(
+            inv.get_hashes_de_transacciones(),
+            vec![vec![1; 32], vec![2; 32]]
+        );
+        assert!(make_inv_tx(&[]).unwrap().is_empty());
+    }
+}
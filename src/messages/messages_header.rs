@@ -4,16 +4,18 @@ use std::str::from_utf8;
 use bitcoin_hashes::sha256d;
 use bitcoin_hashes::Hash;
 
+use crate::common::red::red_configurada;
 use crate::common::utils_bytes::string_to_bytes;
 use crate::errores::NodoBitcoinError;
 
-const MAGIC_NUMBER_TESTNET: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
+pub const MAGIC_NUMBER_TESTNET: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
+pub const MAGIC_NUMBER_MAINNET: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
 
 /// Crea los bytes del header de los mensajes
 /// Recibe el comando del mensaje al que corresponde el header y su payload
 pub fn make_header(command: String, payload: &Vec<u8>) -> Result<Vec<u8>, NodoBitcoinError> {
     let mut result = Vec::new();
-    let magic = MAGIC_NUMBER_TESTNET;
+    let magic = red_configurada().magic_number();
 
     let payload_size = payload.len() as u32;
     let hash = sha256d::Hash::hash(payload);
@@ -46,7 +48,7 @@ pub fn check_header(header: &[u8]) -> Result<(String, usize), NodoBitcoinError>
 
     let magic_num = &header[offset..offset + 4];
 
-    if magic_num != MAGIC_NUMBER_TESTNET {
+    if magic_num != red_configurada().magic_number() {
         return Err(NodoBitcoinError::MagicNumberIncorrecto);
     }
 
@@ -2,6 +2,7 @@ pub mod blocks;
 pub mod getdata;
 pub mod getheaders;
 pub mod headers;
+pub mod inv;
 pub mod messages_header;
 pub mod ping_pong;
 pub mod version;
@@ -40,6 +40,8 @@ pub struct VersionMessage {
 }
 
 const DEFAULT_SERVICES: u64 = 0;
+/// Servicio que anuncia un nodo que solo puede entregar los últimos bloques (BIP159)
+pub const NODE_NETWORK_LIMITED: u64 = 1 << 10;
 const DEFAULT_NONCE: u64 = 0;
 const DEFAULT_TRANS_PORT: u16 = 18333;
 const DEFAULT_TRANS_IP: &str = "192.168.0.66";
@@ -84,6 +86,12 @@ impl VersionMessage {
         }
     }
 
+    /// Cambia los servicios que anuncia el nodo transmisor
+    pub fn set_services(&mut self, services: u64) {
+        self.services = services;
+        self.addr_trans_services = services;
+    }
+
     /// Serializa un mensaje Version y devuelve los bytes del mismo
     pub fn serialize(&self) -> Result<Vec<u8>, NodoBitcoinError> {
         let mut payload = Vec::new();
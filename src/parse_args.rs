@@ -1,5 +1,15 @@
+use crate::blockchain::verify_chain::BLOQUES_A_VERIFICAR_DEFAULT;
 use crate::errores::NodoBitcoinError;
 
+/// Modos de ejecución del nodo. Se indican con un argumento opcional después del archivo de configuración
+#[derive(Debug, PartialEq)]
+pub enum ModoEjecucion {
+    Interfaz,
+    MigrarBloques,
+    Reindexar,
+    VerificarCadena(u64),
+}
+
 /// Parsea los argumentos del main
 pub fn parse_args(args: Vec<String>) -> Result<String, NodoBitcoinError> {
     if args.len() < 2 {
@@ -11,3 +21,61 @@ pub fn parse_args(args: Vec<String>) -> Result<String, NodoBitcoinError> {
         Ok(args[1].to_string())
     }
 }
+
+/// Parsea el modo de ejecución de los argumentos del main. Si no se indica se abre la interfaz
+pub fn parse_modo(args: &[String]) -> Result<ModoEjecucion, NodoBitcoinError> {
+    match args.get(2).map(|modo| modo.as_str()) {
+        None => Ok(ModoEjecucion::Interfaz),
+        Some("--migrar-bloques") => Ok(ModoEjecucion::MigrarBloques),
+        Some("--reindex") => Ok(ModoEjecucion::Reindexar),
+        Some("--verifychain") => match args.get(3) {
+            None => Ok(ModoEjecucion::VerificarCadena(BLOQUES_A_VERIFICAR_DEFAULT)),
+            Some(cantidad) => match cantidad.parse::<u64>() {
+                Ok(cantidad) if cantidad > 0 => Ok(ModoEjecucion::VerificarCadena(cantidad)),
+                _ => Err(NodoBitcoinError::CantidadDeBloquesInvalida),
+            },
+        },
+        Some(_) => Err(NodoBitcoinError::ModoDesconocido),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(valores: &[&str]) -> Vec<String> {
+        valores.iter().map(|valor| valor.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_modo() {
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf"])),
+            Ok(ModoEjecucion::Interfaz)
+        );
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf", "--migrar-bloques"])),
+            Ok(ModoEjecucion::MigrarBloques)
+        );
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf", "--reindex"])),
+            Ok(ModoEjecucion::Reindexar)
+        );
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf", "--verifychain"])),
+            Ok(ModoEjecucion::VerificarCadena(BLOQUES_A_VERIFICAR_DEFAULT))
+        );
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf", "--verifychain", "100"])),
+            Ok(ModoEjecucion::VerificarCadena(100))
+        );
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf", "--verifychain", "0"])),
+            Err(NodoBitcoinError::CantidadDeBloquesInvalida)
+        );
+        assert_eq!(
+            parse_modo(&args(&["app", "nodo.conf", "--otro"])),
+            Err(NodoBitcoinError::ModoDesconocido)
+        );
+    }
+}
@@ -135,6 +135,20 @@ pub fn init_block_broadcasting(
                     }
                 }
 
+                // transacciones que llegan sin inv, por ejemplo los padres pedidos de una huérfana
+                if command == "tx" {
+                    let tx = match Transaction::deserialize(&header) {
+                        Ok(tx) => tx,
+                        Err(_) => {
+                            log_error_message(thread_logger.clone(), format!("No se pudo deserializar la transacción recibida en conexión {}.", socket.id));
+                            continue;
+                        }
+                    };
+                    if thread_sender_tx_manager.send(TransactionMessages::NewTx(tx, Some(socket.id))).is_err() {
+                        continue;
+                    };
+                }
+
                 if command == "inv" {
                     log_info_message(thread_logger.clone(), format!("Mensaje inv recibido en conexión {}", socket.id));
                     let get_data = match GetDataMessage::new_for_tx(&header) {
@@ -207,7 +221,7 @@ pub fn init_block_broadcasting(
                             }
                         };
 
-                        if thread_sender_tx_manager.send(TransactionMessages::NewTx(tx)).is_err(){
+                        if thread_sender_tx_manager.send(TransactionMessages::NewTx(tx, Some(socket.id))).is_err(){
                             continue;
                         };
                         log_info_message(thread_logger.clone(), "Nueva transacción enviada al manager".to_string());
@@ -1,10 +1,11 @@
 use super::admin_connections::AdminConnections;
+use crate::blockchain::prune::prune_habilitado;
 use crate::config;
 use crate::errores::NodoBitcoinError;
 use crate::log::{log_error_message, log_info_message, LogMessages};
 use crate::messages::messages_header::check_header;
 use crate::messages::messages_header::make_header;
-use crate::messages::version::VersionMessage;
+use crate::messages::version::{VersionMessage, NODE_NETWORK_LIMITED};
 use chrono::Utc;
 use std::io::Read;
 use std::io::Write;
@@ -79,7 +80,10 @@ pub fn handshake(
         Err(_) => return Err(NodoBitcoinError::NoSePuedeLeerValorDeArchivoConfig),
     };
 
-    let version_message = VersionMessage::new(version, timestamp, address);
+    let mut version_message = VersionMessage::new(version, timestamp, address);
+    if prune_habilitado() {
+        version_message.set_services(NODE_NETWORK_LIMITED);
+    }
     let mensaje = version_message.serialize()?;
     if socket.write_all(&mensaje).is_err() {
         return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes);
@@ -3,12 +3,14 @@ use super::connection::connect;
 use crate::app_manager::ApplicationManagerMessages;
 use crate::blockchain::block::SerializedBlock;
 use crate::blockchain::blockheader::BlockHeader;
-use crate::blockchain::file::get_blocks_filename;
+use crate::blockchain::file::get_blocks_dirname;
 use crate::blockchain::file::get_headers_filename;
 use crate::blockchain::file::{
     escribir_archivo, escribir_archivo_bloque, existe_archivo_headers, leer_ultimo_header,
 };
-use crate::blockchain::index::dump_hash_in_the_index;
+use crate::blockchain::index::{dump_hash_in_the_index, indexar_bloque_guardado};
+use crate::blockchain::journal::{actualizar_indices, Journal};
+use crate::common::red::red_configurada;
 use crate::common::utils_data::total_reintentos;
 use crate::common::utils_timestamp::{obtener_timestamp_dia, timestamp_to_datetime};
 use crate::config;
@@ -29,6 +31,11 @@ pub const GENESIS_BLOCK: [u8; 32] = [
     0xae, 0xc3, 0xce, 0xd9, 0x0f, 0xa3, 0xf4, 0x08, 0x71, 0x95, 0x26, 0xf8, 0xd7, 0x7f, 0x49, 0x43,
 ];
 
+pub const GENESIS_BLOCK_MAINNET: [u8; 32] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0xd6, 0x68, 0x9c, 0x08, 0x5a, 0xe1, 0x65, 0x83, 0x1e, 0x93,
+    0x4f, 0xf7, 0x63, 0xae, 0x46, 0xa2, 0xa6, 0xc1, 0x72, 0xb3, 0xf1, 0xb6, 0x0a, 0x8c, 0xe2, 0x6f,
+];
+
 pub fn _version() -> Result<u32, NodoBitcoinError> {
     let version = match (config::get_valor("VERSION".to_string())?).parse::<u32>() {
         Ok(res) => res,
@@ -45,7 +52,7 @@ fn start_block() -> Result<[u8; 32], NodoBitcoinError> {
             let header_serialized = BlockHeader::deserialize(&last_file_header)?;
             header_serialized.hash()?
         }
-        false => GENESIS_BLOCK,
+        false => red_configurada().bloque_genesis(),
     };
     Ok(start_block)
 }
@@ -115,7 +122,8 @@ fn read_bytes_header(
     }
 }
 
-fn get_timestamp_inicial() -> u32 {
+/// Devuelve el timestamp del día inicial del config desde el cual se descargan los bloques
+pub fn get_timestamp_inicial() -> u32 {
     let fecha_inicial_result = config::get_valor("DIA_INICIAL".to_string());
     if fecha_inicial_result.is_err() {
         return 0;
@@ -621,7 +629,6 @@ fn blocks_joined_guardar(
     blockheaders: Vec<BlockHeader>,
     intento: usize,
 ) -> Result<(), NodoBitcoinError> {
-
     let bloques_a_guardar = match blocks.lock() {
         Ok(mutex_guard) => mutex_guard.clone(),
         Err(_) => return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes),
@@ -649,30 +656,71 @@ fn blocks_joined_guardar(
     Ok(())
 }
 
+/// Guarda los bloques y después los headers dentro de un journal
+/// Si falla a la mitad deshace lo escrito y limpia los índices de lo descartado
 fn guardar_headers_y_bloques(
     logger: Sender<LogMessages>,
-    mut bloques_a_guardar: Vec<SerializedBlock>,
+    bloques_a_guardar: Vec<SerializedBlock>,
     blockheaders: Vec<BlockHeader>,
 ) -> Result<(), NodoBitcoinError> {
-    let headers_path = get_headers_filename()?;
-    let block_path = get_blocks_filename()?;
-    log_info_message(logger.clone(), "Guardando headers...".to_string());
-    for bh in blockheaders {
-        let bytes = bh.serialize()?;
-        let indice = escribir_archivo(headers_path.clone(), &bytes)? - 1;
-        if let Err(error) = dump_hash_in_the_index(headers_path.clone(), bh.hash()?, indice) {
-            println!("ERROR write_headers_and_block_file {}.", error);
+    let mut journal = Journal::iniciar_escritura()?;
+    let resultado = escribir_headers_y_bloques(
+        logger.clone(),
+        &journal,
+        bloques_a_guardar,
+        blockheaders,
+    )
+    .and_then(|_| journal.finalizar());
+    if let Err(error) = resultado {
+        if let Err(error) = journal
+            .deshacer()
+            .and_then(|reparaciones| actualizar_indices(&reparaciones, logger.clone()))
+        {
+            log_error_message(
+                logger,
+                format!("No se pudo deshacer la escritura incompleta: {}", error),
+            );
         }
+        return Err(error);
     }
-    log_info_message(logger.clone(), "Headers guardados".to_string());
+    log_info_message(logger, "Headers guardados".to_string());
+    Ok(())
+}
 
+fn escribir_headers_y_bloques(
+    logger: Sender<LogMessages>,
+    journal: &Journal,
+    mut bloques_a_guardar: Vec<SerializedBlock>,
+    blockheaders: Vec<BlockHeader>,
+) -> Result<(), NodoBitcoinError> {
+    let headers_path = get_headers_filename()?;
+    let blocks_dir = get_blocks_dirname()?;
+
+    // los bloques se guardan antes que los headers para que nunca quede un header sin su bloque
     if !bloques_a_guardar.is_empty() {
         log_info_message(logger.clone(), "Guardando bloques...".to_string());
         bloques_a_guardar.sort();
         for bloque in bloques_a_guardar {
-            escribir_archivo_bloque(block_path.clone(), &bloque.serialize()?)?;
+            let ubicacion_bloque =
+                escribir_archivo_bloque(blocks_dir.clone(), &bloque.serialize()?)?;
+            if let Err(error) = indexar_bloque_guardado(&bloque, ubicacion_bloque) {
+                log_error_message(
+                    logger.clone(),
+                    format!("No se pudo indexar el bloque: {}", error),
+                );
+            }
+        }
+        journal.sincronizar_bloques()?;
+        log_info_message(logger.clone(), "Bloques guardados".to_string());
+    }
+
+    log_info_message(logger, "Guardando headers...".to_string());
+    for bh in blockheaders {
+        let bytes = bh.serialize()?;
+        let indice = escribir_archivo(headers_path.clone(), &bytes)? - 1;
+        if let Err(error) = dump_hash_in_the_index(headers_path.clone(), bh.hash()?, indice) {
+            println!("ERROR write_headers_and_block_file {}.", error);
         }
-        log_info_message(logger, "Bloques guardados".to_string());
     }
     Ok(())
 }
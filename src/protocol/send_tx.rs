@@ -2,11 +2,12 @@ use super::admin_connections::AdminConnections;
 use crate::{
     blockchain::{
         block::SerializedBlock,
-        transaction::{create_tx_to_send, Transaction},
+        transaction::{create_tx_to_send, Fee, Transaction},
     },
+    common::{address::Address, uint256::Uint256},
     errores::NodoBitcoinError,
     log::{log_error_message, log_info_message, LogMessages},
-    messages::messages_header::make_header,
+    messages::{inv::make_inv_tx, messages_header::make_header},
     wallet::{user::Account, uxto_set::UTXOSet},
 };
 use std::sync::mpsc;
@@ -48,6 +49,25 @@ pub fn send_tx(
     Ok(())
 }
 
+/// Anuncia la transacción con un mensaje inv a todos los nodos a los que se envían transacciones
+pub fn send_inv_tx(
+    mut admin_connections: AdminConnections,
+    logger: mpsc::Sender<LogMessages>,
+    txid: Uint256,
+) -> Result<(), NodoBitcoinError> {
+    for inv_msg in make_inv_tx(&[txid.get_bytes()])? {
+        for connection in admin_connections.get_connections_for_send_tx() {
+            if let Err(error) = connection.write_message(&inv_msg) {
+                log_error_message(
+                    logger.clone(),
+                    format! {"Error al enviar el inv de la transacción a un peer: {}.", error},
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 /*
 Cuentas de prueba:
     - Public key: mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun
@@ -76,11 +96,18 @@ pub fn _mock_tx_obj() -> Result<Transaction, NodoBitcoinError> {
     let utxos_by_account = utxo_set.utxos_for_account;
     let utxos = utxos_by_account.get(&public_key).unwrap().clone();
 
-    let target_address = "mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a".to_string();
+    let target_address = Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a")?;
     let target_amount: u64 = 1700000;
 
     let fee: u64 = 71052;
 
-    let tx_obj = create_tx_to_send(account, target_address, target_amount, fee, utxos)?;
+    let tx_obj = create_tx_to_send(
+        account,
+        target_address,
+        target_amount,
+        Fee::Total(fee),
+        utxos,
+        vec![],
+    )?;
     Ok(tx_obj)
 }
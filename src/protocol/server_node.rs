@@ -13,7 +13,7 @@ use std::{
 use chrono::Utc;
 
 use crate::{
-    blockchain::block::SerializedBlock,
+    blockchain::{block::SerializedBlock, prune::prune_habilitado, transaction::Transaction},
     common::utils_bytes::ping_nonce,
     config,
     errores::NodoBitcoinError,
@@ -23,9 +23,10 @@ use crate::{
         getdata::GetDataMessage,
         getheaders::GetHeadersMessage,
         headers::make_headers_msg,
+        inv::make_inv_tx,
         messages_header::{check_header, make_header},
         ping_pong::{make_ping, make_pong},
-        version::VersionMessage,
+        version::{VersionMessage, NODE_NETWORK_LIMITED},
     },
     wallet::transaction_manager::TransactionMessages,
 };
@@ -34,6 +35,9 @@ const READ_TIMEOUT_SECONDS: u64 = 10;
 
 pub enum ServerNodeMessages {
     GetBlockResponse(Option<SerializedBlock>),
+    GetTxResponse(Option<Transaction>),
+    MempoolResponse(Vec<[u8; 32]>),
+    AnnounceTx([u8; 32]),
     ShutDown,
 }
 
@@ -172,7 +176,18 @@ fn server_run(
                     drop(senders_locked);
                     break;
                 }
-                ServerNodeMessages::GetBlockResponse(_) => {}
+                ServerNodeMessages::AnnounceTx(hash) => {
+                    let senders_locked = match senders_threads_mutex.lock() {
+                        Ok(senders_locked) => senders_locked,
+                        Err(_) => continue,
+                    };
+                    for sender_client in senders_locked.iter() {
+                        _ = sender_client.send(ServerNodeMessages::AnnounceTx(hash));
+                    }
+                }
+                ServerNodeMessages::GetBlockResponse(_)
+                | ServerNodeMessages::GetTxResponse(_)
+                | ServerNodeMessages::MempoolResponse(_) => {}
             }
         }
     }
@@ -235,7 +250,10 @@ fn shakehand(stream: &mut TcpStream, logger: Sender<LogMessages>) -> Result<(),
         }
     };
 
-    let version_message = VersionMessage::new(my_version, timestamp, client_address);
+    let mut version_message = VersionMessage::new(my_version, timestamp, client_address);
+    if prune_habilitado() {
+        version_message.set_services(NODE_NETWORK_LIMITED);
+    }
     let mensaje = version_message.serialize()?;
     if stream.write_all(&mensaje).is_err() {
         return Err(NodoBitcoinError::ErrorEnHandshake);
@@ -285,7 +303,7 @@ fn handle_message(
 }
 
 /// Escucha por mensajes del cliente y realiza las acciones 
-/// necesarias en caso de recibir mensaje getdata, headers o ping
+/// necesarias en caso de recibir mensaje getdata, headers, mempool o ping
 /// También envía el mensaje ping en caso de que pase un determinado 
 /// tiempo sin recibir mensajes de parte del cliente
 fn thread_connection(
@@ -322,7 +340,14 @@ fn thread_connection(
                     );
                     break;
                 }
-                ServerNodeMessages::GetBlockResponse(_) => {}
+                ServerNodeMessages::AnnounceTx(hash) => {
+                    if send_inv_tx(&[hash], stream, logger.clone()).is_err() {
+                        break;
+                    }
+                }
+                ServerNodeMessages::GetBlockResponse(_)
+                | ServerNodeMessages::GetTxResponse(_)
+                | ServerNodeMessages::MempoolResponse(_) => {}
             }
         }
 
@@ -385,7 +410,11 @@ fn thread_connection(
             }
         }
         if command == "getdata" {
-            _ = send_block(message, stream, logger.clone(), tx_sender.clone());
+            _ = send_data(message, stream, logger.clone(), tx_sender.clone());
+            continue;
+        }
+        if command == "mempool" {
+            _ = send_mempool(stream, logger.clone(), tx_sender.clone());
             continue;
         }
     }
@@ -397,56 +426,146 @@ fn thread_connection(
 
 /// Recibe un vector de hash de bloques
 /// Devuelve un vector con los bloques correspondientes a esos hashes
+/// y otro con los hashes de los bloques que no se tienen (por ejemplo, porque se borraron con prune)
 fn get_blocks_from_hashes(
     hashes: Vec<Vec<u8>>,
     tx_sender: Sender<TransactionMessages>,
-) -> Result<Vec<SerializedBlock>, NodoBitcoinError> {
+) -> Result<(Vec<SerializedBlock>, Vec<Vec<u8>>), NodoBitcoinError> {
     let mut blocks: Vec<SerializedBlock> = Vec::new();
+    let mut not_found: Vec<Vec<u8>> = Vec::new();
 
     let (sender, receiver) = channel();
     for hash in hashes {
-        _ = tx_sender.send(TransactionMessages::GetBlockRequest(hash, sender.clone()));
+        _ = tx_sender.send(TransactionMessages::GetBlockRequest(
+            hash.clone(),
+            sender.clone(),
+        ));
+
+        if let Ok(ServerNodeMessages::GetBlockResponse(block)) = receiver.recv() {
+            match block {
+                Some(block) => blocks.push(block),
+                None => not_found.push(hash),
+            }
+        }
+    }
+    Ok((blocks, not_found))
+}
 
-        if let Ok(message) = receiver.recv() {
-            match message {
-                ServerNodeMessages::ShutDown => {}
-                ServerNodeMessages::GetBlockResponse(block) => {
-                    if block.is_none() {
-                        continue;
-                    }
-                    blocks.push(block.unwrap());
-                }
+/// Recibe un vector de hash de transacciones
+/// Devuelve un vector con las transacciones de la mempool correspondientes a esos hashes
+/// y otro con los hashes de las que no se tienen
+fn get_txs_from_hashes(
+    hashes: Vec<Vec<u8>>,
+    tx_sender: Sender<TransactionMessages>,
+) -> Result<(Vec<Transaction>, Vec<Vec<u8>>), NodoBitcoinError> {
+    let mut txs: Vec<Transaction> = Vec::new();
+    let mut not_found: Vec<Vec<u8>> = Vec::new();
+
+    let (sender, receiver) = channel();
+    for hash in hashes {
+        _ = tx_sender.send(TransactionMessages::GetTxRequest(
+            hash.clone(),
+            sender.clone(),
+        ));
+
+        if let Ok(ServerNodeMessages::GetTxResponse(tx)) = receiver.recv() {
+            match tx {
+                Some(tx) => txs.push(tx),
+                None => not_found.push(hash),
             }
         }
     }
-    Ok(blocks)
+    Ok((txs, not_found))
 }
 
-/// Se encarga de interpretar el mensaje getdata, buscar los bloques a devolver
-/// y enviar el mensaje block con los bloques pedidos por el cliente
-fn send_block(
+/// Se encarga de interpretar el mensaje getdata, buscar los bloques y transacciones a devolver
+/// y enviar los mensajes block y tx con lo pedido por el cliente.
+/// Por los bloques y transacciones que no tiene responde con el mensaje notfound
+fn send_data(
     data_message: Vec<u8>,
     stream: &mut TcpStream,
     logger: Sender<LogMessages>,
     tx_sender: Sender<TransactionMessages>,
 ) -> Result<(), NodoBitcoinError> {
     let get_data_message = GetDataMessage::deserealize(&data_message)?;
-    let hashes = get_data_message.get_hashes();
 
-    let blocks = get_blocks_from_hashes(hashes, tx_sender)?;
+    let (blocks, mut not_found) =
+        get_blocks_from_hashes(get_data_message.get_hashes_de_bloques(), tx_sender.clone())?;
+    let (txs, txs_not_found) =
+        get_txs_from_hashes(get_data_message.get_hashes_de_transacciones(), tx_sender)?;
+    not_found.extend(txs_not_found);
+    not_found.extend(get_data_message.get_hashes_no_soportados());
+
+    if !blocks.is_empty() {
+        let mut blocks_bytes: Vec<u8> = Vec::new();
+        for block in blocks {
+            let block_bytes = block.serialize()?;
+            blocks_bytes.extend(block_bytes);
+        }
+
+        let block_message = make_block(&blocks_bytes)?;
 
-    let mut blocks_bytes: Vec<u8> = Vec::new();
-    for block in blocks {
-        let block_bytes = block.serialize()?;
-        blocks_bytes.extend(block_bytes);
+        if stream.write_all(&block_message).is_err() {
+            log_error_message(logger, "No se puede enviar el mensaje BLOCK".to_string());
+            return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes);
+        }
+    }
+
+    for tx in txs {
+        let payload = tx.serialize()?;
+        let mut tx_message = make_header("tx".to_string(), &payload)?;
+        tx_message.extend_from_slice(&payload);
+        if stream.write_all(&tx_message).is_err() {
+            log_error_message(logger, "No se puede enviar el mensaje TX".to_string());
+            return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes);
+        }
     }
 
-    let block_message = make_block(&blocks_bytes)?;
+    if !not_found.is_empty() {
+        let notfound_message = get_data_message.make_notfound(&not_found)?;
+        if stream.write_all(&notfound_message).is_err() {
+            log_error_message(logger, "No se puede enviar el mensaje NOTFOUND".to_string());
+            return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes);
+        }
+        log_info_message(
+            logger,
+            format!("NOTFOUND enviado por {} inventarios", not_found.len()),
+        );
+    }
+    Ok(())
+}
+
+/// Responde el mensaje mempool con los inv de las transacciones de la mempool
+fn send_mempool(
+    stream: &mut TcpStream,
+    logger: Sender<LogMessages>,
+    tx_sender: Sender<TransactionMessages>,
+) -> Result<(), NodoBitcoinError> {
+    let (sender, receiver) = channel();
+    _ = tx_sender.send(TransactionMessages::GetMempool(sender));
+    let txids = match receiver.recv() {
+        Ok(ServerNodeMessages::MempoolResponse(txids)) => txids,
+        _ => return Err(NodoBitcoinError::ErrorAlLeerSolicitudDelCliente),
+    };
+    send_inv_tx(&txids, stream, logger)
+}
 
-    if stream.write_all(&block_message).is_err() {
-        log_error_message(logger, "No se puede enviar el mensaje BLOCK".to_string());
-        return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes);
+/// Envía al cliente los mensajes inv con los hashes de las transacciones recibidas
+fn send_inv_tx(
+    hashes: &[[u8; 32]],
+    stream: &mut TcpStream,
+    logger: Sender<LogMessages>,
+) -> Result<(), NodoBitcoinError> {
+    for inv_message in make_inv_tx(hashes)? {
+        if stream.write_all(&inv_message).is_err() {
+            log_error_message(logger, "No se puede enviar el mensaje INV".to_string());
+            return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes);
+        }
     }
+    log_info_message(
+        logger,
+        format!("INV enviado por {} transacciones", hashes.len()),
+    );
     Ok(())
 }
 
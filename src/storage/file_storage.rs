@@ -0,0 +1,201 @@
+use super::Storage;
+use crate::blockchain::block_file::{
+    escribir_registro, leer_registro, leer_todos_los_registros, UbicacionBloque,
+};
+use crate::blockchain::file::{
+    escribir_archivo, get_blocks_dirname, get_file_size, get_headers_filename,
+    get_journal_filename, leer_bytes, tamanio_maximo_archivo_bloques,
+};
+use crate::blockchain::index::{dump_hash_in_the_index_en, get_start_index_en, HEADERS_INDEX_DIR};
+use crate::blockchain::journal::{Journal, Reparaciones};
+use crate::blockchain::prune::podar_bloques;
+use crate::errores::NodoBitcoinError;
+use std::fs;
+
+/// Directorio donde se guardan los archivos de estado de la wallet
+pub const WALLET_DIR: &str = ".";
+
+/// Almacenamiento en los archivos de headers, bloques e índices
+pub struct FileStorage {
+    headers_path: String,
+    blocks_dir: String,
+    headers_index_dir: String,
+    wallet_dir: String,
+    journal_path: String,
+    tamanio_maximo_archivo: u64,
+    journal: Option<Journal>,
+}
+
+impl FileStorage {
+    /// Crea el almacenamiento con los archivos recibidos
+    pub fn new(
+        headers_path: String,
+        blocks_dir: String,
+        headers_index_dir: String,
+        wallet_dir: String,
+        journal_path: String,
+    ) -> FileStorage {
+        FileStorage {
+            headers_path,
+            blocks_dir,
+            headers_index_dir,
+            wallet_dir,
+            journal_path,
+            tamanio_maximo_archivo: tamanio_maximo_archivo_bloques(),
+            journal: None,
+        }
+    }
+
+    /// Crea el almacenamiento con los archivos del config
+    pub fn desde_config() -> Result<FileStorage, NodoBitcoinError> {
+        Ok(FileStorage::new(
+            get_headers_filename()?,
+            get_blocks_dirname()?,
+            HEADERS_INDEX_DIR.to_string(),
+            WALLET_DIR.to_string(),
+            get_journal_filename(),
+        ))
+    }
+
+    fn wallet_path(&self, nombre: &str) -> String {
+        format!("{}/{}", self.wallet_dir, nombre)
+    }
+}
+
+impl Storage for FileStorage {
+    fn escribir_header(&mut self, bytes: &[u8]) -> Result<u64, NodoBitcoinError> {
+        Ok(escribir_archivo(self.headers_path.clone(), bytes)? - 1)
+    }
+
+    fn leer_headers(&self, offset: u64, largo: u64) -> Result<Vec<u8>, NodoBitcoinError> {
+        leer_bytes(self.headers_path.clone(), offset, largo)
+    }
+
+    fn tamanio_headers(&self) -> Result<u64, NodoBitcoinError> {
+        get_file_size(self.headers_path.clone())
+    }
+
+    fn escribir_bloque(&mut self, bytes: &[u8]) -> Result<UbicacionBloque, NodoBitcoinError> {
+        escribir_registro(&self.blocks_dir, bytes, self.tamanio_maximo_archivo)
+    }
+
+    fn leer_bloque(&self, ubicacion: UbicacionBloque) -> Result<Vec<u8>, NodoBitcoinError> {
+        let (bytes, _) = leer_registro(&self.blocks_dir, ubicacion)?;
+        Ok(bytes)
+    }
+
+    fn leer_todos_los_bloques(&self) -> Result<Vec<(UbicacionBloque, Vec<u8>)>, NodoBitcoinError> {
+        leer_todos_los_registros(&self.blocks_dir)
+    }
+
+    fn guardar_en_indice(&mut self, hash: [u8; 32], offset: u64) -> Result<(), NodoBitcoinError> {
+        dump_hash_in_the_index_en(&self.headers_index_dir, hash, offset)
+    }
+
+    fn buscar_en_indice(&self, hash: [u8; 32]) -> Result<u64, NodoBitcoinError> {
+        get_start_index_en(&self.headers_index_dir, hash)
+    }
+
+    fn guardar_estado_wallet(
+        &mut self,
+        nombre: &str,
+        bytes: &[u8],
+    ) -> Result<(), NodoBitcoinError> {
+        fs::write(self.wallet_path(nombre), bytes)
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)
+    }
+
+    fn leer_estado_wallet(&self, nombre: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+        fs::read(self.wallet_path(nombre)).map_err(|_| NodoBitcoinError::NoExisteArchivo)
+    }
+
+    fn iniciar_escritura(&mut self) -> Result<(), NodoBitcoinError> {
+        // una escritura anterior que quedó sin terminar se deshace antes de guardar el estado
+        self.deshacer_escritura()?;
+        self.journal = Some(Journal::iniciar(
+            self.journal_path.clone(),
+            self.headers_path.clone(),
+            self.blocks_dir.clone(),
+        )?);
+        Ok(())
+    }
+
+    fn bloques_escritos(&mut self) -> Result<(), NodoBitcoinError> {
+        match &self.journal {
+            Some(journal) => journal.sincronizar_bloques(),
+            None => Ok(()),
+        }
+    }
+
+    fn finalizar_escritura(&mut self) -> Result<(), NodoBitcoinError> {
+        match self.journal.take() {
+            Some(mut journal) => {
+                let resultado = journal.finalizar();
+                if resultado.is_err() {
+                    // queda abierta para que se pueda deshacer
+                    self.journal = Some(journal);
+                }
+                resultado
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn deshacer_escritura(&mut self) -> Result<Reparaciones, NodoBitcoinError> {
+        match self.journal.take() {
+            Some(journal) => journal.deshacer(),
+            None => Ok(Reparaciones::default()),
+        }
+    }
+
+    fn podar_bloques(
+        &mut self,
+        ultimo_timestamp_aplicado: u32,
+    ) -> Result<Vec<[u8; 32]>, NodoBitcoinError> {
+        podar_bloques(&self.blocks_dir, ultimo_timestamp_aplicado)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_storage_guarda_headers_bloques_e_indice() {
+        let dir = std::env::temp_dir().join("file_storage_test");
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+        let mut storage = FileStorage::new(
+            format!("{}/headers.bin", dir),
+            format!("{}/blocks", dir),
+            format!("{}/indexes", dir),
+            dir.to_string(),
+            format!("{}/journal.bin", dir),
+        );
+
+        storage.iniciar_escritura().unwrap();
+        let ubicacion = storage.escribir_bloque(&[3; 100]).unwrap();
+        storage.bloques_escritos().unwrap();
+        let offset = storage.escribir_header(&[1; 80]).unwrap();
+        storage.guardar_en_indice([1; 32], offset).unwrap();
+        storage.finalizar_escritura().unwrap();
+
+        assert_eq!(storage.leer_bloque(ubicacion).unwrap(), vec![3; 100]);
+        assert_eq!(storage.buscar_en_indice([1; 32]).unwrap(), 0);
+        assert_eq!(storage.tamanio_headers().unwrap(), 80);
+        assert!(fs::metadata(format!("{}/journal.bin", dir)).is_err());
+
+        // si falla entre el bloque y el header se descarta el bloque
+        storage.iniciar_escritura().unwrap();
+        storage.escribir_bloque(&[4; 100]).unwrap();
+        storage.bloques_escritos().unwrap();
+        assert!(storage.deshacer_escritura().unwrap().bloques_truncados);
+        assert_eq!(storage.leer_todos_los_bloques().unwrap().len(), 1);
+        assert!(fs::metadata(format!("{}/journal.bin", dir)).is_err());
+
+        storage.guardar_estado_wallet("utxos.dat", &[5; 4]).unwrap();
+        assert_eq!(storage.leer_estado_wallet("utxos.dat").unwrap(), vec![5; 4]);
+        _ = fs::remove_dir_all(dir);
+    }
+}
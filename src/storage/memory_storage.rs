@@ -0,0 +1,137 @@
+use super::Storage;
+use crate::blockchain::block_file::UbicacionBloque;
+use crate::errores::NodoBitcoinError;
+use std::collections::HashMap;
+
+/// Almacenamiento en memoria, pensado para los tests
+/// Los bloques se ubican todos en el archivo 0 y su offset es la posición en el vector
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    headers: Vec<u8>,
+    bloques: Vec<Vec<u8>>,
+    indice: HashMap<[u8; 32], u64>,
+    estado_wallet: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn escribir_header(&mut self, bytes: &[u8]) -> Result<u64, NodoBitcoinError> {
+        let offset = self.headers.len() as u64;
+        self.headers.extend_from_slice(bytes);
+        Ok(offset)
+    }
+
+    fn leer_headers(&self, offset: u64, largo: u64) -> Result<Vec<u8>, NodoBitcoinError> {
+        let desde = offset as usize;
+        let hasta = desde + largo as usize;
+        match self.headers.get(desde..hasta) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
+        }
+    }
+
+    fn tamanio_headers(&self) -> Result<u64, NodoBitcoinError> {
+        Ok(self.headers.len() as u64)
+    }
+
+    fn escribir_bloque(&mut self, bytes: &[u8]) -> Result<UbicacionBloque, NodoBitcoinError> {
+        self.bloques.push(bytes.to_vec());
+        Ok(UbicacionBloque {
+            archivo: 0,
+            offset: (self.bloques.len() - 1) as u64,
+        })
+    }
+
+    fn leer_bloque(&self, ubicacion: UbicacionBloque) -> Result<Vec<u8>, NodoBitcoinError> {
+        if ubicacion.archivo != 0 {
+            return Err(NodoBitcoinError::NoExisteArchivo);
+        }
+        match self.bloques.get(ubicacion.offset as usize) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
+        }
+    }
+
+    fn leer_todos_los_bloques(&self) -> Result<Vec<(UbicacionBloque, Vec<u8>)>, NodoBitcoinError> {
+        Ok(self
+            .bloques
+            .iter()
+            .enumerate()
+            .map(|(posicion, bytes)| {
+                (
+                    UbicacionBloque {
+                        archivo: 0,
+                        offset: posicion as u64,
+                    },
+                    bytes.clone(),
+                )
+            })
+            .collect())
+    }
+
+    fn guardar_en_indice(&mut self, hash: [u8; 32], offset: u64) -> Result<(), NodoBitcoinError> {
+        self.indice.entry(hash).or_insert(offset);
+        Ok(())
+    }
+
+    fn buscar_en_indice(&self, hash: [u8; 32]) -> Result<u64, NodoBitcoinError> {
+        match self.indice.get(&hash) {
+            Some(offset) => Ok(*offset),
+            None => Err(NodoBitcoinError::IndexNoEncontrado),
+        }
+    }
+
+    fn guardar_estado_wallet(
+        &mut self,
+        nombre: &str,
+        bytes: &[u8],
+    ) -> Result<(), NodoBitcoinError> {
+        self.estado_wallet
+            .insert(nombre.to_string(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn leer_estado_wallet(&self, nombre: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+        match self.estado_wallet.get(nombre) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(NodoBitcoinError::NoExisteArchivo),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_storage_headers_e_indice() {
+        let mut storage = MemoryStorage::new();
+        let primero = storage.escribir_header(&[1; 80]).unwrap();
+        let segundo = storage.escribir_header(&[2; 80]).unwrap();
+        storage.guardar_en_indice([2; 32], segundo).unwrap();
+
+        assert_eq!(primero, 0);
+        assert_eq!(storage.tamanio_headers().unwrap(), 160);
+        assert_eq!(storage.buscar_en_indice([2; 32]).unwrap(), 80);
+        assert_eq!(storage.leer_headers(80, 80).unwrap(), vec![2; 80]);
+        assert!(storage.leer_headers(120, 80).is_err());
+        assert!(storage.buscar_en_indice([3; 32]).is_err());
+    }
+
+    #[test]
+    fn test_memory_storage_bloques() {
+        let mut storage = MemoryStorage::new();
+        storage.escribir_bloque(&[1; 10]).unwrap();
+        let ubicacion = storage.escribir_bloque(&[2; 20]).unwrap();
+
+        assert_eq!(storage.leer_bloque(ubicacion).unwrap(), vec![2; 20]);
+        let todos = storage.leer_todos_los_bloques().unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[1], (ubicacion, vec![2; 20]));
+    }
+}
@@ -0,0 +1,73 @@
+pub mod file_storage;
+pub mod memory_storage;
+
+use crate::blockchain::block_file::UbicacionBloque;
+use crate::blockchain::journal::Reparaciones;
+use crate::errores::NodoBitcoinError;
+
+/// Almacenamiento de los datos del nodo: headers, bloques, índice de headers y estado de la wallet
+///
+/// `FileStorage` guarda todo en los archivos del config y `MemoryStorage` lo mantiene en memoria,
+/// para poder correr los tests sin tocar `src/indexes/` ni dejar archivos
+pub trait Storage: Send {
+    /// Agrega el header al final de los headers y devuelve el offset donde quedó
+    fn escribir_header(&mut self, bytes: &[u8]) -> Result<u64, NodoBitcoinError>;
+
+    /// Devuelve `largo` bytes de los headers a partir de `offset`
+    fn leer_headers(&self, offset: u64, largo: u64) -> Result<Vec<u8>, NodoBitcoinError>;
+
+    /// Devuelve el tamaño en bytes de todos los headers guardados
+    fn tamanio_headers(&self) -> Result<u64, NodoBitcoinError>;
+
+    /// Guarda los bytes del bloque y devuelve su ubicación
+    fn escribir_bloque(&mut self, bytes: &[u8]) -> Result<UbicacionBloque, NodoBitcoinError>;
+
+    /// Devuelve los bytes del bloque guardado en la ubicación recibida
+    fn leer_bloque(&self, ubicacion: UbicacionBloque) -> Result<Vec<u8>, NodoBitcoinError>;
+
+    /// Devuelve todos los bloques guardados, en orden, junto a su ubicación
+    fn leer_todos_los_bloques(&self) -> Result<Vec<(UbicacionBloque, Vec<u8>)>, NodoBitcoinError>;
+
+    /// Guarda en el índice de headers el offset del header con el hash recibido
+    fn guardar_en_indice(&mut self, hash: [u8; 32], offset: u64) -> Result<(), NodoBitcoinError>;
+
+    /// Devuelve el offset del header con el hash recibido
+    fn buscar_en_indice(&self, hash: [u8; 32]) -> Result<u64, NodoBitcoinError>;
+
+    /// Guarda el estado de la wallet con el nombre recibido, reemplazando el anterior
+    fn guardar_estado_wallet(&mut self, nombre: &str, bytes: &[u8])
+        -> Result<(), NodoBitcoinError>;
+
+    /// Devuelve el estado de la wallet guardado con el nombre recibido
+    fn leer_estado_wallet(&self, nombre: &str) -> Result<Vec<u8>, NodoBitcoinError>;
+
+    /// Marca el comienzo de una escritura de bloques y headers que tiene que quedar completa o no quedar
+    fn iniciar_escritura(&mut self) -> Result<(), NodoBitcoinError> {
+        Ok(())
+    }
+
+    /// Se llama después de escribir los bloques y antes de escribir sus headers
+    fn bloques_escritos(&mut self) -> Result<(), NodoBitcoinError> {
+        Ok(())
+    }
+
+    /// Da por terminada la escritura iniciada con `iniciar_escritura`
+    fn finalizar_escritura(&mut self) -> Result<(), NodoBitcoinError> {
+        Ok(())
+    }
+
+    /// Descarta lo escrito desde `iniciar_escritura` cuando la escritura falla a la mitad
+    /// Devuelve las reparaciones hechas sobre los archivos
+    fn deshacer_escritura(&mut self) -> Result<Reparaciones, NodoBitcoinError> {
+        Ok(Reparaciones::default())
+    }
+
+    /// Borra los bloques viejos ya aplicados a las UTXOs si el almacenamiento lo permite
+    /// Devuelve los hashes de los bloques borrados
+    fn podar_bloques(
+        &mut self,
+        _ultimo_timestamp_aplicado: u32,
+    ) -> Result<Vec<[u8; 32]>, NodoBitcoinError> {
+        Ok(vec![])
+    }
+}
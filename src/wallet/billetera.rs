@@ -0,0 +1,273 @@
+use std::{thread, time::Duration};
+
+use crate::{
+    config,
+    errores::NodoBitcoinError,
+    wallet::{cifrado::CifradoCuentas, user::Account},
+};
+
+/// Segundos que la wallet queda desbloqueada si no se configura TIEMPO_BLOQUEO_WALLET
+const SEGUNDOS_DE_BLOQUEO_DEFAULT: u64 = 300;
+
+/// Devuelve los segundos después de los que la wallet desbloqueada se vuelve a bloquear sola
+pub fn segundos_de_bloqueo() -> u64 {
+    match config::get_valor("TIEMPO_BLOQUEO_WALLET".to_string()) {
+        Ok(valor) => valor
+            .trim()
+            .parse::<u64>()
+            .unwrap_or(SEGUNDOS_DE_BLOQUEO_DEFAULT),
+        Err(_) => SEGUNDOS_DE_BLOQUEO_DEFAULT,
+    }
+}
+
+/// Cuentas de la wallet con el estado del cifrado de sus secretos
+/// Mientras la wallet con passphrase está desbloqueada guarda la clave derivada, para cifrar
+/// las cuentas nuevas, y las cuentas tienen cargadas sus claves privadas
+#[derive(Debug)]
+pub struct Billetera {
+    pub accounts: Vec<Account>,
+    pub cifrado: Option<CifradoCuentas>,
+    clave: Option<[u8; 32]>,
+    // cuenta los desbloqueos, para que el bloqueo automático de uno viejo no bloquee la
+    // wallet si se volvió a desbloquear
+    desbloqueos: u64,
+}
+
+impl Billetera {
+    pub fn new(accounts: Vec<Account>, cifrado: Option<CifradoCuentas>) -> Billetera {
+        Billetera {
+            accounts,
+            cifrado,
+            clave: None,
+            desbloqueos: 0,
+        }
+    }
+
+    /// Lee las cuentas guardadas. Si no hay archivo la wallet empieza vacía y sin passphrase
+    pub fn cargar() -> Result<Billetera, NodoBitcoinError> {
+        match Account::get_all_accounts() {
+            Ok((accounts, cifrado)) => Ok(Billetera::new(accounts, cifrado)),
+            Err(NodoBitcoinError::NoExisteArchivo) => Ok(Billetera::new(vec![], None)),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Guarda las cuentas, con los secretos cifrados si la wallet tiene passphrase
+    pub fn guardar(&self) -> Result<(), NodoBitcoinError> {
+        Account::save_all_accounts(self.accounts.clone(), self.cifrado.as_ref())
+    }
+
+    /// Devuelve si hay claves privadas que se guardan sin cifrar porque la wallet no tiene
+    /// passphrase
+    pub fn guarda_secretos_sin_cifrar(&self) -> bool {
+        self.cifrado.is_none()
+            && self
+                .accounts
+                .iter()
+                .any(|account| !account.es_solo_lectura())
+    }
+
+    /// Devuelve si la wallet tiene passphrase y no está desbloqueada
+    pub fn esta_bloqueada(&self) -> bool {
+        self.cifrado.is_some() && self.clave.is_none()
+    }
+
+    /// Devuelve las cuentas sin las claves privadas, para el transaction manager
+    pub fn cuentas_sin_secretos(&self) -> Vec<Account> {
+        self.accounts.iter().map(Account::sin_secretos).collect()
+    }
+
+    /// Devuelve si la cuenta de esa dirección es de solo lectura
+    pub fn es_solo_lectura(&self, public_key: &str) -> bool {
+        self.accounts
+            .iter()
+            .any(|account| account.public_key == public_key && account.es_solo_lectura())
+    }
+
+    /// Devuelve la cuenta con sus claves privadas para firmar
+    pub fn cuenta_para_firmar(&self, public_key: &str) -> Result<Account, NodoBitcoinError> {
+        let account = self
+            .accounts
+            .iter()
+            .find(|account| account.public_key == public_key)
+            .ok_or(NodoBitcoinError::CuentaNoEncontrada)?;
+        if account.es_solo_lectura() {
+            return Err(NodoBitcoinError::CuentaSoloLectura);
+        }
+        if account.esta_bloqueada() {
+            return Err(NodoBitcoinError::WalletBloqueada);
+        }
+        Ok(account.clone())
+    }
+
+    /// Agrega una cuenta. Si la wallet tiene passphrase tiene que estar desbloqueada para
+    /// cifrar el secreto de la cuenta nueva, salvo que sea de solo lectura
+    pub fn agregar(&mut self, mut account: Account) -> Result<(), NodoBitcoinError> {
+        if self.cifrado.is_some() && !account.es_solo_lectura() {
+            let clave = self.clave.ok_or(NodoBitcoinError::WalletBloqueada)?;
+            account.cifrar_secreto(&clave)?;
+        }
+        self.accounts.push(account);
+        Ok(())
+    }
+
+    /// Reemplaza la cuenta con la copia actualizada por el transaction manager, conservando
+    /// las claves que tiene la wallet
+    pub fn actualizar(&mut self, account: Account) {
+        for actual in self.accounts.iter_mut() {
+            if actual.public_key == account.public_key {
+                *actual = account.clone().con_secretos_de(actual);
+            }
+        }
+    }
+
+    /// Deriva la clave de la passphrase y carga las claves privadas de todas las cuentas
+    /// Devuelve el número de desbloqueo, que se usa para el bloqueo automático
+    pub fn desbloquear(&mut self, passphrase: &str) -> Result<u64, NodoBitcoinError> {
+        let cifrado = match &self.cifrado {
+            Some(cifrado) => cifrado,
+            None => return Err(NodoBitcoinError::PassphraseIncorrecta),
+        };
+        let clave = cifrado.derivar_clave(passphrase)?;
+        for account in self.accounts.iter_mut() {
+            account.desbloquear(&clave)?;
+        }
+        self.clave = Some(clave);
+        self.desbloqueos += 1;
+        Ok(self.desbloqueos)
+    }
+
+    /// Saca de memoria la clave derivada y las claves privadas de las cuentas
+    pub fn bloquear(&mut self) {
+        if let Some(clave) = self.clave.as_mut() {
+            clave.fill(0);
+        }
+        self.clave = None;
+        for account in self.accounts.iter_mut() {
+            account.bloquear();
+        }
+    }
+
+    /// Bloquea la wallet si sigue abierta desde el desbloqueo recibido
+    /// Devuelve si la bloqueó
+    pub fn bloquear_si_vencio(&mut self, desbloqueo: u64) -> bool {
+        if self.clave.is_none() || desbloqueo != self.desbloqueos {
+            return false;
+        }
+        self.bloquear();
+        true
+    }
+
+    /// Cifra los secretos de todas las cuentas con una passphrase nueva, la guarda y deja la
+    /// wallet bloqueada. Si ya tenía passphrase hay que pasar la actual
+    pub fn cambiar_passphrase(
+        &mut self,
+        actual: &str,
+        nueva: &str,
+    ) -> Result<(), NodoBitcoinError> {
+        let mut accounts = self.accounts.clone();
+        if let Some(cifrado) = &self.cifrado {
+            let clave = cifrado.derivar_clave(actual)?;
+            for account in accounts.iter_mut() {
+                account.desbloquear(&clave)?;
+            }
+        }
+        let (cifrado, clave) = CifradoCuentas::nuevo(nueva)?;
+        for account in accounts.iter_mut() {
+            account.cifrar_secreto(&clave)?;
+        }
+        // se guarda antes de reemplazar las cuentas, si falla queda todo como estaba
+        Account::save_all_accounts(accounts.clone(), Some(&cifrado))?;
+        self.accounts = accounts;
+        self.cifrado = Some(cifrado);
+        self.bloquear();
+        Ok(())
+    }
+}
+
+/// Espera el tiempo de bloqueo y después llama a la función con el número de desbloqueo
+pub fn programar_bloqueo<F>(desbloqueo: u64, bloquear: F)
+where
+    F: FnOnce(u64) + Send + 'static,
+{
+    let segundos = segundos_de_bloqueo();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(segundos));
+        bloquear(desbloqueo);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desbloquear_y_bloquear() {
+        let account = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "wallet1".to_string(),
+        );
+        let (cifrado, clave) = CifradoCuentas::con_iteraciones("passphrase", 10).unwrap();
+        let mut cifrada = account.clone();
+        cifrada.cifrar_secreto(&clave).unwrap();
+        cifrada.bloquear();
+        let mut billetera = Billetera::new(vec![cifrada], Some(cifrado));
+        assert!(!billetera.guarda_secretos_sin_cifrar());
+        assert!(Billetera::new(vec![account.clone()], None).guarda_secretos_sin_cifrar());
+
+        assert!(billetera.esta_bloqueada());
+        assert_eq!(
+            billetera.cuenta_para_firmar(&account.public_key),
+            Err(NodoBitcoinError::WalletBloqueada)
+        );
+        assert_eq!(
+            billetera.agregar(Account::new(
+                "cU7dbzeBRgMEZ5BUst2CFydGRm9gt8uQbNoojWPRRuHb2xk5q5h2".to_string(),
+                "mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a".to_string(),
+                "wallet2".to_string(),
+            )),
+            Err(NodoBitcoinError::WalletBloqueada)
+        );
+        assert_eq!(
+            billetera.desbloquear("otra"),
+            Err(NodoBitcoinError::PassphraseIncorrecta)
+        );
+
+        let primero = billetera.desbloquear("passphrase").unwrap();
+        assert_eq!(
+            billetera
+                .cuenta_para_firmar(&account.public_key)
+                .unwrap()
+                .secret_key,
+            account.secret_key
+        );
+        assert!(billetera.cuentas_sin_secretos()[0].esta_bloqueada());
+
+        // el bloqueo de un desbloqueo anterior no cierra la wallet abierta de nuevo
+        let segundo = billetera.desbloquear("passphrase").unwrap();
+        assert!(!billetera.bloquear_si_vencio(primero));
+        assert!(!billetera.esta_bloqueada());
+        assert!(billetera.bloquear_si_vencio(segundo));
+        assert!(billetera.esta_bloqueada());
+        assert!(billetera.accounts[0].esta_bloqueada());
+    }
+
+    #[test]
+    fn test_cuenta_solo_lectura_en_wallet_bloqueada() {
+        let (cifrado, _) = CifradoCuentas::con_iteraciones("passphrase", 10).unwrap();
+        let mut billetera = Billetera::new(vec![], Some(cifrado));
+        let account =
+            Account::solo_lectura("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun", "frio".to_string())
+                .unwrap();
+
+        // no tiene secreto que cifrar, se agrega aunque la wallet esté bloqueada
+        assert!(billetera.agregar(account.clone()).is_ok());
+        assert!(!billetera.guarda_secretos_sin_cifrar());
+        assert!(billetera.es_solo_lectura(&account.public_key));
+        assert_eq!(
+            billetera.cuenta_para_firmar(&account.public_key),
+            Err(NodoBitcoinError::CuentaSoloLectura)
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::errores::NodoBitcoinError;
+use crate::wallet::cifrado::pbkdf2_hmac_sha512;
+use bitcoin_hashes::{sha256, Hash};
+use rand::RngCore;
+
+/// Lista de palabras en inglés de BIP39, ordenada alfabéticamente
+const PALABRAS: &str = include_str!("bip39_english.txt");
+
+/// Iteraciones de PBKDF2 para pasar del mnemónico a la semilla
+const ITERACIONES_PBKDF2: u32 = 2048;
+
+/// Cantidades de palabras permitidas, de 128 a 256 bits de entropía
+pub const CANTIDADES_DE_PALABRAS: [usize; 5] = [12, 15, 18, 21, 24];
+
+fn lista_de_palabras() -> Vec<&'static str> {
+    PALABRAS.lines().collect()
+}
+
+/// Genera un mnemónico nuevo con la cantidad de palabras pedida usando el generador de
+/// números aleatorios del sistema operativo
+pub fn generar_mnemonico(cantidad_palabras: usize) -> Result<String, NodoBitcoinError> {
+    if !CANTIDADES_DE_PALABRAS.contains(&cantidad_palabras) {
+        return Err(NodoBitcoinError::MnemonicoInvalido);
+    }
+    let mut entropia = vec![0u8; cantidad_palabras * 4 / 3];
+    rand::rngs::OsRng.fill_bytes(&mut entropia);
+    mnemonico_desde_entropia(&entropia)
+}
+
+/// Codifica la entropía en palabras: agrega el checksum (los primeros bits del sha256) y
+/// toma grupos de 11 bits como índices de la lista
+pub fn mnemonico_desde_entropia(entropia: &[u8]) -> Result<String, NodoBitcoinError> {
+    if !matches!(entropia.len(), 16 | 20 | 24 | 28 | 32) {
+        return Err(NodoBitcoinError::MnemonicoInvalido);
+    }
+    let checksum = sha256::Hash::hash(entropia).to_byte_array()[0];
+    let bits_checksum = entropia.len() / 4;
+    let mut bits: Vec<bool> = entropia
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+        .collect();
+    bits.extend((0..bits_checksum).map(|i| (checksum >> (7 - i)) & 1 == 1));
+
+    let palabras = lista_de_palabras();
+    let mnemonico: Vec<&str> = bits
+        .chunks(11)
+        .map(|grupo| {
+            let indice = grupo
+                .iter()
+                .fold(0usize, |indice, bit| (indice << 1) | *bit as usize);
+            palabras[indice]
+        })
+        .collect();
+    Ok(mnemonico.join(" "))
+}
+
+/// Valida que todas las palabras estén en la lista y que el checksum sea correcto
+/// Devuelve la entropía codificada en el mnemónico
+pub fn validar_mnemonico(mnemonico: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+    let palabras = lista_de_palabras();
+    let indices = mnemonico
+        .split_whitespace()
+        .map(|palabra| palabras.binary_search(&palabra.to_lowercase().as_str()))
+        .collect::<Result<Vec<usize>, usize>>()
+        .map_err(|_| NodoBitcoinError::MnemonicoInvalido)?;
+    if !CANTIDADES_DE_PALABRAS.contains(&indices.len()) {
+        return Err(NodoBitcoinError::MnemonicoInvalido);
+    }
+    let bits: Vec<bool> = indices
+        .iter()
+        .flat_map(|indice| (0..11).rev().map(move |i| (indice >> i) & 1 == 1))
+        .collect();
+    let bits_checksum = bits.len() / 33;
+    let (bits_entropia, _) = bits.split_at(bits.len() - bits_checksum);
+    let entropia: Vec<u8> = bits_entropia
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |byte, bit| (byte << 1) | *bit as u8))
+        .collect();
+    if mnemonico_desde_entropia(&entropia)? != normalizar(mnemonico) {
+        return Err(NodoBitcoinError::MnemonicoInvalido);
+    }
+    Ok(entropia)
+}
+
+fn normalizar(mnemonico: &str) -> String {
+    mnemonico
+        .split_whitespace()
+        .map(|palabra| palabra.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Calcula la semilla de 64 bytes del mnemónico con PBKDF2-HMAC-SHA512, usando
+/// "mnemonic" más la passphrase como sal
+/// La passphrase se usa tal cual, sin normalizar los caracteres que no son ASCII
+pub fn semilla_desde_mnemonico(
+    mnemonico: &str,
+    passphrase: &str,
+) -> Result<[u8; 64], NodoBitcoinError> {
+    validar_mnemonico(mnemonico)?;
+    let sal = format!("mnemonic{}", passphrase);
+    Ok(pbkdf2_hmac_sha512(
+        normalizar(mnemonico).as_bytes(),
+        sal.as_bytes(),
+        ITERACIONES_PBKDF2,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::red::Red;
+    use crate::wallet::hd::ClaveExtendida;
+
+    #[test]
+    fn test_vector_de_bip39() {
+        let mnemonico = mnemonico_desde_entropia(&[0; 16]).unwrap();
+        assert_eq!(
+            mnemonico,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        let semilla = semilla_desde_mnemonico(&mnemonico, "TREZOR").unwrap();
+        let semilla_hexa: String = semilla.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(
+            semilla_hexa,
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        assert_eq!(
+            ClaveExtendida::desde_semilla(&semilla, Red::Mainnet)
+                .unwrap()
+                .serializar(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+    }
+
+    #[test]
+    fn test_generar_y_validar_mnemonico() {
+        let mnemonico = generar_mnemonico(24).unwrap();
+        assert_eq!(mnemonico.split_whitespace().count(), 24);
+        assert_eq!(validar_mnemonico(&mnemonico).unwrap().len(), 32);
+        assert_eq!(
+            generar_mnemonico(13),
+            Err(NodoBitcoinError::MnemonicoInvalido)
+        );
+
+        // la última palabra lleva el checksum, cambiarla lo invalida
+        let invalido =
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+        assert_eq!(
+            validar_mnemonico(invalido),
+            Err(NodoBitcoinError::MnemonicoInvalido)
+        );
+        assert_eq!(
+            validar_mnemonico("abandon zzz"),
+            Err(NodoBitcoinError::MnemonicoInvalido)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::errores::NodoBitcoinError;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use bitcoin_hashes::{hmac, sha512, Hash, HashEngine};
+use rand::RngCore;
+
+/// Iteraciones de PBKDF2 para derivar la clave de la passphrase de la wallet. Hacen lento
+/// probar passphrases por fuerza bruta con una copia del archivo de cuentas
+pub const ITERACIONES_POR_DEFECTO: u32 = 100_000;
+
+const LARGO_SAL: usize = 16;
+const LARGO_NONCE: usize = 12;
+
+/// Texto que se cifra junto con los parámetros para comprobar la passphrase aunque la wallet
+/// no tenga cuentas
+const TEXTO_VERIFICADOR: &[u8] = b"los_rustybandidos";
+
+/// Calcula el primer bloque de PBKDF2-HMAC-SHA512, que alcanza para claves de hasta 64 bytes
+pub fn pbkdf2_hmac_sha512(clave: &[u8], sal: &[u8], iteraciones: u32) -> [u8; 64] {
+    let hmac_sha512 = |datos: &[u8]| {
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(clave);
+        engine.input(datos);
+        hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array()
+    };
+    let mut sal_bloque = sal.to_vec();
+    sal_bloque.extend_from_slice(&1u32.to_be_bytes());
+    let mut bloque = hmac_sha512(&sal_bloque);
+    let mut resultado = bloque;
+    for _ in 1..iteraciones {
+        bloque = hmac_sha512(&bloque);
+        for (byte, byte_bloque) in resultado.iter_mut().zip(bloque.iter()) {
+            *byte ^= byte_bloque;
+        }
+    }
+    resultado
+}
+
+/// Cifra los datos con AES-256-GCM. Devuelve el nonce aleatorio seguido del texto cifrado
+/// con su tag de autenticación
+pub fn cifrar(clave: &[u8; 32], datos: &[u8]) -> Result<Vec<u8>, NodoBitcoinError> {
+    let mut nonce = [0u8; LARGO_NONCE];
+    rand::rngs::OsRng
+        .try_fill_bytes(&mut nonce)
+        .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+    let cifrador = Aes256Gcm::new_from_slice(clave).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+    let cifrado = cifrador
+        .encrypt(Nonce::from_slice(&nonce), datos)
+        .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+    let mut resultado = nonce.to_vec();
+    resultado.extend(cifrado);
+    Ok(resultado)
+}
+
+/// Descifra lo que devuelve `cifrar`. Si la clave no es la correcta falla la autenticación
+pub fn descifrar(clave: &[u8; 32], datos: &[u8]) -> Result<Vec<u8>, NodoBitcoinError> {
+    if datos.len() < LARGO_NONCE {
+        return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+    }
+    let (nonce, cifrado) = datos.split_at(LARGO_NONCE);
+    let cifrador = Aes256Gcm::new_from_slice(clave).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+    cifrador
+        .decrypt(Nonce::from_slice(nonce), cifrado)
+        .map_err(|_| NodoBitcoinError::PassphraseIncorrecta)
+}
+
+/// Parámetros con los que se cifran los secretos de las cuentas: la sal y las iteraciones
+/// de PBKDF2, y el texto verificador cifrado con la clave derivada
+#[derive(Debug, Clone, PartialEq)]
+pub struct CifradoCuentas {
+    pub sal: Vec<u8>,
+    pub iteraciones: u32,
+    pub verificador: Vec<u8>,
+}
+
+impl CifradoCuentas {
+    /// Crea parámetros nuevos con una sal aleatoria para la passphrase
+    /// Devuelve también la clave derivada, para cifrar los secretos
+    pub fn nuevo(passphrase: &str) -> Result<(CifradoCuentas, [u8; 32]), NodoBitcoinError> {
+        CifradoCuentas::con_iteraciones(passphrase, ITERACIONES_POR_DEFECTO)
+    }
+
+    pub fn con_iteraciones(
+        passphrase: &str,
+        iteraciones: u32,
+    ) -> Result<(CifradoCuentas, [u8; 32]), NodoBitcoinError> {
+        if passphrase.is_empty() || iteraciones == 0 {
+            return Err(NodoBitcoinError::PassphraseIncorrecta);
+        }
+        let mut sal = vec![0u8; LARGO_SAL];
+        rand::rngs::OsRng
+            .try_fill_bytes(&mut sal)
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        let clave = derivar(passphrase, &sal, iteraciones);
+        let verificador = cifrar(&clave, TEXTO_VERIFICADOR)?;
+        Ok((
+            CifradoCuentas {
+                sal,
+                iteraciones,
+                verificador,
+            },
+            clave,
+        ))
+    }
+
+    /// Deriva la clave de la passphrase y comprueba que descifre el verificador
+    pub fn derivar_clave(&self, passphrase: &str) -> Result<[u8; 32], NodoBitcoinError> {
+        let clave = derivar(passphrase, &self.sal, self.iteraciones);
+        if descifrar(&clave, &self.verificador)? != TEXTO_VERIFICADOR {
+            return Err(NodoBitcoinError::PassphraseIncorrecta);
+        }
+        Ok(clave)
+    }
+}
+
+fn derivar(passphrase: &str, sal: &[u8], iteraciones: u32) -> [u8; 32] {
+    let resultado = pbkdf2_hmac_sha512(passphrase.as_bytes(), sal, iteraciones);
+    let mut clave = [0u8; 32];
+    clave.copy_from_slice(&resultado[..32]);
+    clave
+}
+
+/// Codifica los bytes en hexadecimal
+pub fn a_hexa(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodifica un texto en hexadecimal
+pub fn desde_hexa(texto: &str) -> Result<Vec<u8>, NodoBitcoinError> {
+    // con un largo impar el último byte queda incompleto y falla
+    (0..texto.len())
+        .step_by(2)
+        .map(|i| {
+            texto
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cifrar_y_descifrar() {
+        let (cifrado, clave) = CifradoCuentas::con_iteraciones("correcta", 10).unwrap();
+        let secreto = cifrar(
+            &clave,
+            b"cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw",
+        )
+        .unwrap();
+        assert_eq!(
+            descifrar(&cifrado.derivar_clave("correcta").unwrap(), &secreto).unwrap(),
+            b"cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw"
+        );
+        assert_eq!(
+            cifrado.derivar_clave("incorrecta"),
+            Err(NodoBitcoinError::PassphraseIncorrecta)
+        );
+        let otra_clave = derivar("incorrecta", &cifrado.sal, cifrado.iteraciones);
+        assert_eq!(
+            descifrar(&otra_clave, &secreto),
+            Err(NodoBitcoinError::PassphraseIncorrecta)
+        );
+        assert_eq!(desde_hexa(&a_hexa(&secreto)).unwrap(), secreto);
+        assert!(CifradoCuentas::nuevo("").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::common::red::Red;
+use crate::errores::NodoBitcoinError;
+use rand::RngCore;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+/// Máxima cantidad de intentos para obtener del generador un número válido como clave. Un
+/// número de 32 bytes fuera del orden de la curva es casi imposible, así que no debería
+/// hacer falta más de uno
+const INTENTOS_MAXIMOS: usize = 16;
+
+/// Clave privada generada por el nodo con su clave pública y sus codificaciones para la red
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParDeClaves {
+    pub clave_privada: [u8; 32],
+    pub clave_publica: [u8; 33],
+    pub wif: String,
+    pub direccion: String,
+}
+
+/// Genera una clave privada de secp256k1 con el generador de números aleatorios del sistema
+/// operativo
+pub fn generar_clave_privada() -> Result<[u8; 32], NodoBitcoinError> {
+    let mut clave = [0u8; 32];
+    for _ in 0..INTENTOS_MAXIMOS {
+        rand::rngs::OsRng
+            .try_fill_bytes(&mut clave)
+            .map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        if SecretKey::from_slice(&clave).is_ok() {
+            return Ok(clave);
+        }
+    }
+    Err(NodoBitcoinError::ClaveInvalida)
+}
+
+/// Genera una clave privada nueva, su WIF comprimido y su dirección P2PKH en la red recibida
+pub fn generar_par_de_claves(red: Red) -> Result<ParDeClaves, NodoBitcoinError> {
+    let clave_privada = generar_clave_privada()?;
+    let secret_key =
+        SecretKey::from_slice(&clave_privada).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+    let clave_publica = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).serialize();
+    Ok(ParDeClaves {
+        clave_privada,
+        clave_publica,
+        wif: red.codificar_wif(&clave_privada),
+        direccion: red.direccion_p2pkh(&clave_publica),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::transaction::TxOut;
+    use crate::common::decoder::{point_sec, wif_to_hex};
+
+    #[test]
+    fn test_generar_par_de_claves() {
+        let par = generar_par_de_claves(Red::Testnet).unwrap();
+        assert_eq!(wif_to_hex(&par.wif).unwrap(), par.clave_privada.to_vec());
+        assert_eq!(
+            point_sec(&par.clave_privada).unwrap(),
+            par.clave_publica.to_vec()
+        );
+        assert!(par.wif.starts_with('c'));
+        assert!(par.direccion.starts_with('m') || par.direccion.starts_with('n'));
+        assert!(TxOut::new(1000, par.direccion.clone())
+            .unwrap()
+            .is_user_account_output(par.direccion.clone()));
+
+        let mainnet = generar_par_de_claves(Red::Mainnet).unwrap();
+        assert!(mainnet.wif.starts_with('K') || mainnet.wif.starts_with('L'));
+        assert!(mainnet.direccion.starts_with('1'));
+        assert_ne!(mainnet.clave_privada, par.clave_privada);
+    }
+}
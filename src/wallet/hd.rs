@@ -0,0 +1,588 @@
+use crate::common::decoder::p2pkh_script_serialized;
+use crate::common::red::{codificar_base58check, decodificar_base58check, Red};
+use crate::errores::NodoBitcoinError;
+use bitcoin_hashes::{hash160, hmac, sha512, Hash, HashEngine};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+
+/// A partir de este índice las hijas se derivan endurecidas, sin poder calcularlas desde la
+/// clave pública del padre
+pub const INDICE_ENDURECIDO: u32 = 0x8000_0000;
+
+/// Cadena de las direcciones de recepción
+pub const CADENA_EXTERNA: u32 = 0;
+
+/// Cadena de las direcciones de cambio
+pub const CADENA_CAMBIO: u32 = 1;
+
+/// Cantidad de direcciones sin usar que se derivan por adelantado en cada cadena, para
+/// reconocer los pagos a direcciones que la wallet todavía no marcó como usadas
+pub const LIMITE_DE_HUECO: u32 = 20;
+
+const LARGO_CLAVE_SERIALIZADA: usize = 78;
+
+/// Clave privada extendida de BIP32: una clave privada con su código de cadena, que permite
+/// derivar un árbol de claves hijas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaveExtendida {
+    pub red: Red,
+    pub profundidad: u8,
+    pub huella_padre: [u8; 4],
+    pub indice: u32,
+    pub codigo_cadena: [u8; 32],
+    pub clave: [u8; 32],
+}
+
+fn hmac_sha512(clave: &[u8], datos: &[u8]) -> [u8; 64] {
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(clave);
+    engine.input(datos);
+    hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array()
+}
+
+impl ClaveExtendida {
+    /// Crea la clave maestra a partir de la semilla
+    pub fn desde_semilla(semilla: &[u8], red: Red) -> Result<ClaveExtendida, NodoBitcoinError> {
+        let resultado = hmac_sha512(b"Bitcoin seed", semilla);
+        let mut clave = [0u8; 32];
+        let mut codigo_cadena = [0u8; 32];
+        clave.copy_from_slice(&resultado[..32]);
+        codigo_cadena.copy_from_slice(&resultado[32..]);
+        SecretKey::from_slice(&clave).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        Ok(ClaveExtendida {
+            red,
+            profundidad: 0,
+            huella_padre: [0; 4],
+            indice: 0,
+            codigo_cadena,
+            clave,
+        })
+    }
+
+    /// Devuelve la clave pública comprimida
+    pub fn clave_publica(&self) -> Result<[u8; 33], NodoBitcoinError> {
+        let secp = Secp256k1::new();
+        let secret_key =
+            SecretKey::from_slice(&self.clave).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        Ok(PublicKey::from_secret_key(&secp, &secret_key).serialize())
+    }
+
+    /// Devuelve los primeros 4 bytes del hash160 de la clave pública, que identifican a la
+    /// clave en las hijas
+    pub fn huella(&self) -> Result<[u8; 4], NodoBitcoinError> {
+        let hash = hash160::Hash::hash(&self.clave_publica()?);
+        let mut huella = [0u8; 4];
+        huella.copy_from_slice(&hash.as_byte_array()[..4]);
+        Ok(huella)
+    }
+
+    /// Deriva la clave hija con el índice recibido. Los índices desde INDICE_ENDURECIDO
+    /// derivan hijas endurecidas
+    pub fn derivar_hija(&self, indice: u32) -> Result<ClaveExtendida, NodoBitcoinError> {
+        let mut datos = Vec::with_capacity(37);
+        if indice >= INDICE_ENDURECIDO {
+            datos.push(0);
+            datos.extend_from_slice(&self.clave);
+        } else {
+            datos.extend_from_slice(&self.clave_publica()?);
+        }
+        datos.extend_from_slice(&indice.to_be_bytes());
+        let resultado = hmac_sha512(&self.codigo_cadena, &datos);
+
+        let mut tweak = [0u8; 32];
+        tweak.copy_from_slice(&resultado[..32]);
+        let tweak = Scalar::from_be_bytes(tweak).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        let clave = SecretKey::from_slice(&self.clave)
+            .and_then(|clave| clave.add_tweak(&tweak))
+            .map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        let mut codigo_cadena = [0u8; 32];
+        codigo_cadena.copy_from_slice(&resultado[32..]);
+
+        Ok(ClaveExtendida {
+            red: self.red,
+            profundidad: self
+                .profundidad
+                .checked_add(1)
+                .ok_or(NodoBitcoinError::ClaveInvalida)?,
+            huella_padre: self.huella()?,
+            indice,
+            codigo_cadena,
+            clave: clave.secret_bytes(),
+        })
+    }
+
+    /// Deriva la clave del camino recibido, por ejemplo "m/44'/1'/0'"
+    /// Los índices con ' o h son endurecidos
+    pub fn derivar_camino(&self, camino: &str) -> Result<ClaveExtendida, NodoBitcoinError> {
+        let mut partes = camino.split('/');
+        if partes.next() != Some("m") {
+            return Err(NodoBitcoinError::CaminoDeDerivacionInvalido);
+        }
+        let mut clave = self.clone();
+        for parte in partes {
+            let (numero, endurecido) = match parte.strip_suffix(['\'', 'h']) {
+                Some(numero) => (numero, true),
+                None => (parte, false),
+            };
+            let indice = numero
+                .parse::<u32>()
+                .ok()
+                .filter(|indice| *indice < INDICE_ENDURECIDO)
+                .ok_or(NodoBitcoinError::CaminoDeDerivacionInvalido)?;
+            let indice = if endurecido {
+                indice + INDICE_ENDURECIDO
+            } else {
+                indice
+            };
+            clave = clave.derivar_hija(indice)?;
+        }
+        Ok(clave)
+    }
+
+    /// Serializa la clave como xprv o tprv según la red
+    pub fn serializar(&self) -> String {
+        let mut clave = vec![0];
+        clave.extend_from_slice(&self.clave);
+        serializar_extendida(
+            self.red.version_xprv(),
+            self.profundidad,
+            self.huella_padre,
+            self.indice,
+            &self.codigo_cadena,
+            &clave,
+        )
+    }
+
+    /// Lee una clave privada extendida serializada como xprv o tprv
+    pub fn desde_string(texto: &str) -> Result<ClaveExtendida, NodoBitcoinError> {
+        let (red, bytes) = decodificar_extendida(texto, Red::version_xprv)?;
+        if bytes[45] != 0 {
+            return Err(NodoBitcoinError::ClaveInvalida);
+        }
+        let mut clave = [0u8; 32];
+        clave.copy_from_slice(&bytes[46..]);
+        SecretKey::from_slice(&clave).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        let (profundidad, huella_padre, indice, codigo_cadena) = campos_extendida(&bytes);
+        Ok(ClaveExtendida {
+            red,
+            profundidad,
+            huella_padre,
+            indice,
+            codigo_cadena,
+            clave,
+        })
+    }
+
+    /// Devuelve la clave pública extendida, que deriva las mismas direcciones sin poder firmar
+    pub fn publica(&self) -> Result<ClavePublicaExtendida, NodoBitcoinError> {
+        Ok(ClavePublicaExtendida {
+            red: self.red,
+            profundidad: self.profundidad,
+            huella_padre: self.huella_padre,
+            indice: self.indice,
+            codigo_cadena: self.codigo_cadena,
+            clave: self.clave_publica()?,
+        })
+    }
+
+    /// Devuelve la clave privada en formato WIF comprimido
+    pub fn wif(&self) -> String {
+        self.red.codificar_wif(&self.clave)
+    }
+
+    /// Devuelve la dirección P2PKH de la clave pública comprimida
+    pub fn direccion_p2pkh(&self) -> Result<String, NodoBitcoinError> {
+        Ok(self.red.direccion_p2pkh(&self.clave_publica()?))
+    }
+}
+
+/// Clave pública extendida de BIP32 (xpub o tpub). Solo deriva hijas no endurecidas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClavePublicaExtendida {
+    pub red: Red,
+    pub profundidad: u8,
+    pub huella_padre: [u8; 4],
+    pub indice: u32,
+    pub codigo_cadena: [u8; 32],
+    pub clave: [u8; 33],
+}
+
+impl ClavePublicaExtendida {
+    /// Devuelve los primeros 4 bytes del hash160 de la clave pública
+    pub fn huella(&self) -> [u8; 4] {
+        let hash = hash160::Hash::hash(&self.clave);
+        let mut huella = [0u8; 4];
+        huella.copy_from_slice(&hash.as_byte_array()[..4]);
+        huella
+    }
+
+    /// Deriva la clave pública hija con el índice recibido, que no puede ser endurecido
+    pub fn derivar_hija(&self, indice: u32) -> Result<ClavePublicaExtendida, NodoBitcoinError> {
+        if indice >= INDICE_ENDURECIDO {
+            return Err(NodoBitcoinError::CaminoDeDerivacionInvalido);
+        }
+        let mut datos = self.clave.to_vec();
+        datos.extend_from_slice(&indice.to_be_bytes());
+        let resultado = hmac_sha512(&self.codigo_cadena, &datos);
+
+        let mut tweak = [0u8; 32];
+        tweak.copy_from_slice(&resultado[..32]);
+        let tweak = Scalar::from_be_bytes(tweak).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        let clave = PublicKey::from_slice(&self.clave)
+            .and_then(|clave| clave.add_exp_tweak(&Secp256k1::new(), &tweak))
+            .map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        let mut codigo_cadena = [0u8; 32];
+        codigo_cadena.copy_from_slice(&resultado[32..]);
+
+        Ok(ClavePublicaExtendida {
+            red: self.red,
+            profundidad: self
+                .profundidad
+                .checked_add(1)
+                .ok_or(NodoBitcoinError::ClaveInvalida)?,
+            huella_padre: self.huella(),
+            indice,
+            codigo_cadena,
+            clave: clave.serialize(),
+        })
+    }
+
+    /// Serializa la clave como xpub o tpub según la red
+    pub fn serializar(&self) -> String {
+        serializar_extendida(
+            self.red.version_xpub(),
+            self.profundidad,
+            self.huella_padre,
+            self.indice,
+            &self.codigo_cadena,
+            &self.clave,
+        )
+    }
+
+    /// Lee una clave pública extendida serializada como xpub o tpub
+    pub fn desde_string(texto: &str) -> Result<ClavePublicaExtendida, NodoBitcoinError> {
+        let (red, bytes) = decodificar_extendida(texto, Red::version_xpub)?;
+        let mut clave = [0u8; 33];
+        clave.copy_from_slice(&bytes[45..]);
+        PublicKey::from_slice(&clave).map_err(|_| NodoBitcoinError::ClaveInvalida)?;
+        let (profundidad, huella_padre, indice, codigo_cadena) = campos_extendida(&bytes);
+        Ok(ClavePublicaExtendida {
+            red,
+            profundidad,
+            huella_padre,
+            indice,
+            codigo_cadena,
+            clave,
+        })
+    }
+
+    /// Devuelve la dirección P2PKH de la clave pública
+    pub fn direccion_p2pkh(&self) -> String {
+        self.red.direccion_p2pkh(&self.clave)
+    }
+}
+
+fn serializar_extendida(
+    version: [u8; 4],
+    profundidad: u8,
+    huella_padre: [u8; 4],
+    indice: u32,
+    codigo_cadena: &[u8; 32],
+    clave: &[u8],
+) -> String {
+    let mut bytes = Vec::with_capacity(LARGO_CLAVE_SERIALIZADA);
+    bytes.extend_from_slice(&version);
+    bytes.push(profundidad);
+    bytes.extend_from_slice(&huella_padre);
+    bytes.extend_from_slice(&indice.to_be_bytes());
+    bytes.extend_from_slice(codigo_cadena);
+    bytes.extend_from_slice(clave);
+    codificar_base58check(&bytes)
+}
+
+// decodifica la clave y busca la red por los bytes de versión que devuelve la función recibida
+fn decodificar_extendida(
+    texto: &str,
+    version: fn(&Red) -> [u8; 4],
+) -> Result<(Red, Vec<u8>), NodoBitcoinError> {
+    let bytes = decodificar_base58check(texto.trim())?;
+    if bytes.len() != LARGO_CLAVE_SERIALIZADA {
+        return Err(NodoBitcoinError::ClaveInvalida);
+    }
+    let red = [Red::Mainnet, Red::Testnet]
+        .into_iter()
+        .find(|red| version(red) == bytes[..4])
+        .ok_or(NodoBitcoinError::ClaveInvalida)?;
+    Ok((red, bytes))
+}
+
+fn campos_extendida(bytes: &[u8]) -> (u8, [u8; 4], u32, [u8; 32]) {
+    let mut huella_padre = [0u8; 4];
+    huella_padre.copy_from_slice(&bytes[5..9]);
+    let mut indice = [0u8; 4];
+    indice.copy_from_slice(&bytes[9..13]);
+    let mut codigo_cadena = [0u8; 32];
+    codigo_cadena.copy_from_slice(&bytes[13..45]);
+    (
+        bytes[4],
+        huella_padre,
+        u32::from_be_bytes(indice),
+        codigo_cadena,
+    )
+}
+
+/// Dirección derivada de una cuenta HD
+#[derive(Debug, Clone, PartialEq)]
+pub struct DireccionHD {
+    pub cadena: u32,
+    pub indice: u32,
+    pub direccion: String,
+    pub pk_script: Vec<u8>,
+}
+
+/// Cuenta HD de BIP44: la clave de la cuenta, de la que se derivan la cadena externa con las
+/// direcciones de recepción y la de cambio, y la próxima dirección sin usar de cada una
+/// Las direcciones se derivan de la clave pública extendida, así que se siguen reconociendo
+/// los pagos aunque la clave privada no esté cargada
+#[derive(Debug, Clone, PartialEq)]
+pub struct CuentaHD {
+    pub clave_publica: ClavePublicaExtendida,
+    pub clave_cuenta: Option<ClaveExtendida>,
+    pub proxima_externa: u32,
+    pub proxima_cambio: u32,
+    // direcciones derivadas hasta la próxima sin usar más el límite de hueco en cada cadena
+    direcciones: Vec<DireccionHD>,
+}
+
+impl CuentaHD {
+    pub fn new(
+        clave_cuenta: ClaveExtendida,
+        proxima_externa: u32,
+        proxima_cambio: u32,
+    ) -> Result<CuentaHD, NodoBitcoinError> {
+        let mut cuenta = CuentaHD::desde_clave_publica(
+            clave_cuenta.publica()?,
+            proxima_externa,
+            proxima_cambio,
+        )?;
+        cuenta.clave_cuenta = Some(clave_cuenta);
+        Ok(cuenta)
+    }
+
+    /// Crea la cuenta sin la clave privada, solo para reconocer sus direcciones
+    pub fn desde_clave_publica(
+        clave_publica: ClavePublicaExtendida,
+        proxima_externa: u32,
+        proxima_cambio: u32,
+    ) -> Result<CuentaHD, NodoBitcoinError> {
+        let mut cuenta = CuentaHD {
+            clave_publica,
+            clave_cuenta: None,
+            proxima_externa,
+            proxima_cambio,
+            direcciones: vec![],
+        };
+        cuenta.derivar_faltantes()?;
+        Ok(cuenta)
+    }
+
+    /// Recibe una clave extendida. Si es la maestra deriva la primera cuenta de BIP44 de la red,
+    /// y si no la usa como clave de la cuenta
+    pub fn desde_clave_extendida(clave: ClaveExtendida) -> Result<CuentaHD, NodoBitcoinError> {
+        let clave_cuenta = if clave.profundidad == 0 {
+            clave.derivar_camino(&format!("m/44'/{}'/0'", clave.red.tipo_moneda()))?
+        } else {
+            clave
+        };
+        CuentaHD::new(clave_cuenta, 0, 0)
+    }
+
+    fn derivar_faltantes(&mut self) -> Result<(), NodoBitcoinError> {
+        for (cadena, proxima) in [
+            (CADENA_EXTERNA, self.proxima_externa),
+            (CADENA_CAMBIO, self.proxima_cambio),
+        ] {
+            let clave_cadena = self.clave_publica.derivar_hija(cadena)?;
+            let derivadas = self
+                .direcciones
+                .iter()
+                .filter(|direccion| direccion.cadena == cadena)
+                .count() as u32;
+            for indice in derivadas..proxima.saturating_add(LIMITE_DE_HUECO) {
+                let clave = clave_cadena.derivar_hija(indice)?;
+                let hash = hash160::Hash::hash(&clave.clave);
+                self.direcciones.push(DireccionHD {
+                    cadena,
+                    indice,
+                    pk_script: p2pkh_script_serialized(hash.as_byte_array())?,
+                    direccion: clave.direccion_p2pkh(),
+                });
+            }
+        }
+        self.direcciones
+            .sort_by_key(|direccion| (direccion.cadena, direccion.indice));
+        Ok(())
+    }
+
+    /// Devuelve la clave en formato WIF de la dirección, si la clave privada está cargada
+    pub fn wif(&self, direccion: &DireccionHD) -> Option<String> {
+        let clave_cuenta = self.clave_cuenta.as_ref()?;
+        clave_cuenta
+            .derivar_hija(direccion.cadena)
+            .and_then(|clave_cadena| clave_cadena.derivar_hija(direccion.indice))
+            .map(|clave| clave.wif())
+            .ok()
+    }
+
+    /// Vuelve a cargar la clave privada de la cuenta, que tiene que corresponder a la pública
+    pub fn desbloquear(&mut self, clave_cuenta: ClaveExtendida) -> Result<(), NodoBitcoinError> {
+        if clave_cuenta.publica()? != self.clave_publica {
+            return Err(NodoBitcoinError::ClaveInvalida);
+        }
+        self.clave_cuenta = Some(clave_cuenta);
+        Ok(())
+    }
+
+    /// Saca de memoria la clave privada de la cuenta
+    pub fn bloquear(&mut self) {
+        self.clave_cuenta = None;
+    }
+
+    /// Devuelve la dirección de la cadena con ese índice si ya está derivada
+    pub fn direccion(&self, cadena: u32, indice: u32) -> Option<&DireccionHD> {
+        self.direcciones
+            .iter()
+            .find(|direccion| direccion.cadena == cadena && direccion.indice == indice)
+    }
+
+    /// Devuelve todas las direcciones derivadas de las dos cadenas
+    pub fn direcciones(&self) -> &[DireccionHD] {
+        &self.direcciones
+    }
+
+    /// Busca la dirección derivada que corresponde al script de una salida
+    pub fn buscar_script(&self, pk_script: &[u8]) -> Option<&DireccionHD> {
+        self.direcciones
+            .iter()
+            .find(|direccion| direccion.pk_script == pk_script)
+    }
+
+    /// Devuelve la próxima dirección sin usar de la cadena y la marca como usada
+    pub fn nueva_direccion(&mut self, cadena: u32) -> Result<DireccionHD, NodoBitcoinError> {
+        let indice = match cadena {
+            CADENA_EXTERNA => self.proxima_externa,
+            _ => self.proxima_cambio,
+        };
+        self.marcar_usada(cadena, indice)?;
+        self.direccion(cadena, indice)
+            .cloned()
+            .ok_or(NodoBitcoinError::ClaveInvalida)
+    }
+
+    /// Marca como usada la dirección y las anteriores de la cadena, y deriva las que hagan
+    /// falta para mantener el límite de hueco
+    /// Devuelve si cambió la próxima dirección sin usar
+    pub fn marcar_usada(&mut self, cadena: u32, indice: u32) -> Result<bool, NodoBitcoinError> {
+        let proxima = match cadena {
+            CADENA_EXTERNA => &mut self.proxima_externa,
+            _ => &mut self.proxima_cambio,
+        };
+        if indice < *proxima {
+            return Ok(false);
+        }
+        *proxima = indice + 1;
+        self.derivar_faltantes()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn semilla() -> Vec<u8> {
+        (0u8..16).collect()
+    }
+
+    #[test]
+    fn test_vector_1_de_bip32() {
+        let maestra = ClaveExtendida::desde_semilla(&semilla(), Red::Mainnet).unwrap();
+        assert_eq!(
+            maestra.serializar(),
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+        );
+        let hija = maestra.derivar_hija(INDICE_ENDURECIDO).unwrap();
+        assert_eq!(
+            hija.serializar(),
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7"
+        );
+        let nieta = maestra.derivar_camino("m/0'/1").unwrap();
+        assert_eq!(
+            nieta.serializar(),
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs"
+        );
+        assert_eq!(
+            ClaveExtendida::desde_string(&nieta.serializar()).unwrap(),
+            nieta
+        );
+        assert_eq!(
+            maestra.publica().unwrap().serializar(),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"
+        );
+        // la derivación pública de una hija no endurecida coincide con la privada
+        let nieta_publica = hija.publica().unwrap().derivar_hija(1).unwrap();
+        assert_eq!(
+            nieta_publica.serializar(),
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"
+        );
+        assert_eq!(nieta_publica, nieta.publica().unwrap());
+        assert_eq!(
+            hija.publica().unwrap().derivar_hija(INDICE_ENDURECIDO),
+            Err(NodoBitcoinError::CaminoDeDerivacionInvalido)
+        );
+        assert_eq!(
+            maestra.derivar_camino("44'/0"),
+            Err(NodoBitcoinError::CaminoDeDerivacionInvalido)
+        );
+    }
+
+    #[test]
+    fn test_cuenta_hd_deriva_direcciones_nuevas() {
+        let maestra = ClaveExtendida::desde_semilla(&semilla(), Red::Testnet).unwrap();
+        assert!(maestra.serializar().starts_with("tprv"));
+        let mut cuenta = CuentaHD::desde_clave_extendida(maestra).unwrap();
+        assert_eq!(cuenta.clave_publica.profundidad, 3);
+        assert_eq!(cuenta.direcciones().len(), 2 * LIMITE_DE_HUECO as usize);
+
+        let primera = cuenta.nueva_direccion(CADENA_EXTERNA).unwrap();
+        let segunda = cuenta.nueva_direccion(CADENA_EXTERNA).unwrap();
+        let cambio = cuenta.nueva_direccion(CADENA_CAMBIO).unwrap();
+        assert_eq!((primera.indice, segunda.indice, cambio.indice), (0, 1, 0));
+        assert_ne!(primera.direccion, segunda.direccion);
+        assert!(primera.direccion.starts_with('m') || primera.direccion.starts_with('n'));
+
+        // un pago a una dirección dentro del hueco la marca como usada y deriva más
+        let lejana = cuenta.direccion(CADENA_EXTERNA, 15).unwrap().clone();
+        assert_eq!(cuenta.buscar_script(&lejana.pk_script), Some(&lejana));
+        assert!(cuenta.marcar_usada(CADENA_EXTERNA, 15).unwrap());
+        assert_eq!(cuenta.proxima_externa, 16);
+        assert!(cuenta.direccion(CADENA_EXTERNA, 35).is_some());
+        assert!(!cuenta.marcar_usada(CADENA_EXTERNA, 3).unwrap());
+
+        // sin la clave privada deriva las mismas direcciones pero no puede firmar
+        let clave_publica =
+            ClavePublicaExtendida::desde_string(&cuenta.clave_publica.serializar()).unwrap();
+        assert!(cuenta.clave_publica.serializar().starts_with("tpub"));
+        let mut bloqueada = CuentaHD::desde_clave_publica(clave_publica, 16, 1).unwrap();
+        assert_eq!(bloqueada.direcciones(), cuenta.direcciones());
+        assert_eq!(bloqueada.wif(&lejana), None);
+        let clave_cuenta = cuenta.clave_cuenta.clone().unwrap();
+        assert_eq!(
+            clave_cuenta
+                .derivar_camino("m/0/15")
+                .map(|clave| clave.wif())
+                .ok(),
+            cuenta.wif(&lejana)
+        );
+        bloqueada.desbloquear(clave_cuenta).unwrap();
+        assert_eq!(bloqueada, cuenta);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod billetera;
+pub mod bip39;
+pub mod cifrado;
+pub mod claves;
+pub mod hd;
+pub mod multisig;
+pub mod psbt;
 pub mod transaction_manager;
 pub mod user;
 pub mod uxto_set;
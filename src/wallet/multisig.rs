@@ -0,0 +1,283 @@
+use crate::{
+    blockchain::politica::leer_pushes,
+    common::decoder::{
+        codificar_direccion_segwit, p2sh_script_serialized, script_serialized,
+        segwit_script_serialized,
+    },
+    common::red::{codificar_base58check, Red},
+    errores::NodoBitcoinError,
+    wallet::cifrado::{a_hexa, desde_hexa},
+};
+use bitcoin_hashes::{hash160, sha256, Hash};
+use secp256k1::PublicKey;
+
+/// Máxima cantidad de participantes de un multisig. Con 15 claves comprimidas el redeem
+/// script todavía entra en un push de 520 bytes del script de firma P2SH
+pub const MAXIMO_PARTICIPANTES: usize = 15;
+
+const OP_1: u8 = 0x51;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// Cómo le pagan las salidas al redeem script: con su hash160 en P2SH o con su sha256 en un
+/// testigo P2WSH
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoMultisig {
+    P2SH,
+    P2WSH,
+}
+
+/// Multisig M de N: se gasta con las firmas de `requeridas` de las claves públicas de los
+/// participantes, que van en el orden del redeem script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Multisig {
+    pub requeridas: usize,
+    pub claves_publicas: Vec<Vec<u8>>,
+    pub tipo: TipoMultisig,
+}
+
+impl Multisig {
+    /// Valida que 1 <= M <= N <= 15 y que las claves sean públicas comprimidas y distintas
+    pub fn new(
+        requeridas: usize,
+        claves_publicas: Vec<Vec<u8>>,
+        tipo: TipoMultisig,
+    ) -> Result<Multisig, NodoBitcoinError> {
+        let participantes = claves_publicas.len();
+        if requeridas == 0 || requeridas > participantes || participantes > MAXIMO_PARTICIPANTES {
+            return Err(NodoBitcoinError::MultisigInvalido);
+        }
+        for (indice, clave) in claves_publicas.iter().enumerate() {
+            if clave.len() != 33
+                || PublicKey::from_slice(clave).is_err()
+                || claves_publicas[..indice].contains(clave)
+            {
+                return Err(NodoBitcoinError::MultisigInvalido);
+            }
+        }
+        Ok(Multisig {
+            requeridas,
+            claves_publicas,
+            tipo,
+        })
+    }
+
+    /// Redeem script OP_M <clave>... OP_N OP_CHECKMULTISIG. En P2WSH es el witness script
+    pub fn redeem_script(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        let mut script = vec![OP_1 + self.requeridas as u8 - 1];
+        for clave in &self.claves_publicas {
+            script.extend(script_serialized(clave)?);
+        }
+        script.push(OP_1 + self.claves_publicas.len() as u8 - 1);
+        script.push(OP_CHECKMULTISIG);
+        Ok(script)
+    }
+
+    /// Lee el multisig de su redeem script, por ejemplo el que trae una PSBT. El tipo dice si
+    /// las salidas le pagan en P2SH o en P2WSH, que no está en el script
+    pub fn desde_redeem_script(
+        script: &[u8],
+        tipo: TipoMultisig,
+    ) -> Result<Multisig, NodoBitcoinError> {
+        let (requeridas, claves) = match script {
+            [requeridas, claves @ .., _, OP_CHECKMULTISIG] if *requeridas >= OP_1 => {
+                ((requeridas - OP_1 + 1) as usize, claves)
+            }
+            _ => return Err(NodoBitcoinError::MultisigInvalido),
+        };
+        let claves = leer_pushes(claves)
+            .ok_or(NodoBitcoinError::MultisigInvalido)?
+            .into_iter()
+            .map(|clave| clave.to_vec())
+            .collect();
+        let multisig = Multisig::new(requeridas, claves, tipo)?;
+        // con otro OP_N o pushes no mínimos sería otro script
+        if multisig.redeem_script()? != script {
+            return Err(NodoBitcoinError::MultisigInvalido);
+        }
+        Ok(multisig)
+    }
+
+    /// Script de las salidas que le pagan al multisig
+    pub fn pk_script(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        let redeem_script = self.redeem_script()?;
+        match self.tipo {
+            TipoMultisig::P2SH => {
+                p2sh_script_serialized(hash160::Hash::hash(&redeem_script).as_byte_array())
+            }
+            TipoMultisig::P2WSH => {
+                segwit_script_serialized(0, sha256::Hash::hash(&redeem_script).as_byte_array())
+            }
+        }
+    }
+
+    /// Dirección del multisig en la red: base58 con el prefijo P2SH o bech32 para P2WSH
+    pub fn direccion(&self, red: Red) -> Result<String, NodoBitcoinError> {
+        let redeem_script = self.redeem_script()?;
+        match self.tipo {
+            TipoMultisig::P2SH => {
+                let mut bytes = vec![red.prefijo_p2sh()];
+                bytes.extend_from_slice(hash160::Hash::hash(&redeem_script).as_byte_array());
+                Ok(codificar_base58check(&bytes))
+            }
+            TipoMultisig::P2WSH => codificar_direccion_segwit(
+                red.hrp_segwit(),
+                0,
+                sha256::Hash::hash(&redeem_script).as_byte_array(),
+            ),
+        }
+    }
+
+    /// Serializa el multisig como `M/tipo/clave,clave,...` con las claves en hexadecimal, que
+    /// es como se guarda en el archivo de cuentas
+    pub fn serializar(&self) -> String {
+        let tipo = match self.tipo {
+            TipoMultisig::P2SH => "p2sh",
+            TipoMultisig::P2WSH => "p2wsh",
+        };
+        let claves: Vec<String> = self
+            .claves_publicas
+            .iter()
+            .map(|clave| a_hexa(clave))
+            .collect();
+        format!("{}/{}/{}", self.requeridas, tipo, claves.join(","))
+    }
+
+    /// Lee un multisig serializado con `serializar`
+    pub fn desde_string(texto: &str) -> Result<Multisig, NodoBitcoinError> {
+        let partes: Vec<&str> = texto.split('/').collect();
+        if partes.len() != 3 {
+            return Err(NodoBitcoinError::MultisigInvalido);
+        }
+        let requeridas = partes[0]
+            .parse::<usize>()
+            .map_err(|_| NodoBitcoinError::MultisigInvalido)?;
+        let tipo = match partes[1] {
+            "p2sh" => TipoMultisig::P2SH,
+            "p2wsh" => TipoMultisig::P2WSH,
+            _ => return Err(NodoBitcoinError::MultisigInvalido),
+        };
+        let claves = partes[2]
+            .split(',')
+            .map(desde_hexa)
+            .collect::<Result<Vec<Vec<u8>>, NodoBitcoinError>>()?;
+        Multisig::new(requeridas, claves, tipo)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::blockchain::transaction::{Transaction, TxIn, TxOut};
+    use crate::common::decoder::point_sec;
+    use crate::common::uint256::Uint256;
+    use crate::wallet::user::Account;
+    use crate::wallet::uxto_set::Utxo;
+
+    /// Claves privadas 1, 2 y 3 de los multisig de prueba
+    pub(crate) fn claves_privadas() -> Vec<[u8; 32]> {
+        (1..=3u8)
+            .map(|privada| {
+                let mut bytes = [0u8; 32];
+                bytes[31] = privada;
+                bytes
+            })
+            .collect()
+    }
+
+    /// Cuenta 2 de 3 con las claves privadas 1, 2 y 3, firmando con la clave indicada
+    pub(crate) fn cuenta_multisig(tipo: TipoMultisig, clave: u8) -> Account {
+        let claves = claves_privadas();
+        let publicas = claves
+            .iter()
+            .map(|privada| point_sec(privada).unwrap())
+            .collect();
+        let multisig = Multisig::new(2, publicas, tipo).unwrap();
+        let wif = Red::Testnet.codificar_wif(&claves[clave as usize - 1]);
+        Account::desde_multisig(multisig, &wif, format!("multisig {}", clave)).unwrap()
+    }
+
+    /// Utxo de una transacción previa que le paga el valor a la dirección de la cuenta
+    pub(crate) fn utxo_de(account: &Account, value: u64) -> Utxo {
+        let tx_out = TxOut::new(value, account.public_key.clone()).unwrap();
+        let previous_tx = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([2; 32]), 0)],
+            vec![tx_out.clone()],
+            0,
+        )
+        .unwrap();
+        Utxo {
+            tx_id: previous_tx.txid().unwrap(),
+            output_index: 0,
+            pk_script: tx_out.pk_script.clone(),
+            tx_out,
+            tx: previous_tx,
+        }
+    }
+
+    // claves públicas de las claves privadas 1, 2 y 3: G, 2G y 3G
+    fn claves() -> Vec<Vec<u8>> {
+        [
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        ]
+        .iter()
+        .map(|clave| desde_hexa(clave).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn test_direcciones_multisig() {
+        let p2sh = Multisig::new(2, claves(), TipoMultisig::P2SH).unwrap();
+        assert_eq!(
+            a_hexa(&p2sh.redeem_script().unwrap()),
+            "52210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817982102c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee52102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f953ae"
+        );
+        assert_eq!(
+            a_hexa(&p2sh.pk_script().unwrap()),
+            "a91415fc0754e73eb85d1cbce08786fadb7320ecb8dc87"
+        );
+        assert_eq!(
+            p2sh.direccion(Red::Mainnet).unwrap(),
+            "33hG2q39jRi2NqicRJB4ggY1J8EJm97Szz"
+        );
+        assert_eq!(
+            p2sh.direccion(Red::Testnet).unwrap(),
+            "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS"
+        );
+
+        let p2wsh = Multisig::new(2, claves(), TipoMultisig::P2WSH).unwrap();
+        assert_eq!(
+            p2wsh.direccion(Red::Testnet).unwrap(),
+            "tb1qztp0l0rwc8846ardl02fkyrrx43p96j47scz8l7qz3vnfteqc4equpkpz5"
+        );
+        assert_eq!(Multisig::desde_string(&p2wsh.serializar()).unwrap(), p2wsh);
+        assert_eq!(
+            Multisig::desde_redeem_script(&p2wsh.redeem_script().unwrap(), TipoMultisig::P2WSH)
+                .unwrap(),
+            p2wsh
+        );
+    }
+
+    #[test]
+    fn test_multisig_invalido() {
+        assert_eq!(
+            Multisig::new(3, claves()[..2].to_vec(), TipoMultisig::P2SH),
+            Err(NodoBitcoinError::MultisigInvalido)
+        );
+        assert_eq!(
+            Multisig::new(0, claves(), TipoMultisig::P2WSH),
+            Err(NodoBitcoinError::MultisigInvalido)
+        );
+        let repetidas = vec![claves()[0].clone(), claves()[0].clone()];
+        assert_eq!(
+            Multisig::new(1, repetidas, TipoMultisig::P2WSH),
+            Err(NodoBitcoinError::MultisigInvalido)
+        );
+        // sin comprimir
+        assert_eq!(
+            Multisig::new(1, vec![vec![4; 65]], TipoMultisig::P2WSH),
+            Err(NodoBitcoinError::MultisigInvalido)
+        );
+    }
+}
@@ -0,0 +1,870 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    blockchain::{
+        politica::{tipo_de_script, TipoScript},
+        transaction::{Transaction, TxIn, TxOut},
+    },
+    common::{
+        base64::{codificar_base64, decodificar_base64},
+        decoder::{
+            p2pkh_script_serialized, p2sh_script_serialized, point_sec, script_serialized,
+            segwit_script_serialized, signature_der, wif_to_hex,
+        },
+        uint256::Uint256,
+        utils_bytes::{self, encode_varint},
+    },
+    errores::NodoBitcoinError,
+    wallet::{
+        multisig::{Multisig, TipoMultisig},
+        user::Account,
+        uxto_set::Utxo,
+    },
+};
+use bitcoin_hashes::{hash160, sha256, Hash};
+
+/// Bytes mágicos con los que empieza toda PSBT (BIP174): "psbt" y 0xff
+const MAGIC_PSBT: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+
+const GLOBAL_TX_SIN_FIRMAR: u8 = 0x00;
+
+const ENTRADA_UTXO_PREVIA: u8 = 0x00;
+const ENTRADA_UTXO_TESTIGO: u8 = 0x01;
+const ENTRADA_FIRMA_PARCIAL: u8 = 0x02;
+const ENTRADA_SIGHASH: u8 = 0x03;
+const ENTRADA_REDEEM_SCRIPT: u8 = 0x04;
+const ENTRADA_WITNESS_SCRIPT: u8 = 0x05;
+const ENTRADA_SCRIPT_SIG_FINAL: u8 = 0x07;
+const ENTRADA_TESTIGO_FINAL: u8 = 0x08;
+
+const SALIDA_REDEEM_SCRIPT: u8 = 0x00;
+const SALIDA_WITNESS_SCRIPT: u8 = 0x01;
+
+/// La wallet sólo firma con SIGHASH_ALL
+const SIGHASH_ALL: u32 = 1;
+
+/// Pares de clave y valor de un mapa de la PSBT, en el orden en que se leyeron
+type ParesClaveValor = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Script de firma y testigo finales de un input
+type DatosFinales = (Option<Vec<u8>>, Option<Vec<Vec<u8>>>);
+
+/// Datos de un input de la PSBT: la salida que gasta, los scripts para firmarla y las firmas
+/// que van juntando los participantes hasta que se finaliza
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EntradaPsbt {
+    pub utxo_previa: Option<Transaction>,
+    pub utxo_testigo: Option<TxOut>,
+    pub firmas_parciales: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub sighash: Option<u32>,
+    pub redeem_script: Option<Vec<u8>>,
+    pub witness_script: Option<Vec<u8>>,
+    pub script_sig_final: Option<Vec<u8>>,
+    pub testigo_final: Option<Vec<Vec<u8>>>,
+    pub desconocidos: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Datos de una salida de la PSBT, los scripts de las salidas que le pagan a un multisig
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SalidaPsbt {
+    pub redeem_script: Option<Vec<u8>>,
+    pub witness_script: Option<Vec<u8>>,
+    pub desconocidos: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Transacción parcialmente firmada de BIP174. Lleva la transacción sin firmar con todo lo
+/// que necesita cada participante para firmarla sin conocer la blockchain, y se puede pasar
+/// entre wallets distintas. Los campos que la wallet no usa se conservan como desconocidos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Psbt {
+    pub tx: Transaction,
+    pub entradas: Vec<EntradaPsbt>,
+    pub salidas: Vec<SalidaPsbt>,
+    pub desconocidos: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Psbt {
+    /// Arma la PSBT de la transacción sin firmar que gasta las utxos de la cuenta. Los inputs
+    /// segwit llevan la salida que gastan y los legacy la transacción previa entera, y si la
+    /// cuenta es multisig se agregan los scripts a los inputs y al cambio
+    pub fn crear(
+        tx: Transaction,
+        utxos: &[Utxo],
+        account: &Account,
+    ) -> Result<Psbt, NodoBitcoinError> {
+        if tx.input.iter().any(|tx_in| !esta_vacio(tx_in)) {
+            return Err(NodoBitcoinError::PsbtInvalida);
+        }
+        let multisig = match &account.multisig {
+            Some(multisig) => Some((multisig, multisig.pk_script()?, multisig.redeem_script()?)),
+            None => None,
+        };
+        let mut entradas = vec![];
+        for tx_in in &tx.input {
+            let utxo = utxos
+                .iter()
+                .find(|utxo| {
+                    utxo.tx_id.get_bytes() == tx_in.previous_output.hash
+                        && utxo.output_index == tx_in.previous_output.index
+                })
+                .ok_or(NodoBitcoinError::InputsDesconocidos)?;
+            let mut entrada = EntradaPsbt::default();
+            match tipo_de_script(&utxo.pk_script) {
+                TipoScript::P2WPKH | TipoScript::P2WSH => {
+                    entrada.utxo_testigo = Some(utxo.tx_out.clone())
+                }
+                _ => entrada.utxo_previa = Some(utxo.tx.clone()),
+            }
+            if let Some((multisig, pk_script, redeem_script)) = &multisig {
+                if utxo.pk_script == *pk_script {
+                    agregar_scripts(
+                        multisig,
+                        redeem_script,
+                        &mut entrada.redeem_script,
+                        &mut entrada.witness_script,
+                    );
+                }
+            }
+            entradas.push(entrada);
+        }
+        let mut salidas = vec![];
+        for tx_out in &tx.output {
+            let mut salida = SalidaPsbt::default();
+            if let Some((multisig, pk_script, redeem_script)) = &multisig {
+                if tx_out.pk_script == *pk_script {
+                    agregar_scripts(
+                        multisig,
+                        redeem_script,
+                        &mut salida.redeem_script,
+                        &mut salida.witness_script,
+                    );
+                }
+            }
+            salidas.push(salida);
+        }
+        Ok(Psbt {
+            tx,
+            entradas,
+            salidas,
+            desconocidos: BTreeMap::new(),
+        })
+    }
+
+    /// Serializa la PSBT: los bytes mágicos, el mapa global y un mapa por cada input y por
+    /// cada salida. Cada mapa es una lista de clave y valor con sus largos y termina en cero
+    pub fn serializar(&self) -> Result<Vec<u8>, NodoBitcoinError> {
+        let mut bytes = MAGIC_PSBT.to_vec();
+        escribir(
+            &mut bytes,
+            &[GLOBAL_TX_SIN_FIRMAR],
+            &self.tx.serialize_sin_testigos()?,
+        );
+        escribir_desconocidos(&mut bytes, &self.desconocidos);
+        for entrada in &self.entradas {
+            if let Some(tx) = &entrada.utxo_previa {
+                escribir(&mut bytes, &[ENTRADA_UTXO_PREVIA], &tx.serialize()?);
+            }
+            if let Some(tx_out) = &entrada.utxo_testigo {
+                escribir(&mut bytes, &[ENTRADA_UTXO_TESTIGO], &tx_out.serialize()?);
+            }
+            for (clave_publica, firma) in &entrada.firmas_parciales {
+                let clave = [&[ENTRADA_FIRMA_PARCIAL][..], clave_publica].concat();
+                escribir(&mut bytes, &clave, firma);
+            }
+            if let Some(sighash) = entrada.sighash {
+                escribir(&mut bytes, &[ENTRADA_SIGHASH], &sighash.to_le_bytes());
+            }
+            if let Some(script) = &entrada.redeem_script {
+                escribir(&mut bytes, &[ENTRADA_REDEEM_SCRIPT], script);
+            }
+            if let Some(script) = &entrada.witness_script {
+                escribir(&mut bytes, &[ENTRADA_WITNESS_SCRIPT], script);
+            }
+            if let Some(script) = &entrada.script_sig_final {
+                escribir(&mut bytes, &[ENTRADA_SCRIPT_SIG_FINAL], script);
+            }
+            if let Some(testigo) = &entrada.testigo_final {
+                let mut tx_in = TxIn::new(Uint256::from_be_bytes([0; 32]), 0);
+                tx_in.witness = testigo.clone();
+                escribir(
+                    &mut bytes,
+                    &[ENTRADA_TESTIGO_FINAL],
+                    &tx_in.serialize_witness(),
+                );
+            }
+            escribir_desconocidos(&mut bytes, &entrada.desconocidos);
+        }
+        for salida in &self.salidas {
+            if let Some(script) = &salida.redeem_script {
+                escribir(&mut bytes, &[SALIDA_REDEEM_SCRIPT], script);
+            }
+            if let Some(script) = &salida.witness_script {
+                escribir(&mut bytes, &[SALIDA_WITNESS_SCRIPT], script);
+            }
+            escribir_desconocidos(&mut bytes, &salida.desconocidos);
+        }
+        Ok(bytes)
+    }
+
+    /// Lee una PSBT serializada. La transacción global tiene que estar sin firmar y tiene que
+    /// haber un mapa por cada input y cada salida, sin claves repetidas
+    pub fn deserializar(bytes: &[u8]) -> Result<Psbt, NodoBitcoinError> {
+        if !bytes.starts_with(&MAGIC_PSBT) {
+            return Err(NodoBitcoinError::PsbtInvalida);
+        }
+        let mut offset = MAGIC_PSBT.len();
+
+        let mut tx = None;
+        let mut desconocidos = BTreeMap::new();
+        for (clave, valor) in leer_mapa(bytes, &mut offset)? {
+            if clave == [GLOBAL_TX_SIN_FIRMAR] {
+                let tx_sin_firmar = leer_tx(&valor)?;
+                if tx_sin_firmar.input.iter().any(|tx_in| !esta_vacio(tx_in)) {
+                    return Err(NodoBitcoinError::PsbtInvalida);
+                }
+                tx = Some(tx_sin_firmar);
+            } else {
+                desconocidos.insert(clave, valor);
+            }
+        }
+        let tx = tx.ok_or(NodoBitcoinError::PsbtInvalida)?;
+
+        let mut entradas = vec![];
+        for _ in 0..tx.input.len() {
+            entradas.push(leer_entrada(leer_mapa(bytes, &mut offset)?)?);
+        }
+        let mut salidas = vec![];
+        for _ in 0..tx.output.len() {
+            let mut salida = SalidaPsbt::default();
+            for (clave, valor) in leer_mapa(bytes, &mut offset)? {
+                match clave[..] {
+                    [SALIDA_REDEEM_SCRIPT] => salida.redeem_script = Some(valor),
+                    [SALIDA_WITNESS_SCRIPT] => salida.witness_script = Some(valor),
+                    _ => {
+                        salida.desconocidos.insert(clave, valor);
+                    }
+                }
+            }
+            salidas.push(salida);
+        }
+        if offset != bytes.len() {
+            return Err(NodoBitcoinError::PsbtInvalida);
+        }
+        Ok(Psbt {
+            tx,
+            entradas,
+            salidas,
+            desconocidos,
+        })
+    }
+
+    /// Devuelve la PSBT en base64, que es como la intercambian las wallets
+    pub fn a_base64(&self) -> Result<String, NodoBitcoinError> {
+        Ok(codificar_base64(&self.serializar()?))
+    }
+
+    pub fn desde_base64(texto: &str) -> Result<Psbt, NodoBitcoinError> {
+        let bytes = decodificar_base64(texto).map_err(|_| NodoBitcoinError::PsbtInvalida)?;
+        Psbt::deserializar(&bytes)
+    }
+
+    /// Agrega las firmas de la cuenta a los inputs que puede firmar: los que gastan salidas
+    /// P2PKH o P2WPKH de sus claves y los de multisig P2SH o P2WSH en los que participa
+    /// Devuelve la cantidad de inputs que firmó
+    pub fn firmar(&mut self, account: &Account) -> Result<usize, NodoBitcoinError> {
+        let mut firmados = 0;
+        for index in 0..self.entradas.len() {
+            let entrada = &self.entradas[index];
+            if esta_finalizada(entrada) || entrada.sighash.unwrap_or(SIGHASH_ALL) != SIGHASH_ALL {
+                continue;
+            }
+            let tx_out = self.salida_gastada(index)?;
+            let private_key_hexa = match account.wif_para_script(&tx_out.pk_script) {
+                Some(private_key_wif) => wif_to_hex(&private_key_wif)?,
+                None => continue,
+            };
+            let sec = point_sec(&private_key_hexa)?;
+            let sign_hash = match self.hash_a_firmar(index, &tx_out, &sec)? {
+                Some(sign_hash) => sign_hash,
+                None => continue,
+            };
+            let signature_der = signature_der(&private_key_hexa, &sign_hash);
+            let sig = [
+                signature_der.serialize_der().as_ref(),
+                &[SIGHASH_ALL as u8][..],
+            ]
+            .concat();
+            self.entradas[index].firmas_parciales.insert(sec, sig);
+            firmados += 1;
+        }
+        Ok(firmados)
+    }
+
+    /// Junta en la PSBT los datos y las firmas de otra PSBT de la misma transacción, por
+    /// ejemplo la que firmó otro participante del multisig
+    pub fn combinar(&mut self, otra: &Psbt) -> Result<(), NodoBitcoinError> {
+        if self.tx.serialize_sin_testigos()? != otra.tx.serialize_sin_testigos()? {
+            return Err(NodoBitcoinError::PsbtInvalida);
+        }
+        for (entrada, otra_entrada) in self.entradas.iter_mut().zip(&otra.entradas) {
+            let otra_entrada = otra_entrada.clone();
+            entrada.utxo_previa = entrada.utxo_previa.take().or(otra_entrada.utxo_previa);
+            entrada.utxo_testigo = entrada.utxo_testigo.take().or(otra_entrada.utxo_testigo);
+            entrada.sighash = entrada.sighash.or(otra_entrada.sighash);
+            entrada.redeem_script = entrada.redeem_script.take().or(otra_entrada.redeem_script);
+            entrada.witness_script = entrada
+                .witness_script
+                .take()
+                .or(otra_entrada.witness_script);
+            entrada.script_sig_final = entrada
+                .script_sig_final
+                .take()
+                .or(otra_entrada.script_sig_final);
+            entrada.testigo_final = entrada.testigo_final.take().or(otra_entrada.testigo_final);
+            entrada
+                .firmas_parciales
+                .extend(otra_entrada.firmas_parciales);
+            entrada.desconocidos.extend(otra_entrada.desconocidos);
+            if esta_finalizada(entrada) {
+                limpiar_firmas(entrada);
+            }
+        }
+        for (salida, otra_salida) in self.salidas.iter_mut().zip(&otra.salidas) {
+            let otra_salida = otra_salida.clone();
+            salida.redeem_script = salida.redeem_script.take().or(otra_salida.redeem_script);
+            salida.witness_script = salida.witness_script.take().or(otra_salida.witness_script);
+            salida.desconocidos.extend(otra_salida.desconocidos);
+        }
+        self.desconocidos.extend(otra.desconocidos.clone());
+        Ok(())
+    }
+
+    /// Arma el script de firma o el testigo final de los inputs que ya tienen las firmas que
+    /// necesitan, y les saca los datos que sólo servían para firmar
+    /// Devuelve si quedaron todos los inputs finalizados
+    pub fn finalizar(&mut self) -> Result<bool, NodoBitcoinError> {
+        let mut completa = true;
+        for index in 0..self.entradas.len() {
+            if esta_finalizada(&self.entradas[index]) {
+                continue;
+            }
+            let tx_out = self.salida_gastada(index)?;
+            let entrada = &mut self.entradas[index];
+            match datos_finales(entrada, &tx_out)? {
+                Some((script_sig, testigo)) => {
+                    entrada.script_sig_final = script_sig;
+                    entrada.testigo_final = testigo;
+                    limpiar_firmas(entrada);
+                }
+                None => completa = false,
+            }
+        }
+        Ok(completa)
+    }
+
+    /// Devuelve la transacción firmada de una PSBT con todos los inputs finalizados
+    pub fn extraer(&self) -> Result<Transaction, NodoBitcoinError> {
+        let mut tx = self.tx.clone();
+        for (tx_in, entrada) in tx.input.iter_mut().zip(&self.entradas) {
+            if !esta_finalizada(entrada) {
+                return Err(NodoBitcoinError::PsbtIncompleta);
+            }
+            let script_sig = entrada.script_sig_final.clone().unwrap_or_default();
+            tx_in.script_bytes_amount = encode_varint(script_sig.len()).len();
+            tx_in.script_bytes = script_sig.len();
+            tx_in.signature_script = script_sig;
+            tx_in.witness = entrada.testigo_final.clone().unwrap_or_default();
+        }
+        Ok(tx)
+    }
+
+    // devuelve la salida que gasta el input, la de la transacción previa tiene que ser la del
+    // outpoint del input
+    fn salida_gastada(&self, index: usize) -> Result<TxOut, NodoBitcoinError> {
+        let entrada = &self.entradas[index];
+        let previous_output = &self.tx.input[index].previous_output;
+        if let Some(tx_out) = &entrada.utxo_testigo {
+            return Ok(tx_out.clone());
+        }
+        let previous_tx = entrada
+            .utxo_previa
+            .as_ref()
+            .ok_or(NodoBitcoinError::InputsDesconocidos)?;
+        if previous_tx.txid()?.get_bytes() != previous_output.hash {
+            return Err(NodoBitcoinError::PsbtInvalida);
+        }
+        previous_tx
+            .output
+            .get(previous_output.index as usize)
+            .cloned()
+            .ok_or(NodoBitcoinError::PsbtInvalida)
+    }
+
+    // calcula el hash que firma la clave en el input, o None si la clave no puede firmarlo
+    fn hash_a_firmar(
+        &self,
+        index: usize,
+        tx_out: &TxOut,
+        sec: &[u8],
+    ) -> Result<Option<Vec<u8>>, NodoBitcoinError> {
+        let entrada = &self.entradas[index];
+        let hash_clave = hash160::Hash::hash(sec);
+        let script_p2pkh = p2pkh_script_serialized(hash_clave.as_byte_array())?;
+        let sign_hash = match tipo_de_script(&tx_out.pk_script) {
+            TipoScript::P2PKH if tx_out.pk_script == script_p2pkh => {
+                Some(self.tx.sig_hash_legacy(index, &tx_out.pk_script)?)
+            }
+            TipoScript::P2WPKH
+                if tx_out.pk_script == segwit_script_serialized(0, hash_clave.as_byte_array())? =>
+            {
+                Some(
+                    self.tx
+                        .sig_hash_segwit(index, &script_p2pkh, tx_out.value)?,
+                )
+            }
+            TipoScript::P2SH => match multisig_de_la_entrada(entrada, tx_out, TipoMultisig::P2SH) {
+                Some((multisig, script)) if multisig.claves_publicas.iter().any(|c| c == sec) => {
+                    Some(self.tx.sig_hash_legacy(index, &script)?)
+                }
+                _ => None,
+            },
+            TipoScript::P2WSH => {
+                match multisig_de_la_entrada(entrada, tx_out, TipoMultisig::P2WSH) {
+                    Some((multisig, script))
+                        if multisig.claves_publicas.iter().any(|c| c == sec) =>
+                    {
+                        Some(self.tx.sig_hash_segwit(index, &script, tx_out.value)?)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        Ok(sign_hash)
+    }
+}
+
+// agrega a un input o una salida del multisig el script que necesita para gastarla
+fn agregar_scripts(
+    multisig: &Multisig,
+    script: &[u8],
+    redeem_script: &mut Option<Vec<u8>>,
+    witness_script: &mut Option<Vec<u8>>,
+) {
+    match multisig.tipo {
+        TipoMultisig::P2SH => *redeem_script = Some(script.to_vec()),
+        TipoMultisig::P2WSH => *witness_script = Some(script.to_vec()),
+    }
+}
+
+// devuelve el multisig del redeem script o del witness script del input, si es el script
+// del que la salida gastada tiene el hash
+fn multisig_de_la_entrada(
+    entrada: &EntradaPsbt,
+    tx_out: &TxOut,
+    tipo: TipoMultisig,
+) -> Option<(Multisig, Vec<u8>)> {
+    let script = match tipo {
+        TipoMultisig::P2SH => entrada.redeem_script.clone()?,
+        TipoMultisig::P2WSH => entrada.witness_script.clone()?,
+    };
+    let multisig = Multisig::desde_redeem_script(&script, tipo).ok()?;
+    let pk_script = match tipo {
+        TipoMultisig::P2SH => p2sh_script_serialized(hash160::Hash::hash(&script).as_byte_array()),
+        TipoMultisig::P2WSH => {
+            segwit_script_serialized(0, sha256::Hash::hash(&script).as_byte_array())
+        }
+    }
+    .ok()?;
+    (pk_script == tx_out.pk_script).then_some((multisig, script))
+}
+
+// devuelve el script de firma y el testigo finales del input si tiene las firmas que
+// necesita la salida que gasta
+fn datos_finales(
+    entrada: &EntradaPsbt,
+    tx_out: &TxOut,
+) -> Result<Option<DatosFinales>, NodoBitcoinError> {
+    let finales = match tipo_de_script(&tx_out.pk_script) {
+        TipoScript::P2PKH => match firma_de_clave(entrada, &tx_out.pk_script)? {
+            Some(elementos) => Some((Some(armar_script(&elementos)?), None)),
+            None => None,
+        },
+        TipoScript::P2WPKH => {
+            firma_de_clave(entrada, &tx_out.pk_script)?.map(|elementos| (None, Some(elementos)))
+        }
+        TipoScript::P2SH => {
+            match multisig_de_la_entrada(entrada, tx_out, TipoMultisig::P2SH)
+                .and_then(|(multisig, script)| elementos_multisig(entrada, &multisig, script))
+            {
+                Some(elementos) => Some((Some(armar_script(&elementos)?), None)),
+                None => None,
+            }
+        }
+        TipoScript::P2WSH => multisig_de_la_entrada(entrada, tx_out, TipoMultisig::P2WSH)
+            .and_then(|(multisig, script)| elementos_multisig(entrada, &multisig, script))
+            .map(|elementos| (None, Some(elementos))),
+        _ => None,
+    };
+    Ok(finales)
+}
+
+// busca la firma de la clave a la que le paga la salida P2PKH o P2WPKH y devuelve la firma
+// y la clave, que es lo que va en el script de firma o en el testigo
+fn firma_de_clave(
+    entrada: &EntradaPsbt,
+    pk_script: &[u8],
+) -> Result<Option<Vec<Vec<u8>>>, NodoBitcoinError> {
+    for (clave_publica, firma) in &entrada.firmas_parciales {
+        let hash_clave = hash160::Hash::hash(clave_publica);
+        if p2pkh_script_serialized(hash_clave.as_byte_array())? == pk_script
+            || segwit_script_serialized(0, hash_clave.as_byte_array())? == pk_script
+        {
+            return Ok(Some(vec![firma.clone(), clave_publica.clone()]));
+        }
+    }
+    Ok(None)
+}
+
+// arma los elementos que gastan el multisig con las firmas requeridas en el orden de las
+// claves, o None si todavía faltan firmas
+fn elementos_multisig(
+    entrada: &EntradaPsbt,
+    multisig: &Multisig,
+    script: Vec<u8>,
+) -> Option<Vec<Vec<u8>>> {
+    let firmas: Vec<Vec<u8>> = multisig
+        .claves_publicas
+        .iter()
+        .filter_map(|clave| entrada.firmas_parciales.get(clave).cloned())
+        .take(multisig.requeridas)
+        .collect();
+    if firmas.len() < multisig.requeridas {
+        return None;
+    }
+    // el primer elemento vacío es el que OP_CHECKMULTISIG saca de más del stack
+    let mut elementos = vec![vec![]];
+    elementos.extend(firmas);
+    elementos.push(script);
+    Some(elementos)
+}
+
+fn armar_script(elementos: &[Vec<u8>]) -> Result<Vec<u8>, NodoBitcoinError> {
+    let mut script = vec![];
+    for elemento in elementos {
+        script.extend(script_serialized(elemento)?);
+    }
+    Ok(script)
+}
+
+fn esta_vacio(tx_in: &TxIn) -> bool {
+    tx_in.signature_script.is_empty() && tx_in.witness.is_empty()
+}
+
+fn esta_finalizada(entrada: &EntradaPsbt) -> bool {
+    entrada.script_sig_final.is_some() || entrada.testigo_final.is_some()
+}
+
+// los datos para firmar no hacen falta una vez que el input está finalizado
+fn limpiar_firmas(entrada: &mut EntradaPsbt) {
+    entrada.firmas_parciales.clear();
+    entrada.sighash = None;
+    entrada.redeem_script = None;
+    entrada.witness_script = None;
+}
+
+// escribe una clave y su valor, cada uno con su largo
+fn escribir(bytes: &mut Vec<u8>, clave: &[u8], valor: &[u8]) {
+    bytes.extend(encode_varint(clave.len()));
+    bytes.extend_from_slice(clave);
+    bytes.extend(encode_varint(valor.len()));
+    bytes.extend_from_slice(valor);
+}
+
+// escribe los pares que no conoce la wallet y el cero que termina el mapa
+fn escribir_desconocidos(bytes: &mut Vec<u8>, desconocidos: &BTreeMap<Vec<u8>, Vec<u8>>) {
+    for (clave, valor) in desconocidos {
+        escribir(bytes, clave, valor);
+    }
+    bytes.push(0x00);
+}
+
+fn leer_varint(bytes: &[u8], offset: &mut usize) -> Result<usize, NodoBitcoinError> {
+    let resto = bytes.get(*offset..).ok_or(NodoBitcoinError::PsbtInvalida)?;
+    let (largo, valor) =
+        utils_bytes::leer_varint(resto).map_err(|_| NodoBitcoinError::PsbtInvalida)?;
+    *offset += largo;
+    Ok(valor)
+}
+
+fn leer_bytes(bytes: &[u8], offset: &mut usize, largo: usize) -> Result<Vec<u8>, NodoBitcoinError> {
+    let leidos = bytes
+        .get(*offset..offset.saturating_add(largo))
+        .ok_or(NodoBitcoinError::PsbtInvalida)?;
+    *offset += largo;
+    Ok(leidos.to_vec())
+}
+
+// lee los pares de clave y valor del mapa hasta el cero que lo termina
+fn leer_mapa(bytes: &[u8], offset: &mut usize) -> Result<ParesClaveValor, NodoBitcoinError> {
+    let mut pares = vec![];
+    let mut claves = HashSet::new();
+    loop {
+        let largo_clave = leer_varint(bytes, offset)?;
+        if largo_clave == 0 {
+            return Ok(pares);
+        }
+        let clave = leer_bytes(bytes, offset, largo_clave)?;
+        let largo_valor = leer_varint(bytes, offset)?;
+        let valor = leer_bytes(bytes, offset, largo_valor)?;
+        if !claves.insert(clave.clone()) {
+            return Err(NodoBitcoinError::PsbtInvalida);
+        }
+        pares.push((clave, valor));
+    }
+}
+
+// la transacción tiene que ocupar todo el valor
+fn leer_tx(valor: &[u8]) -> Result<Transaction, NodoBitcoinError> {
+    let tx = Transaction::deserialize(valor).map_err(|_| NodoBitcoinError::PsbtInvalida)?;
+    if tx.serialize()? != valor {
+        return Err(NodoBitcoinError::PsbtInvalida);
+    }
+    Ok(tx)
+}
+
+fn leer_tx_out(valor: &[u8]) -> Result<TxOut, NodoBitcoinError> {
+    let mut offset = 8;
+    let largo_script = leer_varint(valor, &mut offset)?;
+    if offset + largo_script != valor.len() {
+        return Err(NodoBitcoinError::PsbtInvalida);
+    }
+    TxOut::deserialize(valor)
+}
+
+fn leer_entrada(pares: ParesClaveValor) -> Result<EntradaPsbt, NodoBitcoinError> {
+    let mut entrada = EntradaPsbt::default();
+    for (clave, valor) in pares {
+        match (clave[0], clave.len()) {
+            (ENTRADA_UTXO_PREVIA, 1) => entrada.utxo_previa = Some(leer_tx(&valor)?),
+            (ENTRADA_UTXO_TESTIGO, 1) => entrada.utxo_testigo = Some(leer_tx_out(&valor)?),
+            (ENTRADA_FIRMA_PARCIAL, 34) | (ENTRADA_FIRMA_PARCIAL, 66) => {
+                entrada.firmas_parciales.insert(clave[1..].to_vec(), valor);
+            }
+            (ENTRADA_SIGHASH, 1) => {
+                let sighash = valor
+                    .try_into()
+                    .map_err(|_| NodoBitcoinError::PsbtInvalida)?;
+                entrada.sighash = Some(u32::from_le_bytes(sighash));
+            }
+            (ENTRADA_REDEEM_SCRIPT, 1) => entrada.redeem_script = Some(valor),
+            (ENTRADA_WITNESS_SCRIPT, 1) => entrada.witness_script = Some(valor),
+            (ENTRADA_SCRIPT_SIG_FINAL, 1) => entrada.script_sig_final = Some(valor),
+            (ENTRADA_TESTIGO_FINAL, 1) => {
+                let mut tx_in = TxIn::new(Uint256::from_be_bytes([0; 32]), 0);
+                if tx_in.deserialize_witness(&valor)? != valor.len() {
+                    return Err(NodoBitcoinError::PsbtInvalida);
+                }
+                entrada.testigo_final = Some(tx_in.witness);
+            }
+            _ => {
+                entrada.desconocidos.insert(clave, valor);
+            }
+        }
+    }
+    Ok(entrada)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::transaction::{
+        create_psbt_to_send, create_tx_to_send, sign_multisig_tx, Fee,
+    };
+    use crate::common::address::Address;
+    use crate::wallet::cifrado::desde_hexa;
+    use crate::wallet::multisig::tests::{cuenta_multisig, utxo_de};
+
+    #[test]
+    fn test_psbt_base64_ida_y_vuelta() {
+        let account = cuenta_multisig(TipoMultisig::P2SH, 1);
+        let utxos = vec![utxo_de(&account, 100000)];
+        let destinos = vec![
+            (
+                Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+                30000,
+            ),
+            (
+                Address::desde_string("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun").unwrap(),
+                20000,
+            ),
+        ];
+        let mut psbt =
+            create_psbt_to_send(account.clone(), destinos, Fee::Total(1000), utxos).unwrap();
+        assert_eq!(psbt.tx.output.len(), 3);
+        // el cambio vuelve al multisig y lleva su redeem script
+        let redeem_script = account.multisig.unwrap().redeem_script().unwrap();
+        assert_eq!(psbt.salidas[2].redeem_script, Some(redeem_script.clone()));
+        assert_eq!(psbt.entradas[0].redeem_script, Some(redeem_script));
+        assert!(psbt.entradas[0].utxo_previa.is_some());
+        psbt.desconocidos.insert(vec![0xfc, 1], vec![1, 2, 3]);
+
+        let texto = psbt.a_base64().unwrap();
+        assert!(texto.starts_with("cHNidP8"));
+        let leida = Psbt::desde_base64(&texto).unwrap();
+        assert_eq!(leida.desconocidos, psbt.desconocidos);
+        assert_eq!(leida.a_base64().unwrap(), texto);
+
+        let mut bytes = psbt.serializar().unwrap();
+        bytes.pop();
+        assert_eq!(
+            Psbt::deserializar(&bytes),
+            Err(NodoBitcoinError::PsbtInvalida)
+        );
+        assert_eq!(
+            Psbt::desde_base64("cHNidP8="),
+            Err(NodoBitcoinError::PsbtInvalida)
+        );
+    }
+
+    #[test]
+    fn test_psbt_mal_formadas() {
+        // transacción global cortada después del primer input
+        let cortada = desde_hexa("70736274ff01000501000000010000").unwrap();
+        assert_eq!(
+            Psbt::deserializar(&cortada),
+            Err(NodoBitcoinError::PsbtInvalida)
+        );
+
+        // caso de BIP174: la clave de la transacción global tiene un byte de más
+        let tx = "0200000001279a2323a5dfb51fc45f220fa58b0fc13e1e3342792a85d7e36cd6333b5cbc390000000000ffffffff01a05aea0b000000001976a914ffe9c0061097cc3b636f2cb0460fa4fc427d2b4588ac00000000";
+        let clave_invalida = desde_hexa(&format!("70736274ff02000155{}000000", tx)).unwrap();
+        assert_eq!(
+            Psbt::deserializar(&clave_invalida),
+            Err(NodoBitcoinError::PsbtInvalida)
+        );
+        let valida = desde_hexa(&format!("70736274ff010055{}000000", tx)).unwrap();
+        assert_eq!(
+            Psbt::deserializar(&valida).unwrap().serializar().unwrap(),
+            valida
+        );
+
+        // ningún prefijo de una PSBT válida se puede leer
+        let account = cuenta_multisig(TipoMultisig::P2WSH, 1);
+        let destinos = vec![(
+            Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+            30000,
+        )];
+        let psbt = create_psbt_to_send(
+            account.clone(),
+            destinos,
+            Fee::Total(1000),
+            vec![utxo_de(&account, 100000)],
+        )
+        .unwrap();
+        let bytes = psbt.serializar().unwrap();
+        for largo in 0..bytes.len() {
+            assert_eq!(
+                Psbt::deserializar(&bytes[..largo]),
+                Err(NodoBitcoinError::PsbtInvalida)
+            );
+        }
+
+        // bytes cualquiera después del magic no rompen el nodo
+        let mut semilla: u32 = 7;
+        for _ in 0..200 {
+            let mut basura = MAGIC_PSBT.to_vec();
+            for _ in 0..64 {
+                semilla = semilla.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                basura.push((semilla >> 16) as u8);
+            }
+            assert!(Psbt::deserializar(&basura).is_err());
+        }
+        assert_eq!(
+            Psbt::desde_base64("no es base64"),
+            Err(NodoBitcoinError::PsbtInvalida)
+        );
+    }
+
+    #[test]
+    fn test_psbt_multisig_firmada_por_separado() {
+        let primera = cuenta_multisig(TipoMultisig::P2WSH, 3);
+        let segunda = cuenta_multisig(TipoMultisig::P2WSH, 1);
+        let ajena = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "ajena".to_string(),
+        );
+        let utxos = vec![utxo_de(&primera, 100000)];
+        let destinos = vec![(
+            Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+            50000,
+        )];
+        let psbt = create_psbt_to_send(primera.clone(), destinos, Fee::Total(1000), utxos.clone())
+            .unwrap();
+        assert!(psbt.entradas[0].utxo_testigo.is_some());
+        let texto = psbt.a_base64().unwrap();
+
+        // cada participante firma su copia
+        let mut de_primera = Psbt::desde_base64(&texto).unwrap();
+        assert_eq!(de_primera.firmar(&primera).unwrap(), 1);
+        assert!(!de_primera.finalizar().unwrap());
+        assert_eq!(de_primera.extraer(), Err(NodoBitcoinError::PsbtIncompleta));
+        let mut de_segunda = Psbt::desde_base64(&texto).unwrap();
+        assert_eq!(de_segunda.firmar(&segunda).unwrap(), 1);
+        // una clave que no es del multisig no firma
+        assert_eq!(de_segunda.firmar(&ajena).unwrap(), 0);
+
+        let mut combinada = Psbt::desde_base64(&de_primera.a_base64().unwrap()).unwrap();
+        combinada.combinar(&de_segunda).unwrap();
+        assert!(combinada.finalizar().unwrap());
+        assert!(combinada.entradas[0].firmas_parciales.is_empty());
+        let tx = combinada.extraer().unwrap();
+
+        // queda igual que firmando la transacción multisig en dos pasos
+        let mut esperada = psbt.tx.clone();
+        sign_multisig_tx(&mut esperada, &primera, &utxos).unwrap();
+        assert!(sign_multisig_tx(&mut esperada, &segunda, &utxos).unwrap());
+        assert_eq!(tx.serialize().unwrap(), esperada.serialize().unwrap());
+
+        // no se combinan PSBTs de transacciones distintas
+        let otra = create_psbt_to_send(
+            primera.clone(),
+            vec![(
+                Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+                40000,
+            )],
+            Fee::Total(1000),
+            utxos,
+        )
+        .unwrap();
+        assert_eq!(
+            combinada.combinar(&otra),
+            Err(NodoBitcoinError::PsbtInvalida)
+        );
+    }
+
+    #[test]
+    fn test_psbt_p2pkh() {
+        let account = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "test".to_string(),
+        );
+        let utxos = vec![utxo_de(&account, 100000)];
+        let destino = Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap();
+        let mut psbt = create_psbt_to_send(
+            account.clone(),
+            vec![(destino.clone(), 50000)],
+            Fee::Total(1000),
+            utxos.clone(),
+        )
+        .unwrap();
+        assert_eq!(psbt.firmar(&account).unwrap(), 1);
+        assert!(psbt.finalizar().unwrap());
+        let tx = psbt.extraer().unwrap();
+        let esperada =
+            create_tx_to_send(account, destino, 50000, Fee::Total(1000), utxos, vec![]).unwrap();
+        assert_eq!(tx.serialize().unwrap(), esperada.serialize().unwrap());
+    }
+}
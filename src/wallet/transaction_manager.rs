@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -9,18 +9,31 @@ use super::user::Account;
 use crate::app_manager::ApplicationManagerMessages;
 use crate::blockchain::block::SerializedBlock;
 use crate::blockchain::blockheader::BlockHeader;
+use crate::blockchain::estimador_fees::EstimadorFees;
 use crate::blockchain::file_manager::{
+    get_historial_script_from_file, get_transaction_from_file, prune_blocks_from_file,
     read_blocks_from_file, write_headers_and_block_file, FileMessages,
 };
-use crate::blockchain::transaction::{create_tx_to_send, Transaction};
+use crate::blockchain::huerfanas::TxsHuerfanas;
+use crate::blockchain::mempool::{ahora, guardar_mempool, leer_mempool_guardada, Mempool};
+use crate::blockchain::prune::prune_habilitado;
+use crate::blockchain::script_index::scriptindex_habilitado;
+use crate::blockchain::transaction::{
+    create_multisig_tx_to_send, create_psbt_to_send, create_tx_to_bump_fee, create_tx_to_send,
+    create_unsigned_tx_to_send, sign_multisig_tx, Fee, Transaction, TxIn,
+};
+use crate::blockchain::utxos_nodo::{clave_de_input, UtxosNodo};
+use crate::common::address::Address;
+use crate::common::red::red_configurada;
 use crate::common::uint256::Uint256;
 use crate::errores::NodoBitcoinError;
 use crate::log::{log_error_message, log_info_message, LogMessages};
+use crate::messages::getdata::GetDataMessage;
 use crate::protocol::admin_connections::AdminConnections;
 use crate::protocol::block_broadcasting::{init_block_broadcasting, BlockBroadcastingMessages};
-use crate::protocol::send_tx::send_tx;
+use crate::protocol::send_tx::{send_inv_tx, send_tx};
 use crate::protocol::server_node::{init_server, ServerNodeMessages};
-use crate::wallet::uxto_set::{TxReport, UTXOSet};
+use crate::wallet::uxto_set::{TxReport, UTXOSet, Utxo};
 
 #[derive(Clone)]
 pub struct TransactionManager {
@@ -35,6 +48,10 @@ pub struct TransactionManager {
     admin_connections: Option<AdminConnections>,
     blocks: Vec<SerializedBlock>,
     blocks_map: HashMap<[u8; 32], SerializedBlock>,
+    utxos_nodo: UtxosNodo,
+    mempool: Mempool,
+    huerfanas: TxsHuerfanas,
+    estimador_fees: EstimadorFees,
 }
 
 pub enum TransactionMessages {
@@ -56,13 +73,24 @@ pub enum TransactionMessages {
         ),
     ),
     InitServerNode(Sender<TransactionMessages>),
-    SendTx(Account, String, u64, u64, Sender<LogMessages>),
+    SendTx(Account, Address, u64, Fee, Sender<LogMessages>),
+    ExportUnsignedTx(Account, Address, u64, Fee, Sender<LogMessages>),
+    SendMultisigTx(Account, Address, u64, Fee, Sender<LogMessages>),
+    SignPartialTx(Account, Transaction, Sender<LogMessages>),
+    CreatePsbt(Account, Vec<(Address, u64)>, u64, Sender<LogMessages>),
+    BroadcastTx(Transaction, Sender<LogMessages>),
+    BumpFee(Account, Uint256, u64, Sender<LogMessages>),
+    Cpfp(Account, Uint256, u64, Sender<LogMessages>),
     POIInvalido,
     GetBlockRequest(Vec<u8>, Sender<ServerNodeMessages>),
+    GetTxRequest(Vec<u8>, Sender<ServerNodeMessages>),
+    GetMempool(Sender<ServerNodeMessages>),
+    EstimateFee(usize),
+    NewReceiveAddress(String),
     GetMerklePath(Vec<u8>, [u8; 32]),
     SaveBlockHeader(SerializedBlock, BlockHeader, Sender<TransactionMessages>),
     NewBlock(SerializedBlock),
-    NewTx(Transaction),
+    NewTx(Transaction, Option<i32>),
     SenderBlockBroadcasting(Sender<BlockBroadcastingMessages>),
     SenderServerNode(Sender<ServerNodeMessages>),
     LoadSavedUTXOS,
@@ -143,7 +171,43 @@ impl TransactionManager {
                     .send(ApplicationManagerMessages::TransactionManagerUpdate);
             }
             TransactionMessages::AddAccount(accounts, logger) => {
+                if scriptindex_habilitado() {
+                    let nuevas = accounts
+                        .iter()
+                        .filter(|account| {
+                            self.accounts
+                                .iter()
+                                .all(|actual| actual.public_key != account.public_key)
+                        })
+                        .cloned()
+                        .collect::<Vec<Account>>();
+                    self.accounts = accounts.clone();
+                    match self.agregar_cuentas_desde_indice(nuevas) {
+                        Ok(_) => {
+                            log_info_message(
+                                logger,
+                                "UTXOS actualizadas desde el índice de scripts".to_string(),
+                            );
+                            _ = self
+                                .sender_app_manager
+                                .send(ApplicationManagerMessages::TransactionManagerUpdate);
+                            return;
+                        }
+                        Err(error) => {
+                            log_error_message(
+                                logger.clone(),
+                                format!("No se pudo usar el índice de scripts: {}", error),
+                            );
+                        }
+                    }
+                }
                 self.accounts = accounts;
+                if prune_habilitado() {
+                    log_info_message(
+                        logger.clone(),
+                        "Modo prune: las cuentas nuevas solo ven los movimientos de los bloques no borrados".to_string(),
+                    );
+                }
                 self.utxos.last_timestamp = 0;
                 let utxos_updated = match self.update_utxos_from_file(
                     logger.clone(),
@@ -203,6 +267,9 @@ impl TransactionManager {
                     hash_map.insert(hash, block.clone());
                 }
                 self.blocks_map = hash_map;
+                self.cargar_utxos_nodo();
+                self.podar_bloques();
+                self.recargar_mempool();
 
                 thread::spawn(move || {
                     match init_block_broadcasting(
@@ -254,6 +321,7 @@ impl TransactionManager {
                     hash_map.insert(hash, block.clone());
                 }
                 self.blocks_map = hash_map;
+                self.cargar_utxos_nodo();
 
                 log_info_message(logger.clone(), "Inicio del nodo server.".to_string());
                 let file_manger_clone = self.file_manager.clone();
@@ -286,6 +354,64 @@ impl TransactionManager {
                 }
                 _ = sender.send(ServerNodeMessages::GetBlockResponse(response));
             }
+            TransactionMessages::GetTxRequest(hash, sender) => {
+                let key: [u8; 32] = hash.as_slice().try_into().unwrap_or([0u8; 32]);
+                let txid = Uint256::from_be_bytes(key);
+                // solo se sirven las transacciones que aceptó la mempool
+                let response = self
+                    .mempool
+                    .obtener(&txid)
+                    .map(|entrada| entrada.tx.clone());
+                _ = sender.send(ServerNodeMessages::GetTxResponse(response));
+            }
+            TransactionMessages::GetMempool(sender) => {
+                let txids = self
+                    .mempool
+                    .txids()
+                    .iter()
+                    .map(|txid| txid.get_bytes())
+                    .collect();
+                _ = sender.send(ServerNodeMessages::MempoolResponse(txids));
+            }
+            TransactionMessages::EstimateFee(objetivo) => {
+                let sat_por_vbyte = self
+                    .estimador_fees
+                    .estimar_sat_por_vbyte(objetivo, &self.mempool);
+                log_info_message(
+                    self.logger.clone(),
+                    format!(
+                        "Fee estimado para confirmarse en {} bloques: {} sat/vB.",
+                        objetivo, sat_por_vbyte
+                    ),
+                );
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::FeeEstimate(sat_por_vbyte));
+            }
+            TransactionMessages::NewReceiveAddress(account) => {
+                let direccion = match self.accounts.iter_mut().find(|a| a.public_key == account) {
+                    Some(account) => account
+                        .nueva_direccion_recepcion()
+                        .map(|direccion| (direccion, account.clone())),
+                    None => Err(NodoBitcoinError::CuentaNoEncontrada),
+                };
+                match direccion {
+                    Ok((direccion, account)) => {
+                        _ = self
+                            .sender_app_manager
+                            .send(ApplicationManagerMessages::UpdateAccount(Box::new(account)));
+                        _ = self
+                            .sender_app_manager
+                            .send(ApplicationManagerMessages::NewReceiveAddress(direccion));
+                    }
+                    Err(error) => {
+                        log_error_message(
+                            self.logger.clone(),
+                            format!("No se pudo generar una dirección de recepción: {}", error),
+                        );
+                    }
+                }
+            }
             TransactionMessages::GetMerklePath(hash, tx_id) => {
                 let key: [u8; 32] = hash.as_slice().try_into().unwrap_or([0u8; 32]);
                 let mut inv_key = [0u8; 32];
@@ -335,12 +461,31 @@ impl TransactionManager {
                     self.blocks.push(block.clone());
                     let hash = block.header.hash().unwrap();
                     self.blocks_map.insert(hash, block.clone());
+                    self.utxos_nodo.aplicar_bloque(&block);
+                    let confirmadas: Vec<Uint256> =
+                        block.txns.iter().filter_map(|tx| tx.txid().ok()).collect();
+                    self.estimador_fees.procesar_bloque(&confirmadas);
+                    let quitadas = self.mempool.quitar_confirmadas(&block);
+                    let en_mempool: HashSet<Uint256> = self.mempool.txids().into_iter().collect();
+                    self.estimador_fees.quitar_ausentes(&en_mempool);
+                    log_info_message(
+                        self.logger.clone(),
+                        format!("{} transacciones quitadas de la mempool.", quitadas.len()),
+                    );
+                    self.huerfanas.expirar(ahora());
+                    for tx in block.txns.iter() {
+                        self.reevaluar_hijas_de(tx);
+                    }
                 }
 
+                for tx in block.txns.iter() {
+                    self.marcar_direcciones_usadas(tx);
+                }
                 let txns = block.txns.clone();
                 let _ = self
                     .utxos
                     .update_from_blocks(vec![block], self.accounts.clone());
+                self.podar_bloques();
                 for tx in txns {
                     let txid = match tx.txid() {
                         Ok(txid) => txid,
@@ -352,44 +497,8 @@ impl TransactionManager {
                     .sender_app_manager
                     .send(ApplicationManagerMessages::TransactionManagerUpdate);
             }
-            TransactionMessages::NewTx(tx) => {
-                let tx_id = match tx.txid() {
-                    Ok(id) => id,
-                    Err(_) => return,
-                };
-                if self.tx_pendings.contains_key(&tx_id) {
-                    return;
-                }
-                let accounts_to_update = match self.validar_tx_propia(tx.clone()) {
-                    Ok(accounts) => accounts,
-                    Err(_) => vec![],
-                };
-                if !accounts_to_update.is_empty() {
-                    for (account, index, txid, is_tx_in, value) in accounts_to_update.iter() {
-                        // crear una TxReport
-                        // agregarla al hashmap del utxoset
-                        // enviar mensaje a la app manager
-
-                        // obtener el unixtimestamp actual
-                        let unix_timestamp: u32 = match SystemTime::now().duration_since(UNIX_EPOCH)
-                        {
-                            Ok(n) => n.as_secs() as u32,
-                            Err(_) => 0,
-                        };
-
-                        let tx_report =
-                            TxReport::new(true, unix_timestamp, *txid, *value, *is_tx_in, *index);
-                        self.utxos
-                            .tx_report_pending_by_accounts
-                            .entry(account.public_key.clone())
-                            .or_insert(Vec::new())
-                            .push(tx_report.clone());
-                    }
-                }
-                self.tx_pendings.insert(tx_id, tx);
-                _ = self
-                    .sender_app_manager
-                    .send(ApplicationManagerMessages::TransactionManagerUpdate);
+            TransactionMessages::NewTx(tx, peer) => {
+                self.agregar_tx_nueva(tx, peer);
             }
             TransactionMessages::POIInvalido => {
                 // Actualizar la blockchain con el flujo de Initial Block Download
@@ -404,9 +513,11 @@ impl TransactionManager {
                 self.sender_server_node = Some(sender_server_node);
             }
             TransactionMessages::SendTx(account, target_address, target_amount, fee, logger) => {
+                let account = self.cuenta_actual(account);
                 let utxos = self.utxos.clone();
                 let admin_connections = self.admin_connections.clone();
-                let _ = send_new_tx(
+                // la propia queda pendiente y en la mempool, así se le puede aumentar el fee
+                if let Ok(tx) = send_new_tx(
                     account,
                     target_address,
                     target_amount,
@@ -414,7 +525,150 @@ impl TransactionManager {
                     utxos,
                     admin_connections,
                     logger,
-                );
+                ) {
+                    self.agregar_tx_nueva(tx, None);
+                }
+            }
+            TransactionMessages::ExportUnsignedTx(
+                account,
+                target_address,
+                target_amount,
+                fee,
+                logger,
+            ) => {
+                // las cuentas de solo lectura firman fuera del nodo, no se envía ni se guarda
+                let account = self.cuenta_actual(account);
+                let tx = match self.utxos.utxos_for_account.get(&account.public_key) {
+                    Some(utxos) => create_unsigned_tx_to_send(
+                        account,
+                        target_address,
+                        target_amount,
+                        fee,
+                        utxos.clone(),
+                    ),
+                    None => Err(NodoBitcoinError::CuentaNoEncontrada),
+                };
+                let tx = match tx {
+                    Ok(tx) => Some(tx),
+                    Err(error) => {
+                        log_error_message(
+                            logger,
+                            format!("No se pudo armar la transacción sin firmar: {}", error),
+                        );
+                        None
+                    }
+                };
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::UnsignedTx(tx));
+            }
+            TransactionMessages::SendMultisigTx(
+                account,
+                target_address,
+                target_amount,
+                fee,
+                logger,
+            ) => {
+                let account = self.cuenta_actual(account);
+                let firmada = match self.utxos.utxos_for_account.get(&account.public_key) {
+                    Some(utxos) => create_multisig_tx_to_send(
+                        account,
+                        target_address,
+                        target_amount,
+                        fee,
+                        utxos.clone(),
+                    ),
+                    None => Err(NodoBitcoinError::CuentaNoEncontrada),
+                };
+                self.enviar_o_devolver_multisig(firmada, logger);
+            }
+            TransactionMessages::SignPartialTx(account, mut tx, logger) => {
+                let account = self.cuenta_actual(account);
+                let firmada = match self.utxos.utxos_for_account.get(&account.public_key) {
+                    Some(utxos) => {
+                        sign_multisig_tx(&mut tx, &account, utxos).map(|completa| (tx, completa))
+                    }
+                    None => Err(NodoBitcoinError::CuentaNoEncontrada),
+                };
+                self.enviar_o_devolver_multisig(firmada, logger);
+            }
+            TransactionMessages::CreatePsbt(account, destinos, fee, logger) => {
+                let account = self.cuenta_actual(account);
+                let psbt = match self.utxos.utxos_for_account.get(&account.public_key) {
+                    Some(utxos) => {
+                        create_psbt_to_send(account, destinos, Fee::Total(fee), utxos.clone())
+                    }
+                    None => Err(NodoBitcoinError::CuentaNoEncontrada),
+                };
+                let psbt = match psbt {
+                    Ok(psbt) => Some(psbt),
+                    Err(error) => {
+                        log_error_message(logger, format!("No se pudo armar la PSBT: {}", error));
+                        None
+                    }
+                };
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::Psbt(psbt));
+            }
+            TransactionMessages::BroadcastTx(tx, logger) => {
+                let enviada = self.enviar_tx_propia(tx, logger);
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::TxBroadcast(enviada));
+            }
+            TransactionMessages::BumpFee(account, txid, new_fee, logger) => {
+                let account = self.cuenta_actual(account);
+                let aumentado = match self.aumentar_fee(account, txid, new_fee) {
+                    Ok(nuevo_txid) => {
+                        log_info_message(
+                            logger,
+                            format!(
+                                "Transacción {} reemplazada por {} con fee {}.",
+                                txid.to_hexa_le_string(),
+                                nuevo_txid.to_hexa_le_string(),
+                                new_fee
+                            ),
+                        );
+                        true
+                    }
+                    Err(error) => {
+                        log_error_message(
+                            logger,
+                            format!("No se pudo aumentar el fee de la transacción: {}", error),
+                        );
+                        false
+                    }
+                };
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::FeeBumped(aumentado));
+            }
+            TransactionMessages::Cpfp(account, txid, fee, logger) => {
+                let account = self.cuenta_actual(account);
+                match self.acelerar_con_hija(account, txid, fee) {
+                    Ok(hija) => {
+                        let fee_rate = match self.mempool.paquete_de_ancestros(&hija) {
+                            Some(paquete) => format!("{:.2}", paquete.fee_rate()),
+                            None => "desconocido".to_string(),
+                        };
+                        log_info_message(
+                            logger,
+                            format!(
+                                "Transacción {} acelerada con la hija {}. Fee rate del paquete: {} sat/vB.",
+                                txid.to_hexa_le_string(),
+                                hija.to_hexa_le_string(),
+                                fee_rate
+                            ),
+                        );
+                    }
+                    Err(error) => {
+                        log_error_message(
+                            logger,
+                            format!("No se pudo acelerar la transacción: {}", error),
+                        );
+                    }
+                };
             }
             TransactionMessages::LoadSavedUTXOS => {
                 // cargar los utxos guardados en el archivo
@@ -423,6 +677,7 @@ impl TransactionManager {
             TransactionMessages::ShutDown => {
                 // guardar utxos en archivo
                 let _ = self.utxos.save();
+                self.guardar_mempool();
                 let block_broadcasting_is_closed = match &self.sender_block_broadcasting {
                     Some(sender) => {
                         _ = sender.send(BlockBroadcastingMessages::ShutDown);
@@ -464,10 +719,410 @@ impl TransactionManager {
         }
     }
 
+    /// Envía la transacción multisig si ya tiene todas las firmas. Si le faltan se la devuelve
+    /// a la app manager para exportarla y que la firmen los otros participantes
+    fn enviar_o_devolver_multisig(
+        &mut self,
+        firmada: Result<(Transaction, bool), NodoBitcoinError>,
+        logger: Sender<LogMessages>,
+    ) {
+        let respuesta = match firmada {
+            Ok((tx, false)) => ApplicationManagerMessages::MultisigTx(Some(tx), false),
+            Ok((tx, true)) => match self.enviar_tx_propia(tx.clone(), logger) {
+                true => ApplicationManagerMessages::MultisigTx(Some(tx), true),
+                false => ApplicationManagerMessages::MultisigTx(None, false),
+            },
+            Err(error) => {
+                log_error_message(
+                    logger,
+                    format!("No se pudo firmar la transacción multisig: {}", error),
+                );
+                ApplicationManagerMessages::MultisigTx(None, false)
+            }
+        };
+        _ = self.sender_app_manager.send(respuesta);
+    }
+
+    /// Envía a los peers una transacción propia ya firmada, como las multisig o las extraídas
+    /// de una PSBT, y como las demás propias la deja pendiente y en la mempool
+    /// Devuelve si se pudo enviar
+    fn enviar_tx_propia(&mut self, tx: Transaction, logger: Sender<LogMessages>) -> bool {
+        let admin_connections = match self.admin_connections.clone() {
+            Some(admin_connections) => admin_connections,
+            None => return false,
+        };
+        match send_tx(admin_connections, logger.clone(), tx.clone()) {
+            Ok(()) => {
+                self.agregar_tx_nueva(tx, None);
+                true
+            }
+            Err(error) => {
+                log_error_message(
+                    logger,
+                    format!("No se pudo enviar la transacción: {}", error),
+                );
+                false
+            }
+        }
+    }
+
     fn update_pendings(&mut self, tx_id: Uint256) {
         self.tx_pendings.remove(&tx_id);
     }
 
+    /// Devuelve la copia de la cuenta que tiene el transaction manager, que es la que sabe
+    /// cuáles direcciones HD ya se usaron, con las claves de la cuenta recibida para firmar
+    fn cuenta_actual(&self, account: Account) -> Account {
+        self.accounts
+            .iter()
+            .find(|actual| actual.public_key == account.public_key)
+            .cloned()
+            .map(|actual| actual.con_secretos_de(&account))
+            .unwrap_or(account)
+    }
+
+    /// Marca como usadas las direcciones HD a las que les paga la transacción y le manda a la
+    /// app manager las cuentas que cambiaron para que las guarde
+    fn marcar_direcciones_usadas(&mut self, tx: &Transaction) {
+        for account in self.accounts.iter_mut() {
+            match account.marcar_salidas_usadas(tx) {
+                Ok(true) => {
+                    _ = self
+                        .sender_app_manager
+                        .send(ApplicationManagerMessages::UpdateAccount(Box::new(
+                            account.clone(),
+                        )));
+                }
+                Ok(false) => {}
+                Err(error) => {
+                    log_error_message(
+                        self.logger.clone(),
+                        format!("No se pudieron derivar direcciones nuevas: {}", error),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Agrega a la mempool una transacción recibida o enviada por la wallet y, si es de
+    /// alguna de las cuentas, la deja como pendiente
+    /// Si todavía no se conocen sus padres y no es propia, queda como huérfana y se le piden
+    /// los padres al peer que la mandó
+    fn agregar_tx_nueva(&mut self, tx: Transaction, peer: Option<i32>) {
+        let tx_id = match tx.txid() {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        if self.tx_pendings.contains_key(&tx_id) || self.huerfanas.contiene(&tx_id) {
+            return;
+        }
+        let accounts_to_update = match self.validar_tx_propia(tx.clone()) {
+            Ok(accounts) => accounts,
+            Err(_) => vec![],
+        };
+        let resultado_mempool = match self
+            .mempool
+            .agregar_reemplazando(tx.clone(), &self.utxos_nodo)
+        {
+            Ok((_, reemplazadas)) => {
+                self.registrar_aceptada(&tx, tx_id, &reemplazadas);
+                format!(
+                    "agregada a la mempool, reemplazó {} transacciones.",
+                    reemplazadas.len()
+                )
+            }
+            Err(NodoBitcoinError::InputsDesconocidos) if accounts_to_update.is_empty() => {
+                self.agregar_huerfana(tx, peer);
+                return;
+            }
+            Err(error) => format!("rechazada por la mempool: {}", error),
+        };
+        log_info_message(
+            self.logger.clone(),
+            format!(
+                "Transacción {} {}",
+                tx_id.to_hexa_le_string(),
+                resultado_mempool
+            ),
+        );
+        self.registrar_pendiente(tx_id, tx, accounts_to_update);
+    }
+
+    /// Anuncia la transacción que se acaba de aceptar en la mempool, la registra en el
+    /// estimador de fees y saca de las pendientes las que reemplazó
+    fn registrar_aceptada(&mut self, tx: &Transaction, tx_id: Uint256, reemplazadas: &[Uint256]) {
+        self.anunciar_tx(tx_id);
+        if let Some(entrada) = self.mempool.obtener(&tx_id) {
+            self.estimador_fees.registrar_tx(tx_id, entrada);
+        }
+        for reemplazada in reemplazadas.iter() {
+            self.estimador_fees.quitar_tx(reemplazada);
+            self.quitar_pendiente(*reemplazada);
+        }
+        self.reevaluar_hijas_de(tx);
+    }
+
+    /// Deja la transacción como pendiente y agrega los movimientos pendientes de las cuentas
+    fn registrar_pendiente(
+        &mut self,
+        tx_id: Uint256,
+        tx: Transaction,
+        accounts_to_update: Vec<PendingByAccount>,
+    ) {
+        if !accounts_to_update.is_empty() {
+            self.marcar_direcciones_usadas(&tx);
+            for (account, index, txid, is_tx_in, value) in accounts_to_update.iter() {
+                // crear una TxReport
+                // agregarla al hashmap del utxoset
+                // enviar mensaje a la app manager
+
+                // obtener el unixtimestamp actual
+                let unix_timestamp: u32 = match SystemTime::now().duration_since(UNIX_EPOCH) {
+                    Ok(n) => n.as_secs() as u32,
+                    Err(_) => 0,
+                };
+
+                let tx_report =
+                    TxReport::new(true, unix_timestamp, *txid, *value, *is_tx_in, *index);
+                self.utxos
+                    .tx_report_pending_by_accounts
+                    .entry(account.public_key.clone())
+                    .or_insert(Vec::new())
+                    .push(tx_report.clone());
+            }
+        }
+        self.tx_pendings.insert(tx_id, tx);
+        _ = self
+            .sender_app_manager
+            .send(ApplicationManagerMessages::TransactionManagerUpdate);
+    }
+
+    /// Guarda la transacción como huérfana y le pide al peer que la mandó los padres que faltan
+    fn agregar_huerfana(&mut self, tx: Transaction, peer: Option<i32>) {
+        let faltantes = self.mempool.salidas_faltantes(&tx, &self.utxos_nodo);
+        let ahora = ahora();
+        self.huerfanas.expirar(ahora);
+        let tx_id = match self.huerfanas.agregar(tx, faltantes, ahora, peer) {
+            Ok(tx_id) => tx_id,
+            Err(error) => {
+                log_error_message(
+                    self.logger.clone(),
+                    format!("No se pudo guardar la transacción huérfana: {}", error),
+                );
+                return;
+            }
+        };
+        log_info_message(
+            self.logger.clone(),
+            format!(
+                "Transacción {} guardada como huérfana. Huérfanas: {}.",
+                tx_id.to_hexa_le_string(),
+                self.huerfanas.cantidad()
+            ),
+        );
+        if let Some(peer) = peer {
+            let padres: Vec<[u8; 32]> = self
+                .huerfanas
+                .padres_faltantes(&tx_id)
+                .iter()
+                .map(|padre| padre.get_bytes())
+                .collect();
+            self.pedir_txs(peer, &padres);
+        }
+    }
+
+    /// Vuelve a evaluar las huérfanas que gastan salidas de la transacción que se acaba de
+    /// aceptar en la mempool o de confirmar en un bloque
+    fn reevaluar_hijas_de(&mut self, tx: &Transaction) {
+        for hija in self.huerfanas.quitar_hijas_de(tx) {
+            self.agregar_tx_nueva(hija.tx, hija.peer);
+        }
+    }
+
+    /// Le manda un getdata con las transacciones pedidas a la conexión con ese id
+    fn pedir_txs(&self, peer: i32, hashes: &[[u8; 32]]) {
+        let mut admin_connections = match self.admin_connections.clone() {
+            Some(admin_connections) => admin_connections,
+            None => return,
+        };
+        let get_data = match GetDataMessage::new_for_txs(hashes).serialize() {
+            Ok(get_data) => get_data,
+            Err(_) => return,
+        };
+        for connection in admin_connections.get_connections() {
+            if connection.id == peer && connection.write_message(&get_data).is_err() {
+                log_error_message(
+                    self.logger.clone(),
+                    format!(
+                        "Error al pedir los padres de una huérfana en conexión {}",
+                        peer
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Saca la transacción de las pendientes y de los movimientos pendientes de las cuentas,
+    /// por ejemplo cuando otra con más fee la reemplazó
+    fn quitar_pendiente(&mut self, tx_id: Uint256) {
+        if self.tx_pendings.remove(&tx_id).is_some() {
+            self.utxos.quitar_tx_reports_pendientes(tx_id);
+        }
+    }
+
+    /// Reemplaza la transacción pendiente de la cuenta por otra que gasta los mismos inputs
+    /// con el nuevo fee. Primero la agrega a la mempool en lugar de la original, así si no
+    /// cumple BIP125 no se envía ni se toca la original. Una vez aceptada, las pendientes y
+    /// los movimientos de las cuentas pasan a la nueva y recién después se envía a los peers,
+    /// así un error al enviarla no deja la mempool y la wallet apuntando a transacciones
+    /// distintas
+    /// Devuelve el txid de la nueva transacción
+    fn aumentar_fee(
+        &mut self,
+        account: Account,
+        tx_id: Uint256,
+        new_fee: u64,
+    ) -> Result<Uint256, NodoBitcoinError> {
+        let original = match self.tx_pendings.get(&tx_id) {
+            Some(tx) => tx.clone(),
+            None => return Err(NodoBitcoinError::TxPendienteNoEncontrada),
+        };
+        if !original.senaliza_rbf() {
+            return Err(NodoBitcoinError::TxNoReemplazable);
+        }
+        let mut previous_txs = vec![];
+        for tx_in in original.input.iter() {
+            previous_txs.push(self.buscar_tx_previa(&account, tx_in)?);
+        }
+        let nueva = create_tx_to_bump_fee(account, &original, previous_txs, new_fee)?;
+        let accounts_to_update = self.validar_tx_propia(nueva.clone())?;
+        let (nuevo_txid, reemplazadas) = self
+            .mempool
+            .agregar_reemplazando(nueva.clone(), &self.utxos_nodo)?;
+        self.quitar_pendiente(tx_id);
+        self.registrar_aceptada(&nueva, nuevo_txid, &reemplazadas);
+        self.registrar_pendiente(nuevo_txid, nueva.clone(), accounts_to_update);
+        self.enviar_a_peers(nueva)?;
+        Ok(nuevo_txid)
+    }
+
+    /// Gasta con una hija de fee alto las salidas sin confirmar que la transacción le paga a la
+    /// cuenta, así el paquete de las dos llega al fee rate necesario para minarse (CPFP)
+    /// Como en `aumentar_fee`, la hija primero tiene que entrar en la mempool, así una que no
+    /// cumple la política o las reglas de paquetes no llega a los peers ni queda pendiente
+    /// Devuelve el txid de la hija
+    fn acelerar_con_hija(
+        &mut self,
+        account: Account,
+        tx_id: Uint256,
+        fee: u64,
+    ) -> Result<Uint256, NodoBitcoinError> {
+        let chosen_utxos: Vec<Utxo> = self
+            .utxos_sin_confirmar(&account)
+            .into_iter()
+            .filter(|utxo| utxo.tx_id == tx_id)
+            .collect();
+        if chosen_utxos.is_empty() {
+            return Err(NodoBitcoinError::TxPendienteNoEncontrada);
+        }
+        let value: u64 = chosen_utxos.iter().map(|utxo| utxo.tx_out.value).sum();
+        if value <= fee {
+            return Err(NodoBitcoinError::NoHaySuficientesUtxos);
+        }
+        let target_address = Address::en_red(&account.public_key, red_configurada())?;
+        let hija = create_tx_to_send(
+            account,
+            target_address,
+            value - fee,
+            Fee::Total(fee),
+            vec![],
+            chosen_utxos,
+        )?;
+        let accounts_to_update = self.validar_tx_propia(hija.clone())?;
+        let (hija_txid, reemplazadas) = self
+            .mempool
+            .agregar_reemplazando(hija.clone(), &self.utxos_nodo)?;
+        self.registrar_aceptada(&hija, hija_txid, &reemplazadas);
+        self.registrar_pendiente(hija_txid, hija.clone(), accounts_to_update);
+        self.enviar_a_peers(hija)?;
+        Ok(hija_txid)
+    }
+
+    /// Envía la transacción a los peers conectados
+    fn enviar_a_peers(&self, tx: Transaction) -> Result<(), NodoBitcoinError> {
+        let admin_connections = match self.admin_connections.clone() {
+            Some(admin_connections) => admin_connections,
+            None => return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion),
+        };
+        send_tx(admin_connections, self.logger.clone(), tx)
+    }
+
+    /// Devuelve las salidas para la cuenta de las transacciones de la mempool y de las
+    /// pendientes que todavía no gasta ninguna otra transacción de la mempool
+    fn utxos_sin_confirmar(&self, account: &Account) -> Vec<Utxo> {
+        let mut txs: HashMap<Uint256, Transaction> = self.tx_pendings.clone();
+        for txid in self.mempool.txids() {
+            if let Some(entrada) = self.mempool.obtener(&txid) {
+                txs.insert(txid, entrada.tx.clone());
+            }
+        }
+        let mut utxos = vec![];
+        for (tx_id, tx) in txs {
+            for (index, tx_out) in tx.output.iter().enumerate() {
+                let output_index = index as u32;
+                if !account.es_propio(tx_out) || self.mempool.esta_gastada(&(tx_id, output_index)) {
+                    continue;
+                }
+                utxos.push(Utxo {
+                    tx_id,
+                    output_index,
+                    tx_out: tx_out.clone(),
+                    pk_script: tx_out.pk_script.clone(),
+                    tx: tx.clone(),
+                });
+            }
+        }
+        utxos
+    }
+
+    /// Busca la transacción que creó la salida que gasta el input entre las utxos de la cuenta,
+    /// la mempool, las transacciones pendientes y por último en los bloques guardados
+    fn buscar_tx_previa(
+        &self,
+        account: &Account,
+        tx_in: &TxIn,
+    ) -> Result<Transaction, NodoBitcoinError> {
+        let (previous_txid, output_index) = clave_de_input(tx_in);
+        if let Some(utxos) = self.utxos.utxos_for_account.get(&account.public_key) {
+            if let Some(utxo) = utxos
+                .iter()
+                .find(|utxo| utxo.tx_id == previous_txid && utxo.output_index == output_index)
+            {
+                return Ok(utxo.tx.clone());
+            }
+        }
+        if let Some(entrada) = self.mempool.obtener(&previous_txid) {
+            return Ok(entrada.tx.clone());
+        }
+        match self.tx_pendings.get(&previous_txid) {
+            Some(tx) => Ok(tx.clone()),
+            None => get_transaction_from_file(self.file_manager.clone(), previous_txid)
+                .map_err(|_| NodoBitcoinError::NoHaySuficientesUtxos),
+        }
+    }
+
+    /// Devuelve true si la transacción le paga a alguna cuenta o gasta alguna de sus utxos
+    fn es_tx_propia(&self, tx: &Transaction) -> bool {
+        tx.output.iter().any(|tx_out| {
+            self.accounts
+                .iter()
+                .any(|account| account.es_propio(tx_out))
+        }) || tx
+            .input
+            .iter()
+            .any(|tx_in| self.utxos.validar_input(tx_in.clone()).is_ok())
+    }
+
     fn validar_tx_propia(
         &self,
         tx: Transaction,
@@ -549,7 +1204,7 @@ impl TransactionManager {
     fn initialize_utxos_from_file(
         &mut self,
         mut utxo_set: UTXOSet,
-        accounts: Vec<Account>,
+        mut accounts: Vec<Account>,
     ) -> Result<UTXOSet, NodoBitcoinError> {
         let blocks = read_blocks_from_file(self.file_manager.clone())?;
         // filtrar los bloxks por sólo aquellos que tiene transacciones
@@ -558,10 +1213,233 @@ impl TransactionManager {
             .filter(|block| !block.txns.is_empty())
             .collect::<Vec<SerializedBlock>>();
 
+        // las cuentas HD restauradas pueden haber usado más direcciones que las derivadas,
+        // recorriendo los bloques en orden se derivan las que siguen a cada una usada
+        for account in accounts.iter_mut() {
+            let mut cambio = false;
+            for block in blocks_with_tx.iter() {
+                for tx in block.txns.iter() {
+                    cambio |= account.marcar_salidas_usadas(tx)?;
+                }
+            }
+            if cambio {
+                if let Some(actual) = self
+                    .accounts
+                    .iter_mut()
+                    .find(|actual| actual.public_key == account.public_key)
+                {
+                    *actual = account.clone();
+                }
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::UpdateAccount(Box::new(
+                        account.clone(),
+                    )));
+            }
+        }
+
         utxo_set.update_from_blocks(blocks_with_tx, accounts)?;
         Ok(utxo_set)
     }
 
+    /// Carga el historial y las utxos de las cuentas nuevas usando el índice de scripts
+    fn agregar_cuentas_desde_indice(
+        &mut self,
+        cuentas: Vec<Account>,
+    ) -> Result<(), NodoBitcoinError> {
+        for mut account in cuentas {
+            // las direcciones HD con movimientos derivan más, hasta que queda un hueco sin usar
+            let mut recorridos = HashSet::new();
+            let mut cambio = false;
+            loop {
+                let pendientes: Vec<Vec<u8>> = account
+                    .scripts()?
+                    .into_iter()
+                    .filter(|pk_script| !recorridos.contains(pk_script))
+                    .collect();
+                if pendientes.is_empty() {
+                    break;
+                }
+                for pk_script in pendientes {
+                    recorridos.insert(pk_script.clone());
+                    let movimientos =
+                        get_historial_script_from_file(self.file_manager.clone(), pk_script)?;
+                    for movimiento in movimientos.iter() {
+                        cambio |= account.marcar_salidas_usadas(&movimiento.tx)?;
+                    }
+                    self.utxos
+                        .agregar_historial_de_cuenta(account.public_key.clone(), movimientos);
+                }
+            }
+            if cambio {
+                if let Some(actual) = self
+                    .accounts
+                    .iter_mut()
+                    .find(|actual| actual.public_key == account.public_key)
+                {
+                    *actual = account.clone();
+                }
+                _ = self
+                    .sender_app_manager
+                    .send(ApplicationManagerMessages::UpdateAccount(Box::new(account)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Si está habilitado el modo prune, borra los archivos de bloques viejos ya aplicados a las UTXOs
+    /// y los saca de los bloques en memoria, así el nodo server responde notfound por ellos
+    /// Arma las salidas sin gastar del nodo. Con prune los bloques viejos pueden no estar, así
+    /// que se parte de las salidas guardadas antes de podarlos y se aplican solo los bloques
+    /// posteriores
+    fn cargar_utxos_nodo(&mut self) {
+        let guardadas = match prune_habilitado() {
+            true => UtxosNodo::cargar().ok(),
+            false => None,
+        };
+        self.utxos_nodo = match guardadas {
+            Some(guardadas) => guardadas.con_bloques_posteriores(&self.blocks),
+            None => UtxosNodo::desde_bloques(&self.blocks),
+        };
+    }
+
+    fn podar_bloques(&mut self) {
+        if !prune_habilitado() {
+            return;
+        }
+        // las UTXOs se guardan antes porque sin los bloques no se podrían volver a calcular
+        if let Err(error) = self.utxos.save().and_then(|_| self.utxos_nodo.guardar()) {
+            log_error_message(
+                self.logger.clone(),
+                format!(
+                    "No se pudieron guardar las UTXOs antes del prune: {}",
+                    error
+                ),
+            );
+            return;
+        }
+        match prune_blocks_from_file(self.file_manager.clone(), self.utxos.last_timestamp) {
+            Ok(podados) => {
+                if podados.is_empty() {
+                    return;
+                }
+                for hash in podados {
+                    self.blocks_map.remove(&hash);
+                }
+                self.blocks.retain(|block| match block.header.hash() {
+                    Ok(hash) => self.blocks_map.contains_key(&hash),
+                    Err(_) => false,
+                });
+            }
+            Err(error) => {
+                log_error_message(
+                    self.logger.clone(),
+                    format!("Error al borrar los bloques viejos: {}", error),
+                );
+            }
+        }
+    }
+
+    /// Guarda la mempool y las transacciones propias pendientes para recargarlas al volver a iniciar
+    /// De las pendientes solo se guardan las que tocan alguna cuenta de la wallet, las demás
+    /// son transacciones de la red que no hay que reenviar
+    fn guardar_mempool(&self) {
+        let propias: Vec<Transaction> = self
+            .tx_pendings
+            .values()
+            .filter(|tx| self.es_tx_propia(tx))
+            .cloned()
+            .collect();
+        if let Err(error) = guardar_mempool(&self.mempool, &propias) {
+            log_error_message(
+                self.logger.clone(),
+                format!("Error al guardar la mempool: {}", error),
+            );
+        }
+    }
+
+    /// Vuelve a validar contra los bloques actuales las transacciones guardadas al cerrar
+    /// y reenvía a los peers las propias que todavía no se confirmaron
+    fn recargar_mempool(&mut self) {
+        let guardada = match leer_mempool_guardada() {
+            Ok(guardada) => guardada,
+            Err(error) => {
+                log_error_message(
+                    self.logger.clone(),
+                    format!("Error al leer la mempool guardada: {}", error),
+                );
+                return;
+            }
+        };
+        let agregadas = self
+            .mempool
+            .recargar(guardada.transacciones, &self.utxos_nodo);
+
+        let mut reenviadas = 0;
+        for tx in guardada.propias {
+            let txid = match tx.txid() {
+                Ok(txid) => txid,
+                Err(_) => continue,
+            };
+            if self.esta_confirmada(txid) {
+                continue;
+            }
+            // si ya la recargó la mempool está bien, si la rechaza no se vuelve a enviar
+            match self.mempool.agregar(tx.clone(), &self.utxos_nodo) {
+                Ok(_) | Err(NodoBitcoinError::TxYaEnMempool) => {}
+                Err(error) => {
+                    log_error_message(
+                        self.logger.clone(),
+                        format!(
+                            "Transacción propia {} rechazada por la mempool al recargar: {}",
+                            txid.to_hexa_le_string(),
+                            error
+                        ),
+                    );
+                    continue;
+                }
+            }
+            self.tx_pendings.insert(txid, tx.clone());
+            if let Some(admin_connections) = self.admin_connections.clone() {
+                if send_tx(admin_connections, self.logger.clone(), tx).is_ok() {
+                    reenviadas += 1;
+                }
+            }
+        }
+        log_info_message(
+            self.logger.clone(),
+            format!(
+                "Mempool recargada con {} transacciones, {} transacciones propias reenviadas.",
+                agregadas, reenviadas
+            ),
+        );
+    }
+
+    /// Anuncia con un inv la transacción aceptada en la mempool a los peers a los que
+    /// estamos conectados y a los clientes del nodo server
+    fn anunciar_tx(&self, txid: Uint256) {
+        if let Some(admin_connections) = self.admin_connections.clone() {
+            if let Err(error) = send_inv_tx(admin_connections, self.logger.clone(), txid) {
+                log_error_message(
+                    self.logger.clone(),
+                    format!("Error al anunciar la transacción: {}", error),
+                );
+            }
+        }
+        if let Some(sender_server_node) = &self.sender_server_node {
+            _ = sender_server_node.send(ServerNodeMessages::AnnounceTx(txid.get_bytes()));
+        }
+    }
+
+    fn esta_confirmada(&self, txid: Uint256) -> bool {
+        self.blocks.iter().any(|block| {
+            block
+                .txns
+                .iter()
+                .any(|tx| tx.txid().is_ok_and(|id| id == txid))
+        })
+    }
+
     fn guardar_header_y_bloque(&mut self, block: SerializedBlock, header: BlockHeader) {
         let logger = self.logger.clone();
 
@@ -585,13 +1463,13 @@ impl TransactionManager {
 
 fn send_new_tx(
     account: Account,
-    target_address: String,
+    target_address: Address,
     target_amount: u64,
-    fee: u64,
+    fee: Fee,
     utxo_set: UTXOSet,
     admin_connections: Option<AdminConnections>,
     logger: Sender<LogMessages>,
-) -> Result<(), NodoBitcoinError> {
+) -> Result<Transaction, NodoBitcoinError> {
     // obtener UTXOS del account
     let public_key = account.public_key.clone();
     let utxos_by_account = match utxo_set.utxos_for_account.get(&public_key) {
@@ -605,6 +1483,7 @@ fn send_new_tx(
         target_amount,
         fee,
         utxos_by_account,
+        vec![],
     )?;
 
     let admin_connections = match admin_connections {
@@ -612,9 +1491,9 @@ fn send_new_tx(
         None => return Err(NodoBitcoinError::NoSePuedeEnviarTransaccion),
     };
 
-    send_tx(admin_connections, logger, tx_obj)?;
+    send_tx(admin_connections, logger, tx_obj.clone())?;
 
-    Ok(())
+    Ok(tx_obj)
 }
 
 pub fn create_transaction_manager(
@@ -637,6 +1516,10 @@ pub fn create_transaction_manager(
         admin_connections: None,
         blocks: vec![],
         blocks_map: HashMap::new(),
+        utxos_nodo: UtxosNodo::new(),
+        mempool: Mempool::desde_config(),
+        huerfanas: TxsHuerfanas::desde_config(),
+        estimador_fees: EstimadorFees::new(),
     }));
 
     thread::spawn(move || {
@@ -674,3 +1557,101 @@ pub fn _update_from_transactions(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::transaction::TxOut;
+    use crate::blockchain::utxos_nodo::tests::bloque;
+
+    fn manager(accounts: Vec<Account>) -> TransactionManager {
+        let (logger, _) = channel();
+        let (file_manager, _) = channel();
+        let (sender_app_manager, _) = channel();
+        TransactionManager {
+            utxos: UTXOSet::new(),
+            tx_pendings: HashMap::new(),
+            accounts,
+            logger,
+            file_manager,
+            sender_app_manager,
+            sender_block_broadcasting: None,
+            sender_server_node: None,
+            admin_connections: None,
+            blocks: vec![],
+            blocks_map: HashMap::new(),
+            utxos_nodo: UtxosNodo::new(),
+            mempool: Mempool::default(),
+            huerfanas: TxsHuerfanas::new(10),
+            estimador_fees: EstimadorFees::new(),
+        }
+    }
+
+    // cuenta con una utxo confirmada de 100000 y una transacción pendiente que la gasta
+    fn manager_con_pendiente() -> (TransactionManager, Account, Uint256) {
+        let account = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "test".to_string(),
+        );
+        let mut manager = manager(vec![account.clone()]);
+        let tx_out = TxOut::new(100000, account.public_key.clone()).unwrap();
+        let previa = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([1; 32]), 0)],
+            vec![tx_out.clone()],
+            0,
+        )
+        .unwrap();
+        manager
+            .utxos_nodo
+            .aplicar_bloque(&bloque(1, vec![previa.clone()]));
+        let utxo = Utxo {
+            tx_id: previa.txid().unwrap(),
+            output_index: 0,
+            pk_script: tx_out.pk_script.clone(),
+            tx_out,
+            tx: previa,
+        };
+        manager
+            .utxos
+            .utxos_for_account
+            .insert(account.public_key.clone(), vec![utxo.clone()]);
+
+        let original = create_tx_to_send(
+            account.clone(),
+            Address::desde_string("mtm4vS3WH7pg13pjFEmqGq2TSPDcUN6k7a").unwrap(),
+            50000,
+            Fee::Total(1000),
+            vec![utxo],
+            vec![],
+        )
+        .unwrap();
+        let original_txid = original.txid().unwrap();
+        manager.agregar_tx_nueva(original, None);
+        assert!(manager.mempool.contiene(&original_txid));
+        assert!(manager.tx_pendings.contains_key(&original_txid));
+        (manager, account, original_txid)
+    }
+
+    #[test]
+    fn test_aumentar_fee_sin_poder_enviar() {
+        let (mut manager, account, original_txid) = manager_con_pendiente();
+
+        // sin peers el envío falla, pero la mempool ya aceptó el reemplazo
+        assert_eq!(
+            manager.aumentar_fee(account.clone(), original_txid, 3000),
+            Err(NodoBitcoinError::NoSePuedeEnviarTransaccion)
+        );
+        let txids = manager.mempool.txids();
+        assert_eq!(txids.len(), 1);
+        let nuevo_txid = txids[0];
+        assert_ne!(nuevo_txid, original_txid);
+
+        // la wallet sigue a la misma transacción que la mempool
+        let pendientes: Vec<Uint256> = manager.tx_pendings.keys().copied().collect();
+        assert_eq!(pendientes, vec![nuevo_txid]);
+        let reportes = &manager.utxos.tx_report_pending_by_accounts[&account.public_key];
+        assert!(!reportes.is_empty());
+        assert!(reportes.iter().all(|reporte| reporte.tx_id == nuevo_txid));
+    }
+}
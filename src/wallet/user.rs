@@ -1,46 +1,440 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
 };
 
 use crate::{
+    blockchain::transaction::{Transaction, TxOut},
+    common::address::{Address, TipoDireccion},
+    common::decoder::{point_sec, wif_to_hex},
+    common::red::red_configurada,
     common::utils_file::{read_decoded_string_offset, save_encoded_len_bytes},
     errores::NodoBitcoinError,
+    wallet::bip39::semilla_desde_mnemonico,
+    wallet::cifrado::{a_hexa, cifrar, descifrar, desde_hexa, CifradoCuentas},
+    wallet::claves::generar_par_de_claves,
+    wallet::hd::{ClaveExtendida, ClavePublicaExtendida, CuentaHD, CADENA_CAMBIO, CADENA_EXTERNA},
+    wallet::multisig::Multisig,
 };
 
+/// Cuenta de la wallet. La clave y la dirección principales son las de la primera dirección
+/// de recepción, y si la cuenta es HD además tiene todas las direcciones derivadas
+/// Las cuentas multisig tienen como dirección la del multisig y como clave la de uno de los
+/// participantes
+/// Si la wallet tiene passphrase guarda el secreto cifrado, y mientras está bloqueada no
+/// tiene cargadas las claves privadas
 #[derive(Clone, PartialEq, Debug)]
 pub struct Account {
     pub secret_key: String,
     pub public_key: String,
     pub wallet_name: String,
+    pub hd: Option<CuentaHD>,
+    pub multisig: Option<Multisig>,
+    pub secreto_cifrado: Option<Vec<u8>>,
 }
 
 const ACCOUNT_FILENAME: &str = "accounts.dat";
 
+/// Primer campo de los archivos de cuentas con encabezado. Los archivos anteriores empiezan
+/// directamente con la clave de la primera cuenta
+const MARCA_ARCHIVO: &str = "cuentas-v2";
+
+/// Va en el encabezado en lugar de la sal cuando la wallet no tiene passphrase, para marcar
+/// que las claves privadas del archivo no están cifradas
+const MARCA_SIN_CIFRAR: &str = "sin-cifrar";
+
+/// Prefijo del último campo de las cuentas multisig, que en las cuentas HD es la clave pública
+/// extendida
+const PREFIJO_MULTISIG: &str = "multisig:";
+
 impl Account {
     pub fn new(secret_key: String, public_key: String, wallet_name: String) -> Account {
         Account {
             secret_key,
             public_key,
             wallet_name,
+            hd: None,
+            multisig: None,
+            secreto_cifrado: None,
+        }
+    }
+
+    /// Crea una cuenta con una clave privada nueva generada por el nodo, en la red configurada
+    /// La dirección es P2WPKH si se pide segwit y P2PKH si no
+    pub fn generar(wallet_name: String, segwit: bool) -> Result<Account, NodoBitcoinError> {
+        let red = red_configurada();
+        let par = generar_par_de_claves(red)?;
+        let direccion = match segwit {
+            true => red.direccion_p2wpkh(&par.clave_publica)?,
+            false => par.direccion,
+        };
+        Ok(Account::new(par.wif, direccion, wallet_name))
+    }
+
+    /// Crea una cuenta HD a partir de una clave privada extendida (xprv o tprv)
+    /// Si es la clave maestra usa la primera cuenta de BIP44
+    pub fn desde_clave_extendida(
+        clave: &str,
+        wallet_name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let cuenta = CuentaHD::desde_clave_extendida(ClaveExtendida::desde_string(clave)?)?;
+        Account::desde_cuenta_hd(cuenta, wallet_name)
+    }
+
+    /// Crea o restaura una cuenta HD a partir de un mnemónico BIP39 y su passphrase, en la red
+    /// configurada
+    pub fn desde_mnemonico(
+        mnemonico: &str,
+        passphrase: &str,
+        wallet_name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let semilla = semilla_desde_mnemonico(mnemonico, passphrase)?;
+        let maestra = ClaveExtendida::desde_semilla(&semilla, red_configurada())?;
+        Account::desde_cuenta_hd(CuentaHD::desde_clave_extendida(maestra)?, wallet_name)
+    }
+
+    /// Crea una cuenta de solo lectura, sin clave privada, a partir de una dirección o de una
+    /// clave pública extendida (xpub o tpub). El nodo sigue sus saldos y movimientos pero no
+    /// puede firmar sus transacciones
+    pub fn solo_lectura(texto: &str, wallet_name: String) -> Result<Account, NodoBitcoinError> {
+        if texto.starts_with("xpub") || texto.starts_with("tpub") {
+            let clave = ClavePublicaExtendida::desde_string(texto)?;
+            return Account::desde_cuenta_hd(
+                CuentaHD::desde_clave_publica(clave, 0, 0)?,
+                wallet_name,
+            );
+        }
+        // las cuentas que no son HD reconocen sus salidas por el script de la dirección, P2PKH
+        // o segwit, de la red configurada
+        let es_valida = match Address::en_red(texto, red_configurada()) {
+            Ok(direccion) => !matches!(direccion.tipo, TipoDireccion::P2SH(_)),
+            Err(_) => false,
+        };
+        if !es_valida {
+            return Err(NodoBitcoinError::ClaveInvalida);
+        }
+        Ok(Account::new(String::new(), texto.to_string(), wallet_name))
+    }
+
+    fn desde_cuenta_hd(
+        mut cuenta: CuentaHD,
+        wallet_name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        cuenta.marcar_usada(CADENA_EXTERNA, 0)?;
+        let principal = cuenta
+            .direccion(CADENA_EXTERNA, 0)
+            .cloned()
+            .ok_or(NodoBitcoinError::ClaveInvalida)?;
+        Ok(Account {
+            secret_key: cuenta.wif(&principal).unwrap_or_default(),
+            public_key: principal.direccion,
+            wallet_name,
+            hd: Some(cuenta),
+            multisig: None,
+            secreto_cifrado: None,
+        })
+    }
+
+    /// Crea una cuenta multisig con la dirección del multisig en la red configurada
+    /// Si se pasa la clave privada (WIF) de uno de los participantes el nodo agrega su firma a
+    /// las transacciones, y si no la cuenta es de solo lectura
+    pub fn desde_multisig(
+        multisig: Multisig,
+        wif: &str,
+        wallet_name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        if !wif.is_empty() {
+            let clave_publica = point_sec(&wif_to_hex(wif)?)?;
+            if !multisig.claves_publicas.contains(&clave_publica) {
+                return Err(NodoBitcoinError::ClaveInvalida);
+            }
+        }
+        let direccion = multisig.direccion(red_configurada())?;
+        let mut account = Account::new(wif.to_string(), direccion, wallet_name);
+        account.multisig = Some(multisig);
+        Ok(account)
+    }
+
+    /// Devuelve si la salida le paga a alguna dirección de la cuenta
+    pub fn es_propio(&self, tx_out: &TxOut) -> bool {
+        match &self.hd {
+            Some(cuenta) => cuenta.buscar_script(&tx_out.pk_script).is_some(),
+            None => tx_out.is_user_account_output(self.public_key.clone()),
+        }
+    }
+
+    /// Devuelve la clave en formato WIF que firma las salidas con ese script
+    pub fn wif_para_script(&self, pk_script: &[u8]) -> Option<String> {
+        match &self.hd {
+            Some(cuenta) => cuenta
+                .buscar_script(pk_script)
+                .and_then(|direccion| cuenta.wif(direccion)),
+            None if self.secret_key.is_empty() => None,
+            None => Some(self.secret_key.clone()),
+        }
+    }
+
+    /// Devuelve los scripts de todas las direcciones de la cuenta
+    pub fn scripts(&self) -> Result<Vec<Vec<u8>>, NodoBitcoinError> {
+        match &self.hd {
+            Some(cuenta) => Ok(cuenta
+                .direcciones()
+                .iter()
+                .map(|direccion| direccion.pk_script.clone())
+                .collect()),
+            None => Ok(vec![TxOut::new(0, self.public_key.clone())?.pk_script]),
+        }
+    }
+
+    /// Devuelve una dirección de recepción sin usar. Las cuentas que no son HD tienen una sola
+    pub fn nueva_direccion_recepcion(&mut self) -> Result<String, NodoBitcoinError> {
+        match &mut self.hd {
+            Some(cuenta) => Ok(cuenta.nueva_direccion(CADENA_EXTERNA)?.direccion),
+            None => Ok(self.public_key.clone()),
+        }
+    }
+
+    /// Devuelve la próxima dirección de cambio sin usar. Se marca como usada cuando se ve la
+    /// transacción que le paga
+    pub fn direccion_de_cambio(&self) -> String {
+        self.hd
+            .as_ref()
+            .and_then(|cuenta| cuenta.direccion(CADENA_CAMBIO, cuenta.proxima_cambio))
+            .map(|direccion| direccion.direccion.clone())
+            .unwrap_or_else(|| self.public_key.clone())
+    }
+
+    /// Marca como usadas las direcciones de la cuenta a las que les paga la transacción
+    /// Devuelve si cambió alguna cadena, para guardar la cuenta
+    pub fn marcar_salidas_usadas(&mut self, tx: &Transaction) -> Result<bool, NodoBitcoinError> {
+        let cuenta = match &mut self.hd {
+            Some(cuenta) => cuenta,
+            None => return Ok(false),
+        };
+        let mut cambio = false;
+        for tx_out in tx.output.iter() {
+            let usada = cuenta
+                .buscar_script(&tx_out.pk_script)
+                .map(|direccion| (direccion.cadena, direccion.indice));
+            if let Some((cadena, indice)) = usada {
+                cambio |= cuenta.marcar_usada(cadena, indice)?;
+            }
+        }
+        Ok(cambio)
+    }
+
+    /// Devuelve si la cuenta no tiene cargada la clave privada para firmar
+    pub fn esta_bloqueada(&self) -> bool {
+        self.secreto().is_none()
+    }
+
+    /// Devuelve si la cuenta no tiene clave privada, ni cargada ni cifrada
+    /// Solo tiene sentido en las cuentas de la wallet, las copias sin secretos del transaction
+    /// manager no se pueden distinguir
+    pub fn es_solo_lectura(&self) -> bool {
+        self.secreto().is_none() && self.secreto_cifrado.is_none()
+    }
+
+    // el secreto de las cuentas HD es la clave privada extendida de la cuenta, de la que se
+    // derivan las claves de todas sus direcciones
+    fn secreto(&self) -> Option<String> {
+        match &self.hd {
+            Some(cuenta) => cuenta.clave_cuenta.as_ref().map(|clave| clave.serializar()),
+            None if self.secret_key.is_empty() => None,
+            None => Some(self.secret_key.clone()),
+        }
+    }
+
+    fn restaurar_secreto(&mut self, secreto: String) -> Result<(), NodoBitcoinError> {
+        match &mut self.hd {
+            Some(cuenta) => {
+                cuenta.desbloquear(ClaveExtendida::desde_string(&secreto)?)?;
+                self.secret_key = cuenta
+                    .direccion(CADENA_EXTERNA, 0)
+                    .and_then(|principal| cuenta.wif(principal))
+                    .unwrap_or_default();
+            }
+            None => self.secret_key = secreto,
+        }
+        Ok(())
+    }
+
+    /// Cifra el secreto de la cuenta con la clave derivada de la passphrase, para guardarlo
+    pub fn cifrar_secreto(&mut self, clave: &[u8; 32]) -> Result<(), NodoBitcoinError> {
+        if let Some(secreto) = self.secreto() {
+            self.secreto_cifrado = Some(cifrar(clave, secreto.as_bytes())?);
+        }
+        Ok(())
+    }
+
+    /// Descifra el secreto guardado y vuelve a cargar las claves privadas de la cuenta
+    pub fn desbloquear(&mut self, clave: &[u8; 32]) -> Result<(), NodoBitcoinError> {
+        let secreto = match &self.secreto_cifrado {
+            Some(secreto_cifrado) => descifrar(clave, secreto_cifrado)?,
+            None => return Ok(()),
+        };
+        let secreto =
+            String::from_utf8(secreto).map_err(|_| NodoBitcoinError::PassphraseIncorrecta)?;
+        self.restaurar_secreto(secreto)
+    }
+
+    /// Saca de memoria las claves privadas. Las cuentas sin el secreto cifrado no se bloquean
+    /// porque no se podrían volver a desbloquear
+    pub fn bloquear(&mut self) {
+        if self.secreto_cifrado.is_none() {
+            return;
+        }
+        self.secret_key = String::new();
+        if let Some(cuenta) = self.hd.as_mut() {
+            cuenta.bloquear();
+        }
+    }
+
+    /// Devuelve una copia de la cuenta sin las claves privadas ni el secreto cifrado, para los
+    /// actores que solo necesitan reconocer sus direcciones
+    pub fn sin_secretos(&self) -> Account {
+        let mut cuenta = self.clone();
+        cuenta.secret_key = String::new();
+        cuenta.secreto_cifrado = None;
+        if let Some(hd) = cuenta.hd.as_mut() {
+            hd.bloquear();
+        }
+        cuenta
+    }
+
+    /// Copia las claves y el secreto cifrado de otra copia de la misma cuenta, para actualizar
+    /// las direcciones usadas sin perder las claves
+    pub fn con_secretos_de(mut self, otra: &Account) -> Account {
+        self.secret_key = otra.secret_key.clone();
+        self.secreto_cifrado = otra.secreto_cifrado.clone();
+        if let (Some(cuenta), Some(otra_hd)) = (self.hd.as_mut(), otra.hd.as_ref()) {
+            cuenta.clave_cuenta = otra_hd.clave_cuenta.clone();
+        }
+        self
+    }
+
+    // los archivos sin encabezado guardaban en lugar de la clave de las cuentas HD la clave
+    // extendida de la cuenta y las próximas direcciones sin usar de cada cadena, separadas por /
+    fn desde_guardada(
+        clave: String,
+        public_key: String,
+        wallet_name: String,
+    ) -> Result<Account, NodoBitcoinError> {
+        let partes: Vec<&str> = clave.split('/').collect();
+        if partes.len() != 3 {
+            return Ok(Account::new(clave, public_key, wallet_name));
         }
+        let cuenta = CuentaHD::new(
+            ClaveExtendida::desde_string(partes[0])?,
+            indice_guardado(partes[1])?,
+            indice_guardado(partes[2])?,
+        )?;
+        Account::desde_cuenta_hd(cuenta, wallet_name)
     }
 
-    pub fn save_all_accounts(accounts: Vec<Account>) -> Result<(), NodoBitcoinError> {
-        let file = File::create(ACCOUNT_FILENAME).expect("No se pudo crear el archivo");
+    // cada cuenta se guarda con cuatro campos: el secreto, cifrado si la wallet tiene
+    // passphrase, la dirección principal, el nombre y, si es HD, la clave pública extendida
+    // con las próximas direcciones sin usar de cada cadena separadas por /, o si es multisig
+    // el multisig serializado después de PREFIJO_MULTISIG
+    fn desde_registro(campos: Vec<String>, cifrada: bool) -> Result<Account, NodoBitcoinError> {
+        let mut campos = campos.into_iter();
+        let mut siguiente = || campos.next().ok_or(NodoBitcoinError::NoSePuedeLeerLosBytes);
+        let (secreto, public_key, wallet_name, publico_hd) =
+            (siguiente()?, siguiente()?, siguiente()?, siguiente()?);
+
+        let mut account = Account::new(String::new(), public_key, wallet_name);
+        if let Some(multisig) = publico_hd.strip_prefix(PREFIJO_MULTISIG) {
+            account.multisig = Some(Multisig::desde_string(multisig)?);
+        } else if !publico_hd.is_empty() {
+            let partes: Vec<&str> = publico_hd.split('/').collect();
+            if partes.len() != 3 {
+                return Err(NodoBitcoinError::NoSePuedeLeerLosBytes);
+            }
+            account.hd = Some(CuentaHD::desde_clave_publica(
+                ClavePublicaExtendida::desde_string(partes[0])?,
+                indice_guardado(partes[1])?,
+                indice_guardado(partes[2])?,
+            )?);
+        }
+        if secreto.is_empty() {
+            return Ok(account);
+        }
+        if cifrada {
+            account.secreto_cifrado = Some(desde_hexa(&secreto)?);
+        } else {
+            account.restaurar_secreto(secreto)?;
+        }
+        Ok(account)
+    }
+
+    /// Guarda todas las cuentas. Si la wallet tiene passphrase los secretos se guardan cifrados
+    /// El archivo se escribe aparte y después se reemplaza, para no perder las cuentas
+    /// guardadas si falla la escritura
+    pub fn save_all_accounts(
+        accounts: Vec<Account>,
+        cifrado: Option<&CifradoCuentas>,
+    ) -> Result<(), NodoBitcoinError> {
+        let mut buffer = vec![];
+        Account::guardar_cuentas(&mut buffer, &accounts, cifrado)?;
+        let temporal = format!("{}.tmp", ACCOUNT_FILENAME);
+        let mut file =
+            File::create(&temporal).map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        file.write_all(&buffer)
+            .and_then(|_| file.sync_all())
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)?;
+        fs::rename(&temporal, ACCOUNT_FILENAME)
+            .map_err(|_| NodoBitcoinError::NoSePuedeEscribirLosBytes)
+    }
+
+    fn guardar_cuentas(
+        file: &mut dyn Write,
+        accounts: &[Account],
+        cifrado: Option<&CifradoCuentas>,
+    ) -> Result<(), NodoBitcoinError> {
+        let encabezado = match cifrado {
+            Some(cifrado) => [
+                a_hexa(&cifrado.sal),
+                cifrado.iteraciones.to_string(),
+                a_hexa(&cifrado.verificador),
+            ],
+            None => [MARCA_SIN_CIFRAR.to_string(), String::new(), String::new()],
+        };
+        save_encoded_len_bytes(file, MARCA_ARCHIVO.to_string())?;
+        for campo in encabezado {
+            save_encoded_len_bytes(file, campo)?;
+        }
         for account in accounts {
-            account.save(&mut &file)?;
+            account.save(file, cifrado.is_some())?;
         }
         Ok(())
     }
 
-    fn save(&self, file: &mut dyn Write) -> Result<(), NodoBitcoinError> {
-        save_encoded_len_bytes(file, self.secret_key.clone())?;
+    fn save(&self, file: &mut dyn Write, cifrada: bool) -> Result<(), NodoBitcoinError> {
+        let secreto = match (cifrada, &self.secreto_cifrado, self.secreto()) {
+            (true, Some(secreto_cifrado), _) => a_hexa(secreto_cifrado),
+            // nunca se escribe una clave sin cifrar en el archivo de una wallet con passphrase
+            (true, None, Some(_)) => return Err(NodoBitcoinError::NoSePuedeEscribirLosBytes),
+            (_, _, secreto) => secreto.unwrap_or_default(),
+        };
+        let publico_hd = match (&self.hd, &self.multisig) {
+            (Some(cuenta), _) => format!(
+                "{}/{}/{}",
+                cuenta.clave_publica.serializar(),
+                cuenta.proxima_externa,
+                cuenta.proxima_cambio
+            ),
+            (None, Some(multisig)) => format!("{}{}", PREFIJO_MULTISIG, multisig.serializar()),
+            (None, None) => String::new(),
+        };
+        save_encoded_len_bytes(file, secreto)?;
         save_encoded_len_bytes(file, self.public_key.clone())?;
-        save_encoded_len_bytes(file, self.wallet_name.clone())
+        save_encoded_len_bytes(file, self.wallet_name.clone())?;
+        save_encoded_len_bytes(file, publico_hd)
     }
 
-    pub fn get_all_accounts() -> Result<Vec<Account>, NodoBitcoinError> {
+    /// Lee las cuentas guardadas y los parámetros de cifrado si la wallet tiene passphrase
+    /// Las cuentas de una wallet cifrada se leen bloqueadas
+    pub fn get_all_accounts() -> Result<(Vec<Account>, Option<CifradoCuentas>), NodoBitcoinError> {
         let mut file = match File::open(ACCOUNT_FILENAME) {
             Ok(file) => file,
             Err(_) => return Err(NodoBitcoinError::NoExisteArchivo),
@@ -51,36 +445,277 @@ impl Account {
             Ok(_) => {}
             Err(_) => return Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
         };
-        let accounts = Self::parse_accounts(buffer)?;
-        Ok(accounts)
+        Self::parse_accounts(buffer)
     }
 
-    fn parse_accounts(buffer: Vec<u8>) -> Result<Vec<Account>, NodoBitcoinError> {
+    fn parse_accounts(
+        buffer: Vec<u8>,
+    ) -> Result<(Vec<Account>, Option<CifradoCuentas>), NodoBitcoinError> {
         let mut todas = vec![];
-        let mut offset = 0;
         let buffer_len = buffer.len() as u64;
-        while offset < buffer_len {
-            let mut account_bytes = vec![];
-            for _ in 0..3 {
-                let (value, new_offset) = read_decoded_string_offset(buffer.clone(), offset)?;
-                account_bytes.push(value);
-                offset = new_offset;
+        if buffer_len == 0 {
+            return Ok((todas, None));
+        }
+        let mut offset = 0;
+        if leer_campos(&buffer, &mut offset, 1)?[0] != MARCA_ARCHIVO {
+            // archivo sin encabezado, con las claves sin cifrar
+            offset = 0;
+            while offset < buffer_len {
+                let campos = leer_campos(&buffer, &mut offset, 3)?;
+                todas.push(Account::desde_guardada(
+                    campos[0].clone(),
+                    campos[1].clone(),
+                    campos[2].clone(),
+                )?);
             }
-            let account = Account::new(
-                account_bytes[0].clone(),
-                account_bytes[1].clone(),
-                account_bytes[2].clone(),
-            );
-            todas.push(account);
+            return Ok((todas, None));
         }
-        Ok(todas)
+
+        let encabezado = leer_campos(&buffer, &mut offset, 3)?;
+        // los primeros archivos con encabezado dejaban la sal vacía en lugar de la marca
+        let cifrado = if encabezado[0].is_empty() || encabezado[0] == MARCA_SIN_CIFRAR {
+            None
+        } else {
+            Some(CifradoCuentas {
+                sal: desde_hexa(&encabezado[0])?,
+                iteraciones: indice_guardado(&encabezado[1])?,
+                verificador: desde_hexa(&encabezado[2])?,
+            })
+        };
+        while offset < buffer_len {
+            let campos = leer_campos(&buffer, &mut offset, 4)?;
+            todas.push(Account::desde_registro(campos, cifrado.is_some())?);
+        }
+        Ok((todas, cifrado))
+    }
+}
+
+fn indice_guardado(parte: &str) -> Result<u32, NodoBitcoinError> {
+    parte
+        .parse::<u32>()
+        .map_err(|_| NodoBitcoinError::NoSePuedeLeerLosBytes)
+}
+
+fn leer_campos(
+    buffer: &[u8],
+    offset: &mut u64,
+    cantidad: usize,
+) -> Result<Vec<String>, NodoBitcoinError> {
+    let mut campos = vec![];
+    for _ in 0..cantidad {
+        let (valor, nuevo_offset) = read_decoded_string_offset(buffer.to_vec(), *offset)?;
+        campos.push(valor);
+        *offset = nuevo_offset;
     }
+    Ok(campos)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::blockchain::transaction::{Transaction, TxIn, TxOut};
+    use crate::common::decoder::point_sec;
+    use crate::common::red::Red;
+    use crate::common::uint256::Uint256;
+    use crate::common::utils_file::save_encoded_len_bytes;
+    use crate::errores::NodoBitcoinError;
+    use crate::wallet::cifrado::CifradoCuentas;
+    use crate::wallet::hd::ClaveExtendida;
+    use crate::wallet::multisig::tests::claves_privadas;
+    use crate::wallet::multisig::{Multisig, TipoMultisig};
     use crate::wallet::user::Account;
 
+    #[test]
+    fn test_cuenta_hd_save_read() {
+        let maestra = ClaveExtendida::desde_semilla(&[7; 32], Red::Testnet).unwrap();
+        let mut account =
+            Account::desde_clave_extendida(&maestra.serializar(), "hd".to_string()).unwrap();
+        let principal = TxOut::new(1000, account.public_key.clone()).unwrap();
+        assert!(account.es_propio(&principal));
+        assert_eq!(
+            account.wif_para_script(&principal.pk_script),
+            Some(account.secret_key.clone())
+        );
+
+        // la próxima dirección de recepción no es la principal y el cambio va a otra cadena
+        let recepcion = account.nueva_direccion_recepcion().unwrap();
+        let cambio = account.direccion_de_cambio();
+        assert_ne!(recepcion, account.public_key);
+        assert_ne!(cambio, recepcion);
+        let tx_out_cambio = TxOut::new(500, cambio.clone()).unwrap();
+        assert!(account.es_propio(&tx_out_cambio));
+        let tx = Transaction::new(
+            vec![TxIn::new(Uint256::from_be_bytes([1; 32]), 0)],
+            vec![tx_out_cambio],
+            0,
+        )
+        .unwrap();
+        assert!(account.marcar_salidas_usadas(&tx).unwrap());
+        assert_ne!(account.direccion_de_cambio(), cambio);
+
+        let mut mock_write = vec![];
+        assert!(
+            Account::guardar_cuentas(&mut mock_write, std::slice::from_ref(&account), None).is_ok()
+        );
+        let (accounts, cifrado) = Account::parse_accounts(mock_write).unwrap();
+        assert_eq!(accounts, vec![account]);
+        assert_eq!(cifrado, None);
+    }
+
+    #[test]
+    fn test_cuenta_solo_lectura() {
+        let maestra = ClaveExtendida::desde_semilla(&[7; 32], Red::Testnet).unwrap();
+        let completa =
+            Account::desde_clave_extendida(&maestra.serializar(), "hd".to_string()).unwrap();
+        let tpub = completa.hd.as_ref().unwrap().clave_publica.serializar();
+        let hd = Account::solo_lectura(&tpub, "frio hd".to_string()).unwrap();
+        assert_eq!(hd.public_key, completa.public_key);
+        assert!(hd.es_solo_lectura());
+        assert!(!completa.es_solo_lectura());
+        let principal = TxOut::new(1000, hd.public_key.clone()).unwrap();
+        assert!(hd.es_propio(&principal));
+        assert_eq!(hd.wif_para_script(&principal.pk_script), None);
+
+        let direccion =
+            Account::solo_lectura("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun", "frio".to_string())
+                .unwrap();
+        assert!(direccion.es_solo_lectura());
+        assert_eq!(
+            Account::solo_lectura("mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVuo", "frio".to_string()),
+            Err(NodoBitcoinError::ClaveInvalida)
+        );
+        let segwit = Account::solo_lectura(
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+            "frio segwit".to_string(),
+        )
+        .unwrap();
+        assert!(segwit.es_propio(&TxOut::new(1000, segwit.public_key.clone()).unwrap()));
+
+        // se guardan sin secreto y se leen de nuevo de solo lectura
+        let mut mock_write = vec![];
+        Account::guardar_cuentas(&mut mock_write, &[hd.clone(), direccion.clone()], None).unwrap();
+        let (leidas, _) = Account::parse_accounts(mock_write).unwrap();
+        assert_eq!(leidas, vec![hd, direccion]);
+        assert!(leidas.iter().all(Account::es_solo_lectura));
+    }
+
+    #[test]
+    fn test_cuenta_multisig_save_read() {
+        let privadas = claves_privadas();
+        let publicas: Vec<Vec<u8>> = privadas
+            .iter()
+            .map(|privada| point_sec(privada).unwrap())
+            .collect();
+        let multisig = Multisig::new(2, publicas, TipoMultisig::P2SH).unwrap();
+        let wif = Red::Testnet.codificar_wif(&privadas[1]);
+        let account =
+            Account::desde_multisig(multisig.clone(), &wif, "tesoro".to_string()).unwrap();
+        assert_eq!(account.public_key, "2MuFU6ZyBLtDNadMA6RnwJdXGWUSUaoKLeS");
+        let salida = TxOut::new(1000, account.public_key.clone()).unwrap();
+        assert!(account.es_propio(&salida));
+        assert_eq!(account.wif_para_script(&salida.pk_script), Some(wif));
+
+        // sin clave es de solo lectura, y no se acepta la clave de alguien que no participa
+        let solo_lectura =
+            Account::desde_multisig(multisig.clone(), "", "tesoro frio".to_string()).unwrap();
+        assert!(solo_lectura.es_solo_lectura());
+        let ajena = Red::Testnet.codificar_wif(&[9; 32]);
+        assert_eq!(
+            Account::desde_multisig(multisig, &ajena, "ajena".to_string()),
+            Err(NodoBitcoinError::ClaveInvalida)
+        );
+
+        let mut mock_write = vec![];
+        Account::guardar_cuentas(
+            &mut mock_write,
+            &[account.clone(), solo_lectura.clone()],
+            None,
+        )
+        .unwrap();
+        let (leidas, _) = Account::parse_accounts(mock_write).unwrap();
+        assert_eq!(leidas, vec![account, solo_lectura]);
+    }
+
+    #[test]
+    fn test_cuentas_cifradas_save_read() {
+        let maestra = ClaveExtendida::desde_semilla(&[9; 32], Red::Testnet).unwrap();
+        let mut hd =
+            Account::desde_clave_extendida(&maestra.serializar(), "hd".to_string()).unwrap();
+        let mut simple = Account::new(
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+            "simple".to_string(),
+        );
+        let (cifrado, clave) = CifradoCuentas::con_iteraciones("passphrase", 10).unwrap();
+        hd.cifrar_secreto(&clave).unwrap();
+        simple.cifrar_secreto(&clave).unwrap();
+
+        let mut mock_write = vec![];
+        Account::guardar_cuentas(
+            &mut mock_write,
+            &[hd.clone(), simple.clone()],
+            Some(&cifrado),
+        )
+        .unwrap();
+        let texto = String::from_utf8_lossy(&mock_write).to_string();
+        let wif_codificado = bs58::encode(simple.secret_key.as_bytes()).into_string();
+        assert!(!texto.contains(&wif_codificado));
+
+        // se leen bloqueadas pero siguen reconociendo sus direcciones
+        let (mut leidas, leido) = Account::parse_accounts(mock_write).unwrap();
+        assert_eq!(leido, Some(cifrado.clone()));
+        let principal = TxOut::new(1000, hd.public_key.clone()).unwrap();
+        assert!(leidas.iter().all(|account| account.esta_bloqueada()));
+        assert!(leidas[0].es_propio(&principal));
+        assert_eq!(leidas[0].wif_para_script(&principal.pk_script), None);
+
+        let otra_clave = CifradoCuentas::con_iteraciones("otra", 10).unwrap().1;
+        assert_eq!(
+            leidas[0].desbloquear(&otra_clave),
+            Err(NodoBitcoinError::PassphraseIncorrecta)
+        );
+        let clave = cifrado.derivar_clave("passphrase").unwrap();
+        for account in leidas.iter_mut() {
+            account.desbloquear(&clave).unwrap();
+        }
+        assert_eq!(leidas, vec![hd.clone(), simple]);
+
+        hd.bloquear();
+        assert!(hd.esta_bloqueada());
+        assert_eq!(hd.secret_key, "");
+        assert_eq!(hd.clone().con_secretos_de(&leidas[0]), leidas[0]);
+
+        // una cuenta con passphrase que no tiene el secreto cifrado no se guarda
+        let mut sin_cifrar = leidas[1].clone();
+        sin_cifrar.secreto_cifrado = None;
+        assert!(sin_cifrar.save(&mut vec![], true).is_err());
+    }
+
+    #[test]
+    fn test_archivo_sin_encabezado() {
+        let mut mock_write = vec![];
+        for campo in [
+            "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw",
+            "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun",
+            "wallet1",
+        ] {
+            save_encoded_len_bytes(&mut mock_write, campo.to_string()).unwrap();
+        }
+        let (accounts, cifrado) = Account::parse_accounts(mock_write.clone()).unwrap();
+        assert_eq!(cifrado, None);
+        assert_eq!(
+            accounts,
+            vec![Account::new(
+                "cRJzHMCgDLsvttTH8R8t6LLcZgMDs1WtgwQXxk8bFFk7E2AJp1tw".to_string(),
+                "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string(),
+                "wallet1".to_string(),
+            )]
+        );
+
+        // un archivo cortado devuelve error en lugar de entrar en pánico
+        mock_write.truncate(mock_write.len() - 3);
+        assert!(Account::parse_accounts(mock_write).is_err());
+    }
+
     #[test]
     fn test_one_account_save_read() {
         let account = Account::new(
@@ -91,12 +726,17 @@ mod tests {
 
         let mut mock_write = vec![];
 
-        let save = account.save(&mut mock_write);
+        let save = Account::guardar_cuentas(&mut mock_write, std::slice::from_ref(&account), None);
         assert!(save.is_ok());
+        // sin passphrase el archivo queda marcado como sin cifrar
+        let texto = String::from_utf8_lossy(&mock_write).to_string();
+        let marca = bs58::encode(super::MARCA_SIN_CIFRAR.as_bytes()).into_string();
+        assert!(texto.contains(&marca));
 
         let read = Account::parse_accounts(mock_write);
         assert!(read.is_ok());
-        let accounts = read.unwrap();
+        let (accounts, cifrado) = read.unwrap();
+        assert_eq!(cifrado, None);
 
         assert_eq!(accounts.len(), 1);
         assert_eq!(accounts[0].secret_key, account.secret_key);
@@ -114,12 +754,12 @@ mod tests {
 
         let mut mock_write = vec![];
 
-        let save = account1.save(&mut mock_write);
+        let save = Account::guardar_cuentas(&mut mock_write, std::slice::from_ref(&account1), None);
         assert!(save.is_ok());
 
-        let read = Account::parse_accounts(mock_write.clone());
+        let read = Account::parse_accounts(mock_write);
         assert!(read.is_ok());
-        let accounts = read.unwrap();
+        let (accounts, _) = read.unwrap();
 
         assert_eq!(accounts.len(), 1);
         assert_eq!(accounts[0].secret_key, account1.secret_key);
@@ -132,12 +772,14 @@ mod tests {
             "wallet1".to_string(),
         );
 
-        let save = account2.save(&mut mock_write);
+        let mut mock_write = vec![];
+        let save =
+            Account::guardar_cuentas(&mut mock_write, &[account1.clone(), account2.clone()], None);
         assert!(save.is_ok());
 
         let read = Account::parse_accounts(mock_write);
         assert!(read.is_ok());
-        let accounts = read.unwrap();
+        let (accounts, _) = read.unwrap();
 
         assert_eq!(accounts.len(), 2);
         assert_eq!(accounts[0].secret_key, account1.secret_key);
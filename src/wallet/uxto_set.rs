@@ -1,11 +1,13 @@
 use crate::blockchain::block::SerializedBlock;
+use crate::blockchain::script_index::MovimientoScript;
 use crate::blockchain::transaction::{Transaction, TxIn, TxOut};
 use crate::common::uint256::Uint256;
 use crate::common::utils_file::{read_decoded_string_offset, save_encoded_len_bytes};
 use crate::errores::NodoBitcoinError;
+use crate::storage::file_storage::FileStorage;
+use crate::storage::Storage;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Write;
 use std::{fmt, mem};
 
 use super::user::Account;
@@ -278,6 +280,13 @@ impl UTXOSet {
         self.tx_report_pending_by_accounts = hashmap;
     }
 
+    /// Quita los movimientos pendientes de la transacción en todas las cuentas
+    pub fn quitar_tx_reports_pendientes(&mut self, tx_id: Uint256) {
+        for tx_reports in self.tx_report_pending_by_accounts.values_mut() {
+            tx_reports.retain(|tx_report| tx_report.tx_id != tx_id);
+        }
+    }
+
     fn eliminar_tx_report(
         mut tx_report_pending_by_accounts: HashMap<String, Vec<TxReport>>,
         tx_report_to_delete: TxReport,
@@ -424,7 +433,7 @@ impl UTXOSet {
         tx_out: &TxOut,
     ) -> Result<Account, NodoBitcoinError> {
         for account in accounts.iter() {
-            if tx_out.is_user_account_output(account.clone().public_key) {
+            if account.es_propio(tx_out) {
                 return Ok(account.clone());
             }
         }
@@ -497,6 +506,58 @@ impl UTXOSet {
         Ok(())
     }
 
+    /// Recibe la cuenta y los movimientos de su script obtenidos del índice de scripts
+    /// Agrega las utxos y los reportes de la cuenta sin tener que recorrer los bloques
+    pub fn agregar_historial_de_cuenta(
+        &mut self,
+        account: String,
+        movimientos: Vec<MovimientoScript>,
+    ) {
+        for movimiento in movimientos {
+            let salida = movimiento.salida;
+            let tx_out = match movimiento.tx.output.get(salida.output_index as usize) {
+                Some(tx_out) => tx_out.clone(),
+                None => continue,
+            };
+            let utxo = Utxo {
+                tx_id: salida.tx_id,
+                output_index: salida.output_index,
+                tx_out: tx_out.clone(),
+                pk_script: tx_out.pk_script.clone(),
+                tx: movimiento.tx.clone(),
+            };
+            self.agregar_tx_report_desde_out(account.clone(), utxo, salida.timestamp, false);
+
+            match movimiento.gasto {
+                Some(gasto) => {
+                    let tx_report = TxReport::new(
+                        false,
+                        gasto.timestamp,
+                        gasto.tx_id,
+                        -(tx_out.value as i128),
+                        true,
+                        gasto.input_index,
+                    );
+                    if !self.existe_tx_report_para_account(account.clone(), &tx_report) {
+                        self.tx_report_by_accounts
+                            .entry(account.clone())
+                            .or_default()
+                            .push(tx_report);
+                    }
+                }
+                None => {
+                    self.agregar_utxo(
+                        account.clone(),
+                        salida.tx_id,
+                        salida.output_index,
+                        &tx_out,
+                        &movimiento.tx,
+                    );
+                }
+            }
+        }
+    }
+
     pub fn validar_input(&self, tx_in: TxIn) -> Result<String, NodoBitcoinError> {
         let previous_tx_id = Uint256::from_be_bytes(tx_in.previous_output.hash);
         let output_index = tx_in.previous_output.index;
@@ -718,52 +779,54 @@ impl UTXOSet {
         Ok(hashmap)
     }
 
+    /// Guarda las UTXOs y los reportes de las cuentas en los archivos de la wallet
     pub fn save(&self) -> Result<(), NodoBitcoinError> {
-        let file_utxo_for_account =
-            File::create(UTXO_FOR_ACCOUNT_FILENAME).expect("No se pudo crear el archivo");
-        let file_account_for_txid =
-            File::create(ACCOUNT_FOR_TXID_INDEX_FILENAME).expect("No se pudo crear el archivo");
-        let file_tx_report_by_account =
-            File::create(TX_REPORT_BY_ACCOUNT_FILENAME).expect("No se pudo crear el archivo");
-        let file_tx_report_pending_by_account = File::create(TX_REPORT_PENDING_BY_ACCOUNT_FILENAME)
-            .expect("No se pudo crear el archivo");
+        self.save_en(&mut FileStorage::desde_config()?)
+    }
+
+    /// Guarda las UTXOs y los reportes de las cuentas en el almacenamiento recibido
+    pub fn save_en(&self, storage: &mut dyn Storage) -> Result<(), NodoBitcoinError> {
+        let mut utxo_for_account = vec![];
         Self::save_utxos_for_account(
             self.last_timestamp,
             self.utxos_for_account.clone(),
-            &mut &file_utxo_for_account,
+            &mut utxo_for_account,
         )?;
+        let mut account_for_txid = vec![];
         Self::save_account_for_txid_index(
             self.account_for_txid_index.clone(),
-            &mut &file_account_for_txid,
+            &mut account_for_txid,
         )?;
+        let mut tx_report_by_account = vec![];
         Self::save_tx_report_by_accounts(
             self.tx_report_by_accounts.clone(),
-            &mut &file_tx_report_by_account,
+            &mut tx_report_by_account,
         )?;
+        let mut tx_report_pending_by_account = vec![];
         Self::save_tx_report_by_accounts(
             self.tx_report_pending_by_accounts.clone(),
-            &mut &file_tx_report_pending_by_account,
+            &mut tx_report_pending_by_account,
+        )?;
+
+        storage.guardar_estado_wallet(UTXO_FOR_ACCOUNT_FILENAME, &utxo_for_account)?;
+        storage.guardar_estado_wallet(ACCOUNT_FOR_TXID_INDEX_FILENAME, &account_for_txid)?;
+        storage.guardar_estado_wallet(TX_REPORT_BY_ACCOUNT_FILENAME, &tx_report_by_account)?;
+        storage.guardar_estado_wallet(
+            TX_REPORT_PENDING_BY_ACCOUNT_FILENAME,
+            &tx_report_pending_by_account,
         )?;
 
         Ok(())
     }
 
-    fn load_bytes_from_file(filename: String) -> Result<Vec<u8>, NodoBitcoinError> {
-        let mut file_utxo_for_account = match File::open(filename) {
-            Ok(file) => file,
-            Err(_) => return Err(NodoBitcoinError::NoExisteArchivo),
-        };
-        let mut buffer = vec![];
-        match file_utxo_for_account.read_to_end(&mut buffer) {
-            Ok(_) => {}
-            Err(_) => return Err(NodoBitcoinError::NoSePuedeLeerLosBytes),
-        };
-        Ok(buffer)
+    /// Carga las UTXOs y los reportes de las cuentas de los archivos de la wallet
+    pub fn load(&mut self) -> Result<(), NodoBitcoinError> {
+        self.load_de(&FileStorage::desde_config()?)
     }
 
-    pub fn load(&mut self) -> Result<(), NodoBitcoinError> {
-        let buffer_utxos_for_account =
-            Self::load_bytes_from_file(UTXO_FOR_ACCOUNT_FILENAME.to_string())?;
+    /// Carga las UTXOs y los reportes de las cuentas del almacenamiento recibido
+    pub fn load_de(&mut self, storage: &dyn Storage) -> Result<(), NodoBitcoinError> {
+        let buffer_utxos_for_account = storage.leer_estado_wallet(UTXO_FOR_ACCOUNT_FILENAME)?;
 
         let (timestamp, hash_utxos_for_account) =
             Self::load_utxos_for_account_and_timestamp(buffer_utxos_for_account)?;
@@ -771,15 +834,15 @@ impl UTXOSet {
         self.utxos_for_account = hash_utxos_for_account;
 
         let buffer_account_for_txid =
-            Self::load_bytes_from_file(ACCOUNT_FOR_TXID_INDEX_FILENAME.to_string())?;
+            storage.leer_estado_wallet(ACCOUNT_FOR_TXID_INDEX_FILENAME)?;
         self.account_for_txid_index = Self::load_account_for_txid_index(buffer_account_for_txid)?;
 
         let buffer_tx_report_by_account =
-            Self::load_bytes_from_file(TX_REPORT_BY_ACCOUNT_FILENAME.to_string())?;
+            storage.leer_estado_wallet(TX_REPORT_BY_ACCOUNT_FILENAME)?;
         self.tx_report_by_accounts = Self::load_tx_report_by_accounts(buffer_tx_report_by_account)?;
 
         let buffer_tx_report_pending_by_account =
-            Self::load_bytes_from_file(TX_REPORT_PENDING_BY_ACCOUNT_FILENAME.to_string())?;
+            storage.leer_estado_wallet(TX_REPORT_PENDING_BY_ACCOUNT_FILENAME)?;
         self.tx_report_pending_by_accounts =
             Self::load_tx_report_by_accounts(buffer_tx_report_pending_by_account)?;
 
@@ -791,10 +854,13 @@ impl UTXOSet {
 mod tests {
     use crate::{
         blockchain::{
+            block_file::UbicacionBloque,
             blockheader::BlockHeader,
+            script_index::{GastoIndexado, SalidaIndexada},
             transaction::{Outpoint, TxIn, TxOut},
         },
         common::decoder::{decode_base58, p2pkh_script_serialized},
+        storage::memory_storage::MemoryStorage,
     };
 
     use super::*;
@@ -841,6 +907,7 @@ mod tests {
             signature_script: vec![],
             sequence: 0,
             script_bytes_amount: 1,
+            witness: vec![],
         };
         let tx_in2 = TxIn {
             previous_output: Outpoint {
@@ -851,6 +918,7 @@ mod tests {
             signature_script: vec![],
             sequence: 0,
             script_bytes_amount: 1,
+            witness: vec![],
         };
 
         let transaction1 = Transaction {
@@ -942,6 +1010,7 @@ mod tests {
                 signature_script: vec![],
                 script_bytes: 0,
                 script_bytes_amount: 0,
+                witness: vec![],
                 sequence: 0,
             }],
             output: vec![TxOut {
@@ -1333,4 +1402,90 @@ mod tests {
         let tx_reports = hashmap.get(&key1).unwrap();
         assert_eq!(tx_reports.len(), 0);
     }
+
+    #[test]
+    fn test_agregar_historial_de_cuenta() {
+        let public_key = "mnJvq7mbGiPNNhUne4FAqq27Q8xZrAsVun".to_string();
+        let p2pkh_script = get_pk_script_from_account(public_key.clone());
+
+        let tx_recibida = Transaction {
+            input: vec![],
+            output: vec![
+                TxOut {
+                    value: 5,
+                    pk_script: p2pkh_script.clone(),
+                    pk_len: 0,
+                    pk_len_bytes: 0,
+                },
+                TxOut {
+                    value: 7,
+                    pk_script: p2pkh_script,
+                    pk_len: 0,
+                    pk_len_bytes: 0,
+                },
+            ],
+            lock_time: 0,
+            tx_in_count: 0,
+            tx_out_count: 2,
+            version: 1,
+        };
+        let tx_id = tx_recibida.txid().unwrap();
+
+        let salida = |output_index: u32, value: u64| SalidaIndexada {
+            tx_id,
+            output_index,
+            value,
+            timestamp: 100,
+            ubicacion_bloque: UbicacionBloque::default(),
+            posicion: 0,
+        };
+        // el primer output ya fue gastado, el segundo sigue disponible
+        let movimientos = vec![
+            MovimientoScript {
+                salida: salida(0, 5),
+                tx: tx_recibida.clone(),
+                gasto: Some(GastoIndexado {
+                    tx_id: Uint256::_from_u64(99),
+                    input_index: 0,
+                    timestamp: 200,
+                }),
+            },
+            MovimientoScript {
+                salida: salida(1, 7),
+                tx: tx_recibida,
+                gasto: None,
+            },
+        ];
+
+        let mut utxo_set = UTXOSet::new();
+        utxo_set.agregar_historial_de_cuenta(public_key.clone(), movimientos);
+
+        assert_eq!(utxo_set.utxos_for_account[&public_key].len(), 1);
+        assert_eq!(utxo_set.get_available(public_key.clone()).unwrap(), 7);
+        assert_eq!(utxo_set.tx_report_by_accounts[&public_key].len(), 3);
+        assert!(utxo_set.account_for_txid_index.contains_key(&(tx_id, 1)));
+    }
+
+    #[test]
+    fn test_save_y_load_en_memoria() {
+        let mut utxo_set = UTXOSet::new();
+        utxo_set.last_timestamp = 1234;
+        utxo_set
+            .account_for_txid_index
+            .insert((Uint256::from_be_bytes([1; 32]), 0), "cuenta".to_string());
+
+        let mut storage = MemoryStorage::new();
+        utxo_set.save_en(&mut storage).unwrap();
+
+        let mut cargado = UTXOSet::new();
+        cargado.load_de(&storage).unwrap();
+        assert_eq!(cargado.last_timestamp, 1234);
+        assert_eq!(
+            cargado
+                .account_for_txid_index
+                .get(&(Uint256::from_be_bytes([1; 32]), 0)),
+            Some(&"cuenta".to_string())
+        );
+        assert!(UTXOSet::new().load_de(&MemoryStorage::new()).is_err());
+    }
 }